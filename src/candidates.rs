@@ -0,0 +1,188 @@
+//! Several candidate messages requested concurrently: one per model, for
+//! comparing models side by side on real changes, or several from the same
+//! model to choose from. A model may be prefixed with its provider, as in
+//! `anthropic:claude-haiku-4-5`, to compare models of different providers.
+
+use std::time::{Duration, Instant};
+
+use crate::provider::Provider;
+use crate::{generate_message, CommitauraError, GenerateOptions};
+
+#[derive(Debug)]
+pub struct Candidate {
+    pub model: String,
+    pub message: Result<String, CommitauraError>,
+    pub elapsed: Duration,
+}
+
+/// A model to ask for a candidate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    /// The provider serving `model`; `None` for the configured one.
+    pub provider: Option<Provider>,
+    pub model: String,
+}
+
+impl Target {
+    /// Read `gpt-4o` or `anthropic:claude-haiku-4-5`. Only a known provider
+    /// name counts as a prefix, so Ollama tags such as `llama3:8b` stay
+    /// whole.
+    pub fn parse(value: &str) -> Target {
+        match value.split_once(':') {
+            Some((prefix, model)) if !model.is_empty() => match Provider::parse(prefix) {
+                Some(provider) => Target {
+                    provider: Some(provider),
+                    model: model.to_string(),
+                },
+                None => Target::configured(value),
+            },
+            _ => Target::configured(value),
+        }
+    }
+
+    fn configured(model: &str) -> Target {
+        Target {
+            provider: None,
+            model: model.to_string(),
+        }
+    }
+}
+
+/// Most candidates a count may ask for, since each is a request of its own.
+pub const MAX_COUNT: usize = 8;
+
+/// What `--candidates` asks for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Spec {
+    /// This many candidates from the configured model.
+    Count(usize),
+    /// One candidate from each of these models.
+    Models(Vec<Target>),
+}
+
+impl Spec {
+    /// Read `3` as a count and anything else as a model list.
+    pub fn parse(value: &str) -> Spec {
+        match value.trim().parse() {
+            Ok(count) => Spec::Count(count),
+            Err(_) => Spec::Models(parse_list(value)),
+        }
+    }
+
+    /// The model to ask for each candidate, in order.
+    pub fn targets(&self, configured: &str) -> Vec<Target> {
+        match self {
+            Spec::Count(count) => vec![Target::configured(configured); *count],
+            Spec::Models(targets) => targets.clone(),
+        }
+    }
+}
+
+/// Split a `--candidates` value such as `gpt-4o, anthropic:claude-haiku-4-5`
+/// into models.
+pub fn parse_list(list: &str) -> Vec<Target> {
+    let mut targets: Vec<Target> = Vec::new();
+    for target in list
+        .split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .map(Target::parse)
+    {
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+/// Generate a message for `diff` with each of `variants` at the same time,
+/// e.g. the same model with different prompt styles. Candidates come back
+/// in the order of `variants`.
+pub fn generate_each(diff: &str, variants: Vec<GenerateOptions>) -> Vec<Candidate> {
+    generate_each_with(diff, variants, generate_message)
+}
+
+/// [`generate_each`], writing each message with `generate`, such as
+/// [`crate::full_message::generate`].
+pub fn generate_each_with(
+    diff: &str,
+    variants: Vec<GenerateOptions>,
+    generate: impl Fn(&str, &GenerateOptions) -> Result<String, CommitauraError> + Sync,
+) -> Vec<Candidate> {
+    let generate = &generate;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = variants
+            .iter()
+            .map(|opts| {
+                scope.spawn(move || {
+                    let started = Instant::now();
+                    let message = generate(diff, opts);
+                    (message, started.elapsed())
+                })
+            })
+            .collect();
+        variants
+            .iter()
+            .map(|opts| &opts.model)
+            .zip(handles)
+            .map(|(model, handle)| {
+                let (message, elapsed) = handle.join().unwrap_or_else(|_| {
+                    (
+                        Err(CommitauraError::ApiRequestFailed(
+                            "generation thread panicked".to_string(),
+                        )),
+                        Duration::ZERO,
+                    )
+                });
+                Candidate {
+                    model: model.clone(),
+                    message,
+                    elapsed,
+                }
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_model_lists() {
+        assert_eq!(
+            parse_list("gpt-4o, gpt-4o-mini,,gpt-4o"),
+            [Target::configured("gpt-4o"), Target::configured("gpt-4o-mini")]
+        );
+    }
+
+    #[test]
+    fn parses_counts_and_lists() {
+        assert_eq!(
+            Spec::parse("3").targets("gpt-4o"),
+            vec![Target::configured("gpt-4o"); 3]
+        );
+        assert_eq!(
+            Spec::parse("gpt-4o,gpt-4o-mini").targets("o3"),
+            [Target::configured("gpt-4o"), Target::configured("gpt-4o-mini")]
+        );
+    }
+
+    #[test]
+    fn reads_provider_prefixes() {
+        assert_eq!(
+            parse_list("anthropic:claude-haiku-4-5, ollama:llama3:8b, llama3:8b"),
+            [
+                Target {
+                    provider: Some(Provider::Anthropic),
+                    model: "claude-haiku-4-5".to_string(),
+                },
+                Target {
+                    provider: Some(Provider::Ollama),
+                    model: "llama3:8b".to_string(),
+                },
+                Target::configured("llama3:8b"),
+            ]
+        );
+    }
+}
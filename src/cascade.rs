@@ -0,0 +1,188 @@
+//! Two-model cascade: draft with a cheap model, and only pay for a stronger
+//! one when the draft fails the checks below.
+//!
+//! Each later model in the cascade acts as a judge: it sees the changes, the
+//! draft and the problems found, and replies with a corrected message.
+
+use crate::generate::{build_prompt, complete, GenerateOptions, Prompt};
+use crate::summary::summarize_file;
+use crate::{diff, generate_message, injection, message, CommitauraError};
+
+const JUDGE_SYSTEM_MESSAGE: &str = "You are a meticulous reviewer of Git commit messages. You correct drafts so they are accurate, specific and follow Git conventions.";
+
+/// Words that say nothing about a change on their own.
+const GENERIC_WORDS: &[&str] = &[
+    "update",
+    "updates",
+    "updated",
+    "fix",
+    "fixes",
+    "fixed",
+    "change",
+    "changes",
+    "changed",
+    "minor",
+    "misc",
+    "stuff",
+    "file",
+    "files",
+    "code",
+    "some",
+    "various",
+    "bug",
+    "bugs",
+    "cleanup",
+    "refactor",
+    "improve",
+    "improvements",
+    "tweak",
+    "tweaks",
+    "wip",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CascadeOutcome {
+    pub message: String,
+    /// The model whose reply became the message.
+    pub model: String,
+    /// What was wrong with the draft that made a judge step in; empty when
+    /// the first model's draft was accepted.
+    pub problems: Vec<String>,
+}
+
+/// Problems with `message` as a description of `diff`: the formatting rules
+/// of [`message::lint`], plus signs of low confidence such as a generic
+/// subject or one that names nothing the diff touches.
+pub fn check(message: &str, diff: &str) -> Vec<String> {
+    let mut problems = message::lint(message);
+    let subject = message.trim().lines().next().unwrap_or_default();
+    let words: Vec<String> = subject
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < 3 || words.iter().all(|w| GENERIC_WORDS.contains(&w.as_str())) {
+        problems.push("Subject is too generic".to_string());
+    }
+
+    let lower = message.to_lowercase();
+    let mut names: Vec<String> = Vec::new();
+    for file in diff::parse(diff) {
+        let stem = file.path.rsplit('/').next().unwrap_or(&file.path);
+        let stem = stem.split('.').next().unwrap_or(stem);
+        names.push(stem.to_lowercase());
+        let summary = summarize_file(&file);
+        names.extend(
+            summary
+                .added_symbols
+                .iter()
+                .chain(&summary.removed_symbols)
+                .chain(&summary.touched)
+                .filter_map(|s| s.split_whitespace().last())
+                .map(str::to_lowercase),
+        );
+    }
+    names.retain(|n| n.len() > 2);
+    if !names.is_empty() && !names.iter().any(|n| lower.contains(n.as_str())) {
+        problems.push("Message names none of the changed files or definitions".to_string());
+    }
+    problems
+}
+
+/// The judge's prompt: the usual changes context, then the draft and its
+/// problems. The diff is shortened to leave room for the review part.
+pub fn build_judge_prompt(
+    diff: &str,
+    draft: &str,
+    problems: &[String],
+    opts: &GenerateOptions,
+) -> Result<Prompt, CommitauraError> {
+    let review = format!(
+        "\n\nDraft commit message:\n{}\n\nA reviewer found these problems with the draft:\n- {}\n\nReply with the corrected commit message only. Keep whatever in the draft is accurate.",
+        draft,
+        problems.join("\n- ")
+    );
+    let mut budget = opts.clone();
+    let tokenizer = opts.tokenizer();
+    budget.max_tokens = opts
+        .max_tokens
+        .saturating_sub(tokenizer.count(&review)? + tokenizer.count(JUDGE_SYSTEM_MESSAGE)?);
+    let base = build_prompt(diff, &budget)?;
+    Ok(Prompt {
+        system: JUDGE_SYSTEM_MESSAGE.to_string(),
+        user: base.user + &review,
+    })
+}
+
+/// Draft with `models[0]`; while the current message fails [`check`], hand
+/// it to the next model to judge and refine.
+pub fn generate(
+    diff: &str,
+    opts: &GenerateOptions,
+    models: &[String],
+) -> Result<CascadeOutcome, CommitauraError> {
+    let (first, judges) = models.split_first().ok_or_else(|| {
+        CommitauraError::ConfigError("cascade needs at least one model".to_string())
+    })?;
+    let mut outcome = CascadeOutcome {
+        message: generate_message(diff, &opts.with_model(first))?,
+        model: first.clone(),
+        problems: Vec::new(),
+    };
+    for judge in judges {
+        let problems = check(&outcome.message, diff);
+        if problems.is_empty() {
+            break;
+        }
+        let judge_opts = opts.with_model(judge);
+        let prompt = build_judge_prompt(diff, &outcome.message, &problems, &judge_opts)?;
+        let reply = complete(&prompt, &judge_opts, judge_opts.max_response_tokens)?;
+        let refined = message::assemble(&reply, &judge_opts);
+        if outcome.problems.is_empty() {
+            outcome.problems = problems;
+        }
+        if !refined.is_empty() && injection::inspect(&refined, diff).is_none() {
+            outcome.message = refined;
+            outcome.model = judge.clone();
+        }
+    }
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/retry.rs b/src/retry.rs\n--- a/src/retry.rs\n+++ b/src/retry.rs\n@@ -1,1 +1,2 @@\n+pub fn backoff_delay(attempt: u32) -> u64 {}\n";
+
+    #[test]
+    fn accepts_specific_messages() {
+        assert!(check("Add exponential backoff_delay for retries", DIFF).is_empty());
+    }
+
+    #[test]
+    fn flags_generic_or_unrelated_drafts() {
+        assert_eq!(
+            check("Update files.", DIFF),
+            [
+                "Subject ends with a period",
+                "Subject is too generic",
+                "Message names none of the changed files or definitions"
+            ]
+        );
+    }
+
+    #[test]
+    fn judge_prompt_carries_draft_and_problems() {
+        let prompt = build_judge_prompt(
+            DIFF,
+            "Update files",
+            &["Subject is too generic".to_string()],
+            &GenerateOptions::new("key"),
+        )
+        .unwrap();
+        assert!(prompt.user.contains("backoff_delay"));
+        assert!(prompt.user.contains("Draft commit message:\nUpdate files"));
+        assert!(prompt.user.contains("- Subject is too generic"));
+    }
+}
@@ -0,0 +1,209 @@
+//! A changelog built from the history: one release per tag, dated in the
+//! configured time zone, with its commits sorted into sections by the kind
+//! of change, as changelog fragments classify them.
+//!
+//! Nothing about the layout is fixed. The order and titles of sections and
+//! every heading and entry line come from `[changelog]` templates, so the
+//! defaults' Keep a Changelog style can be swapped for a project's own.
+
+use jiff::fmt::strtime;
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+
+use crate::config::ChangelogConfig;
+use crate::fragments::{entry_text, Kind};
+use crate::{git, template, CommitauraError};
+
+/// A tagged release, or the changes since the newest tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// The tag; `None` for unreleased changes.
+    pub version: Option<String>,
+    /// When the tag was made, formatted as configured.
+    pub date: String,
+    /// Short hash and subject of each commit, newest first.
+    pub commits: Vec<(String, String)>,
+}
+
+/// Every release reachable from HEAD, newest first, starting with the
+/// unreleased changes.
+pub fn collect(config: &ChangelogConfig) -> Result<Vec<Release>, CommitauraError> {
+    let timezone = time_zone(config.timezone.as_deref())?;
+    let tags = git::tags_with_dates()?;
+    let mut releases = Vec::new();
+    let mut newer = "HEAD".to_string();
+    let mut version = None;
+    let mut date = String::new();
+    for (tag, tag_date) in tags {
+        releases.push(Release {
+            version: version.take(),
+            date: std::mem::take(&mut date),
+            commits: git::range_commits(&format!("{}..{}", tag, newer))?,
+        });
+        newer = tag.clone();
+        version = Some(tag);
+        date = format_date(tag_date, &config.date_format, &timezone)?;
+    }
+    releases.push(Release {
+        version,
+        date,
+        commits: git::range_commits(&newer)?,
+    });
+    Ok(releases)
+}
+
+/// The time zone called `name` in the IANA database, such as
+/// `Europe/Berlin`, or else the system's.
+pub(crate) fn time_zone(name: Option<&str>) -> Result<TimeZone, CommitauraError> {
+    match name {
+        Some(name) => TimeZone::get(name)
+            .map_err(|e| CommitauraError::ConfigError(format!("changelog.timezone: {}", e))),
+        None => Ok(TimeZone::system()),
+    }
+}
+
+/// Unix time `timestamp` in `timezone`, formatted with strftime `format`.
+fn format_date(
+    timestamp: i64,
+    format: &str,
+    timezone: &TimeZone,
+) -> Result<String, CommitauraError> {
+    let time = Timestamp::from_second(timestamp)
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?
+        .to_zoned(timezone.clone());
+    strtime::format(format, &time)
+        .map_err(|e| CommitauraError::ConfigError(format!("changelog.date_format: {}", e)))
+}
+
+/// Render `releases` with the templates in `config`. Unreleased changes
+/// are left out when there are none; a release whose commits all fall
+/// outside the configured sections keeps its heading.
+pub fn render(releases: &[Release], config: &ChangelogConfig) -> Result<String, CommitauraError> {
+    let mut sections = Vec::new();
+    for section in &config.sections {
+        let kind = Kind::parse(&section.kind).ok_or_else(|| {
+            CommitauraError::ConfigError(format!(
+                "unknown changelog section kind `{}` (use feature, bugfix, removal, doc or misc)",
+                section.kind
+            ))
+        })?;
+        sections.push((kind, section.title.as_str()));
+    }
+
+    let mut blocks = Vec::new();
+    if !config.header.is_empty() {
+        blocks.push(template::render(&config.header, &[])?);
+    }
+    for release in releases {
+        let heading = match &release.version {
+            Some(version) => template::render(
+                &config.release,
+                &[("version", version), ("date", &release.date)],
+            )?,
+            None if release.commits.is_empty() => continue,
+            None => template::render(&config.unreleased, &[])?,
+        };
+        blocks.push(heading);
+        for (kind, title) in &sections {
+            let mut entries = Vec::new();
+            for (hash, subject) in &release.commits {
+                if Kind::classify(subject) == *kind {
+                    let text = entry_text(subject);
+                    entries.push(template::render(
+                        &config.entry,
+                        &[("text", &text), ("subject", subject), ("hash", hash)],
+                    )?);
+                }
+            }
+            if !entries.is_empty() {
+                blocks.push(template::render(&config.section, &[("title", title)])?);
+                blocks.push(entries.join("\n"));
+            }
+        }
+    }
+    Ok(blocks.join("\n\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChangelogSection;
+
+    fn releases() -> Vec<Release> {
+        let commits = |list: &[(&str, &str)]| {
+            list.iter()
+                .map(|(hash, subject)| (hash.to_string(), subject.to_string()))
+                .collect()
+        };
+        vec![
+            Release {
+                version: None,
+                date: String::new(),
+                commits: commits(&[("a1", "fix(http): retry on 503")]),
+            },
+            Release {
+                version: Some("v1.1.0".to_string()),
+                date: "2024-03-02".to_string(),
+                commits: commits(&[
+                    ("b2", "Add upload command"),
+                    ("b3", "Remove legacy flag"),
+                    ("b4", "Fix typo in help"),
+                ]),
+            },
+            Release {
+                version: Some("v1.0.0".to_string()),
+                date: "2024-01-15".to_string(),
+                commits: commits(&[("c5", "Tidy imports")]),
+            },
+        ]
+    }
+
+    #[test]
+    fn formats_dates_in_the_time_zone() {
+        let utc = time_zone(Some("UTC")).unwrap();
+        // 2024-03-01 23:30 UTC.
+        assert_eq!(format_date(1_709_335_800, "%d.%m.%Y", &utc).unwrap(), "01.03.2024");
+        assert_eq!(format_date(1_709_335_800, "%Y-%m-%d %H:%M", &utc).unwrap(), "2024-03-01 23:30");
+        assert!(time_zone(Some("Mars/Olympus_Mons")).is_err());
+    }
+
+    #[test]
+    fn renders_keep_a_changelog_by_default() {
+        let rendered = render(&releases(), &ChangelogConfig::default()).unwrap();
+        assert_eq!(
+            rendered,
+            "# Changelog\n\n## [Unreleased]\n\n### Fixed\n\n- Retry on 503.\n\n\
+             ## [v1.1.0] - 2024-03-02\n\n### Added\n\n- Add upload command.\n\n\
+             ### Removed\n\n- Remove legacy flag.\n\n### Fixed\n\n- Fix typo in help.\n\n\
+             ## [v1.0.0] - 2024-01-15\n\n### Changed\n\n- Tidy imports.\n"
+        );
+    }
+
+    #[test]
+    fn follows_the_configured_layout() {
+        let config = ChangelogConfig {
+            sections: vec![
+                ChangelogSection::new("bugfix", "Bug fixes"),
+                ChangelogSection::new("feature", "New"),
+            ],
+            header: String::new(),
+            release: "{{ date }}: {{ version }}".to_string(),
+            section: "{{ title }}:".to_string(),
+            entry: "* {{ subject }} ({{ hash }})".to_string(),
+            ..ChangelogConfig::default()
+        };
+        let mut releases = releases();
+        releases[0].commits.clear();
+        assert_eq!(
+            render(&releases, &config).unwrap(),
+            "2024-03-02: v1.1.0\n\nBug fixes:\n\n* Fix typo in help (b4)\n\n\
+             New:\n\n* Add upload command (b2)\n\n2024-01-15: v1.0.0\n"
+        );
+
+        let config = ChangelogConfig {
+            sections: vec![ChangelogSection::new("chore", "Chores")],
+            ..ChangelogConfig::default()
+        };
+        assert!(render(&releases, &config).is_err());
+    }
+}
@@ -0,0 +1,965 @@
+//! Configuration read from the user's `~/.config/commitaura/config.toml`
+//! and the repository's `.commitaura.toml`.
+//!
+//! Both files take the same keys. The repository file is laid over the
+//! global one key by key, tables included, so a project can change one
+//! `[guard]` setting and keep the user's others.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::examples::{self, Strategy as ExampleStrategy};
+use crate::generate::PromptStyle;
+use crate::gitmoji::GitmojiFormat;
+use crate::history;
+use crate::provider::Provider;
+use crate::secrets::Redactor;
+use crate::spellcheck::edit_distance;
+use crate::ticket::Placement;
+use crate::CommitauraError;
+
+pub const REPO_CONFIG_FILE: &str = ".commitaura.toml";
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Config {
+    /// API to send prompts to: `openai` (the default, also for compatible
+    /// endpoints), `anthropic` or `ollama`.
+    pub provider: Option<String>,
+    /// Model to generate with, e.g. `gpt-4o-mini`.
+    pub model: Option<String>,
+    /// Sampling temperature; lower is more predictable. `None` uses 0.7.
+    pub temperature: Option<f64>,
+    /// Prompt budget in tokens, when it should be smaller than the model's
+    /// context window, e.g. to save cost.
+    pub max_tokens: Option<usize>,
+    /// Longest message the model may write, in tokens. `None` uses 100, or
+    /// less if the model cannot write that much.
+    pub max_response_tokens: Option<u32>,
+    /// Context window of the model in tokens, for models whose window is
+    /// not built in. `None` uses the known window, or 8192 for Ollama and
+    /// 128000 otherwise.
+    pub context_window: Option<usize>,
+    /// Tokens of the context window kept free besides the reply, as a
+    /// margin for token counts that differ from the provider's. `None`
+    /// uses 256.
+    pub headroom_tokens: Option<usize>,
+    /// The kind of message to ask for: `default`, `conventional`, `brief`,
+    /// `detailed` or `gitmoji`. `None` uses the style `--pair` votes chose,
+    /// or `default`.
+    pub prompt_style: Option<PromptStyle>,
+    /// How the `gitmoji` prompt style writes gitmojis: `unicode` or
+    /// `shortcode`.
+    pub gitmoji_format: GitmojiFormat,
+    /// Generate a subject, a wrapped body and footers instead of a subject
+    /// line alone.
+    pub full_message: bool,
+    /// Base URL of the provider's API, e.g. a local proxy. Only read from
+    /// the global config.
+    pub api_base: Option<String>,
+    /// Credential store to read the API key from when `OPENAI_API_KEY` (or
+    /// `ANTHROPIC_API_KEY`) is unset: `macos`, `secret-service`, `windows` or `none`. `None` tries
+    /// the stores available on this system.
+    pub keychain: Option<String>,
+    /// Strip emoji, smart quotes and other non-ASCII characters from messages.
+    pub ascii_only: bool,
+    /// Follow-up actions offered after a successful commit, e.g.
+    /// `["create-pr", "push", "workflow:ci.yml"]`. `None` uses the defaults.
+    pub post_commit: Option<Vec<String>>,
+    /// Models offered by the model picker. `None` lists every model with known pricing.
+    pub models: Option<Vec<String>>,
+    /// Cheap model first, then stronger models that judge and refine its
+    /// draft only when it fails lint or confidence checks.
+    pub cascade: Option<Vec<String>>,
+    /// Offer this many candidate messages to pick from instead of one, like
+    /// `--candidates N`.
+    pub candidates: Option<usize>,
+    /// Faster model to retry on when the primary model exceeds `--max-wait`.
+    pub fallback_model: Option<String>,
+    /// Start sending the prompt for a very large diff while its later files
+    /// are still being tokenized. The provider must accept chunked requests.
+    pub stream_prompt: bool,
+    /// Give the model the full message of the last commit that touched the
+    /// same files, when the diff leaves room for it.
+    pub parent_message: bool,
+    /// End committed messages with a `Commitaura-Id` trailer and attach the
+    /// journaled prompt and reply to the commit as a note, for `commitaura show`.
+    pub transcript_trailer: bool,
+    /// For Rust library crates, list public API changes (via `cargo public-api`) in the prompt.
+    pub public_api: bool,
+    /// Search the forge's open issues for ones matching the changed files and
+    /// symbols, and offer the best matches to the model to reference.
+    pub related_issues: bool,
+    /// In a sparse checkout, analyze only paths inside the cone and never
+    /// download missing objects from a partial clone's remote.
+    pub sparse_cone_only: bool,
+    /// Also check generated messages with `aspell` or `hunspell`, if installed.
+    pub spellcheck: bool,
+    /// Preferred spellings enforced in generated messages, wrong -> right,
+    /// e.g. `Github = "GitHub"`.
+    pub terminology: BTreeMap<String, String>,
+    /// Conventional Commits scopes by path glob, e.g. `"src/ui/*" = "tui"`.
+    /// Where no glob matches, the scope is the changed module's directory.
+    pub scopes: BTreeMap<String, String>,
+    /// When no `prompt_style` is set, use the style that clearly won the
+    /// `--pair` votes in this repository. `None` means yes.
+    pub tune_style: Option<bool>,
+    /// Offer `.gitignore` entries when untracked build artifacts are found.
+    /// `None` means yes.
+    pub suggest_gitignore: Option<bool>,
+    /// Follow the commit message rules in `CONTRIBUTING.md` or
+    /// `.github/commit-convention.md`, if the repository has them. `None`
+    /// means yes.
+    pub guidelines: Option<bool>,
+    /// Replace the diffs of lock files, minified bundles and files marked
+    /// `linguist-generated` with a line counting their changes. `None`
+    /// means yes.
+    pub summarize_generated: Option<bool>,
+    /// Summarize each file with the model when the diff is too large for
+    /// the prompt, instead of truncating it. `None` means no, since it
+    /// takes a request per file.
+    pub file_summaries: Option<bool>,
+    /// Offer to open tracking issues for TODO and FIXME comments a commit
+    /// adds. `None` means yes.
+    pub todo_issues: Option<bool>,
+    /// Keep each generated message as the branch's draft until it is
+    /// committed, and offer to resume it for the same staged changes.
+    /// `None` means yes.
+    pub drafts: Option<bool>,
+    /// Checks run over the staged lines before a message is generated.
+    pub guard: GuardConfig,
+    /// Custom generation pipeline; `None` runs the default one.
+    pub pipeline: Option<PipelineConfig>,
+    /// Dynamic libraries whose stages are added to those pipelines can use.
+    /// Needs the `plugins` feature.
+    pub plugins: Vec<PathBuf>,
+    /// Command whose test and benchmark results can be summarized in the
+    /// body. Only run when asked for, since it executes repository code.
+    pub results: Option<ResultsConfig>,
+    /// Which configured commands may run, and what they may see.
+    pub sandbox: SandboxConfig,
+    /// Background work done by `commitaura watch`.
+    pub watch: WatchConfig,
+    /// Terminal output.
+    pub ui: UiConfig,
+    /// Changelog news fragments written with each commit.
+    pub fragments: FragmentsConfig,
+    /// The changelog written by `commitaura changelog`.
+    pub changelog: ChangelogConfig,
+    /// Local messages for trivial changes, without the model.
+    pub trivial: TrivialConfig,
+    /// The ticket taken from the branch name and added to each message.
+    pub ticket: TicketConfig,
+    /// The team's own prompts, replacing the built-in ones.
+    pub prompt: PromptConfig,
+    /// Past messages shown to the model as examples of the repository's voice.
+    pub examples: ExamplesConfig,
+    /// The recent commits listed in the prompt for context.
+    pub history: HistoryConfig,
+    /// Secrets masked in the diff before the prompt is built.
+    pub redact: RedactConfig,
+    /// The team's shared cache of messages for lock file and generated
+    /// file changes.
+    pub team_cache: TeamCacheConfig,
+    /// Models chosen by the size of the change.
+    pub routing: RoutingConfig,
+}
+
+/// The `[routing]` table. A change is small while it stays within every
+/// threshold; see [`crate::routing`].
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct RoutingConfig {
+    /// Model for small changes. `None` keeps the configured model.
+    pub small_model: Option<String>,
+    /// Model for large or multi-language changes. `None` keeps the
+    /// configured model.
+    pub large_model: Option<String>,
+    /// Most files a small change touches. `None` uses 8.
+    pub max_files: Option<usize>,
+    /// Most diff tokens of a small change. `None` uses 3000.
+    pub max_tokens: Option<usize>,
+    /// Most programming languages a small change touches. `None` uses 1.
+    pub max_languages: Option<usize>,
+}
+
+impl RoutingConfig {
+    /// Whether any size has a model of its own.
+    pub fn enabled(&self) -> bool {
+        self.small_model.is_some() || self.large_model.is_some()
+    }
+}
+
+/// The `[team_cache]` table. The token is read from the
+/// `COMMITAURA_CACHE_TOKEN` environment variable, never from a file.
+/// Only read from the global config.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct TeamCacheConfig {
+    /// Base URL of a `commitaura cache-server`, e.g.
+    /// `https://cache.internal:7878`. `COMMITAURA_CACHE_URL` overrides it.
+    /// `None` uses no shared cache.
+    pub url: Option<String>,
+}
+
+/// The `[redact]` table.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct RedactConfig {
+    /// Mask secrets at all. `None` means yes.
+    pub enabled: Option<bool>,
+    /// Also mask long random-looking strings. `None` means yes.
+    pub entropy: Option<bool>,
+    /// More secrets to mask: regular expressions by name. Where one has a
+    /// group, only the group is masked.
+    pub patterns: BTreeMap<String, String>,
+}
+
+impl RedactConfig {
+    /// The redactor to run over the diff; `None` when redaction is off.
+    pub fn redactor(&self) -> Result<Option<Redactor>, CommitauraError> {
+        if self.enabled == Some(false) {
+            return Ok(None);
+        }
+        Redactor::new(&self.patterns, self.entropy != Some(false)).map(Some)
+    }
+}
+
+/// The `[history]` table.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct HistoryConfig {
+    /// Most commits to list. `None` lists as many of the last 50 as the
+    /// context window leaves room for.
+    pub count: Option<usize>,
+    /// Include the bodies of the commits most relevant to the change.
+    /// `None` means yes.
+    pub bodies: Option<bool>,
+    /// Only list commits that touched one of the staged files.
+    pub same_files: bool,
+}
+
+impl HistoryConfig {
+    pub fn selection(&self) -> history::Selection {
+        history::Selection {
+            count: self.count,
+            bodies: self.bodies != Some(false),
+            same_files: self.same_files,
+        }
+    }
+}
+
+/// The `[examples]` table.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ExamplesConfig {
+    /// How many past messages to show in full. `None` uses 3; 0 shows none.
+    pub count: Option<usize>,
+    /// How they are picked: `representative`, `recent` or `relevant`.
+    pub strategy: ExampleStrategy,
+}
+
+impl ExamplesConfig {
+    pub fn count(&self) -> usize {
+        self.count.unwrap_or(examples::DEFAULT_COUNT)
+    }
+}
+
+/// The `[prompt]` table. Templates are checked by
+/// [`crate::template::validate_prompt`] and
+/// [`crate::template::validate_system`].
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct PromptConfig {
+    /// Replaces the system prompt. May use `{{ branch }}` and `{{ files }}`.
+    pub system: Option<String>,
+    /// Replaces the user prompt, unless `--prompt-file` is given. Must use
+    /// `{{ diff }}`, and may use `{{ history }}` (or `{{ last_commits }}`),
+    /// `{{ examples }}`, `{{ branch }}` and `{{ files }}`.
+    pub user: Option<String>,
+}
+
+/// The `[ticket]` table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct TicketConfig {
+    /// Regular expression matched against the branch name, e.g.
+    /// `[A-Z]+-\d+`. Its first capture group, or else the whole match, is
+    /// the ticket. `None` adds no ticket.
+    pub pattern: Option<String>,
+    /// Where the ticket goes: `footer` or `subject`.
+    pub placement: Placement,
+    /// Trailer token of the footer.
+    pub footer: String,
+    /// The subject with the ticket, from `{{ ticket }}` and `{{ subject }}`.
+    pub subject: String,
+}
+
+impl Default for TicketConfig {
+    fn default() -> Self {
+        TicketConfig {
+            pattern: None,
+            placement: Placement::Footer,
+            footer: "Refs".to_string(),
+            subject: "{{ ticket }} {{ subject }}".to_string(),
+        }
+    }
+}
+
+/// The `[trivial]` table. Templates may use `{{ version }}`, `{{ files }}`
+/// and `{{ file }}` respectively; an empty template leaves that kind of
+/// change to the model.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct TrivialConfig {
+    /// Write the message locally when the change is a version bump, only
+    /// reformats code, or fixes a typo in a comment or prose.
+    pub enabled: bool,
+    pub version_bump: String,
+    pub formatting: String,
+    pub typo: String,
+}
+
+impl Default for TrivialConfig {
+    fn default() -> Self {
+        TrivialConfig {
+            enabled: false,
+            version_bump: "Bump version to {{ version }}".to_string(),
+            formatting: "Format {{ files }}".to_string(),
+            typo: "Fix typo in {{ file }}".to_string(),
+        }
+    }
+}
+
+/// The `[fragments]` table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct FragmentsConfig {
+    /// Where fragments go, relative to the repository root, e.g. `changes`
+    /// or `changelog.d`. `None` writes none.
+    pub directory: Option<PathBuf>,
+    /// File extension of new fragments.
+    pub extension: String,
+    /// Fragment type for each kind of change, where it differs from the
+    /// kind's name (`feature`, `bugfix`, `removal`, `doc`, `misc`), e.g.
+    /// `bugfix = "fixed"`. An empty name writes no fragment for that kind.
+    pub types: BTreeMap<String, String>,
+}
+
+impl Default for FragmentsConfig {
+    fn default() -> Self {
+        FragmentsConfig {
+            directory: None,
+            extension: "md".to_string(),
+            types: BTreeMap::new(),
+        }
+    }
+}
+
+/// The `[changelog]` table. The templates use `{{ ... }}` placeholders:
+/// `header` none, `release` `{{ version }}` and `{{ date }}`, `section`
+/// `{{ title }}`, and `entry` `{{ text }}`, `{{ subject }}` and `{{ hash }}`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ChangelogConfig {
+    /// Time zone release dates are given in, as named in the IANA
+    /// database, e.g. `Europe/Berlin` or `UTC`. `None` uses the system's.
+    pub timezone: Option<String>,
+    /// strftime format of release dates.
+    pub date_format: String,
+    /// Sections of each release in order, each listing one kind of change:
+    /// `feature`, `bugfix`, `removal`, `doc` or `misc`. Kinds without a
+    /// section are left out.
+    pub sections: Vec<ChangelogSection>,
+    pub header: String,
+    pub release: String,
+    /// Heading of the changes since the newest tag.
+    pub unreleased: String,
+    pub section: String,
+    pub entry: String,
+}
+
+/// A `[[changelog.sections]]` entry.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ChangelogSection {
+    pub kind: String,
+    pub title: String,
+}
+
+impl ChangelogSection {
+    pub fn new(kind: &str, title: &str) -> ChangelogSection {
+        ChangelogSection {
+            kind: kind.to_string(),
+            title: title.to_string(),
+        }
+    }
+}
+
+impl Default for ChangelogConfig {
+    fn default() -> Self {
+        ChangelogConfig {
+            timezone: None,
+            date_format: "%Y-%m-%d".to_string(),
+            sections: vec![
+                ChangelogSection::new("feature", "Added"),
+                ChangelogSection::new("misc", "Changed"),
+                ChangelogSection::new("removal", "Removed"),
+                ChangelogSection::new("bugfix", "Fixed"),
+                ChangelogSection::new("doc", "Documentation"),
+            ],
+            header: "# Changelog".to_string(),
+            release: "## [{{ version }}] - {{ date }}".to_string(),
+            unreleased: "## [Unreleased]".to_string(),
+            section: "### {{ title }}".to_string(),
+            entry: "- {{ text }}".to_string(),
+        }
+    }
+}
+
+/// The `[ui]` table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct UiConfig {
+    /// Colored output; `None` decides by terminal and `NO_COLOR`.
+    pub color: Option<bool>,
+    /// Clear the screen when a command starts.
+    pub clear_screen: bool,
+    /// List recent commit messages before generating.
+    pub show_history: bool,
+    /// Compare the suggested message with the history's style.
+    pub show_style_check: bool,
+    /// Screen-reader friendly output, as with `--a11y`.
+    pub a11y: bool,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            color: None,
+            clear_screen: true,
+            show_history: true,
+            show_style_check: true,
+            a11y: false,
+        }
+    }
+}
+
+/// The `[guard]` table.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct GuardConfig {
+    /// Substrings that flag an added line. `None` uses
+    /// [`crate::guard::DEFAULT_PATTERNS`]; an empty list disables the check.
+    pub patterns: Option<Vec<String>>,
+    /// Abort instead of asking whether to continue when a line is flagged.
+    pub block: bool,
+}
+
+/// The `[pipeline]` table.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct PipelineConfig {
+    /// Stage names in order, e.g. `["filter", "generate", "lint", "assemble"]`.
+    /// Empty runs [`crate::pipeline::DEFAULT_STAGES`].
+    pub stages: Vec<String>,
+    /// Globs of paths the `filter` stage drops, e.g. `"*.lock"`.
+    pub exclude: Vec<String>,
+}
+
+/// The `[results]` table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ResultsConfig {
+    /// Shell command run at the repository root, e.g. `cargo test --quiet`.
+    pub command: String,
+    /// Seconds before the command is killed.
+    pub timeout_secs: u64,
+}
+
+impl Default for ResultsConfig {
+    fn default() -> Self {
+        ResultsConfig {
+            command: String::new(),
+            timeout_secs: 300,
+        }
+    }
+}
+
+/// The `[sandbox]` table.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct SandboxConfig {
+    /// Programs configured commands may start, e.g. `["cargo", "./bench.sh"]`.
+    /// Only read from the global config.
+    pub allow: Vec<String>,
+    /// Extra environment variables passed to them. Names that look like
+    /// credentials are dropped regardless.
+    pub pass_env: Vec<String>,
+}
+
+/// The `[watch]` table.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct WatchConfig {
+    /// Generate a message for the staged changes in the background, so
+    /// `commitaura commit` can show it at once. Off unless set, since it
+    /// spends tokens on changes that may never be committed.
+    pub pregenerate: bool,
+    /// Seconds between checks of the index.
+    pub poll_secs: u64,
+    /// Fewest seconds between two background generations.
+    pub min_interval_secs: u64,
+    /// Most background generations in any hour.
+    pub max_per_hour: usize,
+    /// Prompts estimated larger than this are left for `commitaura commit`.
+    pub max_prompt_tokens: usize,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            pregenerate: false,
+            poll_secs: 2,
+            min_interval_secs: 60,
+            max_per_hour: 10,
+            max_prompt_tokens: 8000,
+        }
+    }
+}
+
+/// Keys only the global config may set. A cloned repository must not
+/// decide where the API key and the staged diff are sent, or which programs
+/// run.
+pub const GLOBAL_ONLY: &[&str] = &["api_base", "team_cache.url", "sandbox.allow"];
+
+impl Config {
+    /// Load the global config with the config of the repository containing
+    /// the current directory laid over it. Missing files are skipped.
+    pub fn load() -> Result<Config, CommitauraError> {
+        let global = global_config_path().filter(|path| path.is_file());
+        let repo = repo_config_path().filter(|path| path.is_file());
+        Config::from_layers(global.as_deref(), repo.as_deref())
+    }
+
+    /// Lay the repository file `repo` over the global file `global`. Keys
+    /// in [`GLOBAL_ONLY`] are ignored in the repository file, with a warning.
+    pub fn from_layers(
+        global: Option<&Path>,
+        repo: Option<&Path>,
+    ) -> Result<Config, CommitauraError> {
+        let mut merged = match global {
+            Some(path) => read_table(path)?,
+            None => toml::Table::new(),
+        };
+        if let Some(path) = repo {
+            let mut table = read_table(path)?;
+            for key in strip_global_only(&mut table) {
+                log::warn!(
+                    "{}: ignoring `{}`, which only the global config may set",
+                    path.display(),
+                    key
+                );
+            }
+            overlay(&mut merged, table);
+        }
+        Config::deserialize(merged).map_err(|e| CommitauraError::ConfigError(e.to_string()))
+    }
+
+    pub fn from_path(path: &Path) -> Result<Config, CommitauraError> {
+        Config::from_paths([path])
+    }
+
+    /// Lay the files at `paths` over each other, later ones winning. Each
+    /// file is checked on its own first, so errors name the file at fault.
+    pub fn from_paths<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<Config, CommitauraError> {
+        let mut merged = toml::Table::new();
+        for path in paths {
+            overlay(&mut merged, read_table(path.as_ref())?);
+        }
+        Config::deserialize(merged).map_err(|e| CommitauraError::ConfigError(e.to_string()))
+    }
+
+    /// Settings that load but would fail, or be ignored, once used: names
+    /// of providers, keychains, pipeline stages and post-commit actions, and
+    /// numbers out of range. Each problem is one line naming the key.
+    pub fn lint(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Err(e) = self.provider() {
+            problems.push(format!("provider: {}", reason(e)));
+        }
+        if let Some(name) = &self.keychain {
+            if name != "none" && crate::keychain::Backend::parse(name).is_none() {
+                problems.push(format!(
+                    "keychain: unknown store `{}` (use macos, secret-service, windows or none)",
+                    name
+                ));
+            }
+        }
+        if let Some(t) = self.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                problems.push(format!("temperature: {} is outside 0 to 2", t));
+            }
+        }
+        if let Some(window) = self.context_window {
+            let reserved =
+                self.max_response_tokens
+                    .unwrap_or(crate::generate::MAX_RESPONSE_TOKENS) as usize
+                    + self
+                        .headroom_tokens
+                        .unwrap_or(crate::generate::HEADROOM_TOKENS);
+            if window <= reserved {
+                problems.push(format!(
+                    "context_window: {} leaves no room for the prompt after {} tokens for the reply and headroom",
+                    window, reserved
+                ));
+            }
+        }
+        match self.candidates {
+            Some(0) => problems.push("candidates: must be at least 1".to_string()),
+            Some(count) if count > crate::candidates::MAX_COUNT => problems.push(format!(
+                "candidates: at most {} are asked for, not {}",
+                crate::candidates::MAX_COUNT,
+                count
+            )),
+            _ => {}
+        }
+        if let Err(e) = crate::forge::parse_actions(self.post_commit.as_deref()) {
+            problems.push(format!("post_commit: {}", reason(e)));
+        }
+        // Plugins can add stages, so their names are only known at run time.
+        if let (Some(pipeline), true) = (&self.pipeline, self.plugins.is_empty()) {
+            if !pipeline.stages.is_empty() {
+                let registry = crate::pipeline::Registry::builtin(pipeline);
+                if let Err(e) = crate::pipeline::Pipeline::new(&registry, &pipeline.stages) {
+                    problems.push(format!("pipeline.stages: {}", reason(e)));
+                }
+            }
+        }
+        if let Err(e) = crate::fragments::directory(&self.fragments) {
+            problems.push(reason(e));
+        }
+        for section in &self.changelog.sections {
+            if crate::fragments::Kind::parse(&section.kind).is_none() {
+                problems.push(format!(
+                    "changelog.sections: unknown kind `{}` (use feature, bugfix, removal, doc or misc)",
+                    section.kind
+                ));
+            }
+        }
+        if let Err(CommitauraError::ConfigError(e)) = crate::changelog::time_zone(self.changelog.timezone.as_deref()) {
+            problems.push(e);
+        }
+        for (glob, scope) in &self.scopes {
+            let valid = !scope.is_empty()
+                && !scope.contains(|c: char| c.is_whitespace() || "():".contains(c));
+            if !valid {
+                problems.push(format!(
+                    "scopes: `{}` maps to `{}`, which is not a valid scope",
+                    glob, scope
+                ));
+            }
+        }
+        if let Some(pattern) = &self.ticket.pattern {
+            if let Err(e) = crate::ticket::extract(pattern, "") {
+                problems.push(format!("ticket.pattern: {}", reason(e)));
+            }
+        }
+        if let Err(e) = crate::template::render(&self.ticket.subject, &[("ticket", ""), ("subject", "")]) {
+            problems.push(format!("ticket.subject: {}", reason(e)));
+        }
+        if let Some(template) = &self.prompt.system {
+            if let Err(e) = crate::template::validate_system(template) {
+                problems.push(format!("prompt.system: {}", reason(e)));
+            }
+        }
+        if let Some(template) = &self.prompt.user {
+            if let Err(e) = crate::template::validate_prompt(template) {
+                problems.push(format!("prompt.user: {}", reason(e)));
+            }
+        }
+        if let Err(e) = self.redact.redactor() {
+            problems.push(format!("redact.patterns: {}", reason(e)));
+        }
+        if self.routing.enabled() && self.cascade.is_some() {
+            problems.push(
+                "routing: has no effect while cascade is set, which names its own models"
+                    .to_string(),
+            );
+        }
+        if self.routing.max_files == Some(0) {
+            problems.push("routing.max_files: must be at least 1".to_string());
+        }
+        if self.watch.poll_secs == 0 {
+            problems.push("watch.poll_secs: must be at least 1".to_string());
+        }
+        problems
+    }
+
+    /// The configuration as TOML, defaults included; unset optional keys
+    /// are left out.
+    pub fn to_toml(&self) -> Result<String, CommitauraError> {
+        toml::to_string(self).map_err(|e| CommitauraError::ConfigError(e.to_string()))
+    }
+
+    /// The configured provider; OpenAI unless set.
+    pub fn provider(&self) -> Result<Provider, CommitauraError> {
+        match &self.provider {
+            None => Ok(Provider::default()),
+            Some(name) => Provider::parse(name).ok_or_else(|| {
+                CommitauraError::ConfigError(format!(
+                    "Unknown provider '{}' (use openai, anthropic or ollama)",
+                    name
+                ))
+            }),
+        }
+    }
+}
+
+/// `key = "value"` lines for each setting, TOML-escaped.
+pub fn render_settings(settings: &[(&str, &str)]) -> String {
+    settings
+        .iter()
+        .map(|(key, value)| format!("{} = {}\n", key, toml::Value::from(*value)))
+        .collect()
+}
+
+/// What a configuration error says, without the "Configuration error" prefix.
+fn reason(error: CommitauraError) -> String {
+    match error {
+        CommitauraError::ConfigError(message) => message,
+        other => other.to_string(),
+    }
+}
+
+/// `error` from parsing `text`, with its line and, for a misspelled key or
+/// value, the closest valid one: "line 3: unknown key `modle`, did you mean
+/// `model`?".
+fn describe(error: &toml::de::Error, text: &str) -> String {
+    let message = error.message().trim_end();
+    let start = error.span().map(|span| span.start.min(text.len()));
+    let line = start.map(|at| text[..at].matches('\n').count() + 1);
+    // serde's wording: unknown field `x`, expected one of `a`, `b`, ...
+    let mut quoted = message.split('`').skip(1).step_by(2);
+    let unknown = if message.starts_with("unknown field") {
+        quoted.next().map(|name| ("key", name))
+    } else if message.starts_with("unknown variant") {
+        quoted.next().map(|name| ("value", name))
+    } else {
+        None
+    };
+    let mut described = match unknown {
+        Some((kind, name)) => {
+            let table = start.and_then(|at| table_at(&text[..at]));
+            let mut out = format!("unknown {} `{}`", kind, name);
+            if let (Some(table), "key") = (table, kind) {
+                out.push_str(&format!(" in [{}]", table));
+            }
+            let closest = quoted
+                .map(|valid| (edit_distance(name, valid), valid))
+                .min()
+                .filter(|(distance, _)| *distance <= 2.max(name.len() / 3));
+            match closest {
+                Some((_, valid)) => out.push_str(&format!(", did you mean `{}`?", valid)),
+                None => {
+                    let valid: Vec<&str> = message.split('`').skip(3).step_by(2).collect();
+                    out.push_str(&format!(" (expected one of {})", valid.join(", ")));
+                }
+            }
+            out
+        }
+        None => message.to_string(),
+    };
+    if let Some(line) = line {
+        described = format!("line {}: {}", line, described);
+    }
+    described
+}
+
+/// The name of the last `[table]` header in `text`.
+fn table_at(text: &str) -> Option<&str> {
+    text.lines().rev().find_map(|line| {
+        let line = line.trim();
+        let name = line.strip_prefix('[')?.split(']').next()?;
+        Some(name.trim_start_matches('[').trim())
+    })
+}
+
+/// The file at `path` as a table, checked as a config on its own first so
+/// errors name the file at fault.
+fn read_table(path: &Path) -> Result<toml::Table, CommitauraError> {
+    let error = |e: &dyn std::fmt::Display| {
+        CommitauraError::ConfigError(format!("{}: {}", path.display(), e))
+    };
+    let text = std::fs::read_to_string(path)?;
+    let table: toml::Table = toml::from_str(&text).map_err(|e| error(&describe(&e, &text)))?;
+    toml::from_str::<Config>(&text).map_err(|e| error(&describe(&e, &text)))?;
+    Ok(table)
+}
+
+/// Remove the [`GLOBAL_ONLY`] keys from `table`, returning those it had.
+fn strip_global_only(table: &mut toml::Table) -> Vec<&'static str> {
+    GLOBAL_ONLY
+        .iter()
+        .copied()
+        .filter(|key| {
+            let (tables, name) = match key.rsplit_once('.') {
+                Some((tables, name)) => (tables.split('.').collect(), name),
+                None => (Vec::new(), *key),
+            };
+            let mut table = &mut *table;
+            for part in tables {
+                match table.get_mut(part) {
+                    Some(toml::Value::Table(inner)) => table = inner,
+                    _ => return false,
+                }
+            }
+            table.remove(name).is_some()
+        })
+        .collect()
+}
+
+/// Set `top`'s keys in `base`, merging tables that are in both.
+fn overlay(base: &mut toml::Table, top: toml::Table) {
+    for (key, value) in top {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(value)) => overlay(base, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// `~/.config/commitaura/config.toml`, or the same under `XDG_CONFIG_HOME`.
+pub fn global_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_home.join("commitaura").join("config.toml"))
+}
+
+pub fn repo_config_path() -> Option<PathBuf> {
+    Some(crate::git::toplevel().ok()?.join(REPO_CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_keys_and_rejects_unknown_ones() {
+        let config: Config = toml::from_str("ascii_only = true\npost_commit = [\"push\"]").unwrap();
+        assert!(config.ascii_only);
+        assert_eq!(config.post_commit, Some(vec!["push".to_string()]));
+
+        assert!(toml::from_str::<Config>("asci_only = true").is_err());
+
+        let config: Config =
+            toml::from_str("[guard]\npatterns = [\"TODO\"]\nblock = true").unwrap();
+        assert_eq!(config.guard.patterns, Some(vec!["TODO".to_string()]));
+        assert!(config.guard.block);
+    }
+
+    #[test]
+    fn explains_misspelled_keys_and_values() {
+        let describe_error = |text: &str| {
+            let error = toml::from_str::<Config>(text).unwrap_err();
+            describe(&error, text)
+        };
+        assert_eq!(
+            describe_error("modle = \"gpt-4o\""),
+            "line 1: unknown key `modle`, did you mean `model`?"
+        );
+        assert_eq!(
+            describe_error("ascii_only = true\n\n[guard]\nblok = true\n"),
+            "line 4: unknown key `blok` in [guard], did you mean `block`?"
+        );
+        assert_eq!(
+            describe_error("prompt_style = \"conventinal\""),
+            "line 1: unknown value `conventinal`, did you mean `conventional`?"
+        );
+        assert!(describe_error("[ui]\nzzz = 1")
+            .starts_with("line 2: unknown key `zzz` in [ui] (expected one of color, "));
+    }
+
+    #[test]
+    fn lints_values_that_only_fail_when_used() {
+        let config: Config = toml::from_str(
+            "provider = \"openia\"\ntemperature = 3.0\npost_commit = [\"psuh\"]\n[pipeline]\nstages = [\"lint\"]",
+        )
+        .unwrap();
+        let problems = config.lint();
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(problems[0].starts_with("provider: "));
+        assert!(Config::default().lint().is_empty());
+        let tight: Config = toml::from_str("context_window = 300").unwrap();
+        assert!(tight.lint()[0].starts_with("context_window: 300 leaves no room"));
+
+        let shown = Config::default().to_toml().unwrap();
+        assert!(toml::from_str::<Config>(&shown).is_ok(), "{}", shown);
+        assert!(shown.contains("[ui]\nclear_screen = true"), "{}", shown);
+    }
+
+    #[test]
+    fn lays_the_repo_file_over_the_global_one() {
+        let dir = std::env::temp_dir().join(format!("commitaura-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (global, repo) = (dir.join("config.toml"), dir.join(REPO_CONFIG_FILE));
+        std::fs::write(&global, "model = \"gpt-4o\"\ntemperature = 0.2\n[guard]\nblock = true\n[ui]\nclear_screen = false\n").unwrap();
+        std::fs::write(&repo, "model = \"gpt-4o-mini\"\nprompt_style = \"conventional\"\n[guard]\npatterns = [\"TODO\"]\n").unwrap();
+
+        let config = Config::from_paths([&global, &repo]).unwrap();
+        assert_eq!(config.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(config.temperature, Some(0.2));
+        assert_eq!(config.prompt_style, Some(PromptStyle::Conventional));
+        assert!(config.guard.block);
+        assert_eq!(config.guard.patterns, Some(vec!["TODO".to_string()]));
+        assert!(!config.ui.clear_screen && config.ui.show_history);
+
+        std::fs::write(&global, "api_base = \"https://proxy.example/v1\"\n").unwrap();
+        std::fs::write(
+            &repo,
+            "api_base = \"https://attacker.example/v1\"\nmodel = \"gpt-4o\"\n",
+        )
+        .unwrap();
+        let config = Config::from_layers(Some(&global), Some(&repo)).unwrap();
+        assert_eq!(config.api_base.as_deref(), Some("https://proxy.example/v1"));
+        assert_eq!(config.model.as_deref(), Some("gpt-4o"));
+        let config = Config::from_layers(None, Some(&repo)).unwrap();
+        assert_eq!(config.api_base, None);
+
+        std::fs::write(&repo, "prompt_style = \"haiku\"\n").unwrap();
+        let error = Config::from_paths([&global, &repo]).unwrap_err().to_string();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(error.contains(REPO_CONFIG_FILE), "{}", error);
+    }
+}
@@ -0,0 +1,101 @@
+//! Detection of staged work that continues the previous commit.
+//!
+//! When the staged paths overlap HEAD's and HEAD is recent, the user is
+//! offered to amend HEAD or to phrase the new message as a follow-up.
+
+use crate::{git, CommitauraError};
+
+/// How recent HEAD must be for staged work to count as a continuation.
+pub const MAX_GAP_SECS: u64 = 30 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Continuation {
+    pub previous_subject: String,
+    /// Staged paths that HEAD also touched.
+    pub shared_paths: Vec<String>,
+    pub seconds_since: u64,
+    /// HEAD is already on a remote branch, so amending would rewrite published history.
+    pub published: bool,
+}
+
+/// Pure decision: the staged change continues HEAD if at least half of its
+/// paths were also touched by HEAD and HEAD is younger than `max_gap`.
+pub fn detect(
+    staged: &[String],
+    head_paths: &[String],
+    seconds_since: u64,
+    max_gap: u64,
+) -> Option<Vec<String>> {
+    if staged.is_empty() || seconds_since > max_gap {
+        return None;
+    }
+    let shared: Vec<String> = staged
+        .iter()
+        .filter(|p| head_paths.contains(p))
+        .cloned()
+        .collect();
+    if shared.len() * 2 >= staged.len() && !shared.is_empty() {
+        Some(shared)
+    } else {
+        None
+    }
+}
+
+/// Check the repository's staged changes against HEAD.
+pub fn detect_in_repo() -> Result<Option<Continuation>, CommitauraError> {
+    // A root commit cannot be amended into meaningfully, and has no history to continue.
+    if !git::has_parent("HEAD") {
+        return Ok(None);
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let seconds_since = now.saturating_sub(git::commit_time("HEAD")?);
+    let staged = git::staged_paths()?;
+    let head_paths = git::commit_paths("HEAD")?;
+
+    Ok(
+        detect(&staged, &head_paths, seconds_since, MAX_GAP_SECS).map(|shared_paths| {
+            Continuation {
+                previous_subject: git::commit_message("HEAD")
+                    .map(|m| m.lines().next().unwrap_or("").to_string())
+                    .unwrap_or_default(),
+                shared_paths,
+                seconds_since,
+                published: git::is_published("HEAD"),
+            }
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn detects_recent_overlapping_work() {
+        let head = paths(&["src/a.rs", "src/b.rs"]);
+        assert_eq!(
+            detect(&paths(&["src/a.rs", "README.md"]), &head, 60, MAX_GAP_SECS),
+            Some(paths(&["src/a.rs"]))
+        );
+        assert_eq!(
+            detect(&paths(&["src/a.rs"]), &head, MAX_GAP_SECS + 1, MAX_GAP_SECS),
+            None
+        );
+        assert_eq!(
+            detect(
+                &paths(&["src/c.rs", "src/d.rs", "src/a.rs"]),
+                &head,
+                60,
+                MAX_GAP_SECS
+            ),
+            None
+        );
+    }
+}
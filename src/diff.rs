@@ -0,0 +1,251 @@
+//! Minimal parser for the unified diff format produced by `git diff`.
+//!
+//! Only the pieces Commitaura needs are modelled: file headers, the change
+//! status of each file and the hunks with their added/removed lines.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The raw `@@ -a,b +c,d @@ context` line.
+    pub header: String,
+    /// Lines of the hunk body, each still carrying its `+`, `-` or ` ` prefix.
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    /// Function or section name git printed after the range, if any.
+    pub fn context(&self) -> Option<&str> {
+        let rest = self.header.strip_prefix("@@")?;
+        let end = rest.find("@@")?;
+        let context = rest[end + 2..].trim();
+        if context.is_empty() {
+            None
+        } else {
+            Some(context)
+        }
+    }
+
+    /// First line number of the hunk in the new file (the `c` in `+c,d`).
+    pub fn new_start(&self) -> Option<usize> {
+        let range = self.header.split_whitespace().find(|w| w.starts_with('+'))?;
+        range[1..].split(',').next()?.parse().ok()
+    }
+
+    /// The line number of each of `lines` in the new file; `None` for
+    /// removed lines, or when the header has no range.
+    pub fn new_line_numbers(&self) -> Vec<Option<usize>> {
+        let mut next = self.new_start();
+        self.lines
+            .iter()
+            .map(|line| {
+                if line.starts_with('-') {
+                    return None;
+                }
+                let number = next;
+                next = next.map(|n| n + 1);
+                number
+            })
+            .collect()
+    }
+
+    /// Rewrite the counts in the header's ranges to match `lines`, after
+    /// some were dropped, so the hunk still reads as a valid patch.
+    pub fn recount(&mut self) {
+        let Some(rest) = self.header.strip_prefix("@@ ") else {
+            return;
+        };
+        let Some((ranges, context)) = rest.split_once(" @@") else {
+            return;
+        };
+        let (Some(old), Some(new)) = (
+            ranges.split_whitespace().find_map(|w| w.strip_prefix('-')),
+            ranges.split_whitespace().find_map(|w| w.strip_prefix('+')),
+        ) else {
+            return;
+        };
+        let count = |prefix: char| {
+            self.lines
+                .iter()
+                .filter(|l| l.starts_with(prefix) || l.starts_with(' '))
+                .count()
+        };
+        let start = |range: &str| range.split(',').next().unwrap_or(range).to_string();
+        self.header = format!(
+            "@@ -{},{} +{},{} @@{}",
+            start(old),
+            count('-'),
+            start(new),
+            count('+'),
+            context
+        );
+    }
+
+    pub fn added(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|l| l.strip_prefix('+'))
+    }
+
+    pub fn removed(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|l| l.strip_prefix('-'))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: String,
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    pub binary: bool,
+    /// Everything from `diff --git` up to the first hunk.
+    pub header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileDiff {
+    pub fn additions(&self) -> usize {
+        self.hunks.iter().map(|h| h.added().count()).sum()
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.hunks.iter().map(|h| h.removed().count()).sum()
+    }
+
+    /// Re-render this file as unified diff text.
+    pub fn to_patch(&self) -> String {
+        let mut out = String::new();
+        for line in &self.header {
+            out.push_str(line);
+            out.push('\n');
+        }
+        for hunk in &self.hunks {
+            out.push_str(&hunk.header);
+            out.push('\n');
+            for line in &hunk.lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// Render a set of files back into a single diff.
+pub fn render(files: &[FileDiff]) -> String {
+    files.iter().map(FileDiff::to_patch).collect()
+}
+
+/// Parse the output of `git diff` into per-file sections.
+pub fn parse(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+
+    for line in diff.lines() {
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            let path = rest
+                .rsplit_once(" b/")
+                .map(|(_, b)| b.to_string())
+                .unwrap_or_else(|| rest.to_string());
+            files.push(FileDiff {
+                path,
+                old_path: None,
+                status: FileStatus::Modified,
+                binary: false,
+                header: vec![line.to_string()],
+                hunks: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(file) = files.last_mut() else {
+            continue;
+        };
+
+        if line.starts_with("@@") {
+            file.hunks.push(Hunk {
+                header: line.to_string(),
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            if line.starts_with('+')
+                || line.starts_with('-')
+                || line.starts_with(' ')
+                || line.starts_with('\\')
+                || line.is_empty()
+            {
+                hunk.lines.push(line.to_string());
+            }
+        } else {
+            if line.starts_with("new file mode") {
+                file.status = FileStatus::Added;
+            } else if line.starts_with("deleted file mode") {
+                file.status = FileStatus::Deleted;
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                file.status = FileStatus::Renamed;
+                file.old_path = Some(from.to_string());
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                file.binary = true;
+            }
+            file.header.push(line.to_string());
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@ fn main() {
+ use std::io;
+-fn old() {}
++fn new() {}
++fn newer() {}
+diff --git a/docs/new.md b/docs/new.md
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/docs/new.md
+@@ -0,0 +1 @@
++# Title
+";
+
+    #[test]
+    fn parses_files_and_hunks() {
+        let files = parse(SAMPLE);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/lib.rs");
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert_eq!(files[0].additions(), 2);
+        assert_eq!(files[0].deletions(), 1);
+        assert_eq!(files[0].hunks[0].context(), Some("fn main() {"));
+        assert_eq!(files[0].hunks[0].new_start(), Some(1));
+        assert_eq!(files[1].status, FileStatus::Added);
+    }
+
+    #[test]
+    fn recounts_cut_hunks() {
+        let mut hunk = parse(SAMPLE).remove(0).hunks.remove(0);
+        hunk.lines.truncate(3);
+        hunk.recount();
+        assert_eq!(hunk.header, "@@ -1,2 +1,2 @@ fn main() {");
+        let mut added = parse(SAMPLE).remove(1).hunks.remove(0);
+        added.recount();
+        assert_eq!(added.header, "@@ -0,0 +1,1 @@");
+    }
+
+    #[test]
+    fn round_trips_patch_text() {
+        assert_eq!(render(&parse(SAMPLE)), SAMPLE);
+    }
+}
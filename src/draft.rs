@@ -0,0 +1,155 @@
+//! Messages kept per branch until they are committed, so a cancelled
+//! session or a crashed editor does not waste the generation.
+//!
+//! Each branch has at most one draft in `.git/commitaura/drafts.json`,
+//! stored with a hash of the index it was generated for. A draft is only
+//! offered again while the index still has that hash: staging anything
+//! else makes it stale, and the next draft for the branch replaces it.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{git, CommitauraError};
+
+const DRAFTS_FILE: &str = "drafts.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Draft {
+    /// [`git::index_fingerprint`] of the index the message describes.
+    pub tree: String,
+    pub message: String,
+    /// Seconds since the Unix epoch.
+    pub saved: u64,
+}
+
+impl Draft {
+    /// How long ago the draft was saved, e.g. "12 min ago".
+    pub fn age(&self) -> String {
+        ago(now().saturating_sub(self.saved))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// `seconds` as "just now", "12 min ago", "5 h ago" or "3 days ago".
+fn ago(seconds: u64) -> String {
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        3600..=86399 => format!("{} h ago", seconds / 3600),
+        _ => match seconds / 86400 {
+            1 => "1 day ago".to_string(),
+            days => format!("{} days ago", days),
+        },
+    }
+}
+
+/// The drafts of the current repository, in its `.git/commitaura/` directory.
+pub fn drafts_path() -> Result<PathBuf, CommitauraError> {
+    Ok(git::git_dir()?.join("commitaura").join(DRAFTS_FILE))
+}
+
+/// The branch and index fingerprint a draft of the current repository is
+/// kept under. A detached HEAD counts as the branch `HEAD`. `None` while
+/// the index has unresolved conflicts. Nothing is written, so drafts can
+/// be looked up in read-only mode.
+pub fn current_key() -> Option<(String, String)> {
+    let branch = match git::symbolic_head() {
+        Some(head) => head
+            .strip_prefix("refs/heads/")
+            .unwrap_or(&head)
+            .to_string(),
+        None => "HEAD".to_string(),
+    };
+    Some((branch, git::index_fingerprint().ok()??))
+}
+
+fn load(path: &Path) -> BTreeMap<String, Draft> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn write(path: &Path, drafts: &BTreeMap<String, Draft>) -> Result<(), CommitauraError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Renamed into place, so a run killed while writing keeps the old drafts.
+    let temp = path.with_extension("tmp");
+    std::fs::write(
+        &temp,
+        serde_json::to_string_pretty(drafts).map_err(std::io::Error::from)?,
+    )?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// The draft `branch` has in the drafts at `path`, if it was saved for
+/// `tree`.
+pub fn find(path: &Path, branch: &str, tree: &str) -> Option<Draft> {
+    load(path).remove(branch).filter(|draft| draft.tree == tree)
+}
+
+/// Keep `message` as `branch`'s draft for `tree`, replacing the one it had.
+pub fn save(path: &Path, branch: &str, tree: &str, message: &str) -> Result<(), CommitauraError> {
+    let mut drafts = load(path);
+    drafts.insert(
+        branch.to_string(),
+        Draft {
+            tree: tree.to_string(),
+            message: message.to_string(),
+            saved: now(),
+        },
+    );
+    write(path, &drafts)
+}
+
+/// Forget `branch`'s draft, once its message is committed.
+pub fn discard(path: &Path, branch: &str) -> Result<(), CommitauraError> {
+    let mut drafts = load(path);
+    if drafts.remove(branch).is_some() {
+        write(path, &drafts)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_one_draft_per_branch_for_its_tree() {
+        let path = std::env::temp_dir().join(format!("commitaura-drafts-{}", std::process::id()));
+        assert_eq!(find(&path, "main", "t1"), None);
+        save(&path, "main", "t1", "Add parser").unwrap();
+        save(&path, "feature", "t2", "Fix lexer").unwrap();
+        assert_eq!(find(&path, "main", "t1").unwrap().message, "Add parser");
+        assert_eq!(find(&path, "main", "t2"), None);
+
+        save(&path, "main", "t3", "Add parser tests").unwrap();
+        assert_eq!(find(&path, "main", "t1"), None);
+        assert_eq!(find(&path, "main", "t3").unwrap().age(), "just now");
+
+        discard(&path, "main").unwrap();
+        assert_eq!(find(&path, "main", "t3"), None);
+        assert_eq!(find(&path, "feature", "t2").unwrap().message, "Fix lexer");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn describes_the_age() {
+        assert_eq!(ago(30), "just now");
+        assert_eq!(ago(12 * 60), "12 min ago");
+        assert_eq!(ago(5 * 3600), "5 h ago");
+        assert_eq!(ago(86400), "1 day ago");
+        assert_eq!(ago(3 * 86400), "3 days ago");
+    }
+}
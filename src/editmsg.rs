@@ -0,0 +1,280 @@
+//! Writing into git's commit message file (`.git/COMMIT_EDITMSG`, or the
+//! file passed to a `prepare-commit-msg` hook).
+//!
+//! By the time the file reaches us git has already filled it with the
+//! `commit.template` contents and its own help comments. The generated
+//! message is merged into that scaffolding instead of replacing it, using the
+//! repository's `core.commentChar`.
+
+use std::path::Path;
+
+use crate::{git, CommitauraError};
+
+/// Characters git tries, in order, when `core.commentChar` is `auto`.
+const AUTO_COMMENT_CHARS: &str = "#;@!$%^&|:";
+
+/// What follows the comment character on the scissors line `commit.verbose`
+/// puts above the diff.
+const CUT_LINE: &str = "------------------------ >8 ------------------------";
+
+/// git's `--cleanup` modes, set for every commit by `commit.cleanup`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cleanup {
+    /// Drop comment lines, then clean up whitespace.
+    Strip,
+    /// Drop trailing whitespace and leading, trailing and repeated blank lines.
+    Whitespace,
+    /// Leave the message as it is.
+    Verbatim,
+    /// Like `Whitespace`, but an edited message ends at the scissors line.
+    Scissors,
+    /// `Strip` for an edited message, `Whitespace` otherwise.
+    Default,
+}
+
+impl Cleanup {
+    pub fn parse(s: &str) -> Option<Cleanup> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "strip" => Some(Cleanup::Strip),
+            "whitespace" => Some(Cleanup::Whitespace),
+            "verbatim" => Some(Cleanup::Verbatim),
+            "scissors" => Some(Cleanup::Scissors),
+            "default" => Some(Cleanup::Default),
+            _ => None,
+        }
+    }
+
+    /// The mode `commit.cleanup` sets; `Default` if unset or unknown.
+    pub fn configured() -> Cleanup {
+        git::config_value("commit.cleanup")
+            .and_then(|value| Cleanup::parse(&value))
+            .unwrap_or(Cleanup::Default)
+    }
+
+    /// Whether git drops comment lines from a message that was or was not
+    /// `edited`.
+    pub fn strips_comments(self, edited: bool) -> bool {
+        self.resolve(edited) == Cleanup::Strip
+    }
+
+    /// The mode git applies to a message that was or was not `edited`.
+    fn resolve(self, edited: bool) -> Cleanup {
+        match self {
+            Cleanup::Default if edited => Cleanup::Strip,
+            Cleanup::Default | Cleanup::Scissors if !edited => Cleanup::Whitespace,
+            mode => mode,
+        }
+    }
+}
+
+/// Clean up `message` as `git commit --cleanup=<mode>` would, for a message
+/// the user `edited` in their editor. An edited message ends at the scissors
+/// line, where git cuts off the `commit.verbose` diff, whatever the mode.
+/// Unlike git's, the result has no trailing newline.
+pub fn cleanup(message: &str, mode: Cleanup, edited: bool, comment: char) -> String {
+    let mode = mode.resolve(edited);
+    let message = if edited {
+        let scissors = format!("{} {}", comment, CUT_LINE);
+        let mut offset = 0;
+        let mut end = message.len();
+        for line in message.split_inclusive('\n') {
+            if line.trim_end() == scissors {
+                end = offset;
+                break;
+            }
+            offset += line.len();
+        }
+        &message[..end]
+    } else {
+        message
+    };
+    if mode == Cleanup::Verbatim {
+        return message.to_string();
+    }
+    let mut lines: Vec<&str> = Vec::new();
+    let mut blank = false;
+    for line in message.lines() {
+        if mode == Cleanup::Strip && line.starts_with(comment) {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if blank && !lines.is_empty() {
+            lines.push("");
+        }
+        blank = false;
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// Indent the lines of `message` that start with the comment character by
+/// one space, so git's cleanup keeps them.
+pub fn protect(message: &str, comment: char) -> String {
+    message
+        .lines()
+        .map(|line| {
+            if line.starts_with(comment) {
+                format!(" {}", line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `message` as it should be passed to `git commit -F`. When `commit.cleanup`
+/// strips comments even from messages that are not edited, lines such as
+/// `#123 is fixed` are protected.
+pub fn for_commit(message: &str) -> String {
+    if Cleanup::configured().strips_comments(false) {
+        protect(message, comment_char(message))
+    } else {
+        message.to_string()
+    }
+}
+
+/// The comment character git will use when cleaning up `message`.
+pub fn comment_char(message: &str) -> char {
+    let configured = git::config_value("core.commentChar");
+    resolve_comment_char(configured.as_deref(), message)
+}
+
+fn resolve_comment_char(configured: Option<&str>, message: &str) -> char {
+    match configured {
+        Some("auto") => AUTO_COMMENT_CHARS
+            .chars()
+            .find(|c| !message.lines().any(|l| l.starts_with(*c)))
+            .unwrap_or('#'),
+        Some(value) => value.chars().next().unwrap_or('#'),
+        None => '#',
+    }
+}
+
+/// Merge `generated` into the `existing` message file contents.
+///
+/// The generated message comes first. Non-comment lines from a commit
+/// template (required sections such as `Ticket:` or `Signed-off-by:`) follow
+/// unless the message already contains them, then git's comment block and,
+/// for `commit.verbose`, everything from the scissors line on. Generated
+/// lines that start with the comment character are indented by one space so
+/// git's cleanup does not drop them.
+pub fn merge(generated: &str, existing: &str, comment: char) -> String {
+    let scissors = format!("{} {}", comment, CUT_LINE);
+    let (head, verbose) = match existing.find(&scissors) {
+        Some(at) => existing.split_at(at),
+        None => (existing, ""),
+    };
+
+    let protected = protect(generated, comment);
+    let message: Vec<&str> = protected.lines().collect();
+
+    let (comments, sections): (Vec<&str>, Vec<&str>) =
+        head.lines().partition(|line| line.starts_with(comment));
+    let sections: Vec<&str> = sections
+        .into_iter()
+        .filter(|line| !message.iter().any(|m| m.trim() == line.trim() && !m.trim().is_empty()))
+        .collect();
+    let sections = sections.join("\n");
+
+    let mut out = message.join("\n");
+    out.push('\n');
+    if !sections.trim().is_empty() {
+        out.push('\n');
+        out.push_str(sections.trim_matches('\n'));
+        out.push('\n');
+    }
+    if !comments.is_empty() {
+        out.push('\n');
+        out.push_str(&comments.join("\n"));
+        out.push('\n');
+    }
+    out.push_str(verbose);
+    out
+}
+
+/// Merge `generated` into the message file at `path`. A missing file is
+/// treated as empty, in which case the configured `commit.template` (if any)
+/// supplies the sections to keep.
+pub fn write_message_file(path: &Path, generated: &str) -> Result<(), CommitauraError> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => template().unwrap_or_default(),
+        Err(e) => return Err(e.into()),
+    };
+    let comment = comment_char(generated);
+    std::fs::write(path, merge(generated, &existing, comment))?;
+    Ok(())
+}
+
+/// Contents of the file named by `commit.template`, if one is configured.
+pub fn template() -> Option<String> {
+    let path = git::config_path("commit.template")?;
+    std::fs::read_to_string(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_template_sections_and_comments() {
+        let existing = "\nTicket: \n\n; Please enter the commit message.\n; Lines starting with ';' will be ignored.\n";
+        let merged = merge("Add tag command\n\n; not a comment", existing, ';');
+        assert_eq!(
+            merged,
+            "Add tag command\n\n ; not a comment\n\nTicket: \n\n; Please enter the commit message.\n; Lines starting with ';' will be ignored.\n"
+        );
+    }
+
+    #[test]
+    fn preserves_verbose_diff_after_scissors() {
+        let existing = "# Please enter the commit message.\n# ------------------------ >8 ------------------------\ndiff --git a/x b/x\n+#include <x>\n";
+        let merged = merge("Add x", existing, '#');
+        assert!(merged.starts_with("Add x\n\n# Please enter"));
+        assert!(merged.ends_with(">8 ------------------------\ndiff --git a/x b/x\n+#include <x>\n"));
+    }
+
+    #[test]
+    fn cleans_up_like_git() {
+        let edited = "\n\nFix parser  \n\n\n#123 was the report\n# Please enter the commit message.\n\n# ------------------------ >8 ------------------------\ndiff --git a/x b/x\n";
+        assert_eq!(
+            cleanup(edited, Cleanup::Default, true, '#'),
+            "Fix parser"
+        );
+        assert_eq!(
+            cleanup(edited, Cleanup::Whitespace, true, '#'),
+            "Fix parser\n\n#123 was the report\n# Please enter the commit message."
+        );
+        assert_eq!(
+            cleanup("Fix parser\n\n#123 was the report\n", Cleanup::Default, false, '#'),
+            "Fix parser\n\n#123 was the report"
+        );
+        assert_eq!(
+            cleanup("Fix parser\n\n#123 was the report", Cleanup::Strip, false, '#'),
+            "Fix parser"
+        );
+        assert_eq!(
+            cleanup(edited, Cleanup::Verbatim, true, '#'),
+            "\n\nFix parser  \n\n\n#123 was the report\n# Please enter the commit message.\n\n"
+        );
+        let unedited = "Fix parser\n# ------------------------ >8 ------------------------\n";
+        assert_eq!(cleanup(unedited, Cleanup::Verbatim, false, '#'), unedited);
+        assert_eq!(
+            protect("Fix parser\n#123 was the report", '#'),
+            "Fix parser\n #123 was the report"
+        );
+        assert_eq!(Cleanup::parse("Scissors"), Some(Cleanup::Scissors));
+    }
+
+    #[test]
+    fn auto_comment_char_avoids_characters_used_by_the_message() {
+        assert_eq!(resolve_comment_char(Some("auto"), "#123 fix\n;x"), '@');
+        assert_eq!(resolve_comment_char(Some(";"), "x"), ';');
+        assert_eq!(resolve_comment_char(None, "x"), '#');
+    }
+}
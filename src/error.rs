@@ -0,0 +1,48 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum CommitauraError {
+    #[error("Tokenizer error: {0}")]
+    TokenizerError(String),
+    #[error("No staged changes detected")]
+    NoStagedChanges,
+    #[error("Git operation failed: {0}")]
+    GitOperationFailed(String),
+    #[error("API request failed: {0}")]
+    ApiRequestFailed(String),
+    #[error("No response from the API within {0:?}")]
+    Timeout(std::time::Duration),
+    #[error("Environment variable not set: {0}")]
+    EnvVarNotSet(String),
+    #[error("OpenAI API error: {0}")]
+    OpenAIError(String),
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+    #[error("Prompt template error: {0}")]
+    PromptTemplateError(String),
+    #[error("Review file error: {0}")]
+    ReviewFileError(String),
+    #[error("Command not allowed: {0}")]
+    CommandNotAllowed(String),
+    #[error("Read-only mode: `git {0}` would change the repository")]
+    ReadOnly(String),
+    #[error("Staged changes contain {0} line(s) matching guardrail patterns")]
+    GuardrailViolation(usize),
+    /// How many messages break the rules, of how many were checked.
+    #[error("{0} of {1} message(s) break the rules")]
+    RulesBroken(usize, usize),
+    /// Each secret's kind, first characters and where it is.
+    #[error("The prompt still contains {} secret(s), so nothing was sent", .0.len())]
+    SecretsInPrompt(Vec<String>),
+    #[error("The reply does not look like a commit message: {0}; text in the changes may have steered the model")]
+    SuspiciousReply(String),
+    #[cfg(feature = "cli")]
+    #[error("Template error: {0}")]
+    TemplateError(#[from] indicatif::style::TemplateError),
+    #[cfg(feature = "cli")]
+    #[error("Dialoguer error: {0}")]
+    DialoguerError(#[from] dialoguer::Error),
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+}
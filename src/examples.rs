@@ -0,0 +1,155 @@
+//! Past commit messages shown to the model as few-shot examples, so the
+//! messages it writes match the repository's own voice and format.
+//!
+//! Examples are picked from the same pool of recent commits as the history
+//! context. Merges, reverts and autosquash commits are never picked, since
+//! their messages are written by git rather than by people, and a message
+//! is only shown once however often it was used.
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::{self, HistoryCommit};
+
+/// Examples shown when none is configured.
+pub const DEFAULT_COUNT: usize = 3;
+
+/// Longest example body shown, in lines; longer ones are cut.
+const MAX_BODY_LINES: usize = 12;
+
+/// How examples are picked from the recent commits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Spread evenly over the recent commits, so one burst of similar work
+    /// does not set the tone.
+    #[default]
+    Representative,
+    /// The newest commits.
+    Recent,
+    /// Commits that touched the same files as the change, then the newest.
+    Relevant,
+}
+
+/// Up to `count` examples from `commits` (newest first), picked by
+/// `strategy`; `paths` are the changed files. Listed newest first.
+pub fn select<'a>(
+    commits: &'a [HistoryCommit],
+    paths: &[String],
+    count: usize,
+    strategy: Strategy,
+) -> Vec<&'a HistoryCommit> {
+    let mut eligible: Vec<(usize, &HistoryCommit)> = Vec::new();
+    for (i, commit) in commits.iter().enumerate() {
+        let repeated = eligible
+            .iter()
+            .any(|(_, seen)| seen.subject == commit.subject && seen.body == commit.body);
+        if is_authored(&commit.subject) && !repeated {
+            eligible.push((i, commit));
+        }
+    }
+    if count == 0 || eligible.is_empty() {
+        return Vec::new();
+    }
+
+    let mut picked: Vec<(usize, &HistoryCommit)> = match strategy {
+        Strategy::Recent => eligible.into_iter().take(count).collect(),
+        Strategy::Relevant => {
+            let mut ranked = eligible;
+            ranked.sort_by_key(|&(i, c)| (std::cmp::Reverse(history::relevance(c, paths)), i));
+            ranked.into_iter().take(count).collect()
+        }
+        Strategy::Representative => {
+            let n = eligible.len();
+            let count = count.min(n);
+            // The middle of each of `count` equal stretches.
+            (0..count)
+                .map(|k| eligible[(2 * k + 1) * n / (2 * count)])
+                .collect()
+        }
+    };
+    picked.sort_by_key(|&(i, _)| i);
+    picked.into_iter().map(|(_, c)| c).collect()
+}
+
+/// `examples` as full messages separated by `---` lines.
+pub fn render(examples: &[&HistoryCommit]) -> String {
+    examples
+        .iter()
+        .map(|c| {
+            let mut lines: Vec<&str> = c.body.lines().collect();
+            if lines.is_empty() {
+                return c.subject.clone();
+            }
+            if lines.len() > MAX_BODY_LINES {
+                lines.truncate(MAX_BODY_LINES);
+                lines.push("[...]");
+            }
+            format!("{}\n\n{}", c.subject, lines.join("\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n---\n")
+}
+
+/// Whether a subject was written by a person rather than by git or an
+/// autosquash workflow.
+fn is_authored(subject: &str) -> bool {
+    const GENERATED: &[&str] = &["Merge ", "Revert \"", "fixup! ", "squash! ", "amend! "];
+    !GENERATED.iter().any(|prefix| subject.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(subject: &str, files: &[&str]) -> HistoryCommit {
+        HistoryCommit {
+            subject: subject.to_string(),
+            body: String::new(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    fn subjects(examples: Vec<&HistoryCommit>) -> Vec<&str> {
+        examples.iter().map(|c| c.subject.as_str()).collect()
+    }
+
+    #[test]
+    fn picks_authored_messages_by_strategy() {
+        let commits = [
+            commit("Merge branch 'main' into topic", &[]),
+            commit("Add retries", &["src/net.rs"]),
+            commit("fixup! Add retries", &["src/net.rs"]),
+            commit("Tidy docs", &["README.md"]),
+            commit("Bump version", &["Cargo.toml"]),
+            commit("Tidy docs", &["README.md"]),
+            commit("Cache lookups", &["src/db.rs"]),
+            commit("Time out slow requests", &["src/net.rs"]),
+        ];
+        let paths = ["src/net.rs".to_string()];
+        let pick = |strategy| subjects(select(&commits, &paths, 2, strategy));
+
+        assert_eq!(pick(Strategy::Recent), ["Add retries", "Tidy docs"]);
+        assert_eq!(
+            pick(Strategy::Relevant),
+            ["Add retries", "Time out slow requests"]
+        );
+        assert_eq!(
+            pick(Strategy::Representative),
+            ["Tidy docs", "Cache lookups"]
+        );
+        assert!(select(&commits, &paths, 0, Strategy::Recent).is_empty());
+    }
+
+    #[test]
+    fn renders_full_messages_with_long_bodies_cut() {
+        let mut long = commit("Rework the parser", &[]);
+        long.body = (1..=20)
+            .map(|n| format!("line {}", n))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let short = commit("Fix typo", &[]);
+        let text = render(&[&short, &long]);
+        assert!(text.starts_with("Fix typo\n---\nRework the parser\n\nline 1\n"));
+        assert!(text.ends_with("line 12\n[...]"), "{}", text);
+    }
+}
@@ -0,0 +1,675 @@
+//! Post-commit follow-up actions (local fix-ups and forge integrations) and
+//! pull request creation on GitHub, GitLab and Bitbucket.
+//!
+//! GitHub goes through the `gh` CLI. GitLab uses its REST API when
+//! `GITLAB_TOKEN` is set and `glab` otherwise. Bitbucket has no official CLI,
+//! so it always goes through the REST API with `BITBUCKET_TOKEN`.
+
+use std::process::Command;
+use std::str::FromStr;
+
+use serde_json::json;
+
+use crate::http;
+use crate::CommitauraError;
+
+/// Actions offered when `post_commit` is not configured.
+pub const DEFAULT_POST_COMMIT: &[&str] = &["create-pr", "push", "amend", "wip", "undo"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+    Bitbucket,
+}
+
+impl Forge {
+    /// Guess the hosting service from a remote URL.
+    pub fn detect(remote_url: &str) -> Option<Forge> {
+        RemoteRepo::parse(remote_url).map(|repo| repo.forge)
+    }
+
+    /// The CLI used to talk to this forge, if there is one.
+    pub fn cli(self) -> Option<&'static str> {
+        match self {
+            Forge::GitHub => Some("gh"),
+            Forge::GitLab => Some("glab"),
+            Forge::Bitbucket => None,
+        }
+    }
+
+    /// What this forge calls a pull request.
+    pub fn pr_noun(self) -> &'static str {
+        match self {
+            Forge::GitHub | Forge::Bitbucket => "pull request",
+            Forge::GitLab => "merge request",
+        }
+    }
+
+    /// Longest description the forge accepts, in characters.
+    pub fn description_limit(self) -> usize {
+        match self {
+            Forge::GitHub => 65_536,
+            Forge::GitLab => 1_048_576,
+            Forge::Bitbucket => 32_768,
+        }
+    }
+
+    /// Markdown dialect to ask the model for.
+    pub fn markdown_flavor(self) -> &'static str {
+        match self {
+            Forge::GitHub => "GitHub Flavored Markdown",
+            Forge::GitLab => "GitLab Flavored Markdown",
+            Forge::Bitbucket => {
+                "plain CommonMark (Bitbucket renders no HTML, task lists or collapsible sections)"
+            }
+        }
+    }
+}
+
+/// A repository on a forge, as identified by a remote URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteRepo {
+    pub forge: Forge,
+    pub host: String,
+    /// `owner/name`, or `group/subgroup/name` on GitLab.
+    pub path: String,
+}
+
+impl RemoteRepo {
+    /// Parse `git@host:owner/repo.git`, `ssh://git@host[:port]/owner/repo` or
+    /// `https://host/owner/repo.git`.
+    pub fn parse(url: &str) -> Option<RemoteRepo> {
+        let url = url.trim();
+        let (host, path) = if let Some((_, rest)) = url.split_once("://") {
+            let rest = rest.rsplit_once('@').map(|(_, r)| r).unwrap_or(rest);
+            let (host, path) = rest.split_once('/')?;
+            (host.split(':').next()?, path)
+        } else {
+            let rest = url.rsplit_once('@').map(|(_, r)| r).unwrap_or(url);
+            rest.split_once(':')?
+        };
+        let host = host.to_ascii_lowercase();
+        let path = path.trim_matches('/').trim_end_matches(".git").to_string();
+        let forge = if host.contains("github") {
+            Forge::GitHub
+        } else if host.contains("gitlab") {
+            Forge::GitLab
+        } else if host.contains("bitbucket") {
+            Forge::Bitbucket
+        } else {
+            return None;
+        };
+        if path.is_empty() {
+            return None;
+        }
+        Some(RemoteRepo { forge, host, path })
+    }
+}
+
+/// Fit a generated description to the forge: drop markup it does not render
+/// and enforce its length limit.
+pub fn adapt_description(forge: Forge, body: &str) -> String {
+    let mut body = body.to_string();
+    if forge == Forge::Bitbucket {
+        body = body
+            .lines()
+            .filter(|line| {
+                let t = line.trim();
+                !(t.starts_with("<details")
+                    || t.starts_with("</details")
+                    || t.starts_with("<summary"))
+            })
+            .map(|line| line.replacen("- [ ] ", "- ", 1).replacen("- [x] ", "- ", 1))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    let limit = forge.description_limit();
+    if body.chars().count() > limit {
+        const NOTE: &str = "\n\n_(description truncated)_";
+        let keep = limit - NOTE.chars().count();
+        body = body.chars().take(keep).collect::<String>() + NOTE;
+    }
+    body
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostCommitAction {
+    /// Push the branch and open a PR with a generated title and body.
+    CreatePr,
+    /// Push the branch, updating the PR it already belongs to.
+    PushToPr,
+    /// Trigger a CI workflow (GitHub workflow file, or the GitLab pipeline) on the branch.
+    TriggerWorkflow(String),
+    /// Edit the new commit's message in the git editor.
+    Amend,
+    /// Prefix the new commit's subject with `WIP: `.
+    MarkWip,
+    /// Undo the commit, keeping its changes staged.
+    Undo,
+}
+
+impl FromStr for PostCommitAction {
+    type Err = CommitauraError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "create-pr" => Ok(PostCommitAction::CreatePr),
+            "push" => Ok(PostCommitAction::PushToPr),
+            "amend" => Ok(PostCommitAction::Amend),
+            "wip" => Ok(PostCommitAction::MarkWip),
+            "undo" => Ok(PostCommitAction::Undo),
+            other => match other.strip_prefix("workflow:") {
+                Some(name) if !name.trim().is_empty() => {
+                    Ok(PostCommitAction::TriggerWorkflow(name.trim().to_string()))
+                }
+                _ => Err(CommitauraError::ConfigError(format!(
+                    "unknown post_commit action `{}` (expected create-pr, push, workflow:<name>, amend, wip or undo)",
+                    other
+                ))),
+            },
+        }
+    }
+}
+
+impl PostCommitAction {
+    /// Menu label; `forge` is the `origin` remote's forge, if it has one.
+    pub fn label(&self, forge: Option<Forge>) -> String {
+        let pr_noun = forge.map_or("pull request", Forge::pr_noun);
+        match self {
+            PostCommitAction::CreatePr => format!("Create {} with generated description", pr_noun),
+            PostCommitAction::PushToPr => format!("Push to existing {}", pr_noun),
+            PostCommitAction::TriggerWorkflow(name) => format!("Trigger workflow {}", name),
+            PostCommitAction::Amend => "Edit the commit message".to_string(),
+            PostCommitAction::MarkWip => "Mark as work in progress (WIP:)".to_string(),
+            PostCommitAction::Undo => "Undo the commit, keeping changes staged".to_string(),
+        }
+    }
+
+    /// Whether the action talks to the `origin` forge rather than only the
+    /// local repository.
+    pub fn needs_remote(&self) -> bool {
+        matches!(
+            self,
+            PostCommitAction::CreatePr | PostCommitAction::PushToPr | PostCommitAction::TriggerWorkflow(_)
+        )
+    }
+}
+
+/// Parse the configured action list, falling back to [`DEFAULT_POST_COMMIT`].
+pub fn parse_actions(
+    configured: Option<&[String]>,
+) -> Result<Vec<PostCommitAction>, CommitauraError> {
+    match configured {
+        Some(list) => list.iter().map(|a| a.parse()).collect(),
+        None => DEFAULT_POST_COMMIT.iter().map(|a| a.parse()).collect(),
+    }
+}
+
+/// Arguments for opening a PR/MR against `base` (a branch name without remote).
+pub fn create_pr_args(forge: Forge, title: &str, body: &str, base: &str) -> Vec<String> {
+    let args: Vec<&str> = match forge {
+        Forge::GitHub => vec![
+            "pr", "create", "--title", title, "--body", body, "--base", base,
+        ],
+        Forge::GitLab => vec![
+            "mr",
+            "create",
+            "--title",
+            title,
+            "--description",
+            body,
+            "--target-branch",
+            base,
+            "--yes",
+        ],
+        // Bitbucket PRs are created through the API, see `create_pr`.
+        Forge::Bitbucket => vec![],
+    };
+    args.into_iter().map(str::to_string).collect()
+}
+
+/// Arguments for triggering `workflow` on `branch`.
+pub fn workflow_args(forge: Forge, workflow: &str, branch: &str) -> Vec<String> {
+    let args: Vec<&str> = match forge {
+        Forge::GitHub => vec!["workflow", "run", workflow, "--ref", branch],
+        // GitLab has a single pipeline per ref; the name is informational.
+        Forge::GitLab => vec!["ci", "run", "--branch", branch],
+        Forge::Bitbucket => vec![],
+    };
+    args.into_iter().map(str::to_string).collect()
+}
+
+/// Run the forge CLI with `args`, letting it talk to the terminal directly.
+pub fn run_cli(forge: Forge, args: &[String]) -> Result<(), CommitauraError> {
+    let cli = forge.cli().ok_or_else(|| {
+        CommitauraError::GitOperationFailed(format!("{:?} has no command line client", forge))
+    })?;
+    let status = Command::new(cli).args(args).status().map_err(|e| {
+        CommitauraError::GitOperationFailed(format!("could not run `{}`: {}", cli, e))
+    })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(format!(
+            "`{} {}` failed",
+            cli,
+            args.first().map(String::as_str).unwrap_or("")
+        )))
+    }
+}
+
+/// Open a pull/merge request from `source` into `target`. Returns its URL
+/// when it was created through an API.
+pub fn create_pr(
+    repo: &RemoteRepo,
+    title: &str,
+    body: &str,
+    source: &str,
+    target: &str,
+) -> Result<Option<String>, CommitauraError> {
+    let body = adapt_description(repo.forge, body);
+    match repo.forge {
+        Forge::GitHub => {
+            run_cli(
+                repo.forge,
+                &create_pr_args(repo.forge, title, &body, target),
+            )?;
+            Ok(None)
+        }
+        Forge::GitLab => match std::env::var("GITLAB_TOKEN") {
+            Ok(token) => create_gitlab_mr(repo, &token, title, &body, source, target).map(Some),
+            Err(_) => {
+                run_cli(
+                    repo.forge,
+                    &create_pr_args(repo.forge, title, &body, target),
+                )?;
+                Ok(None)
+            }
+        },
+        Forge::Bitbucket => {
+            let token = std::env::var("BITBUCKET_TOKEN")
+                .map_err(|_| CommitauraError::EnvVarNotSet("BITBUCKET_TOKEN".to_string()))?;
+            create_bitbucket_pr(repo, &token, title, &body, source, target).map(Some)
+        }
+    }
+}
+
+fn create_gitlab_mr(
+    repo: &RemoteRepo,
+    token: &str,
+    title: &str,
+    body: &str,
+    source: &str,
+    target: &str,
+) -> Result<String, CommitauraError> {
+    let url = format!(
+        "https://{}/api/v4/projects/{}/merge_requests",
+        repo.host,
+        repo.path.replace('/', "%2F")
+    );
+    let response = http::post_json(
+        &url,
+        &[("PRIVATE-TOKEN", token.to_string())],
+        &json!({
+            "source_branch": source,
+            "target_branch": target,
+            "title": title,
+            "description": body,
+        }),
+        None,
+    )?;
+    Ok(response["web_url"].as_str().unwrap_or_default().to_string())
+}
+
+fn create_bitbucket_pr(
+    repo: &RemoteRepo,
+    token: &str,
+    title: &str,
+    body: &str,
+    source: &str,
+    target: &str,
+) -> Result<String, CommitauraError> {
+    let url = format!(
+        "https://api.bitbucket.org/2.0/repositories/{}/pullrequests",
+        repo.path
+    );
+    let response = http::post_json(
+        &url,
+        &[("Authorization", format!("Bearer {}", token))],
+        &json!({
+            "title": title,
+            "description": body,
+            "source": { "branch": { "name": source } },
+            "destination": { "branch": { "name": target } },
+        }),
+        None,
+    )?;
+    Ok(response["links"]["html"]["href"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+/// An issue opened on a forge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    /// The number used to reference it, as in `#12`.
+    pub number: u64,
+    pub url: String,
+}
+
+/// The issue number at the end of an issue URL printed by `gh` or `glab`.
+fn issue_from_url(output: &str) -> Option<Issue> {
+    let url = output
+        .split_whitespace()
+        .rev()
+        .find(|w| w.starts_with("https://"))?;
+    let number = url.trim_end_matches('/').rsplit('/').next()?.parse().ok()?;
+    Some(Issue {
+        number,
+        url: url.to_string(),
+    })
+}
+
+/// Run the forge CLI with `args` and return what it prints.
+fn cli_output(forge: Forge, args: &[&str]) -> Result<String, CommitauraError> {
+    let cli = forge.cli().ok_or_else(|| {
+        CommitauraError::GitOperationFailed(format!("{:?} has no command line client", forge))
+    })?;
+    let output = Command::new(cli).args(args).output().map_err(|e| {
+        CommitauraError::GitOperationFailed(format!("could not run `{}`: {}", cli, e))
+    })?;
+    if !output.status.success() {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "`{} {}` failed: {}",
+            cli,
+            args.first().copied().unwrap_or(""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Open an issue. GitHub and GitLab use their REST APIs when `GITHUB_TOKEN`
+/// or `GITLAB_TOKEN` is set and their CLI otherwise; Bitbucket needs
+/// `BITBUCKET_TOKEN`.
+pub fn create_issue(repo: &RemoteRepo, title: &str, body: &str) -> Result<Issue, CommitauraError> {
+    let body = adapt_description(repo.forge, body);
+    let (url, headers, payload) = match repo.forge {
+        Forge::GitHub => {
+            let Ok(token) = std::env::var("GITHUB_TOKEN") else {
+                let output = cli_output(
+                    repo.forge,
+                    &["issue", "create", "--title", title, "--body", &body],
+                )?;
+                return issue_from_url(&output).ok_or_else(|| {
+                    CommitauraError::GitOperationFailed(format!(
+                        "unexpected `gh` output: {}",
+                        output.trim()
+                    ))
+                });
+            };
+            (
+                format!("{}/repos/{}/issues", github_api(&repo.host), repo.path),
+                vec![("Authorization", format!("Bearer {}", token))],
+                json!({ "title": title, "body": body }),
+            )
+        }
+        Forge::GitLab => {
+            let Ok(token) = std::env::var("GITLAB_TOKEN") else {
+                let output = cli_output(
+                    repo.forge,
+                    &[
+                        "issue",
+                        "create",
+                        "--title",
+                        title,
+                        "--description",
+                        &body,
+                        "--yes",
+                    ],
+                )?;
+                return issue_from_url(&output).ok_or_else(|| {
+                    CommitauraError::GitOperationFailed(format!(
+                        "unexpected `glab` output: {}",
+                        output.trim()
+                    ))
+                });
+            };
+            (
+                format!(
+                    "https://{}/api/v4/projects/{}/issues",
+                    repo.host,
+                    repo.path.replace('/', "%2F")
+                ),
+                vec![("PRIVATE-TOKEN", token)],
+                json!({ "title": title, "description": body }),
+            )
+        }
+        Forge::Bitbucket => {
+            let token = std::env::var("BITBUCKET_TOKEN")
+                .map_err(|_| CommitauraError::EnvVarNotSet("BITBUCKET_TOKEN".to_string()))?;
+            (
+                format!(
+                    "https://api.bitbucket.org/2.0/repositories/{}/issues",
+                    repo.path
+                ),
+                vec![("Authorization", format!("Bearer {}", token))],
+                json!({ "title": title, "content": { "raw": body } }),
+            )
+        }
+    };
+    let response = http::post_json(&url, &headers, &payload, None)?;
+    // GitLab references issues by their per-project `iid`.
+    let number = response["iid"]
+        .as_u64()
+        .or_else(|| response["number"].as_u64())
+        .or_else(|| response["id"].as_u64())
+        .ok_or_else(|| {
+            CommitauraError::ApiRequestFailed("No issue number in response".to_string())
+        })?;
+    let url = response["html_url"]
+        .as_str()
+        .or_else(|| response["web_url"].as_str())
+        .or_else(|| response["links"]["html"]["href"].as_str())
+        .unwrap_or_default();
+    Ok(Issue {
+        number,
+        url: url.to_string(),
+    })
+}
+
+/// The REST API root of a GitHub or GitHub Enterprise host.
+fn github_api(host: &str) -> String {
+    if host == "github.com" {
+        "https://api.github.com".to_string()
+    } else {
+        format!("https://{}/api/v3", host)
+    }
+}
+
+/// An open issue, as listed for finding ones a change may address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenIssue {
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+}
+
+/// Issues beyond this many recently updated ones are not considered.
+const OPEN_ISSUE_LIMIT: &str = "100";
+
+/// The most recently updated open issues. GitHub and GitLab use their REST
+/// APIs when `GITHUB_TOKEN` or `GITLAB_TOKEN` is set and their CLI
+/// otherwise; Bitbucket needs `BITBUCKET_TOKEN`.
+pub fn open_issues(repo: &RemoteRepo) -> Result<Vec<OpenIssue>, CommitauraError> {
+    let listing = match repo.forge {
+        Forge::GitHub => match std::env::var("GITHUB_TOKEN") {
+            Ok(token) => http::get_json(
+                &format!("{}/repos/{}/issues", github_api(&repo.host), repo.path),
+                &[("state", "open"), ("sort", "updated"), ("per_page", OPEN_ISSUE_LIMIT)],
+                &[("Authorization", format!("Bearer {}", token))],
+                None,
+            )?,
+            Err(_) => cli_json(
+                repo.forge,
+                &["issue", "list", "--state", "open", "--limit", OPEN_ISSUE_LIMIT, "--json", "number,title,body"],
+            )?,
+        },
+        Forge::GitLab => match std::env::var("GITLAB_TOKEN") {
+            Ok(token) => http::get_json(
+                &format!(
+                    "https://{}/api/v4/projects/{}/issues",
+                    repo.host,
+                    repo.path.replace('/', "%2F")
+                ),
+                &[("state", "opened"), ("order_by", "updated_at"), ("per_page", OPEN_ISSUE_LIMIT)],
+                &[("PRIVATE-TOKEN", token)],
+                None,
+            )?,
+            Err(_) => cli_json(
+                repo.forge,
+                &["issue", "list", "--per-page", OPEN_ISSUE_LIMIT, "--output", "json"],
+            )?,
+        },
+        Forge::Bitbucket => {
+            let token = std::env::var("BITBUCKET_TOKEN")
+                .map_err(|_| CommitauraError::EnvVarNotSet("BITBUCKET_TOKEN".to_string()))?;
+            http::get_json(
+                &format!("https://api.bitbucket.org/2.0/repositories/{}/issues", repo.path),
+                &[("q", "state=\"new\" OR state=\"open\""), ("sort", "-updated_on"), ("pagelen", "50")],
+                &[("Authorization", format!("Bearer {}", token))],
+                None,
+            )?
+        }
+    };
+    Ok(parse_open_issues(repo.forge, &listing))
+}
+
+fn cli_json(forge: Forge, args: &[&str]) -> Result<serde_json::Value, CommitauraError> {
+    let output = cli_output(forge, args)?;
+    serde_json::from_str(&output).map_err(|e| {
+        CommitauraError::GitOperationFailed(format!("unexpected issue list output: {}", e))
+    })
+}
+
+/// Read an issue listing from the REST API or CLI of `forge`. GitHub's
+/// listing also contains pull requests, which are skipped.
+fn parse_open_issues(forge: Forge, listing: &serde_json::Value) -> Vec<OpenIssue> {
+    let (items, number, body) = match forge {
+        Forge::GitHub => (listing, "number", "/body"),
+        Forge::GitLab => (listing, "iid", "/description"),
+        Forge::Bitbucket => (&listing["values"], "id", "/content/raw"),
+    };
+    items
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|item| item.get("pull_request").is_none())
+        .filter_map(|item| {
+            Some(OpenIssue {
+                number: item[number].as_u64()?,
+                title: item["title"].as_str()?.to_string(),
+                body: item.pointer(body).and_then(|b| b.as_str()).unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_issue_listings() {
+        let github = json!([
+            { "number": 88, "title": "Flaky retry", "body": "Retries hang" },
+            { "number": 90, "title": "Add retry flag", "pull_request": {} },
+        ]);
+        assert_eq!(
+            parse_open_issues(Forge::GitHub, &github),
+            vec![OpenIssue { number: 88, title: "Flaky retry".to_string(), body: "Retries hang".to_string() }]
+        );
+        let gitlab = json!([{ "iid": 7, "id": 7001, "title": "Slow sync", "description": null }]);
+        assert_eq!(parse_open_issues(Forge::GitLab, &gitlab)[0].number, 7);
+        let bitbucket = json!({ "values": [{ "id": 3, "title": "Crash", "content": { "raw": "On start" } }] });
+        assert_eq!(parse_open_issues(Forge::Bitbucket, &bitbucket)[0].body, "On start");
+    }
+
+    #[test]
+    fn detects_forge_from_remote() {
+        assert_eq!(Forge::detect("git@github.com:a/b.git"), Some(Forge::GitHub));
+        assert_eq!(
+            Forge::detect("https://gitlab.example.com/a/b"),
+            Some(Forge::GitLab)
+        );
+        assert_eq!(Forge::detect("ssh://git.sr.ht/~a/b"), None);
+    }
+
+    #[test]
+    fn parses_remote_urls() {
+        let repo = RemoteRepo::parse("git@bitbucket.org:team/app.git").unwrap();
+        assert_eq!(repo.forge, Forge::Bitbucket);
+        assert_eq!(repo.host, "bitbucket.org");
+        assert_eq!(repo.path, "team/app");
+
+        let repo =
+            RemoteRepo::parse("ssh://git@gitlab.example.com:2222/group/sub/app.git").unwrap();
+        assert_eq!(repo.forge, Forge::GitLab);
+        assert_eq!(repo.host, "gitlab.example.com");
+        assert_eq!(repo.path, "group/sub/app");
+
+        let repo = RemoteRepo::parse("https://github.com/a/b").unwrap();
+        assert_eq!((repo.forge, repo.path.as_str()), (Forge::GitHub, "a/b"));
+    }
+
+    #[test]
+    fn adapts_descriptions_to_the_forge() {
+        let body = "<details>\n<summary>Log</summary>\n- [ ] check CI\n</details>";
+        assert_eq!(adapt_description(Forge::Bitbucket, body), "- check CI");
+        assert_eq!(adapt_description(Forge::GitHub, body), body);
+
+        let long = "x".repeat(40_000);
+        let adapted = adapt_description(Forge::Bitbucket, &long);
+        assert_eq!(
+            adapted.chars().count(),
+            Forge::Bitbucket.description_limit()
+        );
+        assert!(adapted.ends_with("_(description truncated)_"));
+    }
+
+    #[test]
+    fn parses_action_list() {
+        let list = vec!["create-pr".to_string(), "workflow:ci.yml".to_string()];
+        assert_eq!(
+            parse_actions(Some(&list)).unwrap(),
+            vec![
+                PostCommitAction::CreatePr,
+                PostCommitAction::TriggerWorkflow("ci.yml".to_string())
+            ]
+        );
+        assert_eq!(
+            parse_actions(None).unwrap().len(),
+            DEFAULT_POST_COMMIT.len()
+        );
+        assert!("deploy".parse::<PostCommitAction>().is_err());
+        assert!(!"undo".parse::<PostCommitAction>().unwrap().needs_remote());
+    }
+
+    #[test]
+    fn reads_issue_numbers_from_cli_output() {
+        let issue =
+            issue_from_url("Creating issue in a/b\n\nhttps://github.com/a/b/issues/42\n").unwrap();
+        assert_eq!(issue.number, 42);
+        assert!(issue_from_url("no url here").is_none());
+    }
+
+    #[test]
+    fn builds_prefilled_cli_arguments() {
+        let args = create_pr_args(Forge::GitLab, "Title", "Body", "main");
+        assert_eq!(args[..2], ["mr", "create"]);
+        assert!(args.windows(2).any(|w| w == ["--description", "Body"]));
+    }
+}
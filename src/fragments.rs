@@ -0,0 +1,243 @@
+//! Changelog news fragments, as used by towncrier and similar tools.
+//!
+//! Instead of editing the changelog, each change adds a small file such as
+//! `changes/1234.bugfix.md` holding its entry. The fragment is written from
+//! the commit message: its kind from the message's conventional type or
+//! leading verb, its name from the first issue the message references.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::FragmentsConfig;
+use crate::pregen::fnv1a;
+use crate::{message, CommitauraError};
+
+/// The kind of change a fragment records, named as in towncrier's defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Feature,
+    Bugfix,
+    Removal,
+    Doc,
+    Misc,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 5] = [
+        Kind::Feature,
+        Kind::Bugfix,
+        Kind::Removal,
+        Kind::Doc,
+        Kind::Misc,
+    ];
+
+    /// The kind called `name`, as returned by [`Kind::name`].
+    pub fn parse(name: &str) -> Option<Kind> {
+        Kind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Feature => "feature",
+            Kind::Bugfix => "bugfix",
+            Kind::Removal => "removal",
+            Kind::Doc => "doc",
+            Kind::Misc => "misc",
+        }
+    }
+
+    /// Classify a commit by the type of a conventional subject, e.g.
+    /// `fix(http): ...`, or else by its first word.
+    pub fn classify(subject: &str) -> Kind {
+        if let Some(prefix) = message::conventional(subject) {
+            return match prefix.kind {
+                "feat" => Kind::Feature,
+                "fix" => Kind::Bugfix,
+                "docs" => Kind::Doc,
+                "revert" => Kind::Removal,
+                _ => Kind::Misc,
+            };
+        }
+        let first = subject
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        match first.trim_end_matches(':') {
+            "fix" | "fixes" | "fixed" | "correct" | "corrects" | "resolve" | "resolves"
+            | "repair" | "repairs" => Kind::Bugfix,
+            "add" | "adds" | "added" | "implement" | "implements" | "introduce" | "introduces"
+            | "support" | "supports" | "allow" | "allows" | "enable" | "enables" => Kind::Feature,
+            "remove" | "removes" | "removed" | "delete" | "deletes" | "drop" | "drops"
+            | "deprecate" | "deprecates" => Kind::Removal,
+            "document" | "documents" | "docs" => Kind::Doc,
+            _ => Kind::Misc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    /// Relative to the repository root.
+    pub path: PathBuf,
+    pub kind: Kind,
+    pub text: String,
+}
+
+/// The configured directory, checked to be relative and to stay inside
+/// the repository; `None` when fragments are not configured.
+pub fn directory(config: &FragmentsConfig) -> Result<Option<&Path>, CommitauraError> {
+    let Some(directory) = config.directory.as_deref() else {
+        return Ok(None);
+    };
+    let inside = directory
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return Err(CommitauraError::ConfigError(format!(
+            "fragments.directory `{}` must be relative to the repository root, without `..`",
+            directory.display()
+        )));
+    }
+    Ok(Some(directory))
+}
+
+/// The fragment for a commit with `message`, or `None` when fragments are
+/// not configured or the kind is configured to get none. Fails if the
+/// configured directory is outside the repository.
+pub fn for_message(
+    message: &str,
+    config: &FragmentsConfig,
+) -> Result<Option<Fragment>, CommitauraError> {
+    let Some(directory) = directory(config)? else {
+        return Ok(None);
+    };
+    let subject = message.lines().next().unwrap_or_default().trim();
+    let kind = Kind::classify(subject);
+    let type_name = config
+        .types
+        .get(kind.name())
+        .map_or(kind.name(), String::as_str);
+    if type_name.is_empty() {
+        return Ok(None);
+    }
+    let text = entry_text(subject);
+    // Fragments without an issue are "orphans"; the hash keeps their names apart.
+    let id = match issue_number(message) {
+        Some(number) => number.to_string(),
+        None => format!("+{:08x}", fnv1a(&[&text]) as u32),
+    };
+    Ok(Some(Fragment {
+        path: directory.join(format!("{}.{}.{}", id, type_name, config.extension)),
+        kind,
+        text,
+    }))
+}
+
+/// The subject as a changelog entry: without a conventional prefix, as a
+/// capitalized sentence.
+pub(crate) fn entry_text(subject: &str) -> String {
+    let text = message::conventional(subject).map_or(subject, |prefix| prefix.description.trim());
+    let mut chars = text.chars();
+    let mut entry: String = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    };
+    if !entry.ends_with(['.', '!', '?']) {
+        entry.push('.');
+    }
+    entry
+}
+
+/// The first `#123` the message references.
+fn issue_number(message: &str) -> Option<u64> {
+    message.split('#').skip(1).find_map(|rest| {
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().ok()
+    })
+}
+
+/// Whether the staged paths already include a fragment, written by hand.
+pub fn already_staged(staged: &[String], config: &FragmentsConfig) -> bool {
+    config.directory.as_ref().is_some_and(|directory| {
+        staged
+            .iter()
+            .any(|path| Path::new(path).starts_with(directory))
+    })
+}
+
+/// Write `fragment` below `root`, creating its directory if needed.
+pub fn write(fragment: &Fragment, root: &Path) -> Result<(), CommitauraError> {
+    let path = root.join(&fragment.path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, format!("{}\n", fragment.text))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FragmentsConfig {
+        FragmentsConfig {
+            directory: Some(PathBuf::from("changes")),
+            ..FragmentsConfig::default()
+        }
+    }
+
+    #[test]
+    fn classifies_conventional_and_plain_subjects() {
+        assert_eq!(Kind::classify("fix(http): retry on 503"), Kind::Bugfix);
+        assert_eq!(Kind::classify("feat!: drop the v1 API"), Kind::Feature);
+        assert_eq!(Kind::classify("chore: bump deps"), Kind::Misc);
+        assert_eq!(Kind::classify("Add --timings flag"), Kind::Feature);
+        assert_eq!(Kind::classify("Remove the legacy parser"), Kind::Removal);
+        assert_eq!(Kind::classify("Refactor config loading"), Kind::Misc);
+    }
+
+    #[test]
+    fn names_fragments_after_the_referenced_issue() {
+        let fragment = for_message("fix(http): retry on 503\n\nFixes #1234", &config())
+            .unwrap()
+            .unwrap();
+        assert_eq!(fragment.path, PathBuf::from("changes/1234.bugfix.md"));
+        assert_eq!(fragment.text, "Retry on 503.");
+
+        let orphan = for_message("Add --timings flag", &config())
+            .unwrap()
+            .unwrap();
+        let name = orphan.path.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with('+') && name.ends_with(".feature.md"));
+    }
+
+    #[test]
+    fn renames_or_skips_kinds_as_configured() {
+        let mut config = config();
+        config
+            .types
+            .insert("bugfix".to_string(), "fixed".to_string());
+        config.types.insert("misc".to_string(), String::new());
+        let fragment = for_message("Fix crash (#7)", &config).unwrap().unwrap();
+        assert_eq!(fragment.path, PathBuf::from("changes/7.fixed.md"));
+        assert_eq!(
+            for_message("Refactor config loading", &config).unwrap(),
+            None
+        );
+        assert_eq!(
+            for_message("Fix crash", &FragmentsConfig::default()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_fragments_inside_the_repository() {
+        for outside in ["/tmp/changes", "../changes", "changes/../../x"] {
+            let config = FragmentsConfig {
+                directory: Some(PathBuf::from(outside)),
+                ..FragmentsConfig::default()
+            };
+            assert!(for_message("Fix crash", &config).is_err(), "{}", outside);
+        }
+    }
+}
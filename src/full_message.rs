@@ -0,0 +1,214 @@
+//! Full commit messages: a subject, a wrapped body and trailer footers.
+//!
+//! The model replies with the parts as a JSON object instead of free text,
+//! so the layout git expects (a short subject, a blank line, a body wrapped
+//! at 72 columns, then `Token: value` footers) is produced here rather than
+//! left to the model.
+
+use serde::Deserialize;
+
+use crate::generate::{build_prompt, complete_json, with_scope, GenerateOptions, Prompt};
+use crate::{diff, injection, message, CommitauraError};
+
+const FULL_INSTRUCTIONS: &str = "\n\nWrite a full commit message: a subject line of at most 72 characters in the imperative mood, a body of one or more short paragraphs (or \"- \" bullets) explaining what changed and why, and footers where they apply, such as \"BREAKING CHANGE\" when existing users must change something, or \"Refs\" for issues the changes address. Leave the body empty only for trivial changes.\n\nReply with a JSON object with a string field \"subject\", a string field \"body\" and a field \"footers\" holding a list of objects with string fields \"token\" and \"value\".";
+
+/// Response tokens for subject, body and footers together.
+const MAX_RESPONSE_TOKENS: u32 = 500;
+
+/// Column the body is wrapped at.
+pub const BODY_WIDTH: usize = 72;
+
+#[derive(Debug, Deserialize)]
+struct Reply {
+    subject: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    footers: Vec<Footer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Footer {
+    token: String,
+    value: String,
+}
+
+/// The usual prompt for `diff`, extended to ask for the parts of a full
+/// message in a JSON reply. The diff is shortened to leave room for the
+/// instructions.
+pub fn build_full_prompt(diff: &str, opts: &GenerateOptions) -> Result<Prompt, CommitauraError> {
+    let mut budget = opts.clone();
+    budget.max_tokens = opts
+        .prompt_budget(MAX_RESPONSE_TOKENS)
+        .saturating_sub(opts.tokenizer().count(FULL_INSTRUCTIONS)?);
+    let base = build_prompt(diff, &budget)?;
+    Ok(Prompt {
+        system: base.system,
+        user: base.user + FULL_INSTRUCTIONS,
+    })
+}
+
+/// Assemble the message from the model's reply, which may be wrapped in a
+/// code fence.
+pub fn parse_reply(reply: &str, opts: &GenerateOptions) -> Result<String, CommitauraError> {
+    let json = reply
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```");
+    let reply: Reply = serde_json::from_str(json).map_err(|e| {
+        CommitauraError::ApiRequestFailed(format!("Malformed full message reply: {}", e))
+    })?;
+    // Only the first line can be the subject.
+    let subject = reply.subject.lines().next().unwrap_or_default().trim();
+    if subject.is_empty() {
+        return Err(CommitauraError::ApiRequestFailed(
+            "Received empty commit message from LLM.".to_string(),
+        ));
+    }
+    let mut message = subject.to_string();
+    let body = wrap(reply.body.trim(), BODY_WIDTH);
+    if !body.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(&body);
+    }
+    let footers: Vec<String> = reply
+        .footers
+        .iter()
+        .filter_map(|footer| {
+            let value = footer
+                .value
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let token = footer_token(&footer.token)?;
+            (!value.is_empty()).then(|| format!("{}: {}", token, value))
+        })
+        .collect();
+    if !footers.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(&footers.join("\n"));
+    }
+    Ok(message::assemble(&message, opts))
+}
+
+/// A footer token as git trailers spell it: words joined by `-`, except
+/// `BREAKING CHANGE`, which keeps its space.
+fn footer_token(token: &str) -> Option<String> {
+    let words: Vec<&str> = token
+        .trim()
+        .trim_end_matches(':')
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    let token = words.join("-");
+    Some(if token.eq_ignore_ascii_case("breaking-change") {
+        "BREAKING CHANGE".to_string()
+    } else {
+        token
+    })
+}
+
+/// Wrap each paragraph of `text` at `width` columns. A line starting with
+/// `- ` or `* ` begins a list item, continued with a two-space indent.
+/// Words longer than `width`, such as URLs, are never broken, and no line
+/// starts with a `#` word like `#88`, which git would take for a comment.
+pub fn wrap(text: &str, width: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    // Words of the current paragraph or list item, and its continuation indent.
+    let mut words: Vec<&str> = Vec::new();
+    let mut indent = "";
+    let flush = |words: &mut Vec<&str>, indent: &str, lines: &mut Vec<String>| {
+        let mut line = String::new();
+        for word in words.drain(..) {
+            if !line.trim().is_empty()
+                && !word.starts_with('#')
+                && line.chars().count() + 1 + word.chars().count() > width
+            {
+                lines.push(std::mem::replace(&mut line, indent.to_string()));
+            }
+            if !line.trim().is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        if !line.trim().is_empty() {
+            lines.push(line);
+        }
+    };
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            flush(&mut words, indent, &mut lines);
+            if lines.last().is_some_and(|l| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+        if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+            flush(&mut words, indent, &mut lines);
+            indent = "  ";
+        } else if indent.is_empty() || !line.starts_with(' ') {
+            // Unindented text after a list item starts a paragraph of its own.
+            if !indent.is_empty() {
+                flush(&mut words, indent, &mut lines);
+            }
+            indent = "";
+        }
+        words.extend(trimmed.split_whitespace());
+    }
+    flush(&mut words, indent, &mut lines);
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Generate a full message for `diff` from a structured reply.
+pub fn generate(diff: &str, opts: &GenerateOptions) -> Result<String, CommitauraError> {
+    let prompt = build_full_prompt(diff, opts)?;
+    let message = parse_reply(&complete_json(&prompt, opts, MAX_RESPONSE_TOKENS)?, opts)?;
+    injection::check(&message, diff)?;
+    Ok(with_scope(
+        message,
+        opts.scope_for(&diff::parse(diff)).as_deref(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_subject_body_and_footers() {
+        let opts = GenerateOptions::new("key");
+        let reply = "```json\n{\"subject\": \"Drop the v1 config format\", \"body\": \"The loader only reads the v2 format now, which lets the parser drop its compatibility shims and the warnings they printed.\", \"footers\": [{\"token\": \"BREAKING-CHANGE\", \"value\": \"v1 files must be converted with `commitaura init`.\"}, {\"token\": \"Refs\", \"value\": \"#88\"}, {\"token\": \"Reviewed by\", \"value\": \"\"}]}\n```";
+        assert_eq!(
+            parse_reply(reply, &opts).unwrap(),
+            "Drop the v1 config format\n\n\
+             The loader only reads the v2 format now, which lets the parser drop its\n\
+             compatibility shims and the warnings they printed.\n\n\
+             BREAKING CHANGE: v1 files must be converted with `commitaura init`.\n\
+             Refs: #88"
+        );
+        assert_eq!(
+            parse_reply("{\"subject\": \"Fix typo\"}", &opts).unwrap(),
+            "Fix typo"
+        );
+        assert!(parse_reply("{\"subject\": \" \"}", &opts).is_err());
+    }
+
+    #[test]
+    fn wraps_paragraphs_and_list_items() {
+        let text = "First paragraph that is long enough to need wrapping.\n\n- an item that also runs past the width\n- short\nAfter the list.";
+        assert_eq!(
+            wrap(text, 30),
+            "First paragraph that is long\nenough to need wrapping.\n\n\
+             - an item that also runs past\n  the width\n- short\nAfter the list."
+        );
+        assert_eq!(
+            wrap("Stops the crash reported in #88.", 30),
+            "Stops the crash reported in #88."
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Lock files, minified bundles and other generated files.
+//!
+//! Their diffs are long, written by tools rather than people, and tell the
+//! model nothing it could not get from the file name. Each such file keeps
+//! its header, but its hunks are replaced by one line saying how much
+//! changed, e.g. `Cargo.lock: 214 lines changed (lock file)`.
+//!
+//! Besides the built-in lists, files marked `linguist-generated` in the
+//! top-level `.gitattributes` count as generated, as they do on GitHub.
+
+use std::path::Path;
+
+use crate::diff::{self, FileDiff};
+use crate::glob::Glob;
+use crate::CommitauraError;
+
+/// Lock files of package managers, by file name.
+pub const LOCK_FILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "Gemfile.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "composer.lock",
+    "go.sum",
+    "flake.lock",
+    "Package.resolved",
+    "pubspec.lock",
+    "mix.lock",
+    "packages.lock.json",
+];
+
+/// Extensions of files that are minified when they have very long lines.
+const BUNDLE_EXTENSIONS: &[&str] = &["js", "mjs", "cjs", "css"];
+
+/// Changed lines at least this long mark a bundle as minified.
+const MINIFIED_LINE_LEN: usize = 500;
+
+/// Why a file counts as generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    LockFile,
+    Minified,
+    /// Marked `linguist-generated` in `.gitattributes`.
+    Generated,
+}
+
+impl Kind {
+    fn label(self) -> &'static str {
+        match self {
+            Kind::LockFile => "lock file",
+            Kind::Minified => "minified",
+            Kind::Generated => "generated",
+        }
+    }
+}
+
+/// A `.gitattributes` line that sets or unsets `linguist-generated`.
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    generated: bool,
+}
+
+/// Finds generated files in diffs.
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    rules: Vec<Rule>,
+}
+
+impl Detector {
+    /// A detector that also honors the `linguist-generated` attributes in
+    /// the `.gitattributes` text `attributes`.
+    pub fn new(attributes: &str) -> Detector {
+        let rules = attributes
+            .lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let pattern = words.next().filter(|p| !p.starts_with('#'))?;
+                let generated = words.find_map(|attr| match attr {
+                    "linguist-generated" | "linguist-generated=true" => Some(true),
+                    "-linguist-generated" | "!linguist-generated" | "linguist-generated=false" => {
+                        Some(false)
+                    }
+                    _ => None,
+                })?;
+                Some(Rule {
+                    glob: Glob::new(pattern).ok()?,
+                    generated,
+                })
+            })
+            .collect();
+        Detector { rules }
+    }
+
+    /// A detector with the rules of the `.gitattributes` in `root`.
+    pub fn load(root: &Path) -> Result<Detector, CommitauraError> {
+        match std::fs::read_to_string(root.join(".gitattributes")) {
+            Ok(text) => Ok(Detector::new(&text)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Detector::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Why `file` counts as generated, if it does. An explicit
+    /// `-linguist-generated` overrides the built-in lists.
+    pub fn classify(&self, file: &FileDiff) -> Option<Kind> {
+        let marked = self
+            .rules
+            .iter()
+            .rev()
+            .find(|rule| rule.glob.is_match(&file.path))
+            .map(|rule| rule.generated);
+        match marked {
+            Some(true) => return Some(Kind::Generated),
+            Some(false) => return None,
+            None => {}
+        }
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+        if LOCK_FILES.contains(&name) {
+            Some(Kind::LockFile)
+        } else if is_minified(name, file) {
+            Some(Kind::Minified)
+        } else {
+            None
+        }
+    }
+
+    /// `diff` with the hunks of each generated file replaced by a line
+    /// counting its changed lines.
+    pub fn summarize(&self, diff: &str) -> String {
+        let mut files = diff::parse(diff);
+        let mut changed = false;
+        for file in &mut files {
+            if file.hunks.is_empty() {
+                continue;
+            }
+            if let Some(kind) = self.classify(file) {
+                file.header.push(format!(
+                    "{}: {} lines changed ({})",
+                    file.path,
+                    file.additions() + file.deletions(),
+                    kind.label()
+                ));
+                file.hunks.clear();
+                changed = true;
+            }
+        }
+        if changed {
+            diff::render(&files)
+        } else {
+            diff.to_string()
+        }
+    }
+}
+
+/// Whether `file`, named `name`, is a minified bundle or its source map.
+fn is_minified(name: &str, file: &FileDiff) -> bool {
+    let Some((stem, extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+    if extension == "map" {
+        return stem
+            .rsplit_once('.')
+            .is_some_and(|(_, inner)| BUNDLE_EXTENSIONS.contains(&inner));
+    }
+    if !BUNDLE_EXTENSIONS.contains(&extension) {
+        return false;
+    }
+    stem.ends_with(".min")
+        || stem.ends_with(".bundle")
+        || file
+            .hunks
+            .iter()
+            .flat_map(|hunk| &hunk.lines)
+            .any(|line| line.len() > MINIFIED_LINE_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, lines: &[&str]) -> String {
+        format!(
+            "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1,{1} +1,{1} @@\n{2}\n",
+            path,
+            lines.len(),
+            lines.join("\n")
+        )
+    }
+
+    #[test]
+    fn classifies_lock_files_bundles_and_marked_files() {
+        let detector = Detector::new(
+            "# generated\nsrc/proto/** linguist-generated\nyarn.lock -linguist-generated\n",
+        );
+        let long = format!("+{}", "a;".repeat(300));
+        let kind =
+            |path: &str, lines: &[&str]| detector.classify(&diff::parse(&file(path, lines))[0]);
+        assert_eq!(kind("Cargo.lock", &["+x"]), Some(Kind::LockFile));
+        assert_eq!(kind("web/package-lock.json", &["+x"]), Some(Kind::LockFile));
+        assert_eq!(kind("yarn.lock", &["+x"]), None);
+        assert_eq!(kind("dist/app.min.js", &["+x"]), Some(Kind::Minified));
+        assert_eq!(kind("dist/app.js.map", &["+x"]), Some(Kind::Minified));
+        assert_eq!(kind("dist/app.js", &[&long]), Some(Kind::Minified));
+        assert_eq!(kind("src/app.js", &["+x"]), None);
+        assert_eq!(kind("src/proto/api.rs", &["+x"]), Some(Kind::Generated));
+        assert_eq!(kind("src/main.rs", &[&long]), None);
+    }
+
+    #[test]
+    fn summarizes_generated_files_in_one_line() {
+        let diff = file("Cargo.lock", &["-old = 1", "+new = 2", "+more = 3"])
+            + &file("src/lib.rs", &["+fn f() {}"]);
+        assert_eq!(
+            Detector::default().summarize(&diff),
+            "diff --git a/Cargo.lock b/Cargo.lock\n--- a/Cargo.lock\n+++ b/Cargo.lock\nCargo.lock: 3 lines changed (lock file)\n"
+                .to_string()
+                + &file("src/lib.rs", &["+fn f() {}"])
+        );
+    }
+}
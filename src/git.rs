@@ -0,0 +1,1454 @@
+//! Thin wrappers around the `git` command line.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::history::{self, HistoryCommit};
+use crate::{editmsg, CommitauraError};
+
+/// Environment variables through which a caller can point git at another
+/// index, repository or work tree. Tools such as the `pre-commit` framework,
+/// `git stash` and IDE partial commits set these while they run.
+const PATH_OVERRIDES: &[&str] = &[
+    "GIT_INDEX_FILE",
+    "GIT_DIR",
+    "GIT_WORK_TREE",
+    "GIT_COMMON_DIR",
+    "GIT_OBJECT_DIRECTORY",
+];
+
+static NO_LAZY_FETCH: AtomicBool = AtomicBool::new(false);
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Subcommands that only ever read the repository. In read-only mode,
+/// every other subcommand is refused unless [`reads_only`] recognises its
+/// arguments as a read.
+const READING_SUBCOMMANDS: &[&str] = &[
+    "blame",
+    "cat-file",
+    "check-attr",
+    "check-ignore",
+    "check-ref-format",
+    "describe",
+    "diff",
+    "diff-files",
+    "diff-index",
+    "diff-tree",
+    "for-each-ref",
+    "grep",
+    "log",
+    "ls-files",
+    "ls-tree",
+    "merge-base",
+    "name-rev",
+    "rev-list",
+    "rev-parse",
+    "shortlog",
+    "show",
+    "show-ref",
+    "var",
+    "version",
+];
+
+/// Stop git from downloading missing objects from a partial clone's promisor
+/// remote for the rest of the process. Commands that need a missing object
+/// fail instead (git 2.44 and later; older versions ignore this).
+pub fn disable_lazy_fetch() {
+    NO_LAZY_FETCH.store(true, Ordering::Relaxed);
+}
+
+/// Refuse every git operation that would change the repository for the
+/// rest of the process. Reading history, diffs and config still works.
+pub fn enable_read_only() {
+    READ_ONLY.store(true, Ordering::Relaxed);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// Fail with [`CommitauraError::ReadOnly`] if read-only mode is on and
+/// `git <subcommand>` changes the repository.
+pub fn ensure_writable(subcommand: &str) -> Result<(), CommitauraError> {
+    ensure_reads_only(&[subcommand])
+}
+
+/// Fail with [`CommitauraError::ReadOnly`] if read-only mode is on and
+/// `operation`, a subcommand and its arguments, changes the repository.
+fn ensure_reads_only(operation: &[&str]) -> Result<(), CommitauraError> {
+    if is_read_only() && !reads_only(operation) {
+        let subcommand = operation.first().copied().unwrap_or_default();
+        return Err(CommitauraError::ReadOnly(subcommand.to_string()));
+    }
+    Ok(())
+}
+
+/// Options with which `git branch` changes refs or config.
+const BRANCH_CHANGING_OPTIONS: &[&str] = &[
+    "-d", "-D", "--delete", "-m", "-M", "--move", "-c", "-C", "--copy", "-f", "--force", "-u",
+    "--set-upstream-to", "--unset-upstream", "--edit-description", "-t", "--track", "--no-track",
+];
+
+/// Options with which `git tag` changes refs.
+const TAG_CHANGING_OPTIONS: &[&str] = &[
+    "-d", "--delete", "-f", "--force", "-a", "--annotate", "-s", "--sign", "-u", "--local-user",
+    "-m", "--message", "-F", "--file",
+];
+
+/// Options of `git branch` and `git tag` listings that take a value.
+const LISTING_VALUE_OPTIONS: &[&str] = &[
+    "--contains", "--no-contains", "--merged", "--no-merged", "--points-at", "--sort", "--format",
+];
+
+/// Whether `operation`, a subcommand and its arguments, leaves refs, the
+/// index, the work tree, the object store, config and remotes alone.
+/// Subcommands that both read and write count as reads only in the forms
+/// that list or show.
+fn reads_only(operation: &[&str]) -> bool {
+    let Some((subcommand, args)) = operation.split_first() else {
+        return true;
+    };
+    let has = |options: &[&str]| {
+        args.iter()
+            .any(|arg| options.contains(&arg.split('=').next().unwrap_or(arg)))
+    };
+    let mut positional = Vec::new();
+    let mut args_left = args.iter();
+    while let Some(arg) = args_left.next() {
+        if LISTING_VALUE_OPTIONS.contains(arg) {
+            args_left.next();
+        } else if !arg.starts_with('-') {
+            positional.push(*arg);
+        }
+    }
+    let action = positional.first().copied();
+    match *subcommand {
+        subcommand if READING_SUBCOMMANDS.contains(&subcommand) => true,
+        "notes" => matches!(action, None | Some("show" | "list")),
+        "stash" => matches!(action, Some("show" | "list")),
+        "worktree" | "sparse-checkout" => action == Some("list"),
+        "remote" => matches!(action, None | Some("get-url" | "show")),
+        "config" => {
+            matches!(action, Some("get" | "list"))
+                || has(&["--get", "--get-all", "--get-regexp", "--list", "-l"])
+        }
+        // With a second name, or `--delete`, it changes HEAD.
+        "symbolic-ref" => positional.len() <= 1 && !has(&["-d", "--delete"]),
+        // Without a name, or with `--list`, they only list.
+        "branch" => {
+            (positional.is_empty() || has(&["-l", "--list"])) && !has(BRANCH_CHANGING_OPTIONS)
+        }
+        "tag" => (positional.is_empty() || has(&["-l", "--list"])) && !has(TAG_CHANGING_OPTIONS),
+        "hash-object" => !has(&["-w"]),
+        _ => false,
+    }
+}
+
+/// The subcommand of a git argument list and its arguments, after global
+/// options such as `-c key=value`.
+fn operation<'a, 'b>(args: &'b [&'a str]) -> &'b [&'a str] {
+    let mut i = 0;
+    while i < args.len() {
+        match args[i] {
+            "-c" | "-C" => i += 2,
+            option if option.starts_with('-') => i += 1,
+            _ => return &args[i..],
+        }
+    }
+    &[]
+}
+
+/// `git` with `args`, refused in read-only mode if it would change the
+/// repository. Every git invocation that can write goes through here.
+fn command(args: &[&str]) -> Result<Command, CommitauraError> {
+    ensure_reads_only(operation(args))?;
+    let mut cmd = git_command();
+    cmd.args(args);
+    Ok(cmd)
+}
+
+/// A `git` command that honours the caller's environment overrides.
+///
+/// Overrides are inherited as usual, but relative paths are made absolute
+/// against the current directory first so they keep pointing at the same
+/// files whatever directory the child ends up running in.
+pub fn git_command() -> Command {
+    let mut cmd = Command::new("git");
+    if NO_LAZY_FETCH.load(Ordering::Relaxed) {
+        cmd.env("GIT_NO_LAZY_FETCH", "1");
+    }
+    if let Ok(cwd) = std::env::current_dir() {
+        for (var, path) in resolve_overrides(&cwd, |var| std::env::var_os(var)) {
+            cmd.env(var, path);
+        }
+    }
+    cmd
+}
+
+/// The relative path overrides reported by `lookup`, resolved against `cwd`.
+fn resolve_overrides(
+    cwd: &std::path::Path,
+    lookup: impl Fn(&str) -> Option<OsString>,
+) -> Vec<(&'static str, PathBuf)> {
+    PATH_OVERRIDES
+        .iter()
+        .filter_map(|var| {
+            let path = PathBuf::from(lookup(var)?);
+            (!path.as_os_str().is_empty() && path.is_relative()).then(|| (*var, cwd.join(path)))
+        })
+        .collect()
+}
+
+/// The alternate index set through `GIT_INDEX_FILE`, if any.
+pub fn index_override() -> Option<PathBuf> {
+    std::env::var_os("GIT_INDEX_FILE")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// `f` run on the repository through libgit2, or `None` if it fails and
+/// `git` should be run instead.
+#[cfg(feature = "libgit2")]
+fn with_libgit2<T>(
+    what: &str,
+    f: impl FnOnce(&crate::repo::GitRepo) -> Result<T, git2::Error>,
+) -> Option<T> {
+    // Only `git` can be kept from fetching a partial clone's missing blobs.
+    if NO_LAZY_FETCH.load(Ordering::Relaxed) {
+        return None;
+    }
+    match crate::repo::GitRepo::open().and_then(|repo| f(&repo)) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::debug!("libgit2 could not {}, running git instead: {}", what, e);
+            None
+        }
+    }
+}
+
+pub fn check_staged_changes() -> Result<(), CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(staged) = with_libgit2("diff the index", |repo| repo.has_staged_changes()) {
+        return if staged { Ok(()) } else { Err(CommitauraError::NoStagedChanges) };
+    }
+    let output = git_command()
+        .args(["diff", "--staged", "--quiet"])
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+
+    if output.success() {
+        Err(CommitauraError::NoStagedChanges)
+    } else {
+        Ok(())
+    }
+}
+
+/// A commit message written to a file for `git commit -F`; removed on drop.
+struct MessageFile(PathBuf);
+
+impl MessageFile {
+    fn create(dir: &Path, message: &str) -> Result<MessageFile, CommitauraError> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("COMMIT_MSG-{}", std::process::id()));
+        std::fs::write(&path, message.as_bytes())?;
+        Ok(MessageFile(path))
+    }
+}
+
+impl Drop for MessageFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// `git commit` with `args`, taking `message` from a UTF-8 file instead of
+/// argv, where Windows code pages can mangle CJK text or emoji. The encoding
+/// is pinned so the commit is labelled as the UTF-8 it contains even where
+/// `i18n.commitEncoding` says otherwise.
+fn commit_command(args: &[&str], message: &MessageFile) -> Result<Command, CommitauraError> {
+    let mut cmd = command(&["-c", "i18n.commitEncoding=UTF-8", "commit"])?;
+    cmd.args(args).arg("-F").arg(&message.0);
+    Ok(cmd)
+}
+
+fn message_file(message: &str) -> Result<MessageFile, CommitauraError> {
+    MessageFile::create(&git_dir()?.join("commitaura"), &editmsg::for_commit(message))
+}
+
+pub fn perform_git_commit(message: &str) -> Result<(), CommitauraError> {
+    ensure_writable("commit")?;
+    #[cfg(feature = "libgit2")]
+    if with_libgit2("commit", |repo| repo.commit(message)).is_some() {
+        return Ok(());
+    }
+    let file = message_file(message)?;
+    let status = commit_command(&[], &file)?
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(
+            "Git commit failed".to_string(),
+        ))
+    }
+}
+
+pub fn get_last_commit_messages() -> Result<String, CommitauraError> {
+    recent_subjects(5)
+}
+
+/// Subjects of the last `count` commits, newest first. Empty before the
+/// first commit.
+pub fn recent_subjects(count: usize) -> Result<String, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(commits) = with_libgit2("read the log", |repo| repo.recent_commits(count)) {
+        return Ok(history::subjects(&commits, count));
+    }
+    let output = git_command()
+        .args(["log", &format!("-{}", count), "--pretty=format:%s"])
+        .output()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+
+    String::from_utf8(output.stdout).map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))
+}
+
+/// The last `count` commits with their bodies and touched paths, newest
+/// first. Empty before the first commit.
+pub fn recent_commits(count: usize) -> Result<Vec<HistoryCommit>, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(commits) = with_libgit2("read the log", |repo| repo.recent_commits(count)) {
+        return Ok(commits);
+    }
+    let output = git_command()
+        .args(["log", &format!("-{}", count), "--format=%x1e%s%x1f%b%x1f", "--name-only"])
+        .output()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    Ok(history::parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Like [`recent_commits`], starting at `rev` instead of HEAD.
+pub fn recent_commits_from(rev: &str, count: usize) -> Result<Vec<HistoryCommit>, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(commits) = with_libgit2("read the log", |repo| repo.recent_commits_from(rev, count)) {
+        return Ok(commits);
+    }
+    let log = run_git(&["log", &format!("-{}", count), "--format=%x1e%s%x1f%b%x1f", "--name-only", rev])?;
+    Ok(history::parse_log(&log))
+}
+
+/// Full message of the newest commit reachable from `rev` that touched any
+/// of `paths`: whichever of the files' last-touching commits came last.
+/// `None` if none of them has history yet.
+pub fn last_commit_touching(rev: &str, paths: &[String]) -> Result<Option<String>, CommitauraError> {
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    let output = git_command()
+        .args(["log", "-1", "--format=%B", rev, "--"])
+        .args(paths)
+        .output()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    // An unborn branch has no log; that is no history, not an error.
+    let message = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((output.status.success() && !message.is_empty()).then_some(message))
+}
+
+pub fn get_staged_diff() -> Result<String, CommitauraError> {
+    get_staged_diff_in(&[])
+}
+
+/// Staged diff limited to `pathspecs`; an empty list means everything.
+pub fn get_staged_diff_in(pathspecs: &[String]) -> Result<String, CommitauraError> {
+    // Pathspec magic such as `:(top)` is git's own.
+    #[cfg(feature = "libgit2")]
+    if pathspecs.is_empty() {
+        if let Some(diff) = with_libgit2("diff the index", |repo| repo.staged_diff()) {
+            return Ok(diff);
+        }
+    }
+    let diff_output = git_command()
+        .args(["diff", "--staged", "--"])
+        .args(pathspecs)
+        .output()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+
+    String::from_utf8(diff_output.stdout).map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))
+}
+
+/// A commit as needed for rewording: enough to recreate it with a new message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    pub hash: String,
+    pub tree: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author date in git's raw format (`<unix seconds> <offset>`).
+    pub author_date: String,
+    pub message: String,
+}
+
+impl CommitInfo {
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+}
+
+fn run_git(args: &[&str]) -> Result<String, CommitauraError> {
+    let output = command(args)?
+        .output()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))
+}
+
+/// Git's well-known empty tree, for diffing against "nothing".
+pub const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Commits in `base..HEAD`, oldest first. Fails if the range contains merges.
+pub fn list_commits(base: &str) -> Result<Vec<CommitInfo>, CommitauraError> {
+    let range = format!("{}..HEAD", base);
+    if !run_git(&["rev-list", "--merges", &range])?.trim().is_empty() {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "{} contains merge commits; only linear history can be reworded",
+            range
+        )));
+    }
+
+    let log = run_git(&[
+        "log",
+        "--reverse",
+        "--date=raw",
+        "--format=%H%x1f%T%x1f%an%x1f%ae%x1f%ad%x1f%B%x1e",
+        &range,
+    ])?;
+    Ok(log
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').splitn(6, '\x1f');
+            Some(CommitInfo {
+                hash: fields.next().filter(|h| !h.is_empty())?.to_string(),
+                tree: fields.next()?.to_string(),
+                author_name: fields.next()?.to_string(),
+                author_email: fields.next()?.to_string(),
+                author_date: fields.next()?.to_string(),
+                message: fields.next()?.trim_end().to_string(),
+            })
+        })
+        .collect())
+}
+
+/// The patch introduced by a single commit.
+pub fn commit_diff(hash: &str) -> Result<String, CommitauraError> {
+    run_git(&["show", "--format=", "--patch", hash])
+}
+
+/// Attach `text` to `commit` as a note under `refs/notes/<notes_ref>`,
+/// replacing any note already there.
+pub fn add_note(notes_ref: &str, commit: &str, text: &str) -> Result<(), CommitauraError> {
+    let file = MessageFile::create(&git_dir()?.join("commitaura"), text)?;
+    let path = file.0.to_string_lossy().to_string();
+    run_git(&["notes", &format!("--ref={}", notes_ref), "add", "--force", "--file", &path, commit]).map(|_| ())
+}
+
+/// The note attached to `commit` under `refs/notes/<notes_ref>`, if any.
+pub fn note(notes_ref: &str, commit: &str) -> Option<String> {
+    run_git(&["notes", &format!("--ref={}", notes_ref), "show", commit]).ok()
+}
+
+/// The newest commit on any ref whose message has the trailer `token: value`.
+pub fn commit_with_trailer(token: &str, value: &str) -> Option<String> {
+    let grep = format!("--grep=^{}: {}$", basic_regex_escape(token), basic_regex_escape(value));
+    let found = run_git(&["log", "--all", "-1", "--basic-regexp", "--format=%H", &grep]).ok()?;
+    let found = found.trim();
+    (!found.is_empty()).then(|| found.to_string())
+}
+
+/// `text` as a POSIX basic regular expression that matches it literally.
+fn basic_regex_escape(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '\\' | '.' | '[' | '*' | '^' | '$' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+/// Fold `fixup!`/`squash!`/`amend!` commits in `base..HEAD` into their targets
+/// with a non-interactive `git rebase --autosquash`.
+pub fn autosquash(base: &str) -> Result<(), CommitauraError> {
+    // A rebase checks commits out into the index, which would clobber a
+    // temporary index that belongs to whoever invoked us.
+    if let Some(index) = index_override() {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "refusing to rebase while GIT_INDEX_FILE points at {}",
+            index.display()
+        )));
+    }
+    let status = command(&["rebase", "--interactive", "--autosquash", base])?
+        .env("GIT_SEQUENCE_EDITOR", "true")
+        .env("GIT_EDITOR", "true")
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(
+            "Autosquash rebase failed; a fixup probably conflicts with a later commit".to_string(),
+        ))
+    }
+}
+
+/// Recreate `commits` on top of `base` with new messages and move HEAD to the
+/// result. Trees, authors and author dates are kept, so the working tree is
+/// untouched.
+pub fn rewrite_messages(
+    base: &str,
+    commits: &[(CommitInfo, String)],
+) -> Result<String, CommitauraError> {
+    use std::io::Write;
+
+    let old_head = head_commit()?;
+    let mut parent = run_git(&["rev-parse", base])?.trim().to_string();
+
+    for (commit, message) in commits {
+        let mut child = command(&["commit-tree", &commit.tree, "-p", &parent, "-F", "-"])?
+            .env("GIT_AUTHOR_NAME", &commit.author_name)
+            .env("GIT_AUTHOR_EMAIL", &commit.author_email)
+            .env("GIT_AUTHOR_DATE", &commit.author_date)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(message.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(CommitauraError::GitOperationFailed(format!(
+                "git commit-tree failed for {}",
+                commit.hash
+            )));
+        }
+        parent = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    }
+
+    run_git(&[
+        "update-ref",
+        "-m",
+        "commitaura: reword",
+        "HEAD",
+        &parent,
+        &old_head,
+    ])?;
+    Ok(parent)
+}
+
+/// Root of the work tree.
+pub fn toplevel() -> Result<PathBuf, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(Some(dir)) = with_libgit2("open the repository", |repo| Ok(repo.toplevel().map(Path::to_path_buf))) {
+        return Ok(dir);
+    }
+    Ok(PathBuf::from(
+        run_git(&["rev-parse", "--show-toplevel"])?.trim(),
+    ))
+}
+
+/// The repository's `.git` directory, as an absolute path.
+pub fn git_dir() -> Result<PathBuf, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(dir) = with_libgit2("open the repository", |repo| Ok(repo.git_dir().to_path_buf())) {
+        return Ok(dir);
+    }
+    Ok(PathBuf::from(
+        run_git(&["rev-parse", "--absolute-git-dir"])?.trim(),
+    ))
+}
+
+/// Directory git runs hooks from: `core.hooksPath`, or `.git/hooks`.
+pub fn hooks_dir() -> Result<PathBuf, CommitauraError> {
+    // Relative results are relative to the current directory.
+    let path = PathBuf::from(run_git(&["rev-parse", "--git-path", "hooks"])?.trim());
+    Ok(std::env::current_dir()?.join(path))
+}
+
+/// Tree hash of `rev`.
+pub fn rev_tree(rev: &str) -> Result<String, CommitauraError> {
+    Ok(run_git(&["rev-parse", &format!("{}^{{tree}}", rev)])?
+        .trim()
+        .to_string())
+}
+
+/// Tree hash of the index, writing its tree objects if needed.
+pub fn index_tree() -> Result<String, CommitauraError> {
+    Ok(run_git(&["write-tree"])?.trim().to_string())
+}
+
+/// A hash of the index's entries that changes whenever [`index_tree`]
+/// would, without writing anything, so it works in read-only mode. `None`
+/// while the index has unresolved conflicts.
+pub fn index_fingerprint() -> Result<Option<String>, CommitauraError> {
+    let entries = run_git(&["ls-files", "--stage", "-z"])?;
+    let conflicted = entries
+        .split('\0')
+        .filter_map(|entry| entry.split_once('\t'))
+        .any(|(meta, _)| !meta.ends_with(" 0"));
+    if conflicted {
+        return Ok(None);
+    }
+    let digest = ring::digest::digest(&ring::digest::SHA256, entries.as_bytes());
+    Ok(Some(digest.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()))
+}
+
+/// Commit hash of HEAD.
+pub fn head_commit() -> Result<String, CommitauraError> {
+    Ok(run_git(&["rev-parse", "HEAD"])?.trim().to_string())
+}
+
+/// Commit hash `reference` points at, such as `refs/heads/main`.
+pub fn ref_commit(reference: &str) -> Result<String, CommitauraError> {
+    Ok(run_git(&["rev-parse", "--verify", &format!("{}^{{commit}}", reference)])?
+        .trim()
+        .to_string())
+}
+
+/// Point `reference` at `commit`, but only if it still points at
+/// `expected`, leaving the index and work tree alone. `reason` goes into
+/// the reflog.
+pub fn move_ref(
+    reference: &str,
+    commit: &str,
+    expected: &str,
+    reason: &str,
+) -> Result<(), CommitauraError> {
+    run_git(&["update-ref", "-m", reason, reference, commit, expected]).map(|_| ())
+}
+
+/// The changes from `base`, or from nothing, to `tree`. Needs no index or
+/// work tree, so it works in a bare repository.
+pub fn tree_diff(base: Option<&str>, tree: &str) -> Result<String, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(diff) = with_libgit2("diff the trees", |repo| repo.tree_diff(base, tree)) {
+        return Ok(diff);
+    }
+    run_git(&["diff-tree", "-p", "-M", base.unwrap_or(EMPTY_TREE), tree])
+}
+
+/// Create a commit of `tree` with `parents` and `message`, cleaned up as
+/// `git commit -F` would, and return its id. HEAD, the index and the work
+/// tree are left alone, so it works in a bare repository. With
+/// `update_ref`, the ref is moved to the commit, but only if it does not
+/// exist yet or still points at the first parent.
+pub fn commit_tree(
+    tree: &str,
+    parents: &[String],
+    message: &str,
+    update_ref: Option<&str>,
+) -> Result<String, CommitauraError> {
+    ensure_writable("commit-tree")?;
+    let message = editmsg::cleanup(
+        &editmsg::for_commit(message),
+        editmsg::Cleanup::configured(),
+        false,
+        editmsg::comment_char(message),
+    );
+    if message.is_empty() {
+        return Err(CommitauraError::GitOperationFailed("The commit message is empty".to_string()));
+    }
+    #[cfg(feature = "libgit2")]
+    if let Some(commit) = with_libgit2("create the commit", |repo| repo.commit_tree(tree, parents, &message, update_ref)) {
+        return Ok(commit);
+    }
+    let file = MessageFile::create(&git_dir()?.join("commitaura"), &message)?;
+    let path = file.0.to_string_lossy().to_string();
+    let mut args = vec!["commit-tree", tree, "-F", &path];
+    for parent in parents {
+        args.extend(["-p", parent]);
+    }
+    let commit = run_git(&args)?.trim().to_string();
+    if let Some(reference) = update_ref {
+        // The zero id, as long as the repository's hashes, means the ref
+        // must not exist yet.
+        let old = match parents.first() {
+            Some(parent) => run_git(&["rev-parse", &format!("{}^{{commit}}", parent)])?.trim().to_string(),
+            None => "0".repeat(commit.len()),
+        };
+        run_git(&["update-ref", "-m", "commitaura: commit", reference, &commit, &old])?;
+    }
+    Ok(commit)
+}
+
+/// Replace the index with `tree`, leaving the work tree alone.
+pub fn read_tree(tree: &str) -> Result<(), CommitauraError> {
+    run_git(&["read-tree", tree]).map(|_| ())
+}
+
+/// Set the index entries of `paths` to what they are in `tree`, removing
+/// those `tree` lacks (`git reset <tree> -- <paths>`).
+pub fn reset_paths_to(tree: &str, paths: &[String]) -> Result<(), CommitauraError> {
+    let mut args = vec!["reset", "--quiet", tree, "--"];
+    args.extend(paths.iter().map(String::as_str));
+    run_git(&args).map(|_| ())
+}
+
+/// Abandon the rebase in progress, restoring the branch it started from.
+pub fn abort_rebase() -> Result<(), CommitauraError> {
+    run_git(&["rebase", "--abort"]).map(|_| ())
+}
+
+/// Check `tree` out into `dir` through a throwaway index, leaving the real
+/// index and work tree alone.
+pub fn export_tree(tree: &str, dir: &Path) -> Result<(), CommitauraError> {
+    let index =
+        std::env::temp_dir().join(format!("commitaura-index-{}-{}", std::process::id(), tree));
+    let mut prefix = dir.as_os_str().to_owned();
+    prefix.push("/");
+    let steps: [Vec<OsString>; 2] = [
+        vec!["read-tree".into(), tree.into()],
+        vec![
+            "checkout-index".into(),
+            "--all".into(),
+            "--prefix".into(),
+            prefix,
+        ],
+    ];
+    let result = steps.iter().try_for_each(|args| {
+        let output = git_command()
+            .args(args)
+            .env("GIT_INDEX_FILE", &index)
+            .output()
+            .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(CommitauraError::GitOperationFailed(format!(
+                "exporting {} failed: {}",
+                tree,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+    });
+    let _ = std::fs::remove_file(&index);
+    result
+}
+
+/// Value of git config `key`, if set.
+pub fn config_value(key: &str) -> Option<String> {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("read the config", |repo| repo.config_value(key)) {
+        return value;
+    }
+    run_git(&["config", "--get", key])
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+/// Git config `key` interpreted as a path, with `~` expanded.
+pub fn config_path(key: &str) -> Option<PathBuf> {
+    run_git(&["config", "--path", "--get", key])
+        .ok()
+        .map(|value| PathBuf::from(value.trim()))
+}
+
+/// The branch HEAD points at, e.g. `refs/heads/main`; `None` when detached.
+pub fn symbolic_head() -> Option<String> {
+    #[cfg(feature = "libgit2")]
+    if let Some(head) = with_libgit2("read HEAD", |repo| repo.symbolic_head()) {
+        return head;
+    }
+    run_git(&["symbolic-ref", "--quiet", "HEAD"])
+        .ok()
+        .map(|r| r.trim().to_string())
+}
+
+/// Whether the repository is a shallow clone.
+pub fn is_shallow() -> bool {
+    #[cfg(feature = "libgit2")]
+    if let Some(shallow) = with_libgit2("open the repository", |repo| Ok(repo.is_shallow())) {
+        return shallow;
+    }
+    run_git(&["rev-parse", "--is-shallow-repository"]).is_ok_and(|out| out.trim() == "true")
+}
+
+/// Number of commits reachable from `rev`.
+pub fn count_commits(rev: &str) -> Result<usize, CommitauraError> {
+    run_git(&["rev-list", "--count", rev])?
+        .trim()
+        .parse()
+        .map_err(|e| CommitauraError::GitOperationFailed(format!("bad commit count: {}", e)))
+}
+
+/// Fetch `commits` more commits of history into a shallow clone.
+pub fn deepen(commits: usize) -> Result<(), CommitauraError> {
+    fetch(&[&format!("--deepen={}", commits)])
+}
+
+/// Fetch the complete history, and tags, into a shallow clone.
+pub fn unshallow() -> Result<(), CommitauraError> {
+    fetch(&["--unshallow", "--tags"])
+}
+
+fn fetch(args: &[&str]) -> Result<(), CommitauraError> {
+    let status = command(&["fetch"])?
+        .args(args)
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(
+            "Git fetch failed".to_string(),
+        ))
+    }
+}
+
+/// URL of the given remote, if it exists.
+pub fn remote_url(remote: &str) -> Option<String> {
+    run_git(&["remote", "get-url", remote])
+        .ok()
+        .map(|url| url.trim().to_string())
+}
+
+pub fn current_branch() -> Result<String, CommitauraError> {
+    Ok(run_git(&["rev-parse", "--abbrev-ref", "HEAD"])?
+        .trim()
+        .to_string())
+}
+
+/// The remote's default branch as a ref such as `origin/main`.
+pub fn default_branch(remote: &str) -> String {
+    run_git(&[
+        "symbolic-ref",
+        "--short",
+        &format!("refs/remotes/{}/HEAD", remote),
+    ])
+    .map(|r| r.trim().to_string())
+    .unwrap_or_else(|_| format!("{}/main", remote))
+}
+
+/// Subjects of the commits in `range`, newest first.
+pub fn commit_subjects(range: &str) -> Result<String, CommitauraError> {
+    run_git(&["log", "--pretty=format:%s", range])
+}
+
+/// Short hash and subject of each commit in `range` that is not a merge,
+/// newest first.
+pub fn range_commits(range: &str) -> Result<Vec<(String, String)>, CommitauraError> {
+    Ok(run_git(&["log", "--no-merges", "--format=%h%x09%s", range])?
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(hash, subject)| (hash.to_string(), subject.to_string()))
+        .collect())
+}
+
+/// Short hash and full message of each commit in `range` that is not a
+/// merge, oldest first.
+pub fn range_messages(range: &str) -> Result<Vec<(String, String)>, CommitauraError> {
+    Ok(run_git(&["log", "--no-merges", "--reverse", "--format=%h%x1f%B%x1e", range])?
+        .split('\x1e')
+        .filter_map(|record| record.trim_start_matches('\n').split_once('\x1f'))
+        .map(|(hash, message)| (hash.to_string(), message.trim_end().to_string()))
+        .collect())
+}
+
+/// Tags reachable from HEAD, newest first, each with the Unix time it was
+/// made (the tagger's, or the commit's for a lightweight tag).
+pub fn tags_with_dates() -> Result<Vec<(String, i64)>, CommitauraError> {
+    let format = "--format=%(refname:short)%09%(creatordate:unix)";
+    Ok(run_git(&["for-each-ref", "--sort=-creatordate", "--merged", "HEAD", format, "refs/tags"])?
+        .lines()
+        .filter_map(|line| {
+            let (tag, time) = line.split_once('\t')?;
+            Some((tag.to_string(), time.parse().ok()?))
+        })
+        .collect())
+}
+
+/// Best common ancestor of `a` and `b`.
+pub fn merge_base(a: &str, b: &str) -> Result<String, CommitauraError> {
+    Ok(run_git(&["merge-base", a, b])?.trim().to_string())
+}
+
+/// Subjects of the commits only HEAD's side of `target...HEAD` has, newest
+/// first: merge commits and commits whose patch `target` already contains
+/// (cherry-picked or rebased) are left out.
+pub fn unique_subjects(target: &str) -> Result<String, CommitauraError> {
+    run_git(&[
+        "log",
+        "--no-merges",
+        "--right-only",
+        "--cherry-pick",
+        "--pretty=format:%s",
+        &format!("{}...HEAD", target),
+    ])
+}
+
+/// Diff of `range` (e.g. `origin/main...HEAD`).
+pub fn range_diff(range: &str) -> Result<String, CommitauraError> {
+    run_git(&["diff", range])
+}
+
+/// Push the current branch, setting its upstream on the first push.
+pub fn push_current_branch(remote: &str) -> Result<(), CommitauraError> {
+    let status = command(&["push", "--set-upstream", remote, "HEAD"])?
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(
+            "Git push failed".to_string(),
+        ))
+    }
+}
+
+/// Paths with staged changes.
+pub fn staged_paths() -> Result<Vec<String>, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("diff the index", |repo| repo.staged_paths()) {
+        return Ok(value);
+    }
+    // Rename detection would read blobs, which a partial clone may not have.
+    Ok(run_git(&["diff", "--staged", "--name-only", "--no-renames"])?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Size in bytes of the staged version of `path`.
+pub fn staged_size(path: &str) -> Option<u64> {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("read the index", |repo| repo.staged_size(path)) {
+        return value;
+    }
+    run_git(&["cat-file", "-s", &format!(":{}", path)]).ok()?.trim().parse().ok()
+}
+
+/// The value `git check-attr` gives attribute `name` for each of `paths`,
+/// relative to the repository root: `set`, `unset`, `unspecified` or the
+/// value itself. Every `.gitattributes` file and `info/attributes` count.
+pub fn check_attr(name: &str, paths: &[String]) -> Result<Vec<(String, String)>, CommitauraError> {
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    let root = toplevel()?;
+    let root = root.to_string_lossy();
+    let mut args = vec!["-C", &root, "check-attr", "-z", name, "--"];
+    args.extend(paths.iter().map(String::as_str));
+    Ok(parse_check_attr(&run_git(&args)?))
+}
+
+/// `git check-attr -z` output as `(path, value)` pairs.
+fn parse_check_attr(output: &str) -> Vec<(String, String)> {
+    let fields: Vec<&str> = output.split('\0').collect();
+    fields
+        .chunks_exact(3)
+        .map(|field| (field[0].to_string(), field[2].to_string()))
+        .collect()
+}
+
+/// Untracked, non-ignored paths; wholly untracked directories are listed
+/// once, with a trailing `/`.
+pub fn untracked_paths() -> Result<Vec<String>, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("list untracked files", |repo| repo.untracked_paths()) {
+        return Ok(value);
+    }
+    Ok(run_git(&["ls-files", "--others", "--exclude-standard", "--directory"])?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Paths touched by the given commit.
+pub fn commit_paths(rev: &str) -> Result<Vec<String>, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("read a commit", |repo| repo.commit_paths(rev)) {
+        return Ok(value);
+    }
+    Ok(run_git(&["show", "--name-only", "--no-renames", "--format=", rev])?
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Committer timestamp of `rev` in seconds since the epoch.
+pub fn commit_time(rev: &str) -> Result<u64, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("read a commit", |repo| repo.commit_time(rev)) {
+        return Ok(value);
+    }
+    run_git(&["log", "-1", "--format=%ct", rev])?
+        .trim()
+        .parse()
+        .map_err(|e| CommitauraError::GitOperationFailed(format!("bad commit time: {}", e)))
+}
+
+/// Full message of `rev`.
+pub fn commit_message(rev: &str) -> Result<String, CommitauraError> {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("read a commit", |repo| repo.commit_message(rev)) {
+        return Ok(value);
+    }
+    Ok(run_git(&["log", "-1", "--format=%B", rev])?
+        .trim_end()
+        .to_string())
+}
+
+/// Names of the entries at the top of `rev`'s tree.
+pub fn tree_names(rev: &str) -> Result<Vec<String>, CommitauraError> {
+    Ok(run_git(&["ls-tree", "--name-only", rev])?
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Names of the entries directly inside `dir` in the index, `dir` being
+/// relative to the repository root.
+pub fn index_names(dir: &str) -> Result<Vec<String>, CommitauraError> {
+    let prefix = format!("{}/", dir.trim_end_matches('/'));
+    let pathspec = format!(":(top){}", prefix);
+    let mut names: Vec<String> = run_git(&["ls-files", "--full-name", "--", &pathspec])?
+        .lines()
+        .filter_map(|path| path.strip_prefix(&prefix))
+        .filter_map(|rest| rest.split('/').next())
+        .map(str::to_string)
+        .collect();
+    names.dedup();
+    Ok(names)
+}
+
+/// The branch or tag `commit` is the tip of, e.g. `feature/login` or
+/// `origin/main`.
+pub fn tip_name(commit: &str) -> Option<String> {
+    let name = run_git(&["name-rev", "--name-only", "--no-undefined", commit]).ok()?;
+    let name = name.trim();
+    let name = name.strip_prefix("remotes/").unwrap_or(name);
+    let name = name.strip_prefix("tags/").unwrap_or(name);
+    (!name.contains(['~', '^'])).then(|| name.to_string())
+}
+
+/// Abbreviated hash of `rev`.
+pub fn short_hash(rev: &str) -> Result<String, CommitauraError> {
+    Ok(run_git(&["rev-parse", "--short", rev])?.trim().to_string())
+}
+
+/// Whether `rev` has a parent, i.e. is not a root commit.
+pub fn has_parent(rev: &str) -> bool {
+    #[cfg(feature = "libgit2")]
+    if let Some(value) = with_libgit2("read a commit", |repo| repo.has_parent(rev)) {
+        return value;
+    }
+    run_git(&["rev-parse", "--verify", "--quiet", &format!("{}^", rev)]).is_ok()
+}
+
+/// Whether `rev` is reachable from any remote-tracking branch.
+pub fn is_published(rev: &str) -> bool {
+    run_git(&["branch", "--remotes", "--contains", rev])
+        .map(|out| !out.trim().is_empty())
+        .unwrap_or(false)
+}
+
+/// The most recent tag reachable from HEAD, if any.
+pub fn latest_tag() -> Option<String> {
+    run_git(&["describe", "--tags", "--abbrev=0", "HEAD"])
+        .ok()
+        .map(|tag| tag.trim().to_string())
+}
+
+/// Create annotated tag `name` on HEAD, GPG-signed if `sign` is set.
+pub fn create_tag(name: &str, message: &str, sign: bool) -> Result<(), CommitauraError> {
+    use std::io::Write;
+
+    let mut child = command(&[
+        "tag",
+        if sign { "--sign" } else { "--annotate" },
+        // The default cleanup would strip lines starting with '#'.
+        "--cleanup=whitespace",
+        "-F",
+        "-",
+        name,
+    ])?
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(message.as_bytes())?;
+    if child.wait()?.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(format!(
+            "git tag {} failed",
+            name
+        )))
+    }
+}
+
+/// Replace HEAD's message without touching its tree, even if changes are staged.
+pub fn amend_message(message: &str) -> Result<(), CommitauraError> {
+    let file = message_file(message)?;
+    let output = commit_command(&["--amend", "--only"], &file)?
+        .output()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(format!(
+            "git commit --amend failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Open HEAD's message in the git editor and amend it with the result.
+pub fn edit_message() -> Result<(), CommitauraError> {
+    let status = command(&["commit", "--amend", "--only"])?
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(
+            "Git commit --amend failed".to_string(),
+        ))
+    }
+}
+
+/// Open `path` in the user's editor as git would (`GIT_EDITOR`,
+/// `core.editor`, `VISUAL`, `EDITOR`) and wait for it to close.
+pub fn run_editor(path: &Path) -> Result<(), CommitauraError> {
+    let editor = run_git(&["var", "GIT_EDITOR"])?.trim().to_string();
+    // The editor setting is a shell snippet and may carry its own arguments.
+    let status = std::process::Command::new("sh")
+        .args(["-c", &format!("{} \"$@\"", editor), "editor"])
+        .arg(path)
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(format!(
+            "Editor '{}' exited with an error",
+            editor
+        )))
+    }
+}
+
+/// Let the user edit the commit message `text` in their git editor, laid
+/// out like `git commit -e` would: with git's help comments and, with
+/// `commit.verbose`, `diff` below the scissors line. The result is cleaned
+/// up as git would clean up an edited message.
+pub fn edit_text(text: &str, diff: &str) -> Result<String, CommitauraError> {
+    let dir = git_dir()?.join("commitaura");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("MESSAGE_EDITMSG");
+    let mode = editmsg::Cleanup::configured();
+    let comment = editmsg::comment_char(text);
+    let mut contents = format!(
+        "{}\n\n{} Please enter the commit message for your changes. Lines starting\n",
+        editmsg::protect(text, comment),
+        comment
+    );
+    if mode.strips_comments(true) {
+        contents.push_str(&format!(
+            "{0} with '{0}' will be ignored, and an empty message aborts the commit.\n",
+            comment
+        ));
+    } else {
+        contents.push_str(&format!(
+            "{0} with '{0}' will be kept; you may remove them yourself if you want to.\n{0} An empty message aborts the commit.\n",
+            comment
+        ));
+    }
+    let verbose = config_value("commit.verbose").is_some_and(|v| {
+        matches!(v.as_str(), "true" | "yes" | "on") || v.parse::<u32>().is_ok_and(|n| n > 0)
+    });
+    if verbose || mode == editmsg::Cleanup::Scissors {
+        contents.push_str(&format!(
+            "{0} ------------------------ >8 ------------------------\n{0} Do not modify or remove the line above.\n{0} Everything below it will be ignored.\n",
+            comment
+        ));
+    }
+    if verbose {
+        contents.push_str(diff);
+    }
+    std::fs::write(&path, contents)?;
+    run_editor(&path)?;
+    Ok(editmsg::cleanup(&std::fs::read_to_string(&path)?, mode, true, comment))
+}
+
+/// Move HEAD back to `before`, the commit it was at before the last commit
+/// or amend, leaving the changes staged. `None` means the branch had no
+/// commits yet, which leaves nothing to reset to.
+pub fn undo_commit(before: Option<&str>) -> Result<(), CommitauraError> {
+    let before = before.ok_or_else(|| {
+        CommitauraError::GitOperationFailed("the root commit cannot be undone".to_string())
+    })?;
+    run_git(&["reset", "--soft", before]).map(|_| ())
+}
+
+/// Diff between HEAD's parent and the index: what HEAD would contain if the
+/// staged changes were amended into it.
+pub fn get_amend_diff() -> Result<String, CommitauraError> {
+    get_amend_diff_in(&[])
+}
+
+/// [`get_amend_diff`] limited to `pathspecs`; an empty list means everything.
+pub fn get_amend_diff_in(pathspecs: &[String]) -> Result<String, CommitauraError> {
+    // Amending a root commit replaces everything in it.
+    let base = if has_parent("HEAD") { "HEAD^" } else { EMPTY_TREE };
+    let mut args = vec!["diff", "--staged", base, "--"];
+    args.extend(pathspecs.iter().map(String::as_str));
+    run_git(&args)
+}
+
+/// Stage work-tree changes to the given tracked paths (`git add -u`).
+pub fn restage(paths: &[String]) -> Result<(), CommitauraError> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let mut args = vec!["add", "--update", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    run_git(&args).map(|_| ())
+}
+
+/// Stage the work-tree changes to every tracked file, deletions included,
+/// as `git commit -a` does (`git add -u`).
+pub fn stage_tracked() -> Result<(), CommitauraError> {
+    run_git(&["add", "--update"]).map(|_| ())
+}
+
+/// Work-tree changes to tracked files that are not staged yet.
+pub fn get_unstaged_diff() -> Result<String, CommitauraError> {
+    run_git(&["diff", "--no-color", "--no-ext-diff"])
+}
+
+/// Stage the changes in `patch`, a diff of the work tree against the index
+/// that may leave some hunks out (`git apply --cached`).
+pub fn apply_to_index(patch: &str) -> Result<(), CommitauraError> {
+    use std::io::Write;
+
+    let mut child = command(&["apply", "--cached", "--recount", "-"])?
+        .stdin(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(patch.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(format!(
+            "git apply --cached failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Staged and unstaged changes to tracked files: what `git stash push`
+/// would save.
+pub fn get_worktree_diff() -> Result<String, CommitauraError> {
+    run_git(&["diff", "HEAD"])
+}
+
+/// Stash the changes to tracked files under `message`.
+pub fn stash_push(message: &str) -> Result<(), CommitauraError> {
+    run_git(&["stash", "push", "--quiet", "-m", message]).map(|_| ())
+}
+
+fn stash_ref(index: usize) -> String {
+    format!("stash@{{{}}}", index)
+}
+
+/// The changes saved in stash entry `index`, against the commit it was
+/// made on.
+pub fn stash_diff(index: usize) -> Result<String, CommitauraError> {
+    let stash = stash_ref(index);
+    run_git(&["diff", &format!("{}^1", stash), &stash])
+}
+
+/// The message stash entry `index` is listed with, e.g. `WIP on main: 1a2b3c4 Fix parser`.
+pub fn stash_message(index: usize) -> Result<String, CommitauraError> {
+    Ok(run_git(&["log", "-1", "--format=%s", &stash_ref(index)])?.trim().to_string())
+}
+
+/// Give stash entry `index` a new message, keeping the `On <branch>:`
+/// prefix `git stash push -m` would give it. A stash cannot be reworded in
+/// place, so the entry is stored again under the new message, which puts it
+/// at `stash@{0}`, and only then is the old entry dropped.
+pub fn rename_stash(index: usize, message: &str) -> Result<(), CommitauraError> {
+    let old = stash_message(index)?;
+    let branch = old
+        .strip_prefix("WIP on ")
+        .or_else(|| old.strip_prefix("On "))
+        .and_then(|rest| rest.split_once(": "))
+        .map(|(branch, _)| branch);
+    let message = match branch {
+        Some(branch) => format!("On {}: {}", branch, message),
+        None => message.to_string(),
+    };
+    let commit = run_git(&["rev-parse", &stash_ref(index)])?.trim().to_string();
+    run_git(&["stash", "store", "-m", &message, &commit])?;
+    run_git(&["stash", "drop", "--quiet", &stash_ref(index + 1)]).map(|_| ())
+}
+
+/// Stage the given paths, new files included (`git add`).
+pub fn stage(paths: &[String]) -> Result<(), CommitauraError> {
+    let mut args = vec!["add", "--"];
+    args.extend(paths.iter().map(String::as_str));
+    run_git(&args).map(|_| ())
+}
+
+/// Staged diff against HEAD with whitespace and blank-line changes ignored.
+pub fn staged_diff_ignoring_whitespace() -> Result<String, CommitauraError> {
+    run_git(&["diff", "--staged", "--ignore-all-space", "--ignore-blank-lines"])
+}
+
+/// Amend the staged changes into HEAD, keeping its message.
+pub fn amend_no_edit() -> Result<(), CommitauraError> {
+    run_git(&["commit", "--amend", "--no-edit"]).map(|_| ())
+}
+
+/// Output of `git sparse-checkout list`.
+pub fn sparse_checkout_list() -> Result<String, CommitauraError> {
+    run_git(&["sparse-checkout", "list"])
+}
+
+/// Amend HEAD with the staged changes and `message`. Like `git commit
+/// --amend`, this keeps HEAD's author and author date.
+pub fn perform_git_amend(message: &str) -> Result<(), CommitauraError> {
+    amend_with(message, &[])
+}
+
+/// [`perform_git_amend`], giving the commit the current time as its author
+/// date.
+pub fn perform_git_amend_redated(message: &str) -> Result<(), CommitauraError> {
+    amend_with(message, &["--date=now"])
+}
+
+fn amend_with(message: &str, args: &[&str]) -> Result<(), CommitauraError> {
+    let file = message_file(message)?;
+    let mut args = args.to_vec();
+    args.push("--amend");
+    let status = commit_command(&args, &file)?
+        .status()
+        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CommitauraError::GitOperationFailed(
+            "Git commit --amend failed".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn finds_the_subcommand_after_global_options() {
+        assert_eq!(operation(&["-c", "i18n.commitEncoding=UTF-8", "commit", "-F", "x"]), ["commit", "-F", "x"]);
+        assert_eq!(operation(&["--no-pager", "log", "-1"]), ["log", "-1"]);
+        assert!(operation(&[]).is_empty());
+    }
+
+    #[test]
+    fn escapes_basic_regexes() {
+        assert_eq!(basic_regex_escape("a.b*[c]^$\\+?"), "a\\.b\\*\\[c]\\^\\$\\\\+?");
+    }
+
+    #[test]
+    fn parses_check_attr_output() {
+        let output = "big.bin\0filter\0unspecified\0model.onnx\0filter\0lfs\0";
+        assert_eq!(
+            parse_check_attr(output),
+            [("big.bin".to_string(), "unspecified".to_string()), ("model.onnx".to_string(), "lfs".to_string())]
+        );
+    }
+
+    #[test]
+    fn classifies_operations_by_their_arguments() {
+        for read in [
+            &["log", "-1"][..],
+            &["notes", "--ref=commitaura", "show", "HEAD"],
+            &["config", "--get", "user.name"],
+            &["symbolic-ref", "--quiet", "HEAD"],
+            &["remote", "get-url", "origin"],
+            &["branch", "--remotes", "--contains", "HEAD"],
+            &["sparse-checkout", "list"],
+            &["stash", "list"],
+            &["tag", "--list", "v*"],
+        ] {
+            assert!(reads_only(read), "{:?}", read);
+        }
+        for write in [
+            &["commit", "-F", "x"][..],
+            &["write-tree"],
+            &["notes", "--ref=commitaura", "add", "--force", "HEAD"],
+            &["config", "user.name", "x"],
+            &["symbolic-ref", "HEAD", "refs/heads/other"],
+            &["remote", "add", "fork", "url"],
+            &["branch", "topic"],
+            &["branch", "-D", "topic"],
+            &["tag", "-a", "v1.0", "-m", "Release"],
+            &["sparse-checkout", "set", "src"],
+            &["stash", "push"],
+            &["some-future-subcommand"],
+        ] {
+            assert!(!reads_only(write), "{:?}", write);
+        }
+    }
+
+    #[test]
+    fn message_files_keep_multibyte_text_and_clean_up() {
+        let dir = std::env::temp_dir().join(format!("commitaura-msg-{}", std::process::id()));
+        let message = "修正: ログイン処理 ✨\n\nÜmlaut und emoji 🎉";
+        let path = {
+            let file = MessageFile::create(&dir, message).unwrap();
+            assert_eq!(std::fs::read_to_string(&file.0).unwrap(), message);
+            file.0.clone()
+        };
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relative_overrides_are_made_absolute() {
+        let resolved = resolve_overrides(Path::new("/work/repo"), |var| match var {
+            "GIT_INDEX_FILE" => Some(".git/next-index-42.lock".into()),
+            "GIT_DIR" => Some("/elsewhere/.git".into()),
+            "GIT_WORK_TREE" => Some("".into()),
+            _ => None,
+        });
+        assert_eq!(
+            resolved,
+            vec![(
+                "GIT_INDEX_FILE",
+                PathBuf::from("/work/repo/.git/next-index-42.lock")
+            )]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! `.gitignore` suggestions for untracked build artifacts.
+//!
+//! Untracked junk tends to get staged by `git add .` and then described in
+//! the commit message. When the untracked files look like build output, the
+//! model is asked for ignore entries, which are shown as a diff to approve.
+
+use std::path::Path;
+
+use crate::generate::{complete, GenerateOptions, Prompt};
+use crate::CommitauraError;
+
+const SYSTEM_MESSAGE: &str =
+    "You are a helpful assistant that writes minimal, conventional .gitignore entries.";
+
+/// Directory names and file suffixes that are almost always generated.
+const ARTIFACT_DIRS: &[&str] = &[
+    "target/",
+    "node_modules/",
+    "dist/",
+    "build/",
+    "out/",
+    "__pycache__/",
+    ".venv/",
+    "venv/",
+    ".pytest_cache/",
+    ".mypy_cache/",
+    ".gradle/",
+    ".next/",
+    "coverage/",
+];
+const ARTIFACT_SUFFIXES: &[&str] = &[
+    ".o",
+    ".a",
+    ".so",
+    ".dylib",
+    ".dll",
+    ".exe",
+    ".class",
+    ".pyc",
+    ".log",
+    ".tmp",
+    ".swp",
+    ".DS_Store",
+];
+
+/// Untracked entries beyond which the tree counts as noisy even without
+/// recognised artifacts.
+pub const MAX_UNTRACKED: usize = 50;
+
+/// Whether `path` (as listed by `git ls-files --others --directory`) looks
+/// like build output or editor/OS litter.
+pub fn looks_like_artifact(path: &str) -> bool {
+    ARTIFACT_DIRS
+        .iter()
+        .any(|dir| path == *dir || path.ends_with(&format!("/{}", dir)))
+        || ARTIFACT_SUFFIXES
+            .iter()
+            .any(|suffix| path.ends_with(suffix))
+}
+
+/// Whether the untracked entries are worth offering ignore rules for.
+pub fn is_noisy(untracked: &[String]) -> bool {
+    untracked.len() > MAX_UNTRACKED || untracked.iter().any(|p| looks_like_artifact(p))
+}
+
+pub fn build_prompt(untracked: &[String], existing: &str) -> Prompt {
+    let listed: Vec<&str> = untracked.iter().take(200).map(String::as_str).collect();
+    Prompt {
+        system: SYSTEM_MESSAGE.to_string(),
+        user: format!(
+            "Suggest .gitignore entries for the build artifacts, dependencies, caches and editor or OS files among these untracked paths. Leave out anything that looks like source code, documentation or configuration a developer would commit. Prefer directory and glob patterns over single files. Reply with one pattern per line and nothing else; reply with nothing if no entry is needed.\n\nCurrent .gitignore:\n{}\n\nUntracked paths:\n{}\n",
+            existing.trim(),
+            listed.join("\n")
+        ),
+    }
+}
+
+/// Clean up the model's reply into new entries: code fences, bullets and
+/// comments are dropped, as are entries `existing` already has.
+pub fn parse_entries(reply: &str, existing: &str) -> Vec<String> {
+    let present: Vec<&str> = existing.lines().map(str::trim).collect();
+    let mut entries: Vec<String> = Vec::new();
+    for line in reply.lines() {
+        let line = line.trim();
+        let entry = line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .unwrap_or(line)
+            .trim();
+        if entry.is_empty()
+            || entry.starts_with("```")
+            || entry.starts_with('#')
+            || entry.contains(char::is_whitespace)
+            || present.contains(&entry)
+            || entries.iter().any(|e| e == entry)
+        {
+            continue;
+        }
+        entries.push(entry.to_string());
+    }
+    entries
+}
+
+/// Ask the model for entries covering `untracked`.
+pub fn suggest(
+    untracked: &[String],
+    existing: &str,
+    opts: &GenerateOptions,
+) -> Result<Vec<String>, CommitauraError> {
+    let reply = complete(&build_prompt(untracked, existing), opts, 200)?;
+    Ok(parse_entries(&reply, existing))
+}
+
+/// Unified diff adding `entries` to the end of `existing`.
+pub fn render_diff(existing: &str, entries: &[String]) -> String {
+    let old_lines = existing.lines().count();
+    let mut diff = format!(
+        "--- a/.gitignore\n+++ b/.gitignore\n@@ -{},0 +{},{} @@\n",
+        old_lines,
+        old_lines + 1,
+        entries.len()
+    );
+    for entry in entries {
+        diff.push_str(&format!("+{}\n", entry));
+    }
+    diff
+}
+
+/// Append `entries` to the `.gitignore` at `path`, creating it if needed.
+pub fn append(path: &Path, entries: &[String]) -> Result<(), CommitauraError> {
+    let mut text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    for entry in entries {
+        text.push_str(entry);
+        text.push('\n');
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_artifacts() {
+        assert!(looks_like_artifact("target/"));
+        assert!(looks_like_artifact("web/node_modules/"));
+        assert!(looks_like_artifact("src/.DS_Store"));
+        assert!(!looks_like_artifact("src/build.rs"));
+        assert!(!is_noisy(&["notes.md".to_string()]));
+    }
+
+    #[test]
+    fn keeps_only_new_plain_entries() {
+        let reply =
+            "```gitignore\n# Rust\n/target/\n- node_modules/\n*.log\n*.log\nnot a pattern\n```";
+        assert_eq!(
+            parse_entries(reply, "/target/\n"),
+            ["node_modules/", "*.log"]
+        );
+        assert_eq!(
+            render_diff("/target/\n", &["*.log".to_string()]),
+            "--- a/.gitignore\n+++ b/.gitignore\n@@ -1,0 +2,1 @@\n+*.log\n"
+        );
+    }
+}
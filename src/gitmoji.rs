@@ -0,0 +1,319 @@
+//! Gitmoji subjects, such as `✨ Add dark mode` or `:bug: Fix login`.
+//!
+//! The model picks the gitmoji from [`GITMOJIS`], the official list from
+//! gitmoji.dev. Whatever it writes is checked against that list and
+//! rewritten in the configured form; a gitmoji that is not on it is
+//! dropped, and replaced by the one for the subject's Conventional
+//! Commits type if it has one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::message;
+
+/// How the gitmoji is written in the subject.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GitmojiFormat {
+    /// The emoji itself, e.g. `✨`.
+    #[default]
+    Unicode,
+    /// The shortcode, e.g. `:sparkles:`, for tools that cannot show emoji.
+    Shortcode,
+}
+
+/// One entry of the gitmoji list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gitmoji {
+    pub emoji: &'static str,
+    pub code: &'static str,
+    pub description: &'static str,
+}
+
+impl Gitmoji {
+    /// The gitmoji as `format` writes it.
+    pub fn render(&self, format: GitmojiFormat) -> &'static str {
+        match format {
+            GitmojiFormat::Unicode => self.emoji,
+            GitmojiFormat::Shortcode => self.code,
+        }
+    }
+}
+
+const fn gitmoji(emoji: &'static str, code: &'static str, description: &'static str) -> Gitmoji {
+    Gitmoji {
+        emoji,
+        code,
+        description,
+    }
+}
+
+/// The official gitmoji list.
+pub const GITMOJIS: &[Gitmoji] = &[
+    gitmoji("🎨", ":art:", "Improve structure / format of the code."),
+    gitmoji("⚡️", ":zap:", "Improve performance."),
+    gitmoji("🔥", ":fire:", "Remove code or files."),
+    gitmoji("🐛", ":bug:", "Fix a bug."),
+    gitmoji("🚑️", ":ambulance:", "Critical hotfix."),
+    gitmoji("✨", ":sparkles:", "Introduce new features."),
+    gitmoji("📝", ":memo:", "Add or update documentation."),
+    gitmoji("🚀", ":rocket:", "Deploy stuff."),
+    gitmoji("💄", ":lipstick:", "Add or update the UI and style files."),
+    gitmoji("🎉", ":tada:", "Begin a project."),
+    gitmoji("✅", ":white_check_mark:", "Add, update, or pass tests."),
+    gitmoji("🔒️", ":lock:", "Fix security or privacy issues."),
+    gitmoji("🔐", ":closed_lock_with_key:", "Add or update secrets."),
+    gitmoji("🔖", ":bookmark:", "Release / Version tags."),
+    gitmoji("🚨", ":rotating_light:", "Fix compiler / linter warnings."),
+    gitmoji("🚧", ":construction:", "Work in progress."),
+    gitmoji("💚", ":green_heart:", "Fix CI Build."),
+    gitmoji("⬇️", ":arrow_down:", "Downgrade dependencies."),
+    gitmoji("⬆️", ":arrow_up:", "Upgrade dependencies."),
+    gitmoji("📌", ":pushpin:", "Pin dependencies to specific versions."),
+    gitmoji(
+        "👷",
+        ":construction_worker:",
+        "Add or update CI build system.",
+    ),
+    gitmoji(
+        "📈",
+        ":chart_with_upwards_trend:",
+        "Add or update analytics or track code.",
+    ),
+    gitmoji("♻️", ":recycle:", "Refactor code."),
+    gitmoji("➕", ":heavy_plus_sign:", "Add a dependency."),
+    gitmoji("➖", ":heavy_minus_sign:", "Remove a dependency."),
+    gitmoji("🔧", ":wrench:", "Add or update configuration files."),
+    gitmoji("🔨", ":hammer:", "Add or update development scripts."),
+    gitmoji(
+        "🌐",
+        ":globe_with_meridians:",
+        "Internationalization and localization.",
+    ),
+    gitmoji("✏️", ":pencil2:", "Fix typos."),
+    gitmoji("💩", ":poop:", "Write bad code that needs to be improved."),
+    gitmoji("⏪️", ":rewind:", "Revert changes."),
+    gitmoji("🔀", ":twisted_rightwards_arrows:", "Merge branches."),
+    gitmoji(
+        "📦️",
+        ":package:",
+        "Add or update compiled files or packages.",
+    ),
+    gitmoji("👽️", ":alien:", "Update code due to external API changes."),
+    gitmoji(
+        "🚚",
+        ":truck:",
+        "Move or rename resources (e.g.: files, paths, routes).",
+    ),
+    gitmoji("📄", ":page_facing_up:", "Add or update license."),
+    gitmoji("💥", ":boom:", "Introduce breaking changes."),
+    gitmoji("🍱", ":bento:", "Add or update assets."),
+    gitmoji("♿️", ":wheelchair:", "Improve accessibility."),
+    gitmoji("💡", ":bulb:", "Add or update comments in source code."),
+    gitmoji("🍻", ":beers:", "Write code drunkenly."),
+    gitmoji("💬", ":speech_balloon:", "Add or update text and literals."),
+    gitmoji("🗃️", ":card_file_box:", "Perform database related changes."),
+    gitmoji("🔊", ":loud_sound:", "Add or update logs."),
+    gitmoji("🔇", ":mute:", "Remove logs."),
+    gitmoji(
+        "👥",
+        ":busts_in_silhouette:",
+        "Add or update contributor(s).",
+    ),
+    gitmoji(
+        "🚸",
+        ":children_crossing:",
+        "Improve user experience / usability.",
+    ),
+    gitmoji(
+        "🏗️",
+        ":building_construction:",
+        "Make architectural changes.",
+    ),
+    gitmoji("📱", ":iphone:", "Work on responsive design."),
+    gitmoji("🤡", ":clown_face:", "Mock things."),
+    gitmoji("🥚", ":egg:", "Add or update an easter egg."),
+    gitmoji("🙈", ":see_no_evil:", "Add or update a .gitignore file."),
+    gitmoji("📸", ":camera_flash:", "Add or update snapshots."),
+    gitmoji("⚗️", ":alembic:", "Perform experiments."),
+    gitmoji("🔍️", ":mag:", "Improve SEO."),
+    gitmoji("🏷️", ":label:", "Add or update types."),
+    gitmoji("🌱", ":seedling:", "Add or update seed files."),
+    gitmoji(
+        "🚩",
+        ":triangular_flag_on_post:",
+        "Add, update, or remove feature flags.",
+    ),
+    gitmoji("🥅", ":goal_net:", "Catch errors."),
+    gitmoji("💫", ":dizzy:", "Add or update animations and transitions."),
+    gitmoji(
+        "🗑️",
+        ":wastebasket:",
+        "Deprecate code that needs to be cleaned up.",
+    ),
+    gitmoji(
+        "🛂",
+        ":passport_control:",
+        "Work on code related to authorization, roles and permissions.",
+    ),
+    gitmoji(
+        "🩹",
+        ":adhesive_bandage:",
+        "Simple fix for a non-critical issue.",
+    ),
+    gitmoji("🧐", ":monocle_face:", "Data exploration/inspection."),
+    gitmoji("⚰️", ":coffin:", "Remove dead code."),
+    gitmoji("🧪", ":test_tube:", "Add a failing test."),
+    gitmoji("👔", ":necktie:", "Add or update business logic."),
+    gitmoji("🩺", ":stethoscope:", "Add or update healthcheck."),
+    gitmoji("🧱", ":bricks:", "Infrastructure related changes."),
+    gitmoji("🧑‍💻", ":technologist:", "Improve developer experience."),
+    gitmoji(
+        "💸",
+        ":money_with_wings:",
+        "Add sponsorships or money related infrastructure.",
+    ),
+    gitmoji(
+        "🧵",
+        ":thread:",
+        "Add or update code related to multithreading or concurrency.",
+    ),
+    gitmoji(
+        "🦺",
+        ":safety_vest:",
+        "Add or update code related to validation.",
+    ),
+    gitmoji("✈️", ":airplane:", "Improve offline support."),
+];
+
+/// Gitmojis standing in for the Conventional Commits types.
+const TYPES: &[(&str, &str)] = &[
+    ("feat", ":sparkles:"),
+    ("fix", ":bug:"),
+    ("docs", ":memo:"),
+    ("style", ":art:"),
+    ("refactor", ":recycle:"),
+    ("perf", ":zap:"),
+    ("test", ":white_check_mark:"),
+    ("build", ":package:"),
+    ("ci", ":construction_worker:"),
+    ("chore", ":wrench:"),
+    ("revert", ":rewind:"),
+];
+
+/// The gitmoji `token` is, in either form. Emoji are matched with or
+/// without their variation selector, which models often leave out.
+pub fn find(token: &str) -> Option<&'static Gitmoji> {
+    let bare = |text: &str| text.replace('\u{FE0F}', "");
+    GITMOJIS
+        .iter()
+        .find(|g| g.code == token || bare(g.emoji) == bare(token))
+}
+
+/// The gitmoji `subject` starts with, and the rest of the subject.
+pub fn split(subject: &str) -> Option<(&'static Gitmoji, &str)> {
+    let (token, rest) = leading_token(subject)?;
+    find(token).map(|gitmoji| (gitmoji, rest))
+}
+
+/// The list for the prompt, one `gitmoji description` per line.
+pub fn prompt_list(format: GitmojiFormat) -> String {
+    GITMOJIS
+        .iter()
+        .map(|g| format!("{} {}\n", g.render(format), g.description))
+        .collect()
+}
+
+/// `message` with its subject starting with a gitmoji from the official
+/// list, written in `format`. An unknown emoji or shortcode is dropped,
+/// and a Conventional Commits type such as `fix:` is turned into its
+/// gitmoji; other messages are returned without one.
+pub fn normalize(message: &str, format: GitmojiFormat) -> String {
+    let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+    let subject = subject.trim();
+    let normalized = match split(subject) {
+        Some((gitmoji, text)) => format!("{} {}", gitmoji.render(format), text),
+        None => {
+            let text = match leading_token(subject) {
+                Some((token, text)) if is_emoji_like(token) => text,
+                _ => subject,
+            };
+            match from_type(text) {
+                Some((gitmoji, text)) => format!("{} {}", gitmoji.render(format), text),
+                None => text.to_string(),
+            }
+        }
+    };
+    if message.contains('\n') {
+        format!("{}\n{}", normalized, rest)
+    } else {
+        normalized
+    }
+}
+
+/// The first whitespace-separated word of `subject`, and what follows it.
+fn leading_token(subject: &str) -> Option<(&str, &str)> {
+    let subject = subject.trim_start();
+    let end = subject.find(char::is_whitespace).unwrap_or(subject.len());
+    let token = &subject[..end];
+    (!token.is_empty()).then(|| (token, subject[end..].trim_start()))
+}
+
+/// Whether `token` looks like an emoji or a `:shortcode:` rather than a word.
+fn is_emoji_like(token: &str) -> bool {
+    let shortcode = token.len() > 2
+        && token.starts_with(':')
+        && token.ends_with(':')
+        && token[1..token.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+' || c == '-');
+    shortcode || token.chars().all(|c| !c.is_alphanumeric() && !c.is_ascii())
+}
+
+/// The gitmoji for the Conventional Commits type `text` starts with, and
+/// the description after it.
+fn from_type(text: &str) -> Option<(&'static Gitmoji, &str)> {
+    let prefix = message::conventional(text)?;
+    let (_, code) = TYPES.iter().find(|(name, _)| *name == prefix.kind)?;
+    Some((find(code)?, prefix.description))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_listed_gitmojis_in_the_configured_form() {
+        use GitmojiFormat::*;
+        assert_eq!(
+            normalize("✨ Add dark mode", Shortcode),
+            ":sparkles: Add dark mode"
+        );
+        assert_eq!(
+            normalize(":bug: Fix login\n\nBody.", Unicode),
+            "🐛 Fix login\n\nBody."
+        );
+        // Without the variation selector.
+        assert_eq!(
+            normalize("\u{267B} Split parser", Unicode),
+            "♻️ Split parser"
+        );
+        assert_eq!(
+            normalize("fix(auth): Expire tokens", Unicode),
+            "🐛 Expire tokens"
+        );
+        assert_eq!(normalize("🦄 Add unicorns", Unicode), "Add unicorns");
+        assert_eq!(
+            normalize(":unicorn: Add unicorns", Shortcode),
+            "Add unicorns"
+        );
+        assert_eq!(normalize("Add unicorns", Unicode), "Add unicorns");
+    }
+
+    #[test]
+    fn every_gitmoji_is_found_by_either_form() {
+        for gitmoji in GITMOJIS {
+            assert_eq!(find(gitmoji.emoji), Some(gitmoji));
+            assert_eq!(find(gitmoji.code), Some(gitmoji));
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Path patterns in `.gitignore` syntax.
+//!
+//! One matcher serves `.commitauraignore`, `linguist-generated` rules in
+//! `.gitattributes`, the pipeline's `exclude` list and `[scopes]`, so a
+//! pattern means the same thing wherever it is written. `*` and `?` stop
+//! at `/`, `**` crosses it and `[...]` is a character class. A pattern
+//! without a `/` matches a name at any depth; with one, it is anchored to
+//! the repository root.
+
+use regex::Regex;
+
+/// A compiled pattern.
+#[derive(Debug, Clone)]
+pub(crate) struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    /// Compile `pattern`, which must not end in `/`.
+    pub(crate) fn new(pattern: &str) -> Result<Glob, String> {
+        let anchored = pattern.contains('/');
+        let line = pattern.strip_prefix('/').unwrap_or(pattern);
+        let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    let at_start = i == 0 || chars[i - 1] == '/';
+                    let at_end = i + 2 == chars.len();
+                    if at_start && at_end {
+                        regex.push_str(".*");
+                        i += 2;
+                    } else if at_start && chars.get(i + 2) == Some(&'/') {
+                        regex.push_str("(?:.*/)?");
+                        i += 3;
+                    } else {
+                        regex.push_str("[^/]*");
+                        i += 2;
+                    }
+                }
+                '*' => {
+                    regex.push_str("[^/]*");
+                    i += 1;
+                }
+                '?' => {
+                    regex.push_str("[^/]");
+                    i += 1;
+                }
+                '[' => {
+                    let end = chars[i + 1..]
+                        .iter()
+                        .skip(1)
+                        .position(|&c| c == ']')
+                        .map(|p| i + 2 + p)
+                        .ok_or_else(|| "unclosed `[`".to_string())?;
+                    let mut class: String = chars[i + 1..end].iter().collect();
+                    if let Some(rest) = class.strip_prefix('!') {
+                        class = format!("^{}", rest);
+                    }
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                    i = end + 1;
+                }
+                '\\' if i + 1 < chars.len() => {
+                    regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                    i += 2;
+                }
+                c => {
+                    regex.push_str(&regex::escape(&c.to_string()));
+                    i += 1;
+                }
+            }
+        }
+        regex.push('$');
+        let regex =
+            Regex::new(&regex).map_err(|e| format!("invalid pattern `{}`: {}", pattern, e))?;
+        Ok(Glob { regex })
+    }
+
+    /// Whether `path`, relative to the repository root, matches.
+    pub(crate) fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+/// Whether `path` or a directory it is in matches `pattern`, so
+/// `vendor/*` covers everything under `vendor/`. A pattern that does not
+/// compile matches nothing.
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    let Ok(glob) = Glob::new(pattern.strip_suffix('/').unwrap_or(pattern)) else {
+        return false;
+    };
+    path.match_indices('/')
+        .map(|(i, _)| &path[..i])
+        .chain([path])
+        .any(|prefix| glob.is_match(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_like_gitignore() {
+        assert!(matches("*.lock", "frontend/yarn.lock"));
+        assert!(matches("vendor/*", "vendor/serde/src/lib.rs"));
+        assert!(matches("src/**/*.rs", "src/a/b/c.rs"));
+        assert!(matches("**/fixtures", "tests/fixtures/one.json"));
+        assert!(!matches("/*.png", "img/logo.png"));
+        assert!(!matches("src/*.rs", "lib/src/main.rs"));
+        assert!(!matches("[", "["));
+        let glob = Glob::new("file[0-9].txt").unwrap();
+        assert!(glob.is_match("docs/file7.txt"));
+        assert!(!glob.is_match("docs/filex.txt"));
+    }
+}
@@ -0,0 +1,90 @@
+//! Guardrail against committing leftovers: conflict markers and debugging
+//! statements in the lines a change adds.
+
+use crate::diff::FileDiff;
+
+/// Patterns checked when the configuration does not list its own. Each is a
+/// plain substring matched against added lines.
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    "<<<<<<< ",
+    ">>>>>>> ",
+    "dbg!(",
+    "console.log(",
+    "debugger;",
+    "binding.pry",
+    "byebug",
+    "pdb.set_trace()",
+    "breakpoint()",
+    "var_dump(",
+];
+
+/// An added line that matched a guardrail pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub path: String,
+    /// Line number in the new version of the file, when the hunk header has one.
+    pub line: Option<usize>,
+    pub pattern: String,
+    pub text: String,
+}
+
+/// The patterns to check: the configured ones, or [`DEFAULT_PATTERNS`].
+pub fn patterns(configured: Option<&[String]>) -> Vec<String> {
+    match configured {
+        Some(patterns) => patterns.to_vec(),
+        None => DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect(),
+    }
+}
+
+/// Find added lines in `files` containing any of `patterns`. Removed and
+/// context lines are ignored, so deleting a stray `dbg!` is never flagged.
+pub fn scan(files: &[FileDiff], patterns: &[String]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for file in files {
+        for hunk in &file.hunks {
+            let mut line = hunk.new_start();
+            for raw in &hunk.lines {
+                if let Some(added) = raw.strip_prefix('+') {
+                    if let Some(pattern) = patterns.iter().find(|p| !p.is_empty() && added.contains(p.as_str())) {
+                        findings.push(Finding {
+                            path: file.path.clone(),
+                            line,
+                            pattern: pattern.clone(),
+                            text: added.trim().to_string(),
+                        });
+                    }
+                }
+                // Only added and context lines exist in the new file.
+                if raw.starts_with('+') || raw.starts_with(' ') {
+                    line = line.map(|n| n + 1);
+                }
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff;
+
+    #[test]
+    fn flags_added_lines_with_their_new_line_numbers() {
+        let files = diff::parse(
+            "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -10,4 +10,4 @@\n fn run() {\n-    dbg!(old);\n+    let x = compute();\n+    dbg!(x);\n }\n",
+        );
+        let findings = scan(&files, &patterns(None));
+        assert_eq!(
+            findings,
+            vec![Finding {
+                path: "src/lib.rs".to_string(),
+                line: Some(12),
+                pattern: "dbg!(".to_string(),
+                text: "dbg!(x);".to_string(),
+            }]
+        );
+
+        assert!(scan(&files, &patterns(Some(&[]))).is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+//! Commit message rules documented by the repository itself.
+//!
+//! Many projects describe their commit conventions in `CONTRIBUTING.md` or
+//! a dedicated `.github/commit-convention.md`. The model is asked once to
+//! pull the rules out of that document, the first time a message is
+//! written, and they are shown to the model in the user message of every
+//! generation as fenced data, since the repository wrote them. The result
+//! is cached in `.git/commitaura/guidelines.json` until the document
+//! changes, including the finding that it has no rules; read-only runs
+//! leave the cache alone.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use log::{info, warn};
+
+use serde::{Deserialize, Serialize};
+
+use crate::generate::{complete, GenerateOptions, Prompt};
+use crate::pregen::fnv1a;
+use crate::tokenizer::Tokenizer;
+use crate::{git, CommitauraError};
+
+/// Documents searched, most specific first; the first that exists is used.
+pub const SOURCES: &[&str] = &[
+    ".github/commit-convention.md",
+    "CONTRIBUTING.md",
+    ".github/CONTRIBUTING.md",
+    "docs/CONTRIBUTING.md",
+];
+
+const CACHE_FILE: &str = "guidelines.json";
+
+/// Most of the document sent to the model, in tokens, to keep the request
+/// cheap. Less is sent when the prompt budget is smaller.
+const MAX_SOURCE_TOKENS: usize = 3000;
+
+/// Longest list of rules the model may reply with, in tokens.
+const MAX_RULES_TOKENS: u32 = 400;
+
+const SYSTEM_MESSAGE: &str =
+    "You extract the rules for writing commit messages from a project's contributor documentation.";
+
+/// Reply meaning the document has no commit message rules.
+const NONE_REPLY: &str = "NONE";
+
+/// The repository's rules, read with [`load`] the first time a prompt
+/// needs them, so runs that never write a message never read them. Clones
+/// share what was read.
+#[derive(Debug, Clone, Default)]
+pub struct Deferred(Arc<OnceLock<Option<String>>>);
+
+impl Deferred {
+    pub fn get(&self, opts: &GenerateOptions) -> Option<String> {
+        self.0.get_or_init(|| load(opts)).clone()
+    }
+}
+
+/// The commit message rules documented in the current repository, from
+/// the cache or extracted by the model. They only sharpen the message, so
+/// failing to read them is not fatal.
+pub fn load(opts: &GenerateOptions) -> Option<String> {
+    let source = find(&git::toplevel().ok()?)?;
+    let cache = cache_path().ok()?;
+    if let Some(rules) = cached(&cache, &source) {
+        return rules;
+    }
+    info!("Reading the commit message rules in {}", source.path);
+    match extract(&source, opts) {
+        Ok(rules) => {
+            if git::is_read_only() {
+                return rules;
+            }
+            if let Err(e) = store(&cache, &source, rules.as_deref()) {
+                warn!("Could not cache the commit message rules: {}", e);
+            }
+            rules
+        }
+        Err(e) => {
+            warn!("Skipping the commit message rules in {}: {}", source.path, e);
+            None
+        }
+    }
+}
+
+/// A document that may describe the commit conventions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    /// Path relative to the repository root, e.g. `CONTRIBUTING.md`.
+    pub path: String,
+    text: String,
+}
+
+impl Source {
+    /// What the cache entry for this document is keyed by.
+    fn key(&self) -> String {
+        format!("{:016x}", fnv1a(&[&self.path, &self.text]))
+    }
+
+    /// The part of the document about commits: the sections whose heading
+    /// mentions them, or the whole document if it mentions them elsewhere.
+    /// `None` if it never does.
+    pub fn relevant_text(&self) -> Option<String> {
+        let sections = commit_sections(&self.text);
+        if !sections.is_empty() {
+            return Some(sections.join("\n\n"));
+        }
+        self.text
+            .to_lowercase()
+            .contains("commit")
+            .then(|| self.text.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    rules: Option<String>,
+}
+
+/// The first of [`SOURCES`] in `root`.
+pub fn find(root: &Path) -> Option<Source> {
+    SOURCES.iter().find_map(|path| {
+        let text = std::fs::read_to_string(root.join(path)).ok()?;
+        Some(Source {
+            path: path.to_string(),
+            text,
+        })
+    })
+}
+
+pub fn cache_path() -> Result<PathBuf, CommitauraError> {
+    Ok(git::git_dir()?.join("commitaura").join(CACHE_FILE))
+}
+
+/// The rules cached for `source`: `None` if they were never extracted from
+/// this version of it, `Some(None)` if it has none.
+pub fn cached(path: &Path, source: &Source) -> Option<Option<String>> {
+    let entry: Entry = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    (entry.key == source.key()).then_some(entry.rules)
+}
+
+pub fn store(path: &Path, source: &Source, rules: Option<&str>) -> Result<(), CommitauraError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let entry = Entry {
+        key: source.key(),
+        rules: rules.map(str::to_string),
+    };
+    std::fs::write(
+        path,
+        serde_json::to_string(&entry).map_err(std::io::Error::from)?,
+    )?;
+    Ok(())
+}
+
+/// The prompt asking for the commit message rules in `text`, taken from
+/// the document at `path`, for a model that counts tokens with `tokenizer`.
+/// The document is cut to `budget` tokens.
+pub fn build_prompt(
+    path: &str,
+    text: &str,
+    tokenizer: Tokenizer,
+    budget: usize,
+) -> Result<Prompt, CommitauraError> {
+    let text = tokenizer.truncate(text, budget.min(MAX_SOURCE_TOKENS))?;
+    Ok(Prompt {
+        system: SYSTEM_MESSAGE.to_string(),
+        user: format!(
+            "Below is {path} from a repository. List the rules it gives for writing commit messages: format, allowed types and scopes, subject length, tense, issue references, sign-offs and the like. Write one short rule per line starting with \"- \", keeping the document's own wording where you can. Leave out everything not about commit messages, such as code style or how to open pull requests. If it gives no rules for commit messages, reply with {NONE_REPLY} and nothing else.\n\n{text}"
+        ),
+    })
+}
+
+/// Ask the model for the commit message rules in `source`; `None` if it
+/// has none.
+pub fn extract(source: &Source, opts: &GenerateOptions) -> Result<Option<String>, CommitauraError> {
+    let Some(text) = source.relevant_text() else {
+        return Ok(None);
+    };
+    let tokenizer = opts.tokenizer();
+    let instructions = build_prompt(&source.path, "", tokenizer, 0)?;
+    let budget = opts.prompt_budget(MAX_RULES_TOKENS).saturating_sub(
+        tokenizer.count(&instructions.system)? + tokenizer.count(&instructions.user)?,
+    );
+    let reply = complete(
+        &build_prompt(&source.path, &text, tokenizer, budget)?,
+        opts,
+        MAX_RULES_TOKENS,
+    )?;
+    let rules = reply.trim();
+    if rules.is_empty() || rules.trim_matches('.').eq_ignore_ascii_case(NONE_REPLY) {
+        return Ok(None);
+    }
+    Ok(Some(rules.to_string()))
+}
+
+/// The sections of the Markdown `text` whose heading mentions commits,
+/// each up to the next heading of the same or a higher level.
+fn commit_sections(text: &str) -> Vec<String> {
+    let mut sections = Vec::new();
+    let mut current: Option<(usize, Vec<&str>)> = None;
+    let mut fenced = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        let level = if fenced { None } else { heading_level(line) };
+        if let Some(level) = level {
+            if current.as_ref().is_some_and(|(open, _)| level <= *open) {
+                let (_, lines) = current.take().unwrap_or_default();
+                sections.push(lines.join("\n"));
+            }
+            if current.is_none() && line.to_lowercase().contains("commit") {
+                current = Some((level, Vec::new()));
+            }
+        }
+        if let Some((_, lines)) = &mut current {
+            lines.push(line);
+        }
+    }
+    if let Some((_, lines)) = current {
+        sections.push(lines.join("\n"));
+    }
+    sections
+}
+
+/// The level of a Markdown ATX heading, e.g. 2 for `## Commits`.
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    (1..=6)
+        .contains(&level)
+        .then_some(level)
+        .filter(|_| rest.starts_with(' '))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(text: &str) -> Source {
+        Source {
+            path: "CONTRIBUTING.md".to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn sends_only_the_sections_about_commits() {
+        let doc = "# Contributing\n\nThanks!\n\n## Code style\n\nRun rustfmt.\n\n## Commit messages\n\nUse the imperative.\n\n```\n# not a heading\n```\n\n### Scopes\n\nName the crate.\n\n## Pull requests\n\nOne change each.\n";
+        assert_eq!(
+            source(doc).relevant_text().unwrap(),
+            "## Commit messages\n\nUse the imperative.\n\n```\n# not a heading\n```\n\n### Scopes\n\nName the crate.\n"
+        );
+        let plain = "Please sign off every commit.";
+        assert_eq!(source(plain).relevant_text().unwrap(), plain);
+        assert_eq!(
+            source("# Contributing\n\nRun the tests.").relevant_text(),
+            None
+        );
+    }
+
+    #[test]
+    fn caches_rules_until_the_document_changes() {
+        let dir =
+            std::env::temp_dir().join(format!("commitaura-guidelines-{}", std::process::id()));
+        let path = dir.join(CACHE_FILE);
+        let doc = source("## Commits\n\nUse the imperative.");
+        assert_eq!(cached(&path, &doc), None);
+        store(&path, &doc, Some("- Use the imperative mood")).unwrap();
+        assert_eq!(
+            cached(&path, &doc),
+            Some(Some("- Use the imperative mood".to_string()))
+        );
+        assert_eq!(
+            cached(&path, &source("## Commits\n\nUse the past tense.")),
+            None
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+//! Budget-aware selection of commit history for the prompt.
+//!
+//! Rather than a fixed number of subjects, as many recent commits as the
+//! token budget leaves room for after the diff are included. Commits that
+//! touched the same files as the change come first, and the most relevant
+//! of them also contribute their bodies.
+
+use crate::tokenizer::Tokenizer;
+use crate::CommitauraError;
+
+/// Recent commits to choose from.
+pub const POOL_SIZE: usize = 50;
+
+/// Subjects always kept room for, however large the diff.
+pub const MIN_SUBJECTS: usize = 5;
+
+/// Relevant commits whose bodies are included when they fit.
+const MAX_BODIES: usize = 3;
+
+/// Which recent commits the history is chosen from, and how much of each
+/// is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    /// Most subjects to show; `None` shows as many of the last
+    /// [`POOL_SIZE`] as fit.
+    pub count: Option<usize>,
+    /// Include the bodies of the commits most relevant to the change.
+    pub bodies: bool,
+    /// Only show commits that touched one of the changed files.
+    pub same_files: bool,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Selection {
+            count: None,
+            bodies: true,
+            same_files: false,
+        }
+    }
+}
+
+impl Selection {
+    /// How many recent commits to read from git.
+    pub fn pool_size(&self) -> usize {
+        self.count.map_or(POOL_SIZE, |count| count.max(POOL_SIZE))
+    }
+
+    /// Subjects always kept room for.
+    fn min_subjects(&self) -> usize {
+        self.count
+            .map_or(MIN_SUBJECTS, |count| count.min(MIN_SUBJECTS))
+    }
+
+    fn admits(&self, commit: &HistoryCommit, paths: &[String]) -> bool {
+        !self.same_files || relevance(commit, paths) > 0
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryCommit {
+    pub subject: String,
+    pub body: String,
+    /// Paths the commit touched.
+    pub files: Vec<String>,
+}
+
+/// Commits from `git log --format=%x1e%s%x1f%b%x1f --name-only`.
+pub fn parse_log(output: &str) -> Vec<HistoryCommit> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.splitn(3, '\x1f');
+            let subject = fields.next()?.trim();
+            if subject.is_empty() {
+                return None;
+            }
+            Some(HistoryCommit {
+                subject: subject.to_string(),
+                body: fields.next().unwrap_or_default().trim().to_string(),
+                files: fields
+                    .next()
+                    .unwrap_or_default()
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Subjects of the newest `count` commits, one per line.
+pub fn subjects(commits: &[HistoryCommit], count: usize) -> String {
+    commits
+        .iter()
+        .take(count)
+        .map(|c| c.subject.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Subjects of the newest commits `selection` admits for `paths`, as many
+/// as are always kept room for, one per line.
+pub fn newest(commits: &[HistoryCommit], paths: &[String], selection: Selection) -> String {
+    commits
+        .iter()
+        .filter(|c| selection.admits(c, paths))
+        .take(selection.min_subjects())
+        .map(|c| c.subject.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// How many of `paths` the commit touched.
+pub(crate) fn relevance(commit: &HistoryCommit, paths: &[String]) -> usize {
+    commit.files.iter().filter(|f| paths.contains(f)).count()
+}
+
+/// History for the prompt from `commits` (newest first) within `budget`
+/// tokens, as `tokenizer` counts them: subjects in order of relevance to
+/// `paths`, then recency, plus the bodies of the most relevant commits, as
+/// far as `selection` allows.
+/// Listed newest first.
+pub fn fit(
+    commits: &[HistoryCommit],
+    paths: &[String],
+    budget: usize,
+    selection: Selection,
+    tokenizer: Tokenizer,
+) -> Result<String, CommitauraError> {
+    let mut ranked: Vec<(usize, usize)> = commits
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| selection.admits(c, paths))
+        .map(|(i, c)| (i, relevance(c, paths)))
+        .collect();
+    ranked.sort_by_key(|&(i, score)| (std::cmp::Reverse(score), i));
+    ranked.truncate(selection.count.unwrap_or(usize::MAX));
+
+    let mut used = 0;
+    let mut subjects = vec![false; commits.len()];
+    for &(i, _) in &ranked {
+        let cost = tokenizer.count(&commits[i].subject)? + 1;
+        if used + cost > budget {
+            break;
+        }
+        used += cost;
+        subjects[i] = true;
+    }
+
+    let mut bodies = vec![false; commits.len()];
+    let relevant = ranked
+        .iter()
+        .filter(|&&(i, score)| score > 0 && subjects[i] && !commits[i].body.is_empty());
+    let max_bodies = if selection.bodies { MAX_BODIES } else { 0 };
+    for &(i, _) in relevant.take(max_bodies) {
+        let cost = tokenizer.count(&indent(&commits[i].body))? + 1;
+        if used + cost <= budget {
+            used += cost;
+            bodies[i] = true;
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (i, commit) in commits.iter().enumerate() {
+        if subjects[i] {
+            lines.push(commit.subject.clone());
+        }
+        if bodies[i] {
+            lines.push(indent(&commit.body));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+fn indent(body: &str) -> String {
+    body.lines()
+        .map(|l| format!("    {}", l))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P50K: Tokenizer = Tokenizer::P50kBase;
+
+    fn commit(subject: &str, body: &str, files: &[&str]) -> HistoryCommit {
+        HistoryCommit {
+            subject: subject.to_string(),
+            body: body.to_string(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_log_records() {
+        let log = "\x1eAdd retries\x1fBack off exponentially.\n\x1f\n\nsrc/net.rs\nREADME.md\n\x1eInitial commit\x1f\x1f\n\nsrc/main.rs\n";
+        let commits = parse_log(log);
+        assert_eq!(
+            commits,
+            [
+                commit(
+                    "Add retries",
+                    "Back off exponentially.",
+                    &["src/net.rs", "README.md"]
+                ),
+                commit("Initial commit", "", &["src/main.rs"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn prefers_commits_touching_the_same_files() {
+        let commits = [
+            commit("Bump version", "", &["Cargo.toml"]),
+            commit("Tidy docs", "", &["README.md"]),
+            commit("Add retries", "Back off exponentially.", &["src/net.rs"]),
+        ];
+        let paths = ["src/net.rs".to_string()];
+        let all = Selection::default();
+        assert_eq!(
+            fit(&commits, &paths, 1000, all, P50K).unwrap(),
+            "Bump version\nTidy docs\nAdd retries\n    Back off exponentially."
+        );
+        // Only room for one subject: the relevant one wins.
+        let one = P50K.count("Add retries").unwrap() + 1;
+        assert_eq!(
+            fit(&commits, &paths, one, all, P50K).unwrap(),
+            "Add retries"
+        );
+    }
+
+    #[test]
+    fn limits_history_as_selected() {
+        let commits = [
+            commit("Bump version", "", &["Cargo.toml"]),
+            commit("Tidy docs", "", &["README.md"]),
+            commit("Add retries", "Back off exponentially.", &["src/net.rs"]),
+            commit("Log requests", "", &["src/net.rs"]),
+        ];
+        let paths = ["src/net.rs".to_string()];
+        let selection = Selection {
+            count: Some(3),
+            bodies: false,
+            same_files: false,
+        };
+        assert_eq!(
+            fit(&commits, &paths, 1000, selection, P50K).unwrap(),
+            "Bump version\nAdd retries\nLog requests"
+        );
+        let same_files = Selection {
+            same_files: true,
+            ..Selection::default()
+        };
+        assert_eq!(
+            newest(&commits, &paths, same_files),
+            "Add retries\nLog requests"
+        );
+        assert_eq!(selection.pool_size(), POOL_SIZE);
+    }
+}
@@ -0,0 +1,77 @@
+//! The `prepare-commit-msg` hook, so a plain `git commit` opens the editor
+//! with a generated message already in place.
+//!
+//! The installed script calls `commitaura hook`, which writes into the
+//! message file git passes it and never prompts. A hook that fails or finds
+//! no `commitaura` on the `PATH` leaves the commit alone.
+
+use std::path::PathBuf;
+
+use crate::{git, CommitauraError};
+
+pub const HOOK_NAME: &str = "prepare-commit-msg";
+
+/// Marks a hook as ours, so reinstalling may replace it.
+const MARKER: &str = "# Installed by `commitaura install-hook`.";
+
+/// The hook script.
+pub fn script() -> String {
+    format!(
+        r#"#!/bin/sh
+{}
+# $1 is the message file, $2 where the message comes from, $3 a commit.
+command -v commitaura >/dev/null 2>&1 || exit 0
+commitaura hook "$@" || true
+"#,
+        MARKER
+    )
+}
+
+/// Whether a commit whose message comes from `source` (git's second hook
+/// argument) should get a generated message. Messages given with `-m` or
+/// `-F`, merges, squashes and amends already have one; a plain commit or a
+/// `commit.template` does not.
+pub fn should_generate(source: Option<&str>) -> bool {
+    matches!(source, None | Some("template"))
+}
+
+/// Write the hook into the repository's hooks directory, honouring
+/// `core.hooksPath`. Another hook already there is only replaced with
+/// `force`. Returns the hook's path.
+pub fn install(force: bool) -> Result<PathBuf, CommitauraError> {
+    let path = git::hooks_dir()?.join(HOOK_NAME);
+    match std::fs::read_to_string(&path) {
+        Ok(existing) if !force && !existing.contains(MARKER) => {
+            return Err(CommitauraError::GitOperationFailed(format!(
+                "{} already exists; rerun with --force to replace it",
+                path.display()
+            )));
+        }
+        _ => {}
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, script())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_only_for_messages_not_given_yet() {
+        assert!(should_generate(None));
+        assert!(should_generate(Some("template")));
+        for source in ["message", "merge", "squash", "commit"] {
+            assert!(!should_generate(Some(source)), "{}", source);
+        }
+        assert!(script().contains(MARKER));
+    }
+}
@@ -0,0 +1,395 @@
+//! JSON-over-HTTP transport for LLM providers, with optional sanitized
+//! request/response dumps for bug reports (`--debug-http`).
+
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::debug;
+use serde_json::Value;
+
+use crate::CommitauraError;
+
+/// Headers whose values are credentials and must never be written out.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "x-api-key",
+    "api-key",
+    "openai-organization",
+];
+
+/// Appends sanitized HTTP exchanges to a file.
+#[derive(Debug, Clone)]
+pub struct HttpDebugLog {
+    path: PathBuf,
+    /// Values scrubbed from bodies in addition to the secret headers.
+    secrets: Vec<String>,
+}
+
+impl HttpDebugLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        HttpDebugLog {
+            path: path.into(),
+            secrets: Vec::new(),
+        }
+    }
+
+    /// Also mask `secret` wherever it appears in a dumped body.
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        let secret = secret.into();
+        if !secret.is_empty() {
+            self.secrets.push(secret);
+        }
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn record_request(&self, method: &str, url: &str, headers: &[(&str, String)], body: &Value) {
+        let mut out = format!(">>> {} {}\n", method, url);
+        for (name, value) in headers {
+            out.push_str(&format!("{}: {}\n", name, self.header_value(name, value)));
+        }
+        out.push('\n');
+        out.push_str(&self.scrub(&pretty(body)));
+        out.push_str("\n\n");
+        self.append(&out);
+    }
+
+    fn record_response(&self, status: u16, headers: &[(String, String)], body: &str) {
+        let mut out = format!("<<< {}\n", status);
+        for (name, value) in headers {
+            out.push_str(&format!("{}: {}\n", name, self.header_value(name, value)));
+        }
+        out.push('\n');
+        let body = serde_json::from_str::<Value>(body)
+            .map(|json| pretty(&json))
+            .unwrap_or_else(|_| body.to_string());
+        out.push_str(&self.scrub(&body));
+        out.push_str("\n\n");
+        self.append(&out);
+    }
+
+    fn record_transport_error(&self, error: &str) {
+        self.append(&format!("<<< transport error: {}\n\n", self.scrub(error)));
+    }
+
+    fn header_value(&self, name: &str, value: &str) -> String {
+        if SECRET_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            mask(value)
+        } else {
+            self.scrub(value)
+        }
+    }
+
+    fn scrub(&self, text: &str) -> String {
+        self.secrets.iter().fold(text.to_string(), |acc, secret| {
+            acc.replace(secret, &mask(secret))
+        })
+    }
+
+    fn append(&self, text: &str) {
+        // Debug output must never break generation, so failures are only logged.
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(text.as_bytes()));
+        if let Err(e) = result {
+            debug!(
+                "Could not write HTTP debug log {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Keep only the last four characters of a credential.
+pub fn mask(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+    )
+}
+
+fn pretty(json: &Value) -> String {
+    serde_json::to_string_pretty(json).unwrap_or_else(|_| json.to_string())
+}
+
+/// How a request that never got a response is described.
+const UNREACHABLE: &str = "could not reach the API";
+
+/// Whether `e` may go away if the request is sent again: a timeout, a
+/// connection failure, a rate limit or a server error.
+pub fn is_transient(e: &CommitauraError) -> bool {
+    match e {
+        CommitauraError::Timeout(_) | CommitauraError::IoError(_) => true,
+        CommitauraError::ApiRequestFailed(message) => match message.strip_prefix("HTTP ") {
+            Some(rest) => rest
+                .get(..3)
+                .and_then(|status| status.parse::<u16>().ok())
+                .is_some_and(|status| status == 429 || status >= 500),
+            None => message.starts_with(UNREACHABLE),
+        },
+        _ => false,
+    }
+}
+
+/// POST `body` as JSON and parse the JSON response.
+///
+/// Non-2xx responses become [`CommitauraError::ApiRequestFailed`] carrying the
+/// status and response body.
+pub fn post_json(
+    url: &str,
+    headers: &[(&str, String)],
+    body: &Value,
+    debug_log: Option<&HttpDebugLog>,
+) -> Result<Value, CommitauraError> {
+    post_json_within(url, headers, body, debug_log, None)
+}
+
+/// Like [`post_json`], but give up with [`CommitauraError::Timeout`] if the
+/// whole exchange takes longer than `timeout`.
+pub fn post_json_within(
+    url: &str,
+    headers: &[(&str, String)],
+    body: &Value,
+    debug_log: Option<&HttpDebugLog>,
+    timeout: Option<Duration>,
+) -> Result<Value, CommitauraError> {
+    debug!("POST {}", url);
+    if let Some(log) = debug_log {
+        log.record_request("POST", url, headers, body);
+    }
+
+    let mut request = ureq::post(url).set("Content-Type", "application/json");
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    read_json(request.send_json(body), url, debug_log, timeout)
+}
+
+/// Like [`post_json_within`], but the body is sent as it is produced: each
+/// string `pieces` yields goes out as soon as it is ready, with chunked
+/// transfer encoding, so the request is under way while the rest of the
+/// body is still being computed. Together the pieces must be one JSON value.
+pub fn post_json_streamed(
+    url: &str,
+    headers: &[(&str, String)],
+    pieces: impl Iterator<Item = String>,
+    debug_log: Option<&HttpDebugLog>,
+    timeout: Option<Duration>,
+) -> Result<Value, CommitauraError> {
+    debug!("POST {} (streamed)", url);
+    let mut request = ureq::post(url).set("Content-Type", "application/json");
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    let mut body = PieceReader {
+        pieces,
+        current: Vec::new(),
+        read: 0,
+        sent: debug_log.map(|_| Vec::new()),
+    };
+    let result = request.send(&mut body);
+    // The request is only logged once all of it is known.
+    if let (Some(log), Some(sent)) = (debug_log, &body.sent) {
+        let sent = serde_json::from_slice(sent)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(sent).into_owned()));
+        log.record_request("POST", url, headers, &sent);
+    }
+    read_json(result, url, debug_log, timeout)
+}
+
+/// Reads the strings of an iterator one after another, keeping a copy of
+/// what was read in `sent` if it is set.
+struct PieceReader<I> {
+    pieces: I,
+    current: Vec<u8>,
+    read: usize,
+    sent: Option<Vec<u8>>,
+}
+
+impl<I: Iterator<Item = String>> Read for PieceReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.read == self.current.len() {
+            match self.pieces.next() {
+                Some(piece) => {
+                    self.current = piece.into_bytes();
+                    self.read = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let chunk = &self.current[self.read..];
+        let n = chunk.len().min(buf.len());
+        buf[..n].copy_from_slice(&chunk[..n]);
+        if let Some(sent) = &mut self.sent {
+            sent.extend_from_slice(&chunk[..n]);
+        }
+        self.read += n;
+        Ok(n)
+    }
+}
+
+/// GET `url` with the `query` parameters and parse the JSON response.
+pub fn get_json(
+    url: &str,
+    query: &[(&str, &str)],
+    headers: &[(&str, String)],
+    debug_log: Option<&HttpDebugLog>,
+) -> Result<Value, CommitauraError> {
+    debug!("GET {}", url);
+    let mut request = ureq::get(url);
+    for (name, value) in query {
+        request = request.query(name, value);
+    }
+    if let Some(log) = debug_log {
+        log.record_request("GET", request.url(), headers, &Value::Null);
+    }
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    read_json(request.call(), url, debug_log, None)
+}
+
+fn read_json(
+    result: Result<ureq::Response, ureq::Error>,
+    url: &str,
+    debug_log: Option<&HttpDebugLog>,
+    timeout: Option<Duration>,
+) -> Result<Value, CommitauraError> {
+    let (status, response) = match result {
+        Ok(response) => (response.status(), response),
+        Err(ureq::Error::Status(status, response)) => (status, response),
+        Err(ureq::Error::Transport(e)) => {
+            if let Some(log) = debug_log {
+                log.record_transport_error(&e.to_string());
+            }
+            let timed_out = std::error::Error::source(&e)
+                .and_then(|source| source.downcast_ref::<std::io::Error>())
+                .is_some_and(is_timeout);
+            return Err(match timeout {
+                Some(timeout) if timed_out => CommitauraError::Timeout(timeout),
+                _ => CommitauraError::ApiRequestFailed(format!("{}: {}", UNREACHABLE, e)),
+            });
+        }
+    };
+
+    let response_headers: Vec<(String, String)> = response
+        .headers_names()
+        .into_iter()
+        .map(|name| {
+            let value = response.header(&name).unwrap_or("").to_string();
+            (name, value)
+        })
+        .collect();
+    let text = response.into_string().map_err(|e| match timeout {
+        Some(timeout) if is_timeout(&e) => CommitauraError::Timeout(timeout),
+        _ => e.into(),
+    })?;
+    debug!("Response {} from {} ({} bytes)", status, url, text.len());
+    if let Some(log) = debug_log {
+        log.record_response(status, &response_headers, &text);
+    }
+
+    if !(200..300).contains(&status) {
+        return Err(CommitauraError::ApiRequestFailed(format!(
+            "HTTP {}: {}",
+            status,
+            text.trim()
+        )));
+    }
+    serde_json::from_str(&text).map_err(|e| {
+        CommitauraError::ApiRequestFailed(format!("Invalid JSON in API response: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_credentials() {
+        assert_eq!(mask("sk-abcdefghijklmnop"), "****mnop");
+        assert_eq!(mask("short"), "****");
+    }
+
+    #[test]
+    fn dump_never_contains_the_api_key() {
+        let path = std::env::temp_dir().join(format!("commitaura-http-{}.log", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let key = "sk-secret-key-1234567890";
+        let log = HttpDebugLog::new(&path).with_secret(key);
+
+        log.record_request(
+            "POST",
+            "https://api.example.com/v1/chat/completions",
+            &[("Authorization", format!("Bearer {}", key))],
+            &serde_json::json!({ "model": "gpt-4o", "echo": key }),
+        );
+        log.record_response(401, &[], r#"{"error":"bad key sk-secret-key-1234567890"}"#);
+
+        let dump = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!dump.contains(key));
+        assert!(dump.contains("Authorization: ****7890"));
+        assert!(dump.contains("\"model\": \"gpt-4o\""));
+        assert!(dump.contains("<<< 401"));
+    }
+
+    #[test]
+    fn reads_pieces_in_order() {
+        let pieces = ["{\"a\":", "", "[1,", "2]}"].map(String::from);
+        let mut reader = PieceReader {
+            pieces: pieces.into_iter(),
+            current: Vec::new(),
+            read: 0,
+            sent: Some(Vec::new()),
+        };
+        let mut out = String::new();
+        reader.read_to_string(&mut out).unwrap();
+        assert_eq!(out, r#"{"a":[1,2]}"#);
+        assert_eq!(reader.sent.unwrap(), out.as_bytes());
+    }
+
+    #[test]
+    fn slow_server_times_out() {
+        // Accepts the connection but never answers.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+        let timeout = Duration::from_millis(200);
+        let result = post_json_within(&url, &[], &serde_json::json!({}), None, Some(timeout));
+        assert!(matches!(result, Err(CommitauraError::Timeout(t)) if t == timeout));
+    }
+
+    #[test]
+    fn tells_transient_failures_apart() {
+        let failed = |message: &str| CommitauraError::ApiRequestFailed(message.to_string());
+        assert!(is_transient(&failed("HTTP 503: overloaded")));
+        assert!(is_transient(&failed("HTTP 429: slow down")));
+        assert!(is_transient(&failed("could not reach the API: connection refused")));
+        assert!(!is_transient(&failed("HTTP 401: invalid key")));
+        assert!(!is_transient(&failed("Invalid JSON in API response: EOF")));
+        assert!(!is_transient(&CommitauraError::NoStagedChanges));
+    }
+}
@@ -0,0 +1,181 @@
+//! `.commitauraignore`: files whose changes are left out of the prompt.
+//!
+//! The file uses `.gitignore` syntax and sits at the root of the repository.
+//! Lock files, generated code and test snapshots change a lot and say little,
+//! so their hunks only eat into the token budget. A matching file is still
+//! listed, with its line counts, so the model knows it changed.
+
+use std::path::Path;
+
+use crate::diff::{self, FileDiff};
+use crate::glob::Glob;
+use crate::CommitauraError;
+
+/// Name of the ignore file, at the root of the repository.
+pub const IGNORE_FILE: &str = ".commitauraignore";
+
+/// One line of an ignore file.
+#[derive(Debug, Clone)]
+struct Pattern {
+    glob: Glob,
+    /// `!pattern`: matching paths are included again.
+    negated: bool,
+    /// `pattern/`: only matches directories.
+    dir_only: bool,
+}
+
+/// The patterns of an ignore file, in order. Empty when there is none.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreFile {
+    patterns: Vec<Pattern>,
+}
+
+impl IgnoreFile {
+    /// The ignore file in `root`, or an empty one if it does not exist.
+    pub fn load(root: &Path) -> Result<IgnoreFile, CommitauraError> {
+        match std::fs::read_to_string(root.join(IGNORE_FILE)) {
+            Ok(text) => IgnoreFile::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(IgnoreFile::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Patterns in `.gitignore` syntax, one per line.
+    pub fn parse(text: &str) -> Result<IgnoreFile, CommitauraError> {
+        let mut patterns = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let pattern = pattern(line).map_err(|e| {
+                CommitauraError::ConfigError(format!("{} line {}: {}", IGNORE_FILE, number + 1, e))
+            })?;
+            patterns.extend(pattern);
+        }
+        Ok(IgnoreFile { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Whether the changes to `path`, relative to the repository root, are
+    /// left out. As with git, a file in an ignored directory cannot be
+    /// included again.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        let parts: Vec<&str> = path.split('/').collect();
+        (1..parts.len()).any(|n| self.decides(&parts[..n].join("/"), true))
+            || self.decides(path, false)
+    }
+
+    /// Whether the last pattern matching `path` ignores it.
+    fn decides(&self, path: &str, is_dir: bool) -> bool {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| (is_dir || !p.dir_only) && p.glob.is_match(path))
+            .is_some_and(|p| !p.negated)
+    }
+
+    /// `diff` with the hunks of ignored files replaced by a line saying
+    /// how many lines changed.
+    pub fn apply(&self, diff: &str) -> String {
+        if self.is_empty() {
+            return diff.to_string();
+        }
+        let mut files = diff::parse(diff);
+        if !files.iter().any(|file| self.is_ignored(&file.path)) {
+            return diff.to_string();
+        }
+        for file in files.iter_mut().filter(|file| self.is_ignored(&file.path)) {
+            leave_out(file);
+        }
+        diff::render(&files)
+    }
+}
+
+/// Drop `file`'s hunks, noting their size in its header.
+fn leave_out(file: &mut FileDiff) {
+    if file.hunks.is_empty() {
+        return;
+    }
+    file.header.push(format!(
+        "Changes left out by {} (+{} -{})",
+        IGNORE_FILE,
+        file.additions(),
+        file.deletions()
+    ));
+    file.hunks.clear();
+}
+
+/// The pattern on `line`; `None` for blank lines and comments.
+fn pattern(line: &str) -> Result<Option<Pattern>, String> {
+    // Trailing spaces are dropped unless escaped.
+    let trimmed = line.trim_end();
+    let line = if trimmed.ends_with('\\') && line.len() > trimmed.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    };
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    if line.strip_prefix('/').unwrap_or(line).is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Pattern {
+        glob: Glob::new(line)?,
+        negated,
+        dir_only,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_like_gitignore() {
+        let ignore = IgnoreFile::parse(
+            "# lock files\npackage-lock.json\n*.snap\n!keep.snap\n/gen/\ndocs/**/*.pb.go\nbuild/\n!build/keep.txt\n",
+        )
+        .unwrap();
+        for path in [
+            "package-lock.json",
+            "web/package-lock.json",
+            "src/__snapshots__/app.test.js.snap",
+            "gen/api.rs",
+            "docs/pb/v1/api.pb.go",
+            "docs/api.pb.go",
+            "build/keep.txt",
+        ] {
+            assert!(ignore.is_ignored(path), "{}", path);
+        }
+        for path in [
+            "src/package-lock.json.rs",
+            "tests/keep.snap",
+            "src/gen/api.rs",
+            "api.pb.go",
+            "src/main.rs",
+        ] {
+            assert!(!ignore.is_ignored(path), "{}", path);
+        }
+        assert!(IgnoreFile::parse("[abc").is_err());
+    }
+
+    #[test]
+    fn leaves_out_the_hunks_of_ignored_files() {
+        let diff = "diff --git a/package-lock.json b/package-lock.json\n--- a/package-lock.json\n+++ b/package-lock.json\n@@ -1,2 +1,2 @@\n-  \"version\": \"1.0.0\"\n+  \"version\": \"1.1.0\"\n \"lockfileVersion\": 3\ndiff --git a/src/app.js b/src/app.js\n@@ -1 +1 @@\n-old()\n+new()\n";
+        let ignore = IgnoreFile::parse("package-lock.json").unwrap();
+        assert_eq!(
+            ignore.apply(diff),
+            "diff --git a/package-lock.json b/package-lock.json\n--- a/package-lock.json\n+++ b/package-lock.json\nChanges left out by .commitauraignore (+1 -1)\ndiff --git a/src/app.js b/src/app.js\n@@ -1 +1 @@\n-old()\n+new()\n"
+        );
+        assert_eq!(IgnoreFile::default().apply(diff), diff);
+    }
+}
@@ -0,0 +1,273 @@
+//! Settings borrowed from other AI command-line tools by
+//! `commitaura init --import`, so an existing setup can be taken over.
+//!
+//! Supported sources, in the order they are consulted:
+//! - the `OPENAI_BASE_URL` and `OPENAI_MODEL` environment variables;
+//! - aider's `.aider.conf.yml` (current directory, repository root, home);
+//! - Simon Willison's `llm` CLI (`keys.json`, `default_model.txt`);
+//! - aichat's `config.yaml`.
+//!
+//! Only OpenAI-compatible settings are read. Files are parsed just far
+//! enough to find the handful of keys needed, so no YAML parser is involved.
+
+use std::path::{Path, PathBuf};
+
+use log::debug;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedSettings {
+    /// Where the settings were found, e.g. `aider (~/.aider.conf.yml)`.
+    pub source: String,
+    pub api_key: Option<String>,
+    pub api_base: Option<String>,
+    pub model: Option<String>,
+}
+
+impl ImportedSettings {
+    fn is_empty(&self) -> bool {
+        self.api_key.is_none() && self.api_base.is_none() && self.model.is_none()
+    }
+}
+
+/// Every source that yielded at least one setting, in priority order.
+pub fn detect() -> Vec<ImportedSettings> {
+    let home = home_dir();
+    let mut found = vec![from_env(|name| std::env::var(name).ok())];
+
+    let mut aider_dirs = vec![PathBuf::from(".")];
+    aider_dirs.extend(crate::git::toplevel().ok());
+    aider_dirs.extend(home.clone());
+    let mut seen = Vec::new();
+    for dir in aider_dirs {
+        let path = dir.join(".aider.conf.yml");
+        // The current directory is often the repository root.
+        let Ok(canonical) = path.canonicalize() else {
+            continue;
+        };
+        if seen.contains(&canonical) {
+            continue;
+        }
+        seen.push(canonical);
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            found.push(from_aider(&text, &path));
+        }
+    }
+
+    if let Some(dir) = llm_dir(home.as_deref()) {
+        let keys = std::fs::read_to_string(dir.join("keys.json")).unwrap_or_default();
+        let model = std::fs::read_to_string(dir.join("default_model.txt")).unwrap_or_default();
+        found.push(from_llm(&keys, &model, &dir));
+    }
+
+    if let Some(path) = aichat_config(home.as_deref()) {
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            found.push(from_aichat(&text, &path));
+        }
+    }
+
+    found.retain(|s| !s.is_empty());
+    for settings in &found {
+        debug!("Found settings in {}", settings.source);
+    }
+    found
+}
+
+/// The first value each source provides, most important source first.
+pub fn merged(found: &[ImportedSettings]) -> ImportedSettings {
+    let first =
+        |get: fn(&ImportedSettings) -> &Option<String>| found.iter().find_map(|s| get(s).clone());
+    ImportedSettings {
+        source: found
+            .iter()
+            .map(|s| s.source.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        api_key: first(|s| &s.api_key),
+        api_base: first(|s| &s.api_base),
+        model: first(|s| &s.model),
+    }
+}
+
+/// Make `base` usable as [`crate::GenerateOptions::api_base`], which must end
+/// with a slash.
+pub fn normalize_api_base(base: &str) -> String {
+    format!("{}/", base.trim().trim_end_matches('/'))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+fn from_env(var: impl Fn(&str) -> Option<String>) -> ImportedSettings {
+    let non_empty = |name| var(name).filter(|v| !v.trim().is_empty());
+    ImportedSettings {
+        source: "environment".to_string(),
+        api_key: None,
+        api_base: non_empty("OPENAI_BASE_URL").or_else(|| non_empty("OPENAI_API_BASE")),
+        model: non_empty("OPENAI_MODEL"),
+    }
+}
+
+/// The value of a top-level or nested `key: value` YAML line, unquoted and
+/// without a trailing comment.
+fn yaml_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line
+        .trim_start()
+        .trim_start_matches("- ")
+        .strip_prefix(key)?
+        .strip_prefix(':')?;
+    let value = match rest.find(" #") {
+        Some(i) => &rest[..i],
+        None => rest,
+    };
+    let value = value.trim().trim_matches(['"', '\'']);
+    (!value.is_empty()).then_some(value)
+}
+
+fn from_aider(text: &str, path: &Path) -> ImportedSettings {
+    let mut settings = ImportedSettings {
+        source: format!("aider ({})", path.display()),
+        ..ImportedSettings::default()
+    };
+    for line in text.lines() {
+        if let Some(v) = yaml_value(line, "openai-api-key") {
+            settings.api_key = Some(v.to_string());
+        } else if let Some(v) = yaml_value(line, "openai-api-base") {
+            settings.api_base = Some(v.to_string());
+        } else if let Some(v) = yaml_value(line, "model") {
+            settings.model = Some(v.trim_start_matches("openai/").to_string());
+        } else if let Some(v) = yaml_value(line, "api-key") {
+            // `api-key: openai=sk-...`, also used as a list item.
+            if let Some(key) = v.strip_prefix("openai=") {
+                settings.api_key = Some(key.to_string());
+            }
+        } else if let Some(key) = line.trim().strip_prefix("- openai=") {
+            settings.api_key = Some(key.trim().to_string());
+        }
+    }
+    settings
+}
+
+fn llm_dir(home: Option<&Path>) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("LLM_USER_PATH").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    let home = home?;
+    [
+        home.join(".config/io.datasette.llm"),
+        home.join("Library/Application Support/io.datasette.llm"),
+    ]
+    .into_iter()
+    .find(|dir| dir.is_dir())
+}
+
+fn from_llm(keys_json: &str, default_model: &str, dir: &Path) -> ImportedSettings {
+    let keys: serde_json::Value = serde_json::from_str(keys_json).unwrap_or_default();
+    ImportedSettings {
+        source: format!("llm ({})", dir.display()),
+        api_key: keys["openai"].as_str().map(str::to_string),
+        api_base: None,
+        model: Some(default_model.trim())
+            .filter(|m| !m.is_empty())
+            .map(str::to_string),
+    }
+}
+
+fn aichat_config(home: Option<&Path>) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("AICHAT_CONFIG_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("config.yaml"));
+    }
+    let home = home?;
+    [
+        home.join(".config/aichat/config.yaml"),
+        home.join("Library/Application Support/aichat/config.yaml"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+fn from_aichat(text: &str, path: &Path) -> ImportedSettings {
+    let mut settings = ImportedSettings {
+        source: format!("aichat ({})", path.display()),
+        ..ImportedSettings::default()
+    };
+    // Keys of a client entry only count when it is an OpenAI-style client.
+    let mut in_openai_client = false;
+    for line in text.lines() {
+        let top_level = !line.starts_with([' ', '\t', '-']);
+        if top_level {
+            in_openai_client = false;
+            if let Some(model) = yaml_value(line, "model") {
+                // aichat models are `<client>:<model>`.
+                let model = model.split_once(':').map_or(model, |(_, m)| m);
+                settings.model = Some(model.to_string());
+            }
+            continue;
+        }
+        if let Some(kind) = yaml_value(line, "type") {
+            in_openai_client = kind == "openai" || kind == "openai-compatible";
+        } else if line.trim_start().starts_with("- ") {
+            in_openai_client = false;
+        }
+        if !in_openai_client {
+            continue;
+        }
+        if let Some(v) = yaml_value(line, "api_key") {
+            settings.api_key.get_or_insert_with(|| v.to_string());
+        } else if let Some(v) = yaml_value(line, "api_base") {
+            settings.api_base.get_or_insert_with(|| v.to_string());
+        }
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_aider_config() {
+        let settings = from_aider(
+            "# aider\nmodel: openai/gpt-4o-mini\nopenai-api-key: \"sk-aider\"  # personal\nopenai-api-base: https://proxy.example.com/v1\n",
+            Path::new(".aider.conf.yml"),
+        );
+        assert_eq!(settings.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(settings.api_key.as_deref(), Some("sk-aider"));
+        assert_eq!(
+            settings.api_base.as_deref(),
+            Some("https://proxy.example.com/v1")
+        );
+    }
+
+    #[test]
+    fn reads_only_openai_clients_from_aichat() {
+        let settings = from_aichat(
+            "model: openai:gpt-4o\nclients:\n- type: claude\n  api_key: sk-ant\n- type: openai\n  api_key: sk-openai\n  api_base: https://api.openai.com/v1\n",
+            Path::new("config.yaml"),
+        );
+        assert_eq!(settings.model.as_deref(), Some("gpt-4o"));
+        assert_eq!(settings.api_key.as_deref(), Some("sk-openai"));
+    }
+
+    #[test]
+    fn merges_sources_in_priority_order() {
+        let env = from_env(|name| {
+            (name == "OPENAI_BASE_URL").then(|| "http://localhost:8080/v1".to_string())
+        });
+        let llm = from_llm(
+            "{\"openai\": \"sk-llm\"}",
+            "gpt-4o-mini\n",
+            Path::new("llm"),
+        );
+        let settings = merged(&[env, llm]);
+        assert_eq!(settings.api_key.as_deref(), Some("sk-llm"));
+        assert_eq!(settings.model.as_deref(), Some("gpt-4o-mini"));
+        assert_eq!(
+            normalize_api_base(settings.api_base.as_deref().unwrap()),
+            "http://localhost:8080/v1/"
+        );
+    }
+}
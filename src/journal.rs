@@ -0,0 +1,303 @@
+//! Local journal of every prompt sent to a model, kept so a generation can be
+//! replayed against another model or with other settings.
+//!
+//! Entries are appended as JSON lines. Ids are sequence numbers within the
+//! journal file. Each entry also has a [`JournalEntry::key`] that stays the
+//! same wherever it is copied, which `Commitaura-Id` trailers refer to. The
+//! entry behind a commit's trailer is attached to the commit as a git note,
+//! so the transcript can be shared with the commit.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::generate::{Prompt, PromptStyle};
+use crate::timings::StageTiming;
+use crate::{git, CommitauraError};
+
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// The trailer naming the entry a message was generated in.
+pub const TRAILER: &str = "Commitaura-Id";
+
+/// The notes ref transcripts are attached to commits under.
+pub const NOTES_REF: &str = "commitaura";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub api_base: String,
+    pub model: String,
+    pub max_response_tokens: u32,
+    pub temperature: f64,
+    pub system: String,
+    pub user: String,
+    pub reply: String,
+    pub latency_ms: u64,
+    /// Token counts reported by the API, when it reports them.
+    #[serde(default)]
+    pub prompt_tokens: Option<u64>,
+    #[serde(default)]
+    pub completion_tokens: Option<u64>,
+    /// Time spent in each stage of the generation up to this reply, and in
+    /// those after it once [`set_timings`] adds them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timings: Vec<StageTiming>,
+    /// The style this reply was chosen as in a `--pair` vote. See
+    /// [`crate::pair`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vote: Option<PromptStyle>,
+}
+
+impl JournalEntry {
+    pub fn prompt(&self) -> Prompt {
+        Prompt {
+            system: self.system.clone(),
+            user: self.user.clone(),
+        }
+    }
+
+    /// A short id derived from the request and reply, the same in every
+    /// copy of the entry, unlike [`JournalEntry::id`].
+    pub fn key(&self) -> String {
+        let id = self.id.to_string();
+        let timestamp = self.timestamp.to_string();
+        let hash = crate::pregen::fnv1a(&[
+            &id,
+            &timestamp,
+            &self.model,
+            &self.system,
+            &self.user,
+            &self.reply,
+        ]);
+        format!("{:016x}", hash)[..12].to_string()
+    }
+}
+
+/// The entry whose [`JournalEntry::key`] starts with `prefix`, if exactly
+/// one does.
+pub fn find_key(entries: &[JournalEntry], prefix: &str) -> Option<JournalEntry> {
+    let mut matching = entries.iter().filter(|e| e.key().starts_with(prefix));
+    match (matching.next(), matching.next()) {
+        (Some(entry), None) if !prefix.is_empty() => Some(entry.clone()),
+        _ => None,
+    }
+}
+
+/// `message` with a `Commitaura-Id: <key>` trailer, added to its trailer
+/// block if it ends in one.
+pub fn with_trailer(message: &str, key: &str) -> String {
+    crate::message::add_trailer(message, TRAILER, key)
+}
+
+/// The key in `message`'s `Commitaura-Id` trailer, if it has one.
+pub fn trailer_key(message: &str) -> Option<&str> {
+    message.lines().rev().find_map(|line| {
+        let value = line.strip_prefix(TRAILER)?.strip_prefix(':')?.trim();
+        (!value.is_empty()).then_some(value)
+    })
+}
+
+/// The entry as attached to a commit in a git note.
+pub fn to_note(entry: &JournalEntry) -> String {
+    serde_json::to_string_pretty(entry).unwrap_or_default()
+}
+
+pub fn from_note(note: &str) -> Option<JournalEntry> {
+    serde_json::from_str(note).ok()
+}
+
+/// The journal of the current repository, in its `.git/commitaura/` directory.
+pub fn repo_journal() -> Result<PathBuf, CommitauraError> {
+    Ok(git::git_dir()?.join("commitaura").join(JOURNAL_FILE))
+}
+
+/// Every entry in the journal at `path`, oldest first. A missing journal is
+/// empty; lines that fail to parse are skipped.
+pub fn load(path: &Path) -> Result<Vec<JournalEntry>, CommitauraError> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Look up entry `id`.
+pub fn find(path: &Path, id: u64) -> Result<JournalEntry, CommitauraError> {
+    load(path)?
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| {
+            CommitauraError::IoError(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no entry {} in {}", id, path.display()),
+            ))
+        })
+}
+
+/// Average latency of the last `recent` replies from `model`, if there are any.
+pub fn average_latency(entries: &[JournalEntry], model: &str, recent: usize) -> Option<u64> {
+    let latencies: Vec<u64> = entries
+        .iter()
+        .rev()
+        .filter(|e| e.model == model)
+        .take(recent)
+        .map(|e| e.latency_ms)
+        .collect();
+    if latencies.is_empty() {
+        None
+    } else {
+        Some(latencies.iter().sum::<u64>() / latencies.len() as u64)
+    }
+}
+
+/// Append `entry` to the journal at `path`, assigning it the next id.
+/// Failing to write the journal never fails a generation, so errors are only
+/// logged.
+pub fn record(path: &Path, entry: JournalEntry) -> Option<u64> {
+    match append(path, entry) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            debug!("Could not write journal {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Replace the timings of entry `id` with `timings`, e.g. to add the stages
+/// that ran after its reply. Like [`record`], this never fails a generation.
+pub fn set_timings(path: &Path, id: u64, timings: Vec<StageTiming>) {
+    if let Err(e) = update(path, id, |entry| entry.timings = timings) {
+        debug!("Could not update journal {}: {}", path.display(), e);
+    }
+}
+
+/// Record that the reply of entry `id` won a `--pair` vote as `style`.
+pub fn set_vote(path: &Path, id: u64, style: PromptStyle) -> Result<(), CommitauraError> {
+    update(path, id, |entry| entry.vote = Some(style))
+}
+
+/// Rewrite the journal with `change` made to entry `id`, if it is there.
+fn update(
+    path: &Path,
+    id: u64,
+    change: impl FnOnce(&mut JournalEntry),
+) -> Result<(), CommitauraError> {
+    let mut entries = load(path)?;
+    let Some(entry) = entries.iter_mut().find(|e| e.id == id) else {
+        return Ok(());
+    };
+    change(entry);
+    let mut text = String::new();
+    for entry in &entries {
+        text.push_str(&serde_json::to_string(entry).map_err(std::io::Error::from)?);
+        text.push('\n');
+    }
+    // Renamed into place, so a run killed while writing keeps the old journal.
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, text)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+fn append(path: &Path, mut entry: JournalEntry) -> Result<u64, CommitauraError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    entry.id = load(path)?.last().map_or(1, |last| last.id + 1);
+    let line = serde_json::to_string(&entry).map_err(std::io::Error::from)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(entry.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_with_sequential_ids_and_finds_them_again() {
+        let path =
+            std::env::temp_dir().join(format!("commitaura-journal-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let entry = JournalEntry {
+            id: 0,
+            timestamp: 1_700_000_000,
+            api_base: "https://api.openai.com/v1/".to_string(),
+            model: "gpt-4o".to_string(),
+            max_response_tokens: 100,
+            temperature: 0.7,
+            system: "system".to_string(),
+            user: "user".to_string(),
+            reply: "Add journal".to_string(),
+            latency_ms: 850,
+            prompt_tokens: Some(12),
+            completion_tokens: None,
+            timings: Vec::new(),
+            vote: None,
+        };
+
+        assert_eq!(record(&path, entry.clone()), Some(1));
+        assert_eq!(record(&path, entry.clone()), Some(2));
+        let found = find(&path, 2).unwrap();
+        let timings = vec![StageTiming { stage: "post-process".to_string(), micros: 90 }];
+        set_timings(&path, 1, timings.clone());
+        assert_eq!(find(&path, 1).unwrap().timings, timings);
+        assert_eq!(find(&path, 2).unwrap(), found);
+        set_vote(&path, 2, PromptStyle::Brief).unwrap();
+        assert_eq!(find(&path, 2).unwrap().vote, Some(PromptStyle::Brief));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(found, JournalEntry { id: 2, ..entry.clone() });
+
+        let slow = JournalEntry { latency_ms: 1150, ..entry.clone() };
+        let entries = [entry.clone(), slow, entry];
+        assert_eq!(average_latency(&entries, "gpt-4o", 2), Some(1000));
+        assert_eq!(average_latency(&entries, "o3-mini", 2), None);
+    }
+
+    #[test]
+    fn keys_survive_the_note_and_the_trailer() {
+        let entry = JournalEntry {
+            id: 3,
+            timestamp: 1_700_000_000,
+            api_base: String::new(),
+            model: "gpt-4o".to_string(),
+            max_response_tokens: 100,
+            temperature: 0.7,
+            system: "system".to_string(),
+            user: "user".to_string(),
+            reply: "Add journal".to_string(),
+            latency_ms: 850,
+            prompt_tokens: None,
+            completion_tokens: None,
+            timings: Vec::new(),
+            vote: None,
+        };
+        let key = entry.key();
+        assert_eq!(key.len(), 12);
+        assert_eq!(from_note(&to_note(&entry)).unwrap().key(), key);
+        let other = JournalEntry { id: 4, ..entry.clone() };
+        assert_ne!(other.key(), key);
+        let entries = [entry.clone(), other];
+        assert_eq!(find_key(&entries, &key[..6]), Some(entry));
+        assert_eq!(find_key(&entries, ""), None);
+
+        let message = with_trailer("Add journal", &key);
+        assert_eq!(message, format!("Add journal\n\nCommitaura-Id: {}", key));
+        assert_eq!(trailer_key(&message), Some(key.as_str()));
+        assert_eq!(
+            with_trailer("Add journal\n\nBody text.\n\nRefs: #12\n", "ab12cd"),
+            "Add journal\n\nBody text.\n\nRefs: #12\nCommitaura-Id: ab12cd"
+        );
+        assert_eq!(trailer_key("Add journal"), None);
+    }
+}
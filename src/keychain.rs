@@ -0,0 +1,241 @@
+//! API keys stored in the operating system's credential store, so they do
+//! not have to live in shell profiles or `.env` files.
+//!
+//! Keys are looked up under the service `commitaura` and an account named
+//! after the provider: `openai` or `anthropic`.
+//! Inside WSL, the Windows Credential Manager is reached through the
+//! `powershell.exe` interop bridge, so a key stored once on Windows serves
+//! both environments.
+//!
+//! Without the `keychain` feature no store is read, and a configured one is
+//! an error.
+
+#[cfg(feature = "keychain")]
+use std::process::{Command, Stdio};
+
+use log::debug;
+
+use crate::CommitauraError;
+
+pub const SERVICE: &str = "commitaura";
+pub const ACCOUNT: &str = "openai";
+
+/// Reads the generic credential `TARGET` (as stored by
+/// `cmdkey /generic:commitaura /user:openai /pass:<key>`) and prints its
+/// secret. Offsets are those of `CREDENTIALW` on 64-bit Windows.
+#[cfg(feature = "keychain")]
+const CRED_READ_SCRIPT: &str = r#"$ErrorActionPreference = 'Stop'
+Add-Type -Namespace Commitaura -Name Cred -MemberDefinition @'
+[DllImport("advapi32.dll", CharSet = CharSet.Unicode, SetLastError = true)]
+public static extern bool CredReadW(string target, int type, int flags, out IntPtr credential);
+[DllImport("advapi32.dll")]
+public static extern void CredFree(IntPtr credential);
+'@
+$cred = [IntPtr]::Zero
+if (-not [Commitaura.Cred]::CredReadW('TARGET', 1, 0, [ref]$cred)) { exit 2 }
+$size = [Runtime.InteropServices.Marshal]::ReadInt32($cred, 32)
+$blob = [Runtime.InteropServices.Marshal]::ReadIntPtr($cred, 40)
+[Console]::Out.Write([Runtime.InteropServices.Marshal]::PtrToStringUni($blob, $size / 2))
+[Commitaura.Cred]::CredFree($cred)
+"#;
+
+/// Where PowerShell lives when `powershell.exe` is not on the WSL `PATH`.
+#[cfg(feature = "keychain")]
+const WSL_POWERSHELL: &str = "/mnt/c/Windows/System32/WindowsPowerShell/v1.0/powershell.exe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The macOS login keychain, via `security`.
+    MacKeychain,
+    /// The freedesktop Secret Service (GNOME Keyring, KWallet), via `secret-tool`.
+    SecretService,
+    /// The Windows Credential Manager, natively or from WSL.
+    WindowsCredentialManager,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::MacKeychain => "macos",
+            Backend::SecretService => "secret-service",
+            Backend::WindowsCredentialManager => "windows",
+        }
+    }
+
+    /// The backend named `name`, as used in the `keychain` setting.
+    pub fn parse(name: &str) -> Option<Backend> {
+        [
+            Backend::MacKeychain,
+            Backend::SecretService,
+            Backend::WindowsCredentialManager,
+        ]
+        .into_iter()
+        .find(|b| b.name() == name)
+    }
+
+    /// The command that prints the key stored for `account`.
+    #[cfg(feature = "keychain")]
+    fn command(self, account: &str) -> Command {
+        match self {
+            Backend::MacKeychain => {
+                let mut cmd = Command::new("security");
+                cmd.args(["find-generic-password", "-s", SERVICE, "-a", account, "-w"]);
+                cmd
+            }
+            Backend::SecretService => {
+                let mut cmd = Command::new("secret-tool");
+                cmd.args(["lookup", "service", SERVICE, "account", account]);
+                cmd
+            }
+            Backend::WindowsCredentialManager => {
+                let on_path = std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default())
+                    .any(|dir| dir.join("powershell.exe").is_file());
+                let program = if cfg!(windows) || on_path {
+                    "powershell.exe"
+                } else {
+                    WSL_POWERSHELL
+                };
+                let mut cmd = Command::new(program);
+                cmd.args([
+                    "-NoProfile",
+                    "-NonInteractive",
+                    "-Command",
+                    &CRED_READ_SCRIPT.replace("TARGET", &windows_target(account)),
+                ]);
+                cmd
+            }
+        }
+    }
+
+    /// The tool this backend runs to read keys.
+    #[cfg(feature = "keychain")]
+    pub fn program(self) -> std::ffi::OsString {
+        self.command(ACCOUNT).get_program().to_os_string()
+    }
+
+    /// The key stored for `account`, or `None` if this backend has none.
+    #[cfg(feature = "keychain")]
+    pub fn read(self, account: &str) -> Result<Option<String>, CommitauraError> {
+        let output = self
+            .command(account)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| {
+                CommitauraError::ConfigError(format!("{} keychain unavailable: {}", self.name(), e))
+            })?;
+        let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok((output.status.success() && !key.is_empty()).then_some(key))
+    }
+
+    #[cfg(not(feature = "keychain"))]
+    pub fn read(self, _account: &str) -> Result<Option<String>, CommitauraError> {
+        Err(CommitauraError::ConfigError(
+            "A keychain is configured but Commitaura was built without the `keychain` feature"
+                .to_string(),
+        ))
+    }
+}
+
+/// Generic credentials are found by target name alone, so keys other than
+/// the OpenAI one get their own target, e.g. `commitaura:anthropic`.
+#[cfg(feature = "keychain")]
+fn windows_target(account: &str) -> String {
+    if account == ACCOUNT {
+        SERVICE.to_string()
+    } else {
+        format!("{}:{}", SERVICE, account)
+    }
+}
+
+/// Whether `/proc/version` text comes from a WSL kernel.
+pub fn is_wsl_kernel(version: &str) -> bool {
+    let version = version.to_ascii_lowercase();
+    version.contains("microsoft") || version.contains("wsl")
+}
+
+pub fn is_wsl() -> bool {
+    cfg!(target_os = "linux")
+        && std::fs::read_to_string("/proc/version").is_ok_and(|v| is_wsl_kernel(&v))
+}
+
+/// The backends to try on this system, in order.
+#[cfg(feature = "keychain")]
+pub fn available() -> Vec<Backend> {
+    if cfg!(target_os = "macos") {
+        vec![Backend::MacKeychain]
+    } else if cfg!(windows) {
+        vec![Backend::WindowsCredentialManager]
+    } else if is_wsl() {
+        vec![Backend::SecretService, Backend::WindowsCredentialManager]
+    } else {
+        vec![Backend::SecretService]
+    }
+}
+
+/// None: built without the `keychain` feature.
+#[cfg(not(feature = "keychain"))]
+pub fn available() -> Vec<Backend> {
+    Vec::new()
+}
+
+/// Look up the API key stored for `account`. `selection` is the `keychain`
+/// setting: a backend name, `"none"`, or `None` to try every [`available`]
+/// backend. An explicitly selected backend that cannot be used is an error;
+/// in automatic mode it is skipped.
+pub fn lookup(
+    selection: Option<&str>,
+    account: &str,
+) -> Result<Option<(Backend, String)>, CommitauraError> {
+    let backends = match selection {
+        Some("none") => return Ok(None),
+        Some(name) => {
+            let backend = Backend::parse(name).ok_or_else(|| {
+                CommitauraError::ConfigError(format!(
+                    "Unknown keychain '{}' (use macos, secret-service, windows or none)",
+                    name
+                ))
+            })?;
+            return Ok(backend.read(account)?.map(|key| (backend, key)));
+        }
+        None => available(),
+    };
+    for backend in backends {
+        match backend.read(account) {
+            Ok(Some(key)) => return Ok(Some((backend, key))),
+            Ok(None) => {}
+            Err(e) => debug!("{}", e),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_wsl_kernels() {
+        assert!(is_wsl_kernel(
+            "Linux version 5.15.153.1-microsoft-standard-WSL2 (root@...)"
+        ));
+        assert!(!is_wsl_kernel(
+            "Linux version 6.8.0-45-generic (buildd@lcy02)"
+        ));
+    }
+
+    #[test]
+    fn parses_backend_names() {
+        for backend in [
+            Backend::MacKeychain,
+            Backend::SecretService,
+            Backend::WindowsCredentialManager,
+        ] {
+            assert_eq!(Backend::parse(backend.name()), Some(backend));
+        }
+        #[cfg(feature = "keychain")]
+        assert_eq!(windows_target("anthropic"), "commitaura:anthropic");
+        assert!(lookup(Some("vault"), ACCOUNT).is_err());
+        assert_eq!(lookup(Some("none"), ACCOUNT).unwrap(), None);
+    }
+}
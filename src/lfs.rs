@@ -0,0 +1,206 @@
+//! Git LFS awareness.
+//!
+//! A file tracked by LFS is committed as a small pointer naming the real
+//! object, so its diff is a confusing swap of hashes. Such sections are
+//! rewritten as one line saying what changed. Large binaries staged without
+//! LFS are called out instead, since they bloat the history for good.
+
+use std::collections::HashSet;
+
+use crate::diff::{self, FileDiff, FileStatus};
+use crate::{git, CommitauraError};
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Binaries at least this large are worth storing in LFS.
+pub const LARGE_BINARY_BYTES: u64 = 5 * 1024 * 1024;
+
+/// The contents of an LFS pointer file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    /// Object id, e.g. `sha256:4d7a...`.
+    pub oid: String,
+    /// Size of the object in bytes.
+    pub size: u64,
+}
+
+impl Pointer {
+    pub fn parse(text: &str) -> Option<Pointer> {
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty());
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            match line.split_once(' ')? {
+                ("oid", value) => oid = Some(value.to_string()),
+                ("size", value) => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Pointer {
+            oid: oid?,
+            size: size?,
+        })
+    }
+
+    /// The object id shortened for display.
+    fn short_oid(&self) -> &str {
+        let hash = self.oid.split_once(':').map_or(&*self.oid, |(_, h)| h);
+        &hash[..hash.len().min(12)]
+    }
+}
+
+/// Byte counts the way people read them, e.g. `3.4 MB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// What a change to a pointer file means, if `file` is one.
+fn describe(file: &FileDiff) -> Option<String> {
+    let side = |prefix: char| -> String {
+        file.hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .filter_map(|l| l.strip_prefix(prefix).or_else(|| l.strip_prefix(' ')))
+            .map(|l| format!("{}\n", l))
+            .collect()
+    };
+    let (old, new) = (Pointer::parse(&side('-')), Pointer::parse(&side('+')));
+    match (file.status, old, new) {
+        (FileStatus::Deleted, Some(old), _) => Some(format!(
+            "Git LFS object removed: {} ({})",
+            human_size(old.size),
+            old.short_oid()
+        )),
+        (_, None, Some(new)) if file.status == FileStatus::Added => Some(format!(
+            "Git LFS object added: {} ({})",
+            human_size(new.size),
+            new.short_oid()
+        )),
+        (_, Some(old), Some(new)) => Some(format!(
+            "Git LFS object changed: {} -> {} ({} -> {})",
+            human_size(old.size),
+            human_size(new.size),
+            old.short_oid(),
+            new.short_oid()
+        )),
+        _ => None,
+    }
+}
+
+/// `diff` with each LFS pointer change replaced by a line describing the
+/// object, e.g. `Git LFS object changed: 1.2 MB -> 3.4 MB (...)`.
+pub fn describe_pointers(diff: &str) -> String {
+    if !diff.contains(POINTER_VERSION) {
+        return diff.to_string();
+    }
+    let files: Vec<FileDiff> = diff::parse(diff)
+        .into_iter()
+        .map(|mut file| {
+            if let Some(description) = describe(&file) {
+                file.header.push(description);
+                file.hunks.clear();
+            }
+            file
+        })
+        .collect();
+    diff::render(&files)
+}
+
+/// Binaries in `diff` that are added or changed.
+fn staged_binaries(diff: &str) -> impl Iterator<Item = FileDiff> {
+    diff::parse(diff)
+        .into_iter()
+        .filter(|file| file.binary && file.status != FileStatus::Deleted)
+}
+
+/// Warnings for binaries in `diff` of at least [`LARGE_BINARY_BYTES`] that
+/// do not go through LFS. `in_lfs` tells whether a path does, and `size_of`
+/// gives a staged file's size.
+pub fn large_binary_warnings(
+    diff: &str,
+    in_lfs: impl Fn(&str) -> bool,
+    size_of: impl Fn(&str) -> Option<u64>,
+) -> Vec<String> {
+    staged_binaries(diff)
+        .filter(|file| !in_lfs(&file.path))
+        .filter_map(|file| {
+            let size = size_of(&file.path).filter(|&size| size >= LARGE_BINARY_BYTES)?;
+            Some(format!(
+                "{} is a {} binary staged without Git LFS; `git lfs track` keeps it out of the history.",
+                file.path,
+                human_size(size)
+            ))
+        })
+        .collect()
+}
+
+/// [`large_binary_warnings`] for the staged changes of the current
+/// repository, asking git which paths have `filter=lfs`.
+pub fn staged_warnings(diff: &str) -> Result<Vec<String>, CommitauraError> {
+    let binaries: Vec<String> = staged_binaries(diff).map(|file| file.path).collect();
+    let lfs: HashSet<String> = git::check_attr("filter", &binaries)?
+        .into_iter()
+        .filter(|(_, value)| value == "lfs")
+        .map(|(path, _)| path)
+        .collect();
+    Ok(large_binary_warnings(
+        diff,
+        |path| lfs.contains(path),
+        git::staged_size,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTER_CHANGE: &str = "\
+diff --git a/assets/intro.mp4 b/assets/intro.mp4
+index 1111111..2222222 100644
+--- a/assets/intro.mp4
++++ b/assets/intro.mp4
+@@ -1,3 +1,3 @@
+ version https://git-lfs.github.com/spec/v1
+-oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393
+-size 1258291
++oid sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
++size 3565158
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1 @@
+-old
++new
+";
+
+    #[test]
+    fn describes_pointer_changes() {
+        let described = describe_pointers(POINTER_CHANGE);
+        assert!(described
+            .contains("Git LFS object changed: 1.2 MB -> 3.4 MB (4d7a214614ab -> 9f86d081884c)\n"));
+        assert!(!described.contains("+oid"));
+        assert!(described.ends_with("-old\n+new\n"));
+    }
+
+    #[test]
+    fn warns_about_large_untracked_binaries() {
+        let diff = "diff --git a/big.bin b/big.bin\nnew file mode 100644\nBinary files /dev/null and b/big.bin differ\ndiff --git a/model.onnx b/model.onnx\nnew file mode 100644\nBinary files /dev/null and b/model.onnx differ\n";
+        let in_lfs = |path: &str| path.ends_with(".onnx");
+        let warnings = large_binary_warnings(diff, in_lfs, |_| Some(LARGE_BINARY_BYTES * 2));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("big.bin is a 10.0 MB binary"));
+        assert!(large_binary_warnings(diff, |_| false, |_| Some(1024)).is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+//! Commitaura core library.
+//!
+//! Generates Git commit messages from a staged diff with an OpenAI-compatible
+//! LLM. The `commitaura` binary is a thin interactive wrapper around this
+//! crate; other tools can embed the same generation with
+//! `default-features = false`, which drops all terminal UI dependencies.
+//!
+//! The stable API is what this page lists: the items at the crate root, the
+//! [`diff`] parser and the [`pipeline`] and [`plugin`] extension points.
+//! Everything else is internal to the binary and may change in any release.
+//!
+//! ```no_run
+//! use commitaura::{generate_message, GenerateOptions};
+//!
+//! let diff = commitaura::get_staged_diff()?;
+//! let opts = GenerateOptions::new(std::env::var("OPENAI_API_KEY").unwrap());
+//! let message = generate_message(&diff, &opts)?;
+//! println!("{}", message);
+//! # Ok::<(), commitaura::CommitauraError>(())
+//! ```
+
+pub mod diff;
+mod error;
+mod glob;
+pub mod pipeline;
+pub mod plugin;
+
+/// The modules behind the `commitaura` binary. Not part of the stable API:
+/// anything here may change in any release.
+#[doc(hidden)]
+#[path = "."]
+pub mod __private {
+    pub mod candidates;
+    pub mod cascade;
+    pub mod changelog;
+    pub mod config;
+    pub mod continuation;
+    pub mod draft;
+    pub mod editmsg;
+    pub mod examples;
+    pub mod forge;
+    pub mod fragments;
+    pub mod full_message;
+    pub mod generate;
+    pub mod generated;
+    pub mod git;
+    pub mod gitignore;
+    pub mod gitmoji;
+    pub mod guard;
+    pub mod guidelines;
+    pub mod history;
+    pub mod hook;
+    pub mod http;
+    pub mod ignore;
+    pub mod import;
+    pub mod injection;
+    pub mod journal;
+    pub mod keychain;
+    pub mod lfs;
+    pub mod map_reduce;
+    pub mod merge;
+    pub mod message;
+    pub mod output;
+    pub mod pair;
+    pub mod platform;
+    pub mod pr;
+    pub mod pregen;
+    pub mod priority;
+    pub mod pricing;
+    pub mod prompt_test;
+    pub mod provider;
+    pub mod public_api;
+    pub mod related;
+    #[cfg(feature = "libgit2")]
+    pub mod repo;
+    pub mod release;
+    pub mod repo_state;
+    pub mod rerun;
+    pub mod results;
+    pub mod reviewer_notes;
+    pub mod reword;
+    pub mod routing;
+    pub mod rules;
+    pub mod sandbox;
+    pub mod scope;
+    pub mod secrets;
+    pub mod sparse;
+    pub mod spellcheck;
+    pub mod split;
+    pub mod style;
+    pub mod summary;
+    pub mod team_cache;
+    pub mod template;
+    pub mod ticket;
+    pub mod timings;
+    pub mod tokenizer;
+    pub mod todos;
+    pub mod transaction;
+    pub mod trivial;
+    pub mod vendor;
+}
+
+use __private::*;
+
+pub use error::CommitauraError;
+pub use generate::{build_prompt, generate_message, GenerateOptions, Prompt, PromptStyle};
+pub use git::{get_last_commit_messages, get_staged_diff};
+pub use gitmoji::GitmojiFormat;
+pub use provider::Provider;
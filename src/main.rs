@@ -1,39 +1,28 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use colored::*;
+use commitaura::__private::config::{Config, FragmentsConfig, UiConfig};
+use commitaura::__private::forge::{self, PostCommitAction, RemoteRepo};
+use commitaura::__private::candidates::Spec;
+use commitaura::__private::continuation::Continuation;
+use commitaura::__private::git::{check_staged_changes, get_amend_diff_in, get_staged_diff_in};
+use commitaura::__private::provider::Provider;
+use commitaura::__private::prompt_test::Outcome;
+use commitaura::__private::output::{ClipboardSink, CommitSink, FileSink, MessageFileSink, OutputSink, PrSink, StdoutSink};
+use commitaura::__private::repo_state::RepoState;
+use commitaura::__private::reword::RewordAction;
+use commitaura::__private::style::StyleProfile;
+use commitaura::{generate_message, CommitauraError, GenerateOptions};
 use console::{style, Term};
-use dialoguer::{theme::ColorfulTheme, Confirm};
 use indicatif::{ProgressBar, ProgressStyle};
-use log::info;
-use openai_api_rust::chat::*;
-use openai_api_rust::*;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+mod ui;
 use std::time::Duration;
-use thiserror::Error;
-use tiktoken_rs::p50k_base;
-
-const MODEL_NAME: &str = "gpt-4o";
-const MAX_TOKENS: usize = 128000; // Adjust this based on the model's actual limit
-
-#[derive(Error, Debug)]
-enum CommitauraError {
-    #[error("Tokenizer error: {0}")]
-    TokenizerError(String),
-    #[error("No staged changes detected")]
-    NoStagedChanges,
-    #[error("Git operation failed: {0}")]
-    GitOperationFailed(String),
-    #[error("API request failed: {0}")]
-    ApiRequestFailed(String),
-    #[error("Environment variable not set: {0}")]
-    EnvVarNotSet(String),
-    #[error("OpenAI API error: {0}")]
-    OpenAIError(String),
-    #[error("Template error: {0}")]
-    TemplateError(#[from] indicatif::style::TemplateError),
-    #[error("Dialoguer error: {0}")]
-    DialoguerError(#[from] dialoguer::Error),
-    #[error("I/O error: {0}")]
-    IoError(#[from] std::io::Error),
-}
+
+use ui::candidates::Picked;
+use ui::pair::Vote;
+use ui::confirm::Action;
 
 // Removed redundant implementation
 
@@ -43,212 +32,2520 @@ enum CommitauraError {
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// API to use for this run: openai, anthropic or ollama (a local server); overrides `provider` in .commitaura.toml
+    #[arg(long, global = true, value_name = "NAME", value_parser = parse_provider)]
+    provider: Option<Provider>,
+
+    /// Never send raw diff content; send a locally generated change summary instead
+    #[arg(long, global = true)]
+    low_bandwidth: bool,
+
+    /// Append sanitized HTTP requests and responses (API key masked) to FILE for bug reports
+    #[arg(long, global = true, value_name = "FILE")]
+    debug_http: Option<PathBuf>,
+
+    /// Strip emoji, smart quotes and other non-ASCII characters from generated messages
+    #[arg(long, global = true)]
+    ascii_only: bool,
+
+    /// Start subjects with a gitmoji from the official list, as `prompt_style = "gitmoji"` does
+    #[arg(long, global = true)]
+    gitmoji: bool,
+
+    /// Replace the user prompt for this run with FILE; it may use {{ diff }} and {{ history }}
+    #[arg(long, global = true, value_name = "FILE")]
+    prompt_file: Option<PathBuf>,
+
+    /// Restage a hook's changes to HEAD's files and amend them in; the message is only regenerated if more than whitespace changed
+    #[arg(long, global = true)]
+    amend_no_edit: bool,
+
+    /// Choose the model for this run from a list with context window, price and latency hints
+    #[arg(long, global = true)]
+    pick_model: bool,
+
+    /// Give the model this long (e.g. 5s, 1500ms) before retrying on the configured fallback_model
+    #[arg(long, global = true, value_name = "DURATION", value_parser = parse_duration)]
+    max_wait: Option<Duration>,
+
+    /// In a shallow clone, offer to fetch the history that history-based features need
+    #[arg(long, global = true)]
+    deepen: bool,
+
+    /// Refuse every git operation that would change the repository: commits, tags, pushes, fetches, rewrites
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    /// Screen-reader friendly output: status lines instead of spinners, no box art, prompts answered by typing
+    #[arg(long, global = true)]
+    a11y: bool,
+
+    /// Stage changes to all tracked files first, like `git commit -a`; they are unstaged again if nothing is committed
+    #[arg(short, long, global = true, conflicts_with_all = ["amend_no_edit", "dry_run"])]
+    all: bool,
+
+    /// Print only the generated message to stdout and exit: no prompts, nothing committed
+    #[arg(long, global = true, conflicts_with_all = ["amend_no_edit", "commit", "pr", "edit", "candidates", "pair"])]
+    dry_run: bool,
+
+    /// Read a unified diff from stdin and print only its message to stdout, uncolored, for editors and scripts
+    #[arg(long, global = true, conflicts_with_all = ["amend_no_edit", "commit", "pr", "edit", "candidates", "select", "dry_run", "all"])]
+    stdin_diff: bool,
+
+    #[command(flatten)]
+    generation: GenerationArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+/// How the message for a commit is produced.
+#[derive(Args)]
+struct GenerationArgs {
+    /// Pick which files and hunks are sent to the model, with a live token and cost estimate
+    #[arg(long, global = true)]
+    select: bool,
+
+    /// Ask for N candidates at once, or one from each of these models (comma-separated, optionally provider:model), and pick one
+    #[arg(long, global = true, value_name = "N|MODELS", value_parser = parse_candidates)]
+    candidates: Option<Spec>,
+
+    /// Show a terse and a detailed message side by side and vote for one; the repository's default style follows the votes
+    #[arg(long, global = true, conflicts_with_all = ["candidates", "full", "reviewer_notes"])]
+    pair: bool,
+
+    /// Run the [results] command from .commitaura.toml and add its test and benchmark summary to the body
+    #[arg(long, global = true)]
+    with_results: bool,
+
+    /// What you set out to do, in your words: motivation, trade-offs, alternatives considered
+    #[arg(long, global = true, value_name = "TEXT")]
+    intent: Option<String>,
+
+    /// Also write notes for reviewers (trade-offs, alternatives) to FILE, or `clipboard`, from the same request
+    #[arg(long, global = true, value_name = "FILE|clipboard", conflicts_with = "candidates")]
+    reviewer_notes: Option<String>,
+
+    /// Write a full message: subject, wrapped body and footers such as BREAKING CHANGE
+    #[arg(long, global = true, conflicts_with = "reviewer_notes")]
+    full: bool,
+
+    /// Print how long each stage took: git, tokenizing, pipeline stages, API latency, post-processing
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Open the generated message in your editor and use what you save, like `git commit -e`
+    #[arg(short, long, global = true)]
+    edit: bool,
+}
+
+impl GenerationArgs {
+    /// Where reviewer notes go, if they were asked for.
+    fn notes_sink(&self) -> Option<Box<dyn OutputSink>> {
+        self.reviewer_notes.as_deref().map(|target| -> Box<dyn OutputSink> {
+            match target {
+                "clipboard" => Box::new(ClipboardSink),
+                path => Box::new(FileSink(PathBuf::from(path))),
+            }
+        })
+    }
+}
+
+fn parse_provider(s: &str) -> Result<Provider, String> {
+    Provider::parse(s).ok_or_else(|| "use openai, anthropic or ollama".to_string())
+}
+
+fn parse_candidates(s: &str) -> Result<Spec, String> {
+    match Spec::parse(s) {
+        Spec::Count(count) if count < 2 => Err("ask for at least two candidates".to_string()),
+        Spec::Count(count) if count > commitaura::__private::candidates::MAX_COUNT => Err(format!("ask for at most {} candidates; each is a request of its own", commitaura::__private::candidates::MAX_COUNT)),
+        Spec::Models(models) if models.len() < 2 => Err("give a count, e.g. 3, or at least two models, e.g. gpt-4o,anthropic:claude-haiku-4-5".to_string()),
+        spec => Ok(spec),
+    }
+}
+
+/// Parse `5s`, `1500ms` or `2m`; a bare number is seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value.parse().map_err(|_| format!("invalid duration `{}`", s))?;
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        _ => return Err(format!("unknown unit `{}` (use ms, s or m)", unit)),
+    };
+    // `from_secs_f64` panics on a value too large for a `Duration`.
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("duration `{}` is out of range", s))
+}
+
+/// Where an accepted message goes. Several can be combined; without any of
+/// them the message is committed.
+#[derive(Args)]
+struct OutputArgs {
+    /// Commit the staged changes with the message
+    #[arg(long, global = true)]
+    commit: bool,
+
+    /// Copy the message to the clipboard
+    #[arg(long, global = true)]
+    copy: bool,
+
+    /// Write the message to FILE
+    #[arg(long, global = true, value_name = "FILE")]
+    output_file: Option<PathBuf>,
+
+    /// Merge the message into git's commit message file FILE (e.g. from a prepare-commit-msg hook)
+    #[arg(long, global = true, value_name = "FILE")]
+    message_file: Option<PathBuf>,
+
+    /// Print the bare message to stdout; everything else is shown on stderr
+    #[arg(long, global = true)]
+    stdout: bool,
+
+    /// Amend HEAD instead of making a new commit; the message is regenerated from its whole diff, improving on its message
+    #[arg(long, global = true, conflicts_with_all = ["amend_no_edit", "dry_run", "stdin_diff"])]
+    amend: bool,
+
+    /// With --amend, give the commit the current time as its author date instead of keeping the original one
+    #[arg(long, global = true, requires = "amend")]
+    reset_date: bool,
+
+    /// Push the branch and open a pull request titled with the message
+    #[arg(long, global = true)]
+    pr: bool,
+}
+
+impl OutputArgs {
+    fn commits(&self) -> bool {
+        self.commit || self.amend || (!(self.copy || self.stdout || self.pr) && self.output_file.is_none() && self.message_file.is_none())
+    }
+
+    fn sinks(&self, amend: bool) -> Result<Vec<Box<dyn OutputSink>>, CommitauraError> {
+        let mut sinks: Vec<Box<dyn OutputSink>> = Vec::new();
+        // Commit first so a pull request includes the new commit.
+        if self.commits() {
+            sinks.push(Box::new(CommitSink { amend, redate: self.reset_date }));
+        }
+        if self.pr {
+            let repo = commitaura::__private::git::remote_url("origin").as_deref().and_then(RemoteRepo::parse).ok_or_else(|| {
+                CommitauraError::GitOperationFailed("origin is not a GitHub, GitLab or Bitbucket remote".to_string())
+            })?;
+            sinks.push(Box::new(PrSink { repo }));
+        }
+        if let Some(path) = &self.output_file {
+            sinks.push(Box::new(FileSink(path.clone())));
+        }
+        if let Some(path) = &self.message_file {
+            sinks.push(Box::new(MessageFileSink(path.clone())));
+        }
+        if self.copy {
+            sinks.push(Box::new(ClipboardSink));
+        }
+        if self.stdout {
+            sinks.push(Box::new(StdoutSink));
+        }
+        Ok(sinks)
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Automatically generate commit message and commit
-    Commit,
+    Commit {
+        /// Commit a tree built by automation instead of the index, without a work tree, e.g. in a bare repository; prints the new commit's id
+        #[arg(long, requires = "tree")]
+        bare: bool,
+        /// Tree to commit with --bare
+        #[arg(long, value_name = "OID", requires = "bare")]
+        tree: Option<String>,
+        /// Parent of the --bare commit; repeat for a merge, leave out for a root commit
+        #[arg(long, value_name = "OID", requires = "bare")]
+        parent: Vec<String>,
+        /// Point this ref, e.g. refs/heads/main, at the --bare commit, if it is still at the first parent
+        #[arg(long, value_name = "REF", requires = "bare")]
+        update_ref: Option<String>,
+    },
+    /// Regenerate the messages of every commit in <BASE>..HEAD
+    Reword {
+        /// Commit to start after; its descendants up to HEAD are reworded
+        base: String,
+        /// Fold fixup!/squash!/amend! commits into their targets first (git rebase --autosquash)
+        #[arg(long)]
+        fold_fixups: bool,
+        /// Review all proposed messages in one file in your editor, then apply them in a single rewrite
+        #[arg(long)]
+        review_file: bool,
+    },
+    /// List the available models with context window, price per 1K tokens and recent latency
+    Models,
+    /// Re-run a journaled prompt, optionally on another model; without <ID>, list recent entries
+    Replay {
+        /// Journal entry to re-run
+        id: Option<u64>,
+        /// Model to send the prompt to instead of the one originally used
+        #[arg(long)]
+        model: Option<String>,
+    },
+    /// Create .commitaura.toml in the repository root
+    Init {
+        /// Fill in model and api_base from aider, llm, aichat or OPENAI_BASE_URL / OPENAI_MODEL
+        #[arg(long)]
+        import: bool,
+    },
+    /// Create annotated tag <NAME> on HEAD with a message summarizing changes since the previous tag
+    Tag {
+        /// Name of the new tag, e.g. v1.4.0
+        name: String,
+        /// Summarize changes since this ref instead of the most recent tag
+        #[arg(long, value_name = "REF")]
+        since: Option<String>,
+        /// Create a GPG-signed tag (git tag --sign)
+        #[arg(long)]
+        sign: bool,
+    },
+    /// Watch the index and pre-generate a message for staged changes, as configured under [watch]
+    Watch,
+    /// Work on the prompt without sending it to a model
+    Prompt {
+        #[command(subcommand)]
+        command: PromptCommands,
+    },
+    /// Install a prepare-commit-msg hook so `git commit` starts from a generated message
+    InstallHook {
+        /// Replace a prepare-commit-msg hook that Commitaura did not install
+        #[arg(long)]
+        force: bool,
+    },
+    /// Restore the branch and the index to before an operation that was interrupted, or with --last that finished
+    Rollback {
+        /// Undo the last operation that finished (commit --all, reword, amend or split), if nothing has moved the branch since
+        #[arg(long)]
+        last: bool,
+        /// Only restore the staged contents of these files, leaving the branch alone
+        paths: Vec<String>,
+    },
+    /// Show the prompt and reply a message was generated from, by its Commitaura-Id or by commit
+    Show {
+        /// The Commitaura-Id from a commit's trailer (any unique prefix), or a commit whose message has one
+        id: String,
+    },
+    /// Split the staged changes into several commits, one per module, each with its own message
+    Split,
+    /// Print a changelog of the tagged releases, laid out as configured under [changelog]
+    Changelog,
+    /// Report the platform this build targets and which optional capabilities are compiled in and usable
+    Doctor,
+    /// Check commit messages against the rules in .commitaura/rules.toml, e.g. those of a pull request in CI; fails if any rule is broken
+    Assert {
+        /// File holding a message to check, such as the one a commit-msg hook is given; repeat for several, `-` reads stdin
+        #[arg(long, value_name = "FILE")]
+        message_file: Vec<PathBuf>,
+        /// Check the message of every commit in RANGE that is not a merge, e.g. origin/main..HEAD
+        #[arg(long, value_name = "RANGE", required_unless_present = "message_file")]
+        range: Option<String>,
+        /// Rules file to use instead of .commitaura/rules.toml
+        #[arg(long, value_name = "FILE")]
+        rules: Option<PathBuf>,
+        /// Report as text, SARIF (for code scanning) or JUnit XML (for test reports)
+        #[arg(long, value_name = "FORMAT", value_parser = ["text", "sarif", "junit"], default_value = "text")]
+        format: String,
+        /// Write the report to FILE instead of stdout
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Serve a cache of messages for lock file and generated file changes, shared by a team; requires the COMMITAURA_CACHE_TOKEN token unless listening on loopback
+    CacheServer {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = commitaura::__private::team_cache::DEFAULT_LISTEN)]
+        listen: String,
+        /// Keep the entries in FILE, so they survive a restart
+        #[arg(long, value_name = "FILE")]
+        store: Option<PathBuf>,
+    },
+    /// Check or print the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Run as the prepare-commit-msg hook: write a generated message into <FILE>, without prompts
+    Hook {
+        /// The commit message file git passes to the hook
+        file: PathBuf,
+        /// Where the message comes from: message, template, merge, squash or commit
+        source: Option<String>,
+        /// The commit being amended, if any
+        #[arg(value_name = "COMMIT")]
+        sha: Option<String>,
+    },
+    /// Stash the changes to tracked files under a generated message
+    Stash {
+        #[command(subcommand)]
+        command: Option<StashCommands>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StashCommands {
+    /// Describe what stash@{<N>} contains and offer to rename it with the description
+    Describe {
+        /// Index of the stash entry, as in stash@{N}
+        #[arg(default_value_t = 0)]
+        n: usize,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check each config file for unknown keys and invalid values, then the merged settings
+    Lint,
+    /// Print the config files that are read, in the order they are laid over each other
+    Show {
+        /// Print the effective configuration instead: the files merged, with defaults filled in
+        #[arg(long)]
+        resolved: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PromptCommands {
+    /// Render the prompt for bundled and .commitaura/prompt-tests/ fixture diffs with a fake model and compare with the snapshots there
+    Test {
+        /// Accept the rendered output as the new snapshots
+        #[arg(long)]
+        update: bool,
+    },
 }
 
 fn main() -> Result<(), CommitauraError> {
-    env_logger::init();
+    // Warnings such as a model downgrade are shown unless RUST_LOG says otherwise.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     dotenv::dotenv().ok();
 
-    let auth = Auth::from_env()
-        .map_err(|_| CommitauraError::EnvVarNotSet("OPENAI_API_KEY".to_string()))?;
-    let openai = OpenAI::new(auth, "https://api.openai.com/v1/");
-
     let cli = Cli::parse();
+    if cli.read_only {
+        commitaura::__private::git::enable_read_only();
+    }
+    if cli.a11y {
+        ui::enable_accessible();
+    }
     let term = Term::stdout();
+    match &cli.command {
+        Some(Commands::Init { import }) => handle_init(*import),
+        Some(Commands::InstallHook { force }) => handle_install_hook(*force),
+        Some(Commands::Rollback { last, paths }) => handle_rollback(*last, paths),
+        Some(Commands::Config { command }) => handle_config(command),
+        Some(Commands::Show { id }) => handle_show(id),
+        Some(Commands::Doctor) => {
+            handle_doctor();
+            Ok(())
+        }
+        Some(Commands::Assert { message_file, range, rules, format, output }) => handle_assert(message_file, range.as_deref(), rules.as_deref(), format, output.as_deref()),
+        Some(Commands::CacheServer { listen, store }) => handle_cache_server(listen, store.as_deref()),
+        Some(Commands::Changelog) => {
+            let config = Config::load()?;
+            let releases = commitaura::__private::changelog::collect(&config.changelog)?;
+            print!("{}", commitaura::__private::changelog::render(&releases, &config.changelog)?);
+            Ok(())
+        }
+        Some(Commands::Commit { bare: true, tree: Some(tree), parent, update_ref }) => with_options(&cli, |opts, config| handle_bare_commit(opts, config, &cli.generation, tree, parent, update_ref.as_deref(), cli.dry_run)),
+        Some(Commands::Commit { .. }) | None if cli.amend_no_edit => with_options(&cli, |opts, _| handle_amend_no_edit(opts)),
+        Some(Commands::Commit { .. }) | None if cli.stdin_diff => with_options(&cli, |opts, config| handle_stdin_diff(opts, config, &cli.generation)),
+        Some(Commands::Commit { .. }) | None if cli.dry_run => with_options(&cli, |opts, config| handle_dry_run(opts, config, &cli.generation)),
+        Some(Commands::Commit { .. }) | None if cli.all => with_options(&cli, |opts, config| handle_commit_all(opts, &term, config, &cli.generation, cli.deepen, &cli.output)),
+        Some(Commands::Commit { .. }) | None => with_options(&cli, |opts, config| handle_commit(opts, &term, config, &cli.generation, cli.deepen, &cli.output)),
+        Some(Commands::Reword { base, fold_fixups, review_file }) => {
+            with_options(&cli, |opts, config| handle_reword(opts, &term, &config.ui, base, *fold_fixups, *review_file))
+        }
+        Some(Commands::Split) => with_options(&cli, |opts, config| handle_split(opts, config, &cli.generation)),
+        Some(Commands::Models) => with_options(&cli, |opts, config| {
+            let models = ui::models::offered(config.models.as_deref(), opts.provider);
+            ui::models::print_models(&models, &opts.model, &journal_entries(opts)?);
+            Ok(())
+        }),
+        Some(Commands::Replay { id, model }) => with_options(&cli, |opts, _| handle_replay(opts, *id, model.clone())),
+        Some(Commands::Hook { file, source, .. }) => with_options(&cli, |opts, config| handle_hook(opts, config, &cli.generation, file, source.as_deref())),
+        Some(Commands::Tag { name, since, sign }) => with_options(&cli, |opts, config| handle_tag(opts, &term, &config.ui, name, since.clone(), *sign, cli.deepen)),
+        Some(Commands::Watch) => with_options(&cli, handle_watch),
+        Some(Commands::Prompt { command: PromptCommands::Test { update } }) => with_options(&cli, |opts, _| handle_prompt_test(opts, *update)),
+        Some(Commands::Stash { command: None }) => with_options(&cli, |opts, _| handle_stash(opts)),
+        Some(Commands::Stash { command: Some(StashCommands::Describe { n }) }) => with_options(&cli, |opts, _| handle_stash_describe(opts, *n)),
+    }
+}
+
+/// Run `handle` with the config and the generation options set up from
+/// it, the environment and `cli`. Only commands that talk to a model need
+/// these, and with them an API key.
+fn with_options(
+    cli: &Cli,
+    handle: impl FnOnce(&mut GenerateOptions, &Config) -> Result<(), CommitauraError>,
+) -> Result<(), CommitauraError> {
+    let mut config = Config::load()?;
+    let Some(mut opts) = options(cli, &mut config)? else {
+        return Ok(());
+    };
+    handle(&mut opts, &config).inspect_err(|e| {
+        // Every prompt is checked before it is sent; say what stopped it.
+        if let CommitauraError::SecretsInPrompt(report) = e {
+            eprintln!("{} {}", "🛑".bold().red(), "Secrets left in the prompt:".bold().white());
+            for line in report {
+                eprintln!("  {}", line.yellow());
+            }
+        }
+    })
+}
+
+/// The API key and base URL of `provider`, from the environment, the
+/// system's credential store and `config_base`, the global config's
+/// `api_base`. `offline` runs need no key.
+fn connection(provider: Provider, config: &Config, config_base: Option<&str>, offline: bool) -> Result<(String, String), CommitauraError> {
+    let api_key = match provider.key_var() {
+        // A local Ollama server needs no key.
+        None => String::new(),
+        Some(_) if offline => String::new(),
+        // Without OPENAI_API_KEY (or ANTHROPIC_API_KEY), read the key from
+        // the system's credential store.
+        Some(var) => match std::env::var(var) {
+            Ok(key) => key,
+            Err(_) => match commitaura::__private::keychain::lookup(config.keychain.as_deref(), provider.name())? {
+                Some((backend, key)) => {
+                    log::info!("Using the API key from the {} keychain", backend.name());
+                    key
+                }
+                None => return Err(CommitauraError::EnvVarNotSet(var.to_string())),
+            },
+        },
+    };
+    let mut api_base = provider.default_api_base().to_string();
+    let env_base = match provider {
+        Provider::OpenAi => std::env::var("OPENAI_BASE_URL").ok().filter(|b| !b.is_empty()),
+        Provider::Ollama => std::env::var("OLLAMA_HOST").ok().filter(|h| !h.is_empty()).map(|h| commitaura::__private::provider::ollama_base(&h)),
+        Provider::Anthropic => None,
+    };
+    // Only the global config can set `api_base`; see `config::GLOBAL_ONLY`.
+    // It names an OpenAI-style endpoint, so Ollama only follows OLLAMA_HOST.
+    let config_base = match provider {
+        Provider::Ollama => {
+            if config_base.is_some() {
+                log::warn!("Ignoring `api_base` for Ollama; set OLLAMA_HOST to use another server");
+            }
+            None
+        }
+        _ => config_base.map(str::to_string),
+    };
+    if let Some(base) = env_base.or(config_base) {
+        api_base = commitaura::__private::import::normalize_api_base(&base);
+    }
+    if provider == Provider::Ollama && !commitaura::__private::provider::loopback(&api_base) {
+        eprintln!(
+            "{} {}",
+            "⚠".yellow(),
+            format!("Ollama is at {}, not on this machine: the staged diff will leave it.", api_base).yellow()
+        );
+    }
+    Ok((api_key, api_base))
+}
+
+/// The generation options for `cli` and `config`, with the journal's
+/// entries; `None` when the model picker was cancelled.
+fn options(cli: &Cli, config: &mut Config) -> Result<Option<GenerateOptions>, CommitauraError> {
+    if config.ui.a11y {
+        ui::enable_accessible();
+    }
+    let provider = match cli.provider {
+        Some(provider) => provider,
+        None => config.provider()?,
+    };
+    // Prompt tests use a fake model.
+    let offline = matches!(cli.command, Some(Commands::Prompt { .. }));
+    let (api_key, api_base) = connection(provider, config, config.api_base.as_deref(), offline)?;
+    let mut opts = GenerateOptions::for_provider(api_key, provider);
+    opts.api_base = api_base;
+    // The limits size the prompt budget whenever the model is set.
+    if let Some(max_response_tokens) = config.max_response_tokens {
+        opts.max_response_tokens = max_response_tokens;
+    }
+    if let Some(headroom_tokens) = config.headroom_tokens {
+        opts.headroom_tokens = headroom_tokens;
+    }
+    opts.context_window = config.context_window;
+    opts.max_prompt_tokens = config.max_tokens;
+    let model = config.model.clone().unwrap_or_else(|| opts.model.clone());
+    opts.set_model(&model);
+    if let Some(temperature) = config.temperature {
+        opts.temperature = temperature;
+    }
+    opts.prompt_style = if cli.gitmoji { commitaura::__private::generate::PromptStyle::Gitmoji } else { config.prompt_style.unwrap_or_default() };
+    if !cli.gitmoji && config.prompt_style.is_none() && config.tune_style != Some(false) {
+        if let Some(style) = commitaura::__private::pair::load().preferred() {
+            log::info!("Using the {:?} style, which won the --pair votes in this repository", style);
+            opts.prompt_style = style;
+        }
+    }
+    opts.gitmoji_format = config.gitmoji_format;
+    opts.examples = config.examples.count();
+    opts.example_strategy = config.examples.strategy;
+    opts.history_selection = config.history.selection();
+    opts.redactor = config.redact.redactor()?;
+    if config.summarize_generated == Some(false) {
+        opts.generated = None;
+    }
+    opts.file_summaries = config.file_summaries == Some(true);
+    if let Ok(root) = commitaura::__private::git::toplevel() {
+        opts.ignore = commitaura::__private::ignore::IgnoreFile::load(&root)?;
+        if opts.generated.is_some() {
+            opts.generated = Some(commitaura::__private::generated::Detector::load(&root)?);
+        }
+    }
+    opts.scopes = config.scopes.clone();
+    if let Some(color) = config.ui.color {
+        colored::control::set_override(color);
+        console::set_colors_enabled(color);
+    }
 
-    match cli.command {
-        Some(Commands::Commit) | None => handle_commit(&openai, &term)?,
+    opts.low_bandwidth = cli.low_bandwidth;
+    opts.debug_http = cli.debug_http.clone();
+    opts.ascii_only = cli.ascii_only || config.ascii_only;
+    opts.max_wait = cli.max_wait;
+    opts.intent = cli.generation.intent.clone();
+    opts.fallback_model = config.fallback_model.clone();
+    opts.stream_prompt = config.stream_prompt;
+    opts.journal = commitaura::__private::journal::repo_journal().ok();
+    if cli.pick_model {
+        let models = ui::models::offered(config.models.as_deref(), provider);
+        match ui::models::pick_model(&models, &opts.model, &journal_entries(&opts)?)? {
+            Some(model) => opts.set_model(&model),
+            None => return Ok(None),
+        }
+        // The model picked for this run is used whatever the size of the change.
+        config.routing = Default::default();
+    }
+    let prompt_template = match &cli.prompt_file {
+        Some(path) => Some(std::fs::read_to_string(path)?),
+        None => config.prompt.user.clone(),
+    };
+    // Fail before any git or network work if a template is unusable.
+    if let Some(template) = &prompt_template {
+        commitaura::__private::template::validate_prompt(template)?;
+    }
+    if let Some(template) = &config.prompt.system {
+        commitaura::__private::template::validate_system(template)?;
     }
+    opts.prompt_template = prompt_template;
+    opts.system_template = config.prompt.system.clone();
+    opts.branch = current_branch();
+    if let Some(branch) = &opts.branch {
+        opts.ticket = commitaura::__private::ticket::Reference::find(&config.ticket, branch)?;
+    }
+    let generates = !matches!(cli.command, Some(Commands::Models) | Some(Commands::Replay { .. }) | Some(Commands::Prompt { .. }));
+    if generates && config.guidelines != Some(false) {
+        opts.repo_guidelines = Some(commitaura::__private::guidelines::Deferred::default());
+    }
+
+    Ok(Some(opts))
+}
+
+/// The entries of the journal `opts` records to, for the model statistics.
+/// Read only by the commands that show them, since a journal grows long.
+fn journal_entries(opts: &GenerateOptions) -> Result<Vec<commitaura::__private::journal::JournalEntry>, CommitauraError> {
+    match &opts.journal {
+        Some(path) => commitaura::__private::journal::load(path),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Create `.commitaura.toml`, or add settings to an existing one, optionally
+/// with the model and endpoint of an existing AI CLI setup. The endpoint
+/// goes into the global config, the only one that may set it, and API keys
+/// are never copied.
+fn handle_init(import: bool) -> Result<(), CommitauraError> {
+    let path = commitaura::__private::config::repo_config_path()
+        .ok_or_else(|| CommitauraError::GitOperationFailed("not inside a git work tree".to_string()))?;
+    let existing = if path.is_file() { Some(Config::from_path(&path)?) } else { None };
+
+    let mut settings: Vec<(&str, String)> = Vec::new();
+    if import {
+        let found = commitaura::__private::import::detect();
+        if found.is_empty() {
+            println!("{}", style("No aider, llm or aichat settings found, and OPENAI_BASE_URL / OPENAI_MODEL are unset.").bold().yellow());
+        }
+        for source in &found {
+            println!("{} {}", "🔎 Found settings in".dimmed(), source.source.white());
+        }
+        let merged = commitaura::__private::import::merged(&found);
+        if let Some(model) = merged.model.filter(|_| existing.as_ref().is_none_or(|c| c.model.is_none())) {
+            settings.push(("model", model));
+        }
+        if let Some(base) = merged.api_base {
+            add_global_setting("api_base", &commitaura::__private::import::normalize_api_base(&base))?;
+        }
+        if merged.api_key.is_some() && std::env::var_os("OPENAI_API_KEY").is_none() {
+            println!("{}", "   The API key is not copied. Set OPENAI_API_KEY or store the key in your keychain.".dimmed());
+        }
+    }
+
+    let pairs: Vec<(&str, &str)> = settings.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    let lines = commitaura::__private::config::render_settings(&pairs);
+    match &existing {
+        Some(_) if lines.is_empty() => {
+            println!("{}", style(format!("{} already exists; nothing to add.", path.display())).bold().yellow());
+            return Ok(());
+        }
+        // Top-level keys must come before the first [table].
+        Some(_) => std::fs::write(&path, lines.clone() + &std::fs::read_to_string(&path)?)?,
+        None => std::fs::write(&path, format!("# Commitaura settings; see the README for every key.\n{}", lines))?,
+    }
+    print!("{}", lines.white());
+    println!("{} {}", style("✅ Wrote").bold().green(), path.display());
     Ok(())
 }
 
-fn handle_commit(openai: &OpenAI, term: &Term) -> Result<(), CommitauraError> {
-    term.clear_screen()?;
+/// Set `key` in the global config unless it already has a value there.
+fn add_global_setting(key: &str, value: &str) -> Result<(), CommitauraError> {
+    let path = commitaura::__private::config::global_config_path()
+        .ok_or_else(|| CommitauraError::ConfigError("no home directory for the global config".to_string()))?;
+    let text = std::fs::read_to_string(&path).unwrap_or_default();
+    let table: toml::Table = toml::from_str(&text).map_err(|e| CommitauraError::ConfigError(format!("{}: {}", path.display(), e)))?;
+    if table.contains_key(key) {
+        println!("{}", format!("   {} is already set in {}; left as it is.", key, path.display()).dimmed());
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let lines = commitaura::__private::config::render_settings(&[(key, value)]);
+    // Top-level keys must come before the first [table].
+    std::fs::write(&path, lines.clone() + &text)?;
+    print!("{}", lines.white());
+    println!("{} {}", style("✅ Wrote").bold().green(), path.display());
+    Ok(())
+}
+
+/// `opts` switched to the model `[routing]` picks for `diff`, with a note
+/// saying which and why; `None` when it keeps the configured model.
+fn routed(diff: &str, opts: &GenerateOptions, config: &Config) -> Result<Option<(GenerateOptions, String)>, CommitauraError> {
+    // A cascade names its own models.
+    if !config.routing.enabled() || config.cascade.is_some() {
+        return Ok(None);
+    }
+    let Some(route) = commitaura::__private::routing::route_for(diff, opts, &config.routing)? else {
+        return Ok(None);
+    };
+    Ok(Some((opts.with_model(&route.model), route.describe())))
+}
+
+/// `commit -a`: stage the changes to tracked files, then commit as usual.
+/// Like `git commit -a`, nothing stays staged unless a commit is made, so
+/// cancelling or a failure puts the index back.
+fn handle_commit_all(
+    opts: &mut GenerateOptions,
+    term: &Term,
+    config: &Config,
+    generation: &GenerationArgs,
+    deepen: bool,
+    output: &OutputArgs,
+) -> Result<(), CommitauraError> {
+    let Ok(before) = commitaura::__private::git::head_commit() else {
+        // Before the first commit there is no HEAD for a transaction to
+        // record, so only the index is put back when nothing was committed.
+        let index = commitaura::__private::git::index_tree()?;
+        commitaura::__private::git::stage_tracked()?;
+        let result = handle_commit(opts, term, config, generation, deepen, output);
+        if commitaura::__private::git::head_commit().is_err() {
+            if let Err(e) = commitaura::__private::git::read_tree(&index) {
+                log::error!("Could not restore the index: {}", e);
+            }
+        }
+        return result;
+    };
+    commitaura::__private::transaction::run("commit", || {
+        commitaura::__private::git::stage_tracked()?;
+        handle_commit(opts, term, config, generation, deepen, output)?;
+        Ok(commitaura::__private::git::head_commit()? != before)
+    })
+}
+
+fn handle_commit(
+    opts: &mut GenerateOptions,
+    term: &Term,
+    config: &Config,
+    generation: &GenerationArgs,
+    deepen: bool,
+    output: &OutputArgs,
+) -> Result<(), CommitauraError> {
+    if output.stdout {
+        if let Err(e) = StdoutSink::reserve() {
+            log::warn!("Could not keep stdout for the message alone: {}", e);
+        }
+    }
+    if config.ui.clear_screen && !ui::accessible() {
+        term.clear_screen()?;
+    }
     println!("{} {}\n", "🚀".bold().cyan(), style("Commitaura: Commit Assistant").bold().white().on_black());
-    println!("{}", "────────────────────────────────────────────".white());
+    ui::separator();
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(ProgressStyle::default_spinner()
-        .template("{spinner:.green} {msg}")?);
-    pb.set_message("Checking for staged changes...");
-    check_staged_changes()?;
-    pb.set_message("Fetching recent commit messages...");
-    let last_commits = get_last_commit_messages()?;
+    let pb = ui::spinner("green", "Checking for staged changes...")?;
+    // Amending with nothing staged just rewrites HEAD's message.
+    if !output.amend {
+        match check_staged_changes() {
+            Err(CommitauraError::NoStagedChanges) => {
+                if !pb.suspend(|| ui::stage::offer(term, &opts.model))? {
+                    return Err(CommitauraError::NoStagedChanges);
+                }
+            }
+            result => result?,
+        }
+    }
+    let mut state = RepoState::inspect()?;
+    if deepen && state.needs_deepening(5) && pb.suspend(|| confirm_deepen(&state, Some(5)))? {
+        state = RepoState::inspect()?;
+    }
+    ui::status(&pb, "Fetching recent commit messages...");
+    opts.timings.clear();
+    let pool_size = opts.history_selection.pool_size();
+    let history_pool = opts.timings.time("git log", || commitaura::__private::git::recent_commits(state.history_depth(pool_size)))?;
+    let last_commits = commitaura::__private::history::subjects(&history_pool, commitaura::__private::history::MIN_SUBJECTS);
     pb.finish_and_clear();
 
-    display_commit_messages(&last_commits);
+    if let Some(index) = commitaura::__private::git::index_override() {
+        println!("{} {}\n", "Using index:".dimmed(), index.display().to_string().dimmed());
+    }
+    for warning in state.warnings() {
+        println!("{} {}", "⚠".yellow(), warning.yellow());
+    }
+    if state.shallow && !deepen {
+        println!("{}", "  Run with --deepen to fetch more history.".dimmed());
+    }
+    if config.ui.show_history {
+        display_commit_messages(&last_commits);
+    }
+
+    if config.suggest_gitignore != Some(false) {
+        offer_gitignore(opts)?;
+    }
+
+    let mut amend = output.amend;
+    if amend && (!state.can_amend() || commitaura::__private::git::head_commit().is_err()) {
+        return Err(CommitauraError::ConfigError("--amend needs a commit to amend and no rebase, merge or other operation in progress".to_string()));
+    }
+    if amend {
+        opts.amending = Some(commitaura::__private::git::commit_message("HEAD")?);
+    } else if output.commits() && state.can_amend() {
+        if let Some(continuation) = commitaura::__private::continuation::detect_in_repo()? {
+            match ask_continuation(&continuation)? {
+                ContinuationChoice::Amend => {
+                    amend = true;
+                    opts.amending = Some(commitaura::__private::git::commit_message("HEAD")?);
+                }
+                ContinuationChoice::FollowUp => opts.follow_up_of = Some(continuation.previous_subject),
+                ContinuationChoice::Unrelated => {}
+            }
+        }
+    }
+    let sinks = output.sinks(amend)?;
+
+    let layout = commitaura::__private::sparse::detect()?;
+    let pathspecs = match sparse_scope(&layout, config)? {
+        Some(cone) => cone.pathspecs(),
+        None => Vec::new(),
+    };
+    let mut diff = opts.timings.time("git diff", || if amend { get_amend_diff_in(&pathspecs) } else { get_staged_diff_in(&pathspecs) })?;
+    if !check_guardrails(&diff, config)? {
+        println!("{}", style("Commit cancelled by user.").bold().yellow());
+        return Ok(());
+    }
+    for warning in commitaura::__private::lfs::staged_warnings(&diff)? {
+        println!("{} {}", "⚠".yellow(), warning.yellow());
+    }
+    if generation.select {
+        if ui::accessible() {
+            return Err(CommitauraError::ConfigError("--select draws a full-screen picker; it is not available with --a11y".to_string()));
+        }
+        match ui::select::select_diff(term, &diff, &opts.model)? {
+            Some(selected) => diff = selected,
+            None => {
+                println!("{}", style("Commit cancelled by user.").bold().yellow());
+                return Ok(());
+            }
+        }
+    }
+    if let Some(warning) = redaction_warning(&diff, opts, config) {
+        println!("{} {}", "⚠".yellow(), warning.yellow());
+    }
+    if let Some((routed, note)) = routed(&diff, opts, config)? {
+        *opts = routed;
+        println!("{} {}", "🧭".cyan(), note.dimmed());
+    }
+
+    // Exporting whole trees would download every missing blob of a partial clone.
+    if config.public_api && !amend && !layout.partial_clone {
+        let pb = ui::spinner("green", "Comparing public API with cargo public-api...")?;
+        // The report only sharpens the message, so a failing tool is not fatal.
+        match commitaura::__private::public_api::staged_changes(&commitaura::__private::sandbox::Sandbox::new(&config.sandbox)) {
+            Ok(Some(changes)) if !changes.is_empty() => opts.public_api_report = Some(commitaura::__private::public_api::report(&changes)),
+            Ok(_) => {}
+            Err(e) => log::warn!("Skipping public API report: {}", e),
+        }
+        pb.finish_and_clear();
+    }
+
+    if config.related_issues {
+        if let Some(repo) = commitaura::__private::git::remote_url("origin").as_deref().and_then(RemoteRepo::parse) {
+            let pb = ui::spinner("green", format!("Searching open issues on {}...", repo.host))?;
+            // Related issues only sharpen the message, so an unreachable tracker is not fatal.
+            let issues = forge::open_issues(&repo);
+            pb.finish_and_clear();
+            match issues {
+                Ok(issues) => {
+                    let related = commitaura::__private::related::rank(&issues, &commitaura::__private::related::terms(&diff), commitaura::__private::related::MAX_RELATED);
+                    if !related.is_empty() {
+                        let report = commitaura::__private::related::report(&related);
+                        println!("{}", "Possibly related open issues:".bold());
+                        println!("{}", report.dimmed());
+                        opts.related_issues = Some(report);
+                    }
+                }
+                Err(e) => log::warn!("Skipping related issues: {}", e),
+            }
+        }
+    }
+
+    opts.history = last_commits;
+    opts.history_pool = history_pool;
+    // When amending, HEAD is the commit being replaced.
+    opts.parent_message = parent_message(&diff, config, if amend { "HEAD^" } else { "HEAD" })?;
+    let results = match (&config.results, generation.with_results) {
+        (Some(results), true) => {
+            let sandbox = commitaura::__private::sandbox::Sandbox::new(&config.sandbox);
+            // Dropped unwaited when the commit is cancelled, which kills the command.
+            match sandbox.spawn(&results.command, Duration::from_secs(results.timeout_secs)) {
+                Ok(running) => Some(running),
+                Err(e) => {
+                    println!("{} {}", "⚠".yellow(), format!("Not running `{}`: {}", results.command, e).yellow());
+                    None
+                }
+            }
+        }
+        (None, true) => {
+            println!("{} {}", "⚠".yellow(), "--with-results needs a [results] command in .commitaura.toml; skipping.".yellow());
+            None
+        }
+        _ => None,
+    };
+    let candidates = match (&generation.candidates, config.candidates) {
+        (Some(spec), _) => spec.targets(&opts.model),
+        (None, Some(count)) if count > 1 => {
+            let max = commitaura::__private::candidates::MAX_COUNT;
+            if count > max {
+                log::warn!("Asking for {} candidates, not the configured {}; each is a request of its own", max, count);
+            }
+            Spec::Count(count.min(max)).targets(&opts.model)
+        }
+        _ => Vec::new(),
+    };
+    // A model of another provider is reached the way that provider would
+    // be if configured, except that `api_base` names the configured one's
+    // endpoint.
+    let candidates = candidates
+        .iter()
+        .map(|target| match target.provider {
+            Some(provider) if provider != opts.provider => {
+                let (api_key, api_base) = connection(provider, config, None, false)?;
+                Ok(opts.with_provider(provider, api_key, api_base, &target.model))
+            }
+            _ => Ok(opts.with_model(&target.model)),
+        })
+        .collect::<Result<Vec<_>, CommitauraError>>()?;
+    let notes_sink = generation.notes_sink();
+    let mut reviewer_notes = None;
+    let mut results = results;
+    let mut results_summary = None;
+    let mut regenerating = false;
+    let destinations: Vec<String> = sinks.iter().map(|s| s.describe()).collect();
+    // An amend also describes HEAD's changes, and --select only some of the staged ones.
+    let draft_key = if config.drafts != Some(false) && output.commits() && !amend && !generation.select { commitaura::__private::draft::current_key() } else { None };
+    let mut draft = match &draft_key {
+        Some(key) => offer_draft(key)?,
+        None => None,
+    };
+    let mut commit_message = 'generate: loop {
+        let resumed = draft.take();
+        let pb = if resumed.is_some() {
+            ui::spinner("magenta", "Resuming the saved draft...")?
+        } else if regenerating {
+            opts.timings.clear();
+            ui::spinner("magenta", "Regenerating commit message...")?
+        } else {
+            ui::spinner("magenta", "Generating commit message with AI magic...")?
+        };
+        let (mut commit_message, mut warnings) = if let Some(message) = &resumed {
+            pb.finish_and_clear();
+            (message.clone(), Vec::new())
+        } else if notes_sink.is_some() {
+            let generated = commitaura::__private::reviewer_notes::generate(&diff, opts);
+            pb.finish_and_clear();
+            let Some(generated) = or_regenerate(generated)? else {
+                regenerating = true;
+                continue 'generate;
+            };
+            reviewer_notes = Some(generated.notes);
+            let mut notes = Vec::new();
+            if config.pipeline.is_some() || config.cascade.is_some() {
+                notes.push("The configured pipeline or cascade is skipped: the message and reviewer notes come from one request.".to_string());
+            }
+            (generated.message, notes)
+        } else if generation.pair {
+            pb.finish_and_clear();
+            loop {
+                let pb = ui::spinner("magenta", "Generating a terse and a detailed message at once...")?;
+                let pair = commitaura::__private::pair::generate(&diff, opts);
+                pb.finish_and_clear();
+                let labels = commitaura::__private::pair::STYLES.map(commitaura::__private::pair::label);
+                match ui::pair::vote(term, &pair.candidates, &labels)? {
+                    Vote::Pick(i) => {
+                        match commitaura::__private::pair::vote(&pair, i, opts) {
+                            Ok(tally) => println!("{}", format!("Votes in this repository: terse {}, detailed {}.", tally.terse, tally.detailed).dimmed()),
+                            Err(e) => log::warn!("Could not record the vote: {}", e),
+                        }
+                        let message = pair.candidates.into_iter().nth(i).expect("voted for an existing candidate").message?;
+                        break (message, Vec::new());
+                    }
+                    Vote::Regenerate => continue,
+                    Vote::Cancel => {
+                        println!("{}", style("Commit cancelled: no message chosen.").bold().yellow());
+                        return Ok(());
+                    }
+                }
+            }
+        } else if (generation.full || config.full_message) && candidates.is_empty() {
+            let message = commitaura::__private::full_message::generate(&diff, opts);
+            pb.finish_and_clear();
+            let Some(message) = or_regenerate(message)? else {
+                regenerating = true;
+                continue 'generate;
+            };
+            let mut notes = Vec::new();
+            if config.pipeline.is_some() || config.cascade.is_some() {
+                notes.push("The configured pipeline or cascade is skipped: the full message comes from one structured request.".to_string());
+            }
+            (message, notes)
+        } else if candidates.is_empty() {
+            let generated = generate_configured(&diff, opts, config, !regenerating);
+            pb.finish_and_clear();
+            let Some(generated) = or_regenerate(generated)? else {
+                regenerating = true;
+                continue 'generate;
+            };
+            generated
+        } else {
+            pb.finish_and_clear();
+            loop {
+                let pb = ui::spinner("magenta", format!("Generating {} candidates at once...", candidates.len()))?;
+                let results = if generation.full || config.full_message {
+                    commitaura::__private::candidates::generate_each_with(&diff, candidates.clone(), commitaura::__private::full_message::generate)
+                } else {
+                    commitaura::__private::candidates::generate_each(&diff, candidates.clone())
+                };
+                pb.finish_and_clear();
+                match ui::candidates::pick(term, &results)? {
+                    Some(Picked::Message(message)) => break (message, Vec::new()),
+                    Some(Picked::RegenerateAll) => continue,
+                    None => {
+                        println!("{}", style("Commit cancelled: no candidate chosen.").bold().yellow());
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        // A resumed draft already has the results it was saved with.
+        if let (Some(running), Some(results)) = (results.take_if(|_| resumed.is_none()), &config.results) {
+            let pb = ui::spinner("magenta", format!("Waiting for `{}`...", results.command))?;
+            let outcome = running.wait();
+            pb.finish_and_clear();
+            match outcome {
+                Ok(outcome) => {
+                    let summary = commitaura::__private::results::summarize(&results.command, &outcome, commitaura::__private::results::load_last_counts());
+                    if let Some(counts) = commitaura::__private::results::test_counts(&outcome.output()) {
+                        if let Err(e) = commitaura::__private::results::save_last_counts(counts) {
+                            log::warn!("Could not save test counts: {}", e);
+                        }
+                    }
+                    results_summary = Some(summary);
+                }
+                Err(e) => warnings.push(format!("Could not run `{}`: {}", results.command, e)),
+            }
+        }
+        if let Some(summary) = &results_summary {
+            commit_message = commitaura::__private::message::add_paragraph(&commit_message, summary);
+        }
+        let stages = opts.timings.stages();
+        if let (Some(path), Some(id)) = (&opts.journal, opts.timings.journal_entry()) {
+            commitaura::__private::journal::set_timings(path, id, stages.clone());
+        }
+        if generation.timings {
+            println!("{}", "⏱ Timings:".bold().blue());
+            print!("{}", commitaura::__private::timings::report(&stages).dimmed());
+            ui::separator();
+        }
+        for warning in &warnings {
+            println!("{} {}", "⚠".yellow(), warning.yellow());
+        }
+
+        // Checking may shell out to a spellchecker, so it runs while the message is shown.
+        let checker = {
+            let (message, diff) = (commit_message.clone(), diff.clone());
+            let (terminology, spelling) = (config.terminology.clone(), config.spellcheck);
+            std::thread::spawn(move || commitaura::__private::spellcheck::check(&message, &diff, &terminology, spelling))
+        };
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(ProgressStyle::default_spinner()
-        .template("{spinner:.magenta} {msg}")?);
-    pb.set_message("Generating commit message with AI magic...");
-    let commit_message = generate_commit_message(openai, &last_commits)?;
+        save_draft(draft_key.as_ref(), &commit_message);
+        let heading = if resumed.is_some() { "📝 Resumed Draft:" } else { "✨ Suggested Commit Message:" };
+        println!("{}", heading.bold().green());
+        ui::separator();
+        println!("{}", commit_message.bold().white());
+        ui::separator();
+        if let Some(notes) = &reviewer_notes {
+            println!("{}", "📝 Reviewer Notes:".bold().blue());
+            println!("{}", notes.dimmed());
+            ui::separator();
+        }
+        if config.ui.show_style_check {
+            display_style_comparison(&opts.history, &commit_message);
+            ui::separator();
+        }
+
+        let issues = checker.join().unwrap_or_default();
+        if !issues.is_empty() {
+            let reviewed = ui::review::review_issues(term, &commit_message, issues)?;
+            if reviewed != commit_message {
+                commit_message = reviewed;
+                println!("{}", "✨ Corrected Commit Message:".bold().green());
+                println!("{}", commit_message.bold().white());
+                ui::separator();
+            }
+        }
+        if !generation.edit {
+            let split = ui::subject::offer_split(term, &commit_message)?;
+            if split != commit_message {
+                commit_message = split;
+                println!("{}", "✨ Split Commit Message:".bold().green());
+                println!("{}", commit_message.bold().white());
+                ui::separator();
+            }
+        }
+
+        loop {
+            // Corrections and edits are kept too, in case the editor or the session dies.
+            save_draft(draft_key.as_ref(), &commit_message);
+            // With --edit the saved message is final, as with `git commit -e`.
+            let action = if generation.edit { Action::Edit } else { ui::confirm::ask(&destinations)? };
+            match action {
+                Action::Accept => break 'generate commit_message,
+                Action::Edit => {
+                    commit_message = commitaura::__private::git::edit_text(&commit_message, &diff)?;
+                    if commit_message.is_empty() {
+                        println!("{}", style("Commit cancelled: empty message.").bold().yellow());
+                        return Ok(());
+                    }
+                    if generation.edit {
+                        break 'generate commit_message;
+                    }
+                    println!("{}", "✨ Edited Commit Message:".bold().green());
+                    println!("{}", commit_message.bold().white());
+                    ui::separator();
+                }
+                Action::Regenerate => {
+                    regenerating = true;
+                    continue 'generate;
+                }
+                Action::Cancel => {
+                    println!("{}", style("Commit cancelled by user.").bold().yellow());
+                    return Ok(());
+                }
+            }
+        }
+    };
+    // Fail before opening issues or writing a fragment for a commit that cannot happen.
+    if output.commits() {
+        commitaura::__private::git::ensure_writable("commit")?;
+    }
+    let transcript = match (&opts.journal, opts.timings.journal_entry()) {
+        (Some(path), Some(id)) if config.transcript_trailer && output.commits() => commitaura::__private::journal::find(path, id).ok(),
+        _ => None,
+    };
+    if let Some(entry) = &transcript {
+        commit_message = commitaura::__private::journal::with_trailer(&commit_message, &entry.key());
+    }
+    if output.commits() && config.todo_issues != Some(false) {
+        commit_message = offer_todo_issues(&diff, opts, &commit_message)?;
+    }
+    if output.commits() {
+        add_fragment(&commit_message, &config.fragments)?;
+    }
+    // Where an undo goes back to; `None` on an unborn branch.
+    let before = commitaura::__private::git::head_commit().ok();
+    for sink in &sinks {
+        let pb = ui::spinner("cyan", format!("Sending to {}...", sink.describe()))?;
+        sink.write(&commit_message)?;
+        ui::finish(&pb, style(format!("✅ {}: done", sink.describe())).bold().green().to_string());
+    }
+    if let Some((branch, _)) = &draft_key {
+        if let Err(e) = commitaura::__private::draft::drafts_path().and_then(|path| commitaura::__private::draft::discard(&path, branch)) {
+            log::warn!("Could not discard the draft: {}", e);
+        }
+    }
+    if let (Some(sink), Some(notes)) = (&notes_sink, &reviewer_notes) {
+        sink.write(notes)?;
+        println!("{}", style(format!("✅ Reviewer notes: {}", sink.describe())).bold().green());
+    }
+    if let Some(entry) = &transcript {
+        // The commit is made; a missing note only costs teammates the transcript.
+        if let Err(e) = commitaura::__private::git::add_note(commitaura::__private::journal::NOTES_REF, "HEAD", &commitaura::__private::journal::to_note(entry)) {
+            println!("{} {}", "⚠".yellow(), format!("Could not attach the transcript as a note: {}", e).yellow());
+        }
+    }
+    if destinations == ["commit"] || destinations == ["amend"] {
+        offer_post_commit_actions(opts, config, amend, before.as_deref(), state.on_branch())?;
+    }
+    Ok(())
+}
+
+/// The draft saved under `key` for the staged changes, if the user wants
+/// to resume it instead of generating a new message.
+fn offer_draft((branch, tree): &(String, String)) -> Result<Option<String>, CommitauraError> {
+    let Some(draft) = commitaura::__private::draft::find(&commitaura::__private::draft::drafts_path()?, branch, tree) else {
+        return Ok(None);
+    };
+    println!("{} {}", "📝".bold().blue(), format!("A draft for these staged changes was saved on {} {}:", branch, draft.age()).bold().white());
+    println!("   {}", draft.message.lines().next().unwrap_or("").italic().white());
+    let resume = ui::confirm("Resume it instead of generating a new message?", true)?;
+    println!();
+    Ok(resume.then_some(draft.message))
+}
+
+/// Keep `message` as the draft under `key`. A draft only saves a later
+/// generation, so failing to write one is not fatal.
+fn save_draft(key: Option<&(String, String)>, message: &str) {
+    let Some((branch, tree)) = key else {
+        return;
+    };
+    if let Err(e) = commitaura::__private::draft::drafts_path().and_then(|path| commitaura::__private::draft::save(&path, branch, tree, message)) {
+        log::warn!("Could not save the draft: {}", e);
+    }
+}
+
+/// When untracked build artifacts are lying around, offer model-suggested
+/// `.gitignore` entries, shown as a diff to approve.
+fn offer_gitignore(opts: &GenerateOptions) -> Result<(), CommitauraError> {
+    let untracked = commitaura::__private::git::untracked_paths()?;
+    if !commitaura::__private::gitignore::is_noisy(&untracked) {
+        return Ok(());
+    }
+    println!("{} {}", "🧹".bold().yellow(), format!("{} untracked path(s), some look like build artifacts.", untracked.len()).bold().white());
+    if !ui::confirm("Suggest .gitignore entries for them?", true)? {
+        println!();
+        return Ok(());
+    }
+
+    let path = commitaura::__private::git::toplevel()?.join(".gitignore");
+    let existing = std::fs::read_to_string(&path).unwrap_or_default();
+    let pb = ui::spinner("magenta", "Asking for .gitignore entries...")?;
+    let entries = commitaura::__private::gitignore::suggest(&untracked, &existing, opts)?;
     pb.finish_and_clear();
+    if entries.is_empty() {
+        println!("{}\n", "No entries suggested.".dimmed());
+        return Ok(());
+    }
 
-    // Draw a box around the commit message for clarity and style
-    let border = "┌".to_string() + &"─".repeat(48) + "┐";
-    let bottom = "└".to_string() + &"─".repeat(48) + "┘";
-    println!("{}", "✨ Suggested Commit Message:".bold().green());
-    println!("{}", "────────────────────────────────────────────".white());
-    println!("{}", commit_message.bold().white());
-    println!("{}", "────────────────────────────────────────────".white());
-    println!("{}", "────────────────────────────────────────────".white());
-
-    if Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt(style("Proceed with this commit message?").cyan().to_string())
-        .default(true)
-        .interact()? {
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(ProgressStyle::default_spinner().template("{spinner:.cyan} {msg}")?);
-        pb.set_message("Committing changes...");
-        pb.enable_steady_tick(Duration::from_millis(80));
-        perform_git_commit(&commit_message)?;
-        pb.finish_with_message(style("✅ Commit successful!").bold().green().to_string());
+    ui::separator();
+    for line in commitaura::__private::gitignore::render_diff(&existing, &entries).lines() {
+        if line.starts_with('+') && !line.starts_with("+++") {
+            println!("{}", line.green());
+        } else {
+            println!("{}", line.dimmed());
+        }
+    }
+    ui::separator();
+    if ui::confirm("Add these entries to .gitignore?", true)? {
+        commitaura::__private::gitignore::append(&path, &entries)?;
+        println!("{}\n", style("✅ Updated .gitignore (not staged)").bold().green());
     } else {
-        println!("{}", style("Commit cancelled by user.").bold().yellow());
+        println!();
     }
-    println!("\n{}", "Thank you for using Commitaura!".italic().white());
     Ok(())
 }
 
-fn check_staged_changes() -> Result<(), CommitauraError> {
-    let output = std::process::Command::new("git")
-        .args(&["diff", "--staged", "--quiet"])
-        .status()
-        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+/// Offer tracking issues for the TODO and FIXME comments `diff` adds, and
+/// return `message` with a `Refs` line for the ones opened.
+fn offer_todo_issues(diff: &str, opts: &GenerateOptions, message: &str) -> Result<String, CommitauraError> {
+    let found = commitaura::__private::todos::find(&commitaura::diff::parse(diff));
+    if found.is_empty() {
+        return Ok(message.to_string());
+    }
+    let Some(repo) = commitaura::__private::git::remote_url("origin").as_deref().and_then(RemoteRepo::parse) else {
+        return Ok(message.to_string());
+    };
+    let issues = ui::todos::offer_issues(&found, &repo, opts)?;
+    Ok(commitaura::__private::todos::append_refs(message, &issues))
+}
+
+/// Write and stage the changelog fragment for `message`, unless fragments
+/// are not configured or one is already staged.
+fn add_fragment(message: &str, config: &FragmentsConfig) -> Result<(), CommitauraError> {
+    let Some(fragment) = commitaura::__private::fragments::for_message(message, config)? else {
+        return Ok(());
+    };
+    if commitaura::__private::fragments::already_staged(&commitaura::__private::git::staged_paths()?, config) {
+        return Ok(());
+    }
+    let root = commitaura::__private::git::toplevel()?;
+    commitaura::__private::fragments::write(&fragment, &root)?;
+    // git resolves pathspecs against the working directory, not the root.
+    commitaura::__private::git::stage(&[root.join(&fragment.path).to_string_lossy().into_owned()])?;
+    println!("{} {}", "📰 Staged changelog fragment".bold().green(), fragment.path.display().to_string().dimmed());
+    Ok(())
+}
+
+/// With `parent_message` configured, the full message of the last commit
+/// reachable from `rev` that touched the files in `diff`.
+fn parent_message(diff: &str, config: &Config, rev: &str) -> Result<Option<String>, CommitauraError> {
+    if !config.parent_message {
+        return Ok(None);
+    }
+    let paths: Vec<String> = commitaura::diff::parse(diff).into_iter().map(|f| f.path).collect();
+    commitaura::__private::git::last_commit_touching(rev, &paths)
+}
+
+/// Generate the message the way the repository is configured to: through a
+/// custom pipeline, a model cascade, or a single request. Also returns
+/// warnings to show with the message. Without `shortcuts`, as when the
+/// user asked to regenerate, the model is always asked: no trivial-change
+/// template or pre-generated message is used.
+fn generate_configured(diff: &str, opts: &GenerateOptions, config: &Config, shortcuts: bool) -> Result<(String, Vec<String>), CommitauraError> {
+    // Outside a repository there is no merge to describe.
+    if let Some(state) = RepoState::inspect().ok().filter(|_| shortcuts) {
+        if let Some(merge) = commitaura::__private::merge::detect(&state, diff)? {
+            let head = commitaura::__private::git::symbolic_head();
+            let branch = head.as_deref().map(|head| head.strip_prefix("refs/heads/").unwrap_or(head));
+            let note = format!("Written locally ({}); choose Regenerate to ask the model.", merge.describe());
+            return Ok((commitaura::__private::message::assemble(&merge.message(branch), opts), vec![note]));
+        }
+    }
+    if config.trivial.enabled && shortcuts {
+        if let Some(trivial) = commitaura::__private::trivial::classify(diff) {
+            if let Some(message) = commitaura::__private::trivial::message(&trivial, &config.trivial)? {
+                let note = format!("Written locally for a trivial change ({}); choose Regenerate to ask the model.", trivial.kind());
+                return Ok((commitaura::__private::message::assemble(&message, opts), vec![note]));
+            }
+        }
+    }
+    if let Some(message) = commitaura::__private::pregen::cached(diff, opts).filter(|_| shortcuts) {
+        log::info!("Using the message pre-generated by commitaura watch");
+        return Ok((message, Vec::new()));
+    }
+    if let Some(pipeline) = &config.pipeline {
+        let mut registry = commitaura::pipeline::Registry::builtin(pipeline);
+        commitaura::plugin::register_plugins(&mut registry, &config.plugins)?;
+        let stages = if pipeline.stages.is_empty() {
+            commitaura::pipeline::DEFAULT_STAGES.iter().map(|s| s.to_string()).collect()
+        } else {
+            pipeline.stages.clone()
+        };
+        let ctx = commitaura::pipeline::Pipeline::new(&registry, &stages)?.run(diff, opts)?;
+        return Ok((ctx.message.unwrap_or_default(), ctx.warnings));
+    }
+    if let Some(models) = &config.cascade {
+        let outcome = commitaura::__private::cascade::generate(diff, opts, models)?;
+        let mut notes = Vec::new();
+        if !outcome.problems.is_empty() {
+            notes.push(format!("Draft by {} reviewed by {}: {}", models[0], outcome.model, outcome.problems.join("; ")));
+        }
+        return Ok((outcome.message, notes));
+    }
+    let team_cache = team_cache_entry(diff, opts, config).filter(|_| shortcuts);
+    if let Some((client, key)) = &team_cache {
+        // The team cache is only a shortcut, so an unreachable server is not fatal.
+        match client.get(key) {
+            Ok(Some(message)) => {
+                let note = "Taken from the team cache; choose Regenerate to ask the model.".to_string();
+                return Ok((message, vec![note]));
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Skipping the team cache: {}", e),
+        }
+    }
+    let message = generate_message(diff, opts)?;
+    if let Some((client, key)) = &team_cache {
+        if let Err(e) = client.put(key, &message) {
+            log::warn!("Could not share the message through the team cache: {}", e);
+        }
+    }
+    Ok((message, Vec::new()))
+}
+
+/// `result`, or `None` when the reply looked steered by the changes and the
+/// user chose to regenerate instead of stopping.
+fn or_regenerate<T>(result: Result<T, CommitauraError>) -> Result<Option<T>, CommitauraError> {
+    match result {
+        Err(CommitauraError::SuspiciousReply(problem)) => {
+            println!("{} The reply does not look like a commit message: {}.", "⚠".yellow(), problem);
+            if ui::confirm("Regenerate it?", true)? {
+                Ok(None)
+            } else {
+                Err(CommitauraError::SuspiciousReply(problem))
+            }
+        }
+        result => result.map(Some),
+    }
+}
+
+/// The team cache client and key for `diff`, if a cache is configured and
+/// the diff only changes lock files, generated files or vendored packages.
+fn team_cache_entry(diff: &str, opts: &GenerateOptions, config: &Config) -> Option<(commitaura::__private::team_cache::Client, String)> {
+    let url = std::env::var(commitaura::__private::team_cache::URL_ENV).ok().filter(|u| !u.is_empty()).or_else(|| config.team_cache.url.clone())?;
+    if !commitaura::__private::team_cache::shareable(diff, &opts.generated.clone().unwrap_or_default()) {
+        return None;
+    }
+    let token = std::env::var(commitaura::__private::team_cache::TOKEN_ENV).ok().filter(|t| !t.is_empty());
+    let client = match commitaura::__private::team_cache::Client::new(&url, token) {
+        Ok(client) => client,
+        Err(e) => {
+            log::warn!("Not using the team cache: {}", e);
+            return None;
+        }
+    };
+    Some((client, commitaura::__private::team_cache::key(diff, opts)))
+}
+
+/// `commitaura cache-server`: answer team cache requests until killed.
+fn handle_cache_server(listen: &str, store: Option<&Path>) -> Result<(), CommitauraError> {
+    let token = std::env::var(commitaura::__private::team_cache::TOKEN_ENV).ok().filter(|t| !t.is_empty());
+    let listener = std::net::TcpListener::bind(listen)?;
+    let address = listener.local_addr()?;
+    if token.is_none() && !address.ip().is_loopback() {
+        return Err(CommitauraError::ConfigError(format!(
+            "Set {} before listening on {}; without a token anyone who can reach it could read and write the cache",
+            commitaura::__private::team_cache::TOKEN_ENV,
+            address
+        )));
+    }
+    let server = commitaura::__private::team_cache::Server::open(store.map(Path::to_path_buf), token)?;
+    println!("{} {} {}", style("🗄  Serving the team cache on").bold().green(), format!("http://{}", address).white(), format!("({} entries)", server.len()).dimmed());
+    server.serve(listener)
+}
+
+/// Poll the index and, once the staged changes stop changing, generate a
+/// message for them ahead of `commitaura commit`, within the `[watch]` budget.
+fn handle_watch(opts: &mut GenerateOptions, config: &Config) -> Result<(), CommitauraError> {
+    use commitaura::__private::pregen::{Outcome, RateLimiter};
+    let watch = &config.watch;
+    if !watch.pregenerate {
+        println!("{}", style("Nothing to watch for: set pregenerate = true under [watch] in .commitaura.toml.").bold().yellow());
+        return Ok(());
+    }
+    println!("{} {}", "👀".bold().cyan(), style("Commitaura: watching staged changes (Ctrl-C to stop)").bold().white());
+    ui::separator();
+
+    let mut limiter = RateLimiter::new(Duration::from_secs(watch.min_interval_secs), watch.max_per_hour);
+    let (mut previous, mut handled) = (String::new(), String::new());
+    // The changes whose generation failed transiently, when to try them
+    // again, and how long that wait was.
+    let mut retry: Option<(String, std::time::Instant, Duration)> = None;
+    loop {
+        std::thread::sleep(Duration::from_secs(watch.poll_secs.max(1)));
+        let diff = get_staged_diff_in(&[])?;
+        // Wait for the index to settle, so a burst of `git add`s costs one request.
+        let settled = diff == previous;
+        previous = diff.clone();
+        if !settled || diff.trim().is_empty() || diff == handled {
+            continue;
+        }
+        let now = std::time::Instant::now();
+        if retry.as_ref().is_some_and(|(failed, at, _)| *failed == diff && now < *at) {
+            continue;
+        }
+        if !limiter.allows(now) {
+            continue;
+        }
+        // The same history `commit` would send, so the prompts match.
+        let state = RepoState::inspect()?;
+        opts.history_pool = commitaura::__private::git::recent_commits(state.history_depth(opts.history_selection.pool_size()))?;
+        opts.history = commitaura::__private::history::subjects(&opts.history_pool, commitaura::__private::history::MIN_SUBJECTS);
+        opts.parent_message = parent_message(&diff, config, "HEAD")?;
+        match commitaura::__private::pregen::pregenerate(&diff, opts, watch.max_prompt_tokens) {
+            Ok(Outcome::Cached) => {}
+            Ok(Outcome::TooLarge(tokens)) => {
+                println!("{}", format!("Skipped: the prompt is about {} tokens, over max_prompt_tokens ({}).", tokens, watch.max_prompt_tokens).dimmed());
+            }
+            Ok(Outcome::Generated(message)) => {
+                limiter.record(now);
+                println!("{} {}", "✨ Ready:".bold().green(), message.lines().next().unwrap_or_default().white());
+            }
+            Err(e) if commitaura::__private::http::is_transient(&e) => {
+                limiter.record(now);
+                let last_delay = retry.take().filter(|(failed, _, _)| *failed == diff).map(|(_, _, delay)| delay);
+                let delay = commitaura::__private::pregen::retry_delay(last_delay);
+                println!("{} {}", "⚠".yellow(), format!("Pre-generation failed: {}; retrying in {}s.", e, delay.as_secs()).yellow());
+                retry = Some((diff, now + delay, delay));
+                continue;
+            }
+            Err(e) => {
+                limiter.record(now);
+                println!("{} {}", "⚠".yellow(), format!("Pre-generation failed: {}", e).yellow());
+            }
+        }
+        retry = None;
+        handled = diff;
+    }
+}
+
+/// The sparse cone to restrict analysis to, if `sparse_cone_only` is set and
+/// a cone-mode sparse checkout is active. Staged paths left out are listed.
+fn sparse_scope(layout: &commitaura::__private::sparse::Layout, config: &Config) -> Result<Option<commitaura::__private::sparse::SparseCone>, CommitauraError> {
+    if !config.sparse_cone_only {
+        if layout.partial_clone && layout.cone.is_some() {
+            println!("{}\n", "Partial clone detected; set sparse_cone_only = true in .commitaura.toml to keep analysis inside your sparse cone.".dimmed());
+        }
+        return Ok(None);
+    }
+    if layout.partial_clone {
+        commitaura::__private::git::disable_lazy_fetch();
+    }
+    let Some(cone) = &layout.cone else {
+        if layout.non_cone_sparse {
+            println!("{}\n", "⚠ sparse_cone_only needs a cone-mode sparse checkout; analyzing all staged paths.".yellow());
+        }
+        return Ok(None);
+    };
+    let staged = commitaura::__private::git::staged_paths()?;
+    let outside = cone.outside(&staged);
+    if !outside.is_empty() {
+        println!("{} {}", "⚠".yellow(), "Outside the sparse cone, not sent to the model:".yellow());
+        for path in &outside {
+            println!("   {}", path.dimmed());
+        }
+        println!();
+    }
+    Ok(Some(cone.clone()))
+}
+
+/// Warn about conflict markers and debugging leftovers in the added lines.
+/// Returns whether to carry on; with `guard.block` set, findings are an error.
+fn check_guardrails(diff: &str, config: &Config) -> Result<bool, CommitauraError> {
+    let patterns = commitaura::__private::guard::patterns(config.guard.patterns.as_deref());
+    let findings = commitaura::__private::guard::scan(&commitaura::diff::parse(diff), &patterns);
+    if findings.is_empty() {
+        return Ok(true);
+    }
+
+    println!("{} {}", "🛑".bold().red(), "Suspicious staged lines:".bold().white());
+    ui::separator();
+    for finding in &findings {
+        let location = match finding.line {
+            Some(line) => format!("{}:{}", finding.path, line),
+            None => finding.path.clone(),
+        };
+        println!("{} {} {}", location.yellow().bold(), finding.text.white(), format!("({})", finding.pattern).dimmed());
+    }
+    ui::separator();
+    println!();
+
+    if config.guard.block {
+        return Err(CommitauraError::GuardrailViolation(findings.len()));
+    }
+    ui::confirm("Continue anyway?", false)
+}
+
+enum ContinuationChoice {
+    Amend,
+    FollowUp,
+    Unrelated,
+}
+
+/// The staged work looks like more of HEAD's work; ask how to record it.
+fn ask_continuation(continuation: &Continuation) -> Result<ContinuationChoice, CommitauraError> {
+    println!(
+        "{} {} {}",
+        "🔗".bold().blue(),
+        "These changes look like a continuation of".bold().white(),
+        continuation.previous_subject.italic().white()
+    );
+    println!(
+        "   {}",
+        format!(
+            "{} shared file(s), committed {} min ago",
+            continuation.shared_paths.len(),
+            continuation.seconds_since / 60
+        )
+        .dimmed()
+    );
+    let amend_label = if continuation.published {
+        "Amend the previous commit (it is already pushed; this rewrites published history)"
+    } else {
+        "Amend the previous commit"
+    };
+    let choice = ui::select("How should this be recorded?", &[amend_label, "New commit phrased as a follow-up", "New, unrelated commit"], if continuation.published { 1 } else { 0 })?.unwrap_or(2);
+    Ok(match choice {
+        0 => ContinuationChoice::Amend,
+        1 => ContinuationChoice::FollowUp,
+        _ => ContinuationChoice::Unrelated,
+    })
+}
+
+/// After a commit, offer the configured follow-ups: fixing up or undoing the
+/// commit locally, and opening a PR, pushing or running CI through the forge
+/// CLI matching the `origin` remote. Off a branch only the local ones apply.
+/// `before` is the commit HEAD was at before committing or amending.
+fn offer_post_commit_actions(opts: &GenerateOptions, config: &Config, amended: bool, before: Option<&str>, on_branch: bool) -> Result<(), CommitauraError> {
+    let repo = commitaura::__private::git::remote_url("origin").as_deref().and_then(RemoteRepo::parse);
+    let actions: Vec<PostCommitAction> = forge::parse_actions(config.post_commit.as_deref())?
+        .into_iter()
+        .filter(|a| (repo.is_some() && on_branch) || !a.needs_remote())
+        .collect();
+    if actions.is_empty() {
+        return Ok(());
+    }
+    let mut items: Vec<String> = actions.iter().map(|a| a.label(repo.as_ref().map(|r| r.forge))).collect();
+    items.push("Done".to_string());
+
+    loop {
+        let choice = ui::select("What next?", &items, items.len() - 1)?.unwrap_or(items.len() - 1);
+        let Some(action) = actions.get(choice) else {
+            return Ok(());
+        };
+        match run_post_commit_action(action, repo.as_ref(), opts, amended, before) {
+            // Nothing else applies once the commit is gone.
+            Ok(()) if *action == PostCommitAction::Undo => return Ok(()),
+            Ok(()) => {}
+            Err(e) => println!("{} {}", "❌".red(), style(e.to_string()).red()),
+        }
+    }
+}
+
+fn run_post_commit_action(
+    action: &PostCommitAction,
+    repo: Option<&RemoteRepo>,
+    opts: &GenerateOptions,
+    amended: bool,
+    before: Option<&str>,
+) -> Result<(), CommitauraError> {
+    let remote = || {
+        repo.ok_or_else(|| CommitauraError::GitOperationFailed("origin is not a GitHub, GitLab or Bitbucket remote".to_string()))
+    };
+    match action {
+        PostCommitAction::Amend => commitaura::__private::git::edit_message(),
+        PostCommitAction::MarkWip => {
+            let message = commitaura::__private::git::commit_message("HEAD")?;
+            if !message.starts_with("WIP") {
+                commitaura::__private::git::amend_message(&format!("WIP: {}", message))?;
+            }
+            println!("{}", style("✅ Marked as work in progress").bold().green());
+            Ok(())
+        }
+        PostCommitAction::Undo => {
+            commitaura::__private::git::undo_commit(before)?;
+            let undone = if amended { "Undid the amend" } else { "Undid the commit" };
+            println!("{}", style(format!("✅ {}; changes are still staged", undone)).bold().green());
+            Ok(())
+        }
+        PostCommitAction::PushToPr => {
+            remote()?;
+            commitaura::__private::git::push_current_branch("origin")
+        }
+        PostCommitAction::TriggerWorkflow(workflow) => {
+            let forge = remote()?.forge;
+            let branch = commitaura::__private::git::current_branch()?;
+            forge::run_cli(forge, &forge::workflow_args(forge, workflow, &branch))
+        }
+        PostCommitAction::CreatePr => {
+            let repo = remote()?;
+            let forge = repo.forge;
+            let base = commitaura::__private::git::default_branch("origin");
+            let pb = ui::spinner("magenta", format!("Writing {} description...", forge.pr_noun()))?;
+            let range = commitaura::__private::release::CommitRange::branch(&base)?;
+            let pr = commitaura::__private::pr::generate_pr_description(&range.diff, &range.commits, forge, opts)?;
+            pb.finish_and_clear();
+
+            println!("{}", pr.title.bold().white());
+            ui::separator();
+            println!("{}", pr.body.white());
+            ui::separator();
+            if !ui::confirm(&format!("Open this {}?", forge.pr_noun()), true)? {
+                return Ok(());
+            }
+            commitaura::__private::git::push_current_branch("origin")?;
+            let source = commitaura::__private::git::current_branch()?;
+            let target = base.split_once('/').map(|(_, b)| b).unwrap_or(&base);
+            if let Some(url) = forge::create_pr(repo, &pr.title, &pr.body, &source, target)? {
+                println!("{} {}", style(format!("✅ Opened {}", forge.pr_noun())).bold().green(), url);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn handle_reword(opts: &mut GenerateOptions, term: &Term, ui: &UiConfig, base: &str, fold_fixups: bool, review_file: bool) -> Result<(), CommitauraError> {
+    if ui.clear_screen && !ui::accessible() {
+        term.clear_screen()?;
+    }
+    println!("{} {}\n", "✏️".bold().cyan(), style("Commitaura: Reword Commits").bold().white().on_black());
+    ui::separator();
+    // Folding fixups rebases before any message is written, so a later
+    // failure or a cancel must undo the rebase too.
+    commitaura::__private::transaction::run("reword", || reword_steps(opts, base, fold_fixups, review_file))
+}
+
+/// Fold, regenerate and rewrite for [`handle_reword`]. Returns whether to
+/// keep the result.
+fn reword_steps(opts: &mut GenerateOptions, base: &str, fold_fixups: bool, review_file: bool) -> Result<bool, CommitauraError> {
+    if fold_fixups {
+        let pb = ui::spinner("green", "Folding fixup!/squash! commits...")?;
+        commitaura::__private::git::autosquash(base)?;
+        pb.finish_and_clear();
+    }
+
+    let commits = commitaura::__private::git::list_commits(base)?;
+    if commits.is_empty() {
+        println!("{}", style(format!("No commits in {}..HEAD.", base)).bold().yellow());
+        return Ok(true);
+    }
+    let actions = commitaura::__private::reword::plan(&commits);
+
+    let pb = if ui::accessible() { ProgressBar::hidden() } else { ProgressBar::new(commits.len() as u64) };
+    pb.set_style(ProgressStyle::default_bar().template("{spinner:.magenta} [{bar:30.magenta}] {pos}/{len} {msg}")?);
+    let mut rewritten = Vec::with_capacity(commits.len());
+    for (i, (commit, action)) in commits.iter().zip(&actions).enumerate() {
+        if ui::accessible() {
+            println!("Commit {} of {}: {}", i + 1, commits.len(), commit.subject());
+        }
+        pb.set_message(commit.subject().to_string());
+        let message = match action {
+            RewordAction::Regenerate => {
+                let diff = commitaura::__private::git::commit_diff(&commit.hash)?;
+                generate_message(&diff, opts)?
+            }
+            RewordAction::KeepAutosquash(_) | RewordAction::KeepTarget => commit.message.clone(),
+        };
+        // Later commits see the new subjects as their history context.
+        opts.history = format!("{}\n{}", message.lines().next().unwrap_or(""), opts.history);
+        rewritten.push((commit.clone(), message));
+        pb.inc(1);
+    }
+    pb.finish_and_clear();
+
+    if review_file {
+        return review_rewrites(base, rewritten, &actions);
+    }
+
+    for ((commit, message), action) in rewritten.iter().zip(&actions) {
+        println!("{} {}", commit.hash[..7].yellow().bold(), commit.subject().white().italic());
+        match action {
+            RewordAction::Regenerate => println!("        {} {}", "→".green(), message.lines().next().unwrap_or("").green().bold()),
+            RewordAction::KeepAutosquash(kind) => println!("        {}", format!("kept: {} commit", kind.marker()).dimmed()),
+            RewordAction::KeepTarget => println!("        {}", "kept: target of a fixup!/squash! commit".dimmed()),
+        }
+    }
+    ui::separator();
 
-    if output.success() {
-        Err(CommitauraError::NoStagedChanges)
+    if ui::confirm("Rewrite these commits?", false)? {
+        let head = commitaura::__private::git::rewrite_messages(base, &rewritten)?;
+        println!("{} {}", style("✅ Rewrote history, HEAD is now").bold().green(), &head[..7]);
+        Ok(true)
     } else {
-        Ok(())
+        println!("{}", style("Reword cancelled by user.").bold().yellow());
+        Ok(false)
     }
 }
 
-fn perform_git_commit(message: &str) -> Result<(), CommitauraError> {
-    let status = std::process::Command::new("git")
-        .args(&["commit", "-m", message])
-        .status()
-        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+/// Hand every proposal to the user's editor in one file, then apply what
+/// comes back in a single history rewrite. Returns false if the review was
+/// cancelled.
+fn review_rewrites(base: &str, rewritten: Vec<(commitaura::__private::git::CommitInfo, String)>, actions: &[RewordAction]) -> Result<bool, CommitauraError> {
+    let (commits, proposed): (Vec<_>, Vec<_>) = rewritten.into_iter().unzip();
+    let path = commitaura::__private::git::git_dir()?.join("commitaura").join("REWORD_REVIEW");
+    std::fs::create_dir_all(path.parent().expect("review file has a parent"))?;
+    std::fs::write(&path, commitaura::__private::reword::write_review(base, &commits, actions, &proposed))?;
 
-    if status.success() {
-        Ok(())
+    println!("{} {}", "📝".bold().blue(), format!("Opening {} proposals in your editor...", commits.len()).bold().white());
+    commitaura::__private::git::run_editor(&path)?;
+    let edited = std::fs::read_to_string(&path)?;
+    let Some(messages) = commitaura::__private::reword::parse_review(&edited, &commits)? else {
+        println!("{}", style("Reword cancelled: the review file is empty.").bold().yellow());
+        return Ok(false);
+    };
+    std::fs::remove_file(&path)?;
+
+    let changed = commits.iter().zip(&messages).filter(|(c, m)| c.message != **m).count();
+    if changed == 0 {
+        println!("{}", style("No messages changed; history left as it is.").bold().yellow());
+        return Ok(true);
+    }
+    let head = commitaura::__private::git::rewrite_messages(base, &commits.into_iter().zip(messages).collect::<Vec<_>>())?;
+    println!("{} {} {}", style(format!("✅ Reworded {} commit(s),", changed)).bold().green(), style("HEAD is now").bold().green(), &head[..7]);
+    Ok(true)
+}
+
+/// Generate a message for the staged changes without asking anything or
+/// changing the repository. Also returns warnings to show with it.
+fn generate_unattended(opts: &mut GenerateOptions, config: &Config, generation: &GenerationArgs) -> Result<(String, Vec<String>), CommitauraError> {
+    commitaura::__private::git::enable_read_only();
+    check_staged_changes()?;
+    let state = RepoState::inspect()?;
+    opts.history_pool = commitaura::__private::git::recent_commits(state.history_depth(opts.history_selection.pool_size()))?;
+    opts.history = commitaura::__private::history::subjects(&opts.history_pool, commitaura::__private::history::MIN_SUBJECTS);
+    let diff = get_staged_diff_in(&[])?;
+    opts.parent_message = parent_message(&diff, config, "HEAD")?;
+    generate_for(&diff, opts, config, generation, true)
+}
+
+/// Generate a message for `diff` with the options already set up. See
+/// [`generate_configured`] for `shortcuts`.
+fn generate_for(diff: &str, opts: &GenerateOptions, config: &Config, generation: &GenerationArgs, shortcuts: bool) -> Result<(String, Vec<String>), CommitauraError> {
+    // Routed per diff, so each group of a split gets the model its size calls for.
+    let routed = routed(diff, opts, config)?;
+    let opts = match &routed {
+        Some((routed, note)) => {
+            log::info!("{}", note);
+            routed
+        }
+        None => opts,
+    };
+    let (message, warnings) = if generation.full || config.full_message {
+        (commitaura::__private::full_message::generate(diff, opts)?, Vec::new())
     } else {
-        Err(CommitauraError::GitOperationFailed(
-            "Git commit failed".to_string(),
-        ))
+        generate_configured(diff, opts, config, shortcuts)?
+    };
+    let warnings = redaction_warning(diff, opts, config).into_iter().chain(warnings).collect();
+    Ok((message, warnings))
+}
+
+/// What the redactor will mask in `diff` before it is sent, if anything.
+/// A pipeline's `redact` stage reports that itself.
+fn redaction_warning(diff: &str, opts: &GenerateOptions, config: &Config) -> Option<String> {
+    if config.pipeline.as_ref().is_some_and(|p| p.stages.iter().any(|s| s == "redact")) {
+        return None;
+    }
+    let redactions = commitaura::__private::generate::redactions(diff, opts);
+    if redactions.is_empty() {
+        return None;
     }
+    let listed: Vec<String> = redactions.iter().map(|r| r.describe()).collect();
+    Some(format!("Redacted {} secret(s) before sending: {}", redactions.len(), listed.join(", ")))
 }
 
-fn get_last_commit_messages() -> Result<String, CommitauraError> {
-    let output = std::process::Command::new("git")
-        .args(&["log", "-5", "--pretty=format:%s"])
-        .output()
-        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+/// The short name of the checked-out branch; `None` when `HEAD` is detached.
+fn current_branch() -> Option<String> {
+    let head = commitaura::__private::git::symbolic_head()?;
+    Some(head.strip_prefix("refs/heads/").unwrap_or(&head).to_string())
+}
 
-    String::from_utf8(output.stdout).map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))
+/// Commit `tree` on top of `parents` with a message generated from its
+/// changes against the first parent, for automation without a work tree.
+/// Only the new commit's id is printed, or with `dry_run` only the message,
+/// so scripts can capture it.
+fn handle_bare_commit(opts: &mut GenerateOptions, config: &Config, generation: &GenerationArgs, tree: &str, parents: &[String], update_ref: Option<&str>, dry_run: bool) -> Result<(), CommitauraError> {
+    let base = parents.first().map(String::as_str);
+    let diff = commitaura::__private::git::tree_diff(base, tree)?;
+    if diff.trim().is_empty() {
+        return Err(CommitauraError::GitOperationFailed(format!("Tree {} has no changes against {}", tree, base.unwrap_or("an empty tree"))));
+    }
+    if let Some(base) = base {
+        opts.history_pool = commitaura::__private::git::recent_commits_from(base, opts.history_selection.pool_size())?;
+        opts.history = commitaura::__private::history::subjects(&opts.history_pool, commitaura::__private::history::MIN_SUBJECTS);
+        opts.parent_message = parent_message(&diff, config, base)?;
+    }
+    let (message, warnings) = generate_for(&diff, opts, config, generation, true)?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    if dry_run {
+        println!("{}", message);
+        return Ok(());
+    }
+    println!("{}", commitaura::__private::git::commit_tree(tree, parents, &message, update_ref)?);
+    Ok(())
 }
 
-fn generate_commit_message(openai: &OpenAI, last_commits: &str) -> Result<String, CommitauraError> {
-    let diff_output = std::process::Command::new("git")
-        .args(&["diff", "--staged"])
-        .output()
-        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+/// Commit the staged changes as several commits, one per module, once the
+/// grouping and then the messages are confirmed. If a commit fails, the
+/// ones before it are undone and everything is staged again.
+fn handle_split(opts: &mut GenerateOptions, config: &Config, generation: &GenerationArgs) -> Result<(), CommitauraError> {
+    use commitaura::__private::split::Group;
+
+    check_staged_changes()?;
+    let state = RepoState::inspect()?;
+    if let Some(op) = state.operation {
+        return Err(CommitauraError::GitOperationFailed(format!("Cannot split while a {} is in progress", op.name())));
+    }
+    if commitaura::__private::git::head_commit().is_err() {
+        return Err(CommitauraError::GitOperationFailed("Splitting needs a commit to build on; make the first commit whole".to_string()));
+    }
+    opts.history_pool = commitaura::__private::git::recent_commits(state.history_depth(opts.history_selection.pool_size()))?;
+    opts.history = commitaura::__private::history::subjects(&opts.history_pool, commitaura::__private::history::MIN_SUBJECTS);
+    let mut groups = commitaura::__private::split::group(commitaura::diff::parse(&get_staged_diff_in(&[])?));
+
+    loop {
+        if groups.len() < 2 {
+            println!("{}", style("The staged changes all belong together; commit them with `commitaura`.").bold().yellow());
+            return Ok(());
+        }
+        println!("{}", format!("✂️  Proposed split into {} commits:", groups.len()).bold().green());
+        for (i, group) in groups.iter().enumerate() {
+            println!("{} {}", format!("{}.", i + 1).yellow().bold(), group.label().bold().white());
+            for path in group.paths() {
+                println!("   {}", path.dimmed());
+            }
+        }
+        ui::separator();
+        let items = ["✅ Write a message for each", "🔗 Merge some of them", "❌ Cancel"];
+        match ui::select("Split the staged changes like this?", &items, 0)? {
+            Some(0) => break,
+            Some(1) => {
+                let labels: Vec<&str> = groups.iter().map(Group::label).collect();
+                let mut chosen = ui::multi_select("Groups to merge into one commit", &labels)?.unwrap_or_default();
+                // Removing from the back only keeps the indexes valid when they are ascending and distinct.
+                chosen.sort_unstable();
+                chosen.dedup();
+                if chosen.len() < 2 {
+                    println!("{}", "Choose at least two groups to merge.".yellow());
+                    continue;
+                }
+                let mut merged: Vec<Group> = chosen.iter().rev().map(|&i| groups.remove(i)).collect();
+                let mut target = merged.pop().expect("at least two chosen");
+                for group in merged.into_iter().rev() {
+                    target.merge(group);
+                }
+                groups.insert(chosen[0], target);
+            }
+            _ => {
+                println!("{}", style("Split cancelled; nothing was committed.").bold().yellow());
+                return Ok(());
+            }
+        }
+    }
+
+    let mut messages = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        let pb = ui::spinner("magenta", format!("Writing message {} of {} ({})...", i + 1, groups.len(), group.label()))?;
+        let diff = group.diff();
+        opts.parent_message = parent_message(&diff, config, "HEAD")?;
+        let (message, warnings) = generate_for(&diff, opts, config, generation, true)?;
+        pb.finish_and_clear();
+        for warning in &warnings {
+            println!("{} {}", "⚠".yellow(), warning.yellow());
+        }
+        // Later messages are written knowing the earlier ones come first.
+        opts.history = format!("{}\n{}", message.lines().next().unwrap_or(""), opts.history);
+        messages.push(message);
+    }
 
-    let mut diff = String::from_utf8(diff_output.stdout)
-        .map_err(|e| CommitauraError::GitOperationFailed(e.to_string()))?;
+    loop {
+        for (i, (group, message)) in groups.iter().zip(&messages).enumerate() {
+            println!("{} {}", format!("Commit {} of {}", i + 1, groups.len()).bold().green(), format!("({})", group.label()).dimmed());
+            println!("{}", message.bold().white());
+            ui::separator();
+        }
+        let items = ["✅ Commit them all", "✏️  Edit the messages in $EDITOR", "❌ Cancel"];
+        match ui::select("Make these commits?", &items, 0)? {
+            Some(0) => break,
+            Some(1) => {
+                for (group, message) in groups.iter().zip(messages.iter_mut()) {
+                    *message = commitaura::__private::git::edit_text(message, &group.diff())?;
+                    if message.is_empty() {
+                        println!("{}", style("Split cancelled: empty message.").bold().yellow());
+                        return Ok(());
+                    }
+                }
+            }
+            _ => {
+                println!("{}", style("Split cancelled; nothing was committed.").bold().yellow());
+                return Ok(());
+            }
+        }
+    }
 
+    commitaura::__private::git::ensure_writable("commit")?;
+    let staged = commitaura::__private::git::index_tree()?;
+    commitaura::__private::transaction::run("split", || {
+        for (group, message) in groups.iter().zip(&messages) {
+            // HEAD plus this group's files as they are staged.
+            commitaura::__private::git::read_tree("HEAD")?;
+            commitaura::__private::git::reset_paths_to(&staged, &group.paths())?;
+            commitaura::__private::git::perform_git_commit(message)?;
+            println!("{} {}", "✅".green(), message.lines().next().unwrap_or("").bold().white());
+        }
+        Ok(true)
+    })
+}
+
+/// Read a diff from stdin and print just its message, as a filter for
+/// editors and scripts: no color, no spinners, nothing asked. Inside a
+/// repository its history guides the style; the repository is not changed.
+fn handle_stdin_diff(opts: &mut GenerateOptions, config: &Config, generation: &GenerationArgs) -> Result<(), CommitauraError> {
+    commitaura::__private::git::enable_read_only();
+    colored::control::set_override(false);
+    console::set_colors_enabled(false);
+    let mut diff = String::new();
+    std::io::stdin().read_to_string(&mut diff)?;
     if diff.trim().is_empty() {
-        return Err(CommitauraError::NoStagedChanges);
+        return Err(CommitauraError::ConfigError("--stdin-diff read no diff from stdin".to_string()));
+    }
+    // Outside a repository there is just no history to go by.
+    if let Ok(state) = RepoState::inspect() {
+        opts.history_pool = commitaura::__private::git::recent_commits(state.history_depth(opts.history_selection.pool_size())).unwrap_or_default();
+        opts.history = commitaura::__private::history::subjects(&opts.history_pool, commitaura::__private::history::MIN_SUBJECTS);
+        opts.parent_message = parent_message(&diff, config, "HEAD").unwrap_or_default();
+    }
+    // The diff need not be the staged one, so nothing is looked up by it.
+    let (message, warnings) = generate_for(&diff, opts, config, generation, false)?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    println!("{}", message);
+    Ok(())
+}
+
+/// Generate a message for the staged changes and print just the message,
+/// for piping into other tools. Nothing is asked, and the repository is
+/// left as it is.
+fn handle_dry_run(opts: &mut GenerateOptions, config: &Config, generation: &GenerationArgs) -> Result<(), CommitauraError> {
+    let (message, warnings) = generate_unattended(opts, config, generation)?;
+    // Anything but the message goes to stderr, so stdout can be piped.
+    for warning in &warnings {
+        eprintln!("{} {}", "⚠".yellow(), warning.yellow());
+    }
+    println!("{}", message);
+    Ok(())
+}
+
+/// Run as the `prepare-commit-msg` hook: merge a generated message into
+/// git's message file, printing nothing unless something goes wrong.
+fn handle_hook(opts: &mut GenerateOptions, config: &Config, generation: &GenerationArgs, file: &std::path::Path, source: Option<&str>) -> Result<(), CommitauraError> {
+    if !commitaura::__private::hook::should_generate(source) {
+        return Ok(());
+    }
+    match check_staged_changes() {
+        // `git commit --allow-empty`, for one, has nothing to describe.
+        Err(CommitauraError::NoStagedChanges) => return Ok(()),
+        result => result?,
+    }
+    // The hook cannot ask, so a draft for the staged changes is used as it
+    // is, and then discarded: the message now lives in git's message file.
+    let draft_key = if config.drafts != Some(false) { commitaura::__private::draft::current_key() } else { None };
+    if let Some((branch, tree)) = &draft_key {
+        let path = commitaura::__private::draft::drafts_path()?;
+        if let Some(draft) = commitaura::__private::draft::find(&path, branch, tree) {
+            log::info!("Using the draft saved on {} {}", branch, draft.age());
+            MessageFileSink(file.to_path_buf()).write(&draft.message)?;
+            if let Err(e) = commitaura::__private::draft::discard(&path, branch) {
+                log::warn!("Could not discard the draft: {}", e);
+            }
+            return Ok(());
+        }
     }
+    let (message, warnings) = generate_unattended(opts, config, generation)?;
+    for warning in &warnings {
+        log::info!("{}", warning);
+    }
+    MessageFileSink(file.to_path_buf()).write(&message)
+}
 
-    // Estimate tokens and truncate if necessary
-    let system_message =
-        "You are a helpful assistant that generates concise and meaningful Git commit messages.";
-    let prompt = format!(
-        "Write a highly specific, imperative Git commit message based only on the following changes. Do NOT use generic phrases like 'improved readability', 'aesthetic appeal', or 'refactored code'. Instead, reference concrete details such as filenames, functions, variables, or logic that was changed. Be precise about what was changed, how, and why. Do not include any other text except the commit message. Consider the context of the last 5 commit messages:\n\nLast 5 commit messages:\n{}\n\nCurrent changes:\n",
-        last_commits
+/// Install the `prepare-commit-msg` hook.
+fn handle_install_hook(force: bool) -> Result<(), CommitauraError> {
+    let path = commitaura::__private::hook::install(force)?;
+    println!("{} {}", style("✅ Installed").bold().green(), path.display());
+    println!("{}", "   `git commit` now opens your editor with a generated message.".dimmed());
+    Ok(())
+}
+
+/// Roll back the operation an interrupted run left a transaction log for,
+/// or with `last` the last one that finished, after confirming. With
+/// `paths`, only their staged contents are restored.
+fn handle_rollback(last: bool, paths: &[String]) -> Result<(), CommitauraError> {
+    let (transaction, kind) = if last {
+        (commitaura::__private::transaction::last()?, "finished")
+    } else {
+        (commitaura::__private::transaction::pending()?, "interrupted")
+    };
+    let Some(transaction) = transaction else {
+        let hint = if last { "" } else { " (--last undoes the last one that finished)" };
+        println!("{}", style(format!("No {} operation to roll back{}.", kind, hint)).bold().yellow());
+        return Ok(());
+    };
+    let short = |commit: &str| commit[..7.min(commit.len())].to_string();
+    if !paths.is_empty() {
+        if !ui::confirm(&format!("Restore the staged contents of {} file(s) to before the {} {}?", paths.len(), kind, transaction.operation), false)? {
+            return Ok(());
+        }
+        transaction.rollback_paths(paths)?;
+        println!("{}", style(format!("✅ Restored {} file(s) in the index", paths.len())).bold().green());
+        return Ok(());
+    }
+
+    let current = transaction.current()?;
+    // A finished operation is only undone while the branch is where it left it.
+    if let Some(result) = transaction.result.as_deref().filter(|result| *result != current) {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "{} has moved from {} to {} since the {}; not rolling it back",
+            transaction.reference,
+            short(result),
+            short(&current),
+            transaction.operation
+        )));
+    }
+    let dropped = commitaura::__private::git::count_commits(&format!("{}..{}", transaction.head, current)).unwrap_or(0);
+    let question = format!(
+        "Move {} from {} back to {}{} and restore the index to before the {} {}?",
+        transaction.reference,
+        short(&current),
+        short(&transaction.head),
+        if dropped > 0 { format!(" (taking {} commit(s) off it)", dropped) } else { String::new() },
+        kind,
+        transaction.operation
     );
+    if !ui::confirm(&question, false)? {
+        return Ok(());
+    }
+    let (operation, head) = (transaction.operation.clone(), transaction.head.clone());
+    transaction.rollback(&current)?;
+    println!("{} {}", style(format!("✅ Rolled back the {} {}; the branch is at", kind, operation)).bold().green(), short(&head));
+    Ok(())
+}
 
-    let system_tokens = estimate_tokens(system_message)?;
-    let prompt_tokens = estimate_tokens(&prompt)?;
-    let diff_tokens = estimate_tokens(&diff)?;
-    let estimated_tokens = system_tokens + prompt_tokens + diff_tokens;
-
-    if estimated_tokens > MAX_TOKENS {
-        let available_tokens = MAX_TOKENS - system_tokens - prompt_tokens;
-        let bpe = p50k_base().map_err(|e| CommitauraError::TokenizerError(e.to_string()))?;
-        let tokens = bpe.encode_with_special_tokens(&diff);
-        let truncated_tokens = tokens[..available_tokens].to_vec();
-        diff = bpe
-            .decode(truncated_tokens)
-            .map_err(|e| CommitauraError::TokenizerError(e.to_string()))?;
-    }
-
-    let body = ChatBody {
-        model: MODEL_NAME.to_string(),
-        max_tokens: Some(100),
-        temperature: Some(0.7),
-        top_p: Some(1.0),
-        n: Some(1),
-        stream: Some(false),
-        stop: None,
-        presence_penalty: None,
-        frequency_penalty: None,
-        logit_bias: None,
-        user: None,
-        messages: vec![
-            Message {
-                role: Role::System,
-                content: "You are a helpful assistant that generates concise and meaningful Git commit messages.".to_string(),
-            },
-            Message {
-                role: Role::User,
-                content: format!(
-                    "Write a concise and meaningful Git commit message based on the following changes (do not include any other text other than the commit message). Be extremely specific. Do not be vague. Consider the context of the last 5 commit messages:\n\nLast 5 commit messages:\n{}\n\nCurrent changes:\n{}",
-                    last_commits, diff
-                ),
-            },
-        ],
-    };
-
-    let rs = openai
-        .chat_completion_create(&body)
-        .map_err(|e| CommitauraError::OpenAIError(e.to_string()))?;
-
-    let choice = rs.choices;
-    let message = &choice[0]
-        .message
-        .as_ref()
-        .ok_or(CommitauraError::ApiRequestFailed(
-            "No message in API response".to_string(),
-        ))?;
-    let commit_message = message.content.trim().to_string();
-
-    if commit_message.is_empty() {
-        Err(CommitauraError::ApiRequestFailed(
-            "Received empty commit message from LLM.".to_string(),
-        ))
+/// The config files that exist, global first.
+fn config_files() -> Vec<PathBuf> {
+    [commitaura::__private::config::global_config_path(), commitaura::__private::config::repo_config_path()].into_iter().flatten().filter(|path| path.is_file()).collect()
+}
+
+fn handle_config(command: &ConfigCommands) -> Result<(), CommitauraError> {
+    let files = config_files();
+    match command {
+        ConfigCommands::Lint => {
+            if files.is_empty() {
+                println!("{}", style("No config files found; the defaults are used.").bold().yellow());
+                return Ok(());
+            }
+            let mut problems = 0;
+            for path in &files {
+                match Config::from_path(path) {
+                    Ok(_) => println!("{} {}", "✅".green(), path.display()),
+                    Err(CommitauraError::ConfigError(message)) => {
+                        problems += 1;
+                        println!("{} {}", "✗".red(), message.red());
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if problems == 0 {
+                for problem in Config::load()?.lint() {
+                    problems += 1;
+                    println!("{} {}", "✗".red(), problem.red());
+                }
+            }
+            if problems > 0 {
+                return Err(CommitauraError::ConfigError(format!("{} problem{} found", problems, if problems == 1 { "" } else { "s" })));
+            }
+            println!("{}", style("Configuration is valid.").bold().green());
+        }
+        ConfigCommands::Show { resolved: true } => print!("{}", Config::load()?.to_toml()?),
+        ConfigCommands::Show { resolved: false } => {
+            if files.is_empty() {
+                println!("{}", "# No config files found; the defaults are used.".dimmed());
+            }
+            for (i, path) in files.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("{}", format!("# {}", path.display()).dimmed());
+                print!("{}", std::fs::read_to_string(path)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn handle_amend_no_edit(opts: &mut GenerateOptions) -> Result<(), CommitauraError> {
+    use commitaura::__private::rerun::AmendPlan;
+
+    if let Some(op) = RepoState::inspect()?.operation {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "Cannot amend HEAD while a {} is in progress",
+            op.name()
+        )));
+    }
+    // If regenerating fails or a hook rejects the amend, the restaged
+    // changes are unstaged again.
+    commitaura::__private::transaction::run("amend", || {
+        match commitaura::__private::rerun::restage_and_plan()? {
+            AmendPlan::Nothing => {
+                println!("{}", style("Nothing changed in HEAD's files; nothing to amend.").bold().yellow());
+            }
+            AmendPlan::NoEdit => {
+                commitaura::__private::git::amend_no_edit()?;
+                println!("{}", style("✅ Whitespace-only changes amended into HEAD; message kept.").bold().green());
+            }
+            AmendPlan::Regenerate => {
+                let current = commitaura::__private::git::commit_message("HEAD")?;
+                let pb = ui::spinner("magenta", "The hook changed more than whitespace; regenerating message...")?;
+                let state = RepoState::inspect()?;
+                opts.history_pool = commitaura::__private::git::recent_commits(state.history_depth(opts.history_selection.pool_size()))?;
+                opts.history = commitaura::__private::history::subjects(&opts.history_pool, commitaura::__private::history::MIN_SUBJECTS);
+                let regenerated = generate_message(&get_amend_diff_in(&[])?, opts)?;
+                pb.finish_and_clear();
+
+                println!("{}", "Current message:".bold().white());
+                println!("{}", current.white().italic());
+                println!("{}", "Regenerated message:".bold().green());
+                println!("{}", regenerated.bold().white());
+                ui::separator();
+                let choice = ui::select("Amend HEAD with", &["The regenerated message", "The current message", "Cancel (changes stay staged)"], 0)?.unwrap_or(2);
+                match choice {
+                    0 => commitaura::__private::git::perform_git_amend(&regenerated)?,
+                    1 => commitaura::__private::git::amend_no_edit()?,
+                    _ => {
+                        println!("{}", style("Amend cancelled by user.").bold().yellow());
+                        return Ok(true);
+                    }
+                }
+                println!("{}", style("✅ Amended HEAD").bold().green());
+            }
+        }
+        Ok(true)
+    })
+}
+
+/// Print the build's target and, for each optional capability, whether it
+/// is compiled in and can be used on this system.
+fn handle_doctor() {
+    use commitaura::__private::platform::{self, Status};
+
+    println!("{} {}", format!("commitaura {}", env!("CARGO_PKG_VERSION")).bold().white(), format!("({})", platform::target()).dimmed());
+    ui::separator();
+    for capability in platform::capabilities() {
+        let name = format!("{:<10}", capability.name);
+        match capability.status {
+            Status::Available(how) => println!("{} {} {}", "✅".green(), name.bold(), how),
+            Status::Unavailable(why) => println!("{} {} {}", "⚠️".yellow(), name.bold(), why.yellow()),
+            Status::NotCompiled(feature) => println!("{} {} {}", "–".dimmed(), name.bold(), format!("not compiled in (cargo feature `{}`)", feature).dimmed()),
+        }
+    }
+}
+
+/// Check `message_files` and the commits in `range` against the rules in
+/// `rules` (or `.commitaura/rules.toml`, or the defaults), and print a
+/// report in `format`. Fails if any message breaks a rule that is not a
+/// warning.
+fn handle_assert(message_files: &[PathBuf], range: Option<&str>, rules: Option<&Path>, format: &str, output: Option<&Path>) -> Result<(), CommitauraError> {
+    use commitaura::__private::rules::{self, Rules, Severity};
+
+    let repo_rules = commitaura::__private::git::toplevel().unwrap_or_default().join(rules::RULES_FILE);
+    let rules = match rules {
+        Some(path) => Rules::load(path)?,
+        None if repo_rules.is_file() => Rules::load(&repo_rules)?,
+        None => Rules::default(),
+    };
+    let mut messages = Vec::new();
+    for path in message_files {
+        let text = if path.as_os_str() == "-" {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            std::fs::read_to_string(path)?
+        };
+        // Message files from a commit-msg hook still have git's comments.
+        let comment = commitaura::__private::editmsg::comment_char(&text);
+        messages.push((rules::Source::File(path.display().to_string()), commitaura::__private::editmsg::cleanup(&text, commitaura::__private::editmsg::Cleanup::Strip, true, comment)));
+    }
+    if let Some(range) = range {
+        messages.extend(commitaura::__private::git::range_messages(range)?.into_iter().map(|(hash, message)| (rules::Source::Commit(hash), message)));
+    }
+
+    let mut report = Vec::new();
+    for (source, message) in messages {
+        if rules.ignores(&message)? {
+            continue;
+        }
+        let violations = rules::check(&message, &rules)?;
+        report.push((source, violations));
+    }
+    let text = match format {
+        "sarif" => rules::to_sarif(&report),
+        "junit" => rules::to_junit(&report),
+        _ => {
+            let mut text = String::new();
+            for (source, violations) in &report {
+                for v in violations {
+                    let level = if v.severity == Severity::Error { "error" } else { "warning" };
+                    text.push_str(&format!("{}:{}: {}: {} [{}]\n", source, v.line, level, v.message, v.rule));
+                }
+            }
+            text
+        }
+    };
+    match output {
+        Some(path) => std::fs::write(path, &text)?,
+        None => print!("{}", text),
+    }
+
+    let failed = report.iter().filter(|(_, violations)| violations.iter().any(|v| v.severity == Severity::Error)).count();
+    if failed > 0 {
+        return Err(CommitauraError::RulesBroken(failed, report.len()));
+    }
+    if format == "text" && output.is_none() {
+        println!("{}", style(format!("✅ {} message(s) follow the rules.", report.len())).bold().green());
+    }
+    Ok(())
+}
+
+/// The transcript `id` refers to: a key in this clone's journal, or the
+/// note on the commit whose trailer has it, or on commit `id` itself.
+fn handle_show(id: &str) -> Result<(), CommitauraError> {
+    use commitaura::__private::journal::{self, NOTES_REF, TRAILER};
+
+    let entries = journal::load(&journal::repo_journal()?)?;
+    let from_commit = |commit: &str| journal::from_note(&commitaura::__private::git::note(NOTES_REF, commit)?);
+    let entry = journal::find_key(&entries, id)
+        .or_else(|| from_commit(&commitaura::__private::git::commit_with_trailer(TRAILER, id)?))
+        .or_else(|| {
+            let message = commitaura::__private::git::commit_message(id).ok()?;
+            let key = journal::trailer_key(&message)?;
+            journal::find_key(&entries, key).or_else(|| from_commit(id))
+        })
+        .ok_or_else(|| {
+            CommitauraError::GitOperationFailed(format!(
+                "no transcript for `{}` in the journal or in refs/notes/{} (fetch it with `git fetch origin refs/notes/{}:refs/notes/{}`)",
+                id, NOTES_REF, NOTES_REF, NOTES_REF
+            ))
+        })?;
+
+    println!(
+        "{} {}",
+        format!("{}: {}", TRAILER, entry.key()).bold().white(),
+        format!("({}, {} ms)", entry.model, entry.latency_ms).dimmed()
+    );
+    ui::separator();
+    println!("{}", "Prompt".bold().cyan());
+    println!("{}", entry.system.dimmed());
+    println!();
+    println!("{}", entry.user);
+    ui::separator();
+    println!("{}", "Reply".bold().green());
+    println!("{}", entry.reply.trim().white());
+    ui::separator();
+    Ok(())
+}
+
+fn handle_replay(opts: &mut GenerateOptions, id: Option<u64>, model: Option<String>) -> Result<(), CommitauraError> {
+    let path = commitaura::__private::journal::repo_journal()?;
+    let Some(id) = id else {
+        let entries = commitaura::__private::journal::load(&path)?;
+        if entries.is_empty() {
+            println!("{}", style("No journaled generations yet.").bold().yellow());
+        }
+        for entry in entries.iter().rev().take(10) {
+            println!(
+                "{} {} {} {}",
+                format!("#{:<4}", entry.id).yellow().bold(),
+                format!("{:<16}", entry.model).cyan(),
+                format!("{:>6} ms", entry.latency_ms).dimmed(),
+                entry.reply.lines().next().unwrap_or("").white().italic()
+            );
+        }
+        return Ok(());
+    };
+
+    let entry = commitaura::__private::journal::find(&path, id)?;
+    opts.model = model.unwrap_or_else(|| entry.model.clone());
+    // The same request as recorded, not as configured now.
+    opts.temperature = entry.temperature;
+    if !entry.api_base.is_empty() {
+        opts.api_base = entry.api_base.clone();
+    }
+    let pb = ui::spinner("magenta", format!("Replaying #{} on {}...", id, opts.model))?;
+    let reply = commitaura::__private::generate::complete(&entry.prompt(), opts, entry.max_response_tokens)?;
+    pb.finish_and_clear();
+
+    println!("{} {}", "Original".bold().white(), format!("({}, {} ms)", entry.model, entry.latency_ms).dimmed());
+    ui::separator();
+    println!("{}", entry.reply.trim().white());
+    ui::separator();
+    println!("{} {}", "Replay".bold().green(), format!("({})", opts.model).dimmed());
+    ui::separator();
+    println!("{}", commitaura::__private::message::assemble(&reply, opts).green());
+    ui::separator();
+    Ok(())
+}
+
+fn handle_prompt_test(opts: &GenerateOptions, update: bool) -> Result<(), CommitauraError> {
+    let dir = commitaura::__private::git::toplevel()?.join(commitaura::__private::prompt_test::PROMPT_TESTS_DIR);
+    let fixtures = commitaura::__private::prompt_test::fixtures(&dir)?;
+    let mut changed = 0;
+    for fixture in &fixtures {
+        let rendered = commitaura::__private::prompt_test::render(fixture, opts)?;
+        let path = commitaura::__private::prompt_test::snapshot_path(&dir, fixture);
+        match commitaura::__private::prompt_test::check(&path, &rendered, update)? {
+            Outcome::Passed => println!("{} {}", "✓".green(), fixture.name),
+            Outcome::Created => println!("{} {} {}", "+".cyan(), fixture.name, format!("(new snapshot {})", path.display()).dimmed()),
+            Outcome::Updated => println!("{} {} {}", "↻".yellow(), fixture.name, "(snapshot updated)".dimmed()),
+            Outcome::Changed(diff) => {
+                changed += 1;
+                println!("{} {}", "✗".red(), fixture.name.bold());
+                for line in diff.lines() {
+                    match line.chars().next() {
+                        Some('-') => println!("{}", line.red()),
+                        Some('+') => println!("{}", line.green()),
+                        _ => println!("{}", line.dimmed()),
+                    }
+                }
+            }
+        }
+    }
+    if changed > 0 {
+        return Err(CommitauraError::PromptTemplateError(format!(
+            "{} of {} snapshots changed; rerun with --update to accept them",
+            changed,
+            fixtures.len()
+        )));
+    }
+    Ok(())
+}
+
+/// The one-line stash message the model suggests for `diff`.
+fn generate_stash_message(diff: &str, opts: &mut GenerateOptions) -> Result<String, CommitauraError> {
+    let pb = ui::spinner("magenta", "Describing the changes...")?;
+    // The same history `commit` would send, chosen as configured.
+    let state = RepoState::inspect()?;
+    opts.history_pool = commitaura::__private::git::recent_commits(state.history_depth(opts.history_selection.pool_size()))?;
+    opts.history = commitaura::__private::history::subjects(&opts.history_pool, commitaura::__private::history::MIN_SUBJECTS);
+    let message = generate_message(diff, opts);
+    pb.finish_and_clear();
+    // `git stash list` shows one line per entry.
+    Ok(message?.lines().next().unwrap_or_default().trim().to_string())
+}
+
+/// Stash the working tree's changes under a generated message, so the entry
+/// can still be told apart from the others weeks later.
+fn handle_stash(opts: &mut GenerateOptions) -> Result<(), CommitauraError> {
+    if commitaura::__private::git::head_commit().is_err() {
+        // `git diff HEAD` and `git stash` both need a commit to work against.
+        println!("{}", style("Nothing can be stashed before the first commit; commit the changes instead.").bold().yellow());
+        return Ok(());
+    }
+    let diff = commitaura::__private::git::get_worktree_diff()?;
+    if diff.trim().is_empty() {
+        println!("{}", style("No changes to tracked files to stash.").bold().yellow());
+        return Ok(());
+    }
+    let message = generate_stash_message(&diff, opts)?;
+    println!("{}", "✨ Stash Message:".bold().green());
+    println!("{}", message.bold().white());
+    ui::separator();
+    if !ui::confirm("Stash the changes with this message?", true)? {
+        println!("{}", style("Stash cancelled by user.").bold().yellow());
+        return Ok(());
+    }
+    commitaura::__private::git::stash_push(&message)?;
+    println!("{} {}", style("✅ Stashed as").bold().green(), "stash@{0}".white());
+    Ok(())
+}
+
+/// Explain what an existing stash entry holds, and offer to rename it with
+/// the description.
+fn handle_stash_describe(opts: &mut GenerateOptions, index: usize) -> Result<(), CommitauraError> {
+    let current = commitaura::__private::git::stash_message(index)?;
+    let diff = commitaura::__private::git::stash_diff(index)?;
+    if diff.trim().is_empty() {
+        println!("{}", style(format!("stash@{{{}}} holds no changes to tracked files.", index)).bold().yellow());
+        return Ok(());
+    }
+    let message = generate_stash_message(&diff, opts)?;
+    println!("{} {}", format!("stash@{{{}}}:", index).bold().white(), current.white().italic());
+    println!("{}", "✨ Description:".bold().green());
+    println!("{}", message.bold().white());
+    ui::separator();
+    if !ui::confirm("Rename the stash with this description? It moves to stash@{0}.", false)? {
+        return Ok(());
+    }
+    commitaura::__private::git::rename_stash(index, &message)?;
+    println!("{} {}", style("✅ Renamed; now").bold().green(), "stash@{0}".white());
+    Ok(())
+}
+
+fn handle_tag(opts: &GenerateOptions, term: &Term, ui: &UiConfig, name: &str, since: Option<String>, sign: bool, deepen: bool) -> Result<(), CommitauraError> {
+    if ui.clear_screen && !ui::accessible() {
+        term.clear_screen()?;
+    }
+    println!("{} {}\n", "🏷️".bold().cyan(), style("Commitaura: Tag Release").bold().white().on_black());
+    ui::separator();
+
+    // Without the full history the previous tag may be missing, and the notes
+    // would silently cover only the fetched commits.
+    let state = RepoState::inspect()?;
+    if state.shallow && !(deepen && confirm_deepen(&state, None)?) {
+        println!("{}", "⚠ Shallow clone: older commits and tags are missing, so the notes cover only the fetched history.".yellow());
+        if !deepen {
+            println!("{}", "  Run with --deepen to fetch the full history.".dimmed());
+        }
+    }
+
+    let since = since.or_else(commitaura::__private::git::latest_tag);
+    let range = commitaura::__private::release::CommitRange::collect(since.as_deref())?;
+    if range.is_empty() {
+        println!("{}", style(format!("No commits since {}.", since.unwrap_or_default())).bold().yellow());
+        return Ok(());
+    }
+    match &range.since {
+        Some(since) => println!("{} {}", "Changes since".dimmed(), since.yellow().bold()),
+        None => println!("{}", "No previous tag; summarizing the whole history".dimmed()),
+    }
+    display_commit_messages(&range.commits);
+
+    let pb = ui::spinner("magenta", "Writing tag message...")?;
+    let tag_message = commitaura::__private::release::generate_tag_message(name, &range, opts)?;
+    pb.finish_and_clear();
+
+    println!("{} {}", "✨ Tag Message for".bold().green(), name.bold().green());
+    ui::separator();
+    println!("{}", tag_message.white());
+    ui::separator();
+
+    if ui::confirm(&format!("Create {}tag {}?", if sign { "signed " } else { "" }, name), true)? {
+        commitaura::__private::git::create_tag(name, &tag_message, sign)?;
+        println!("{} {}", style("✅ Created tag").bold().green(), name);
     } else {
-        info!("Generated commit message: {}", commit_message);
-        Ok(commit_message)
+        println!("{}", style("Tag cancelled by user.").bold().yellow());
     }
+    Ok(())
 }
 
-fn estimate_tokens(text: &str) -> Result<usize, CommitauraError> {
-    let bpe = p50k_base().map_err(|e| CommitauraError::TokenizerError(e.to_string()))?;
-    let tokens = bpe.encode_with_special_tokens(text);
-    Ok(tokens.len())
+/// Ask before fetching history into a shallow clone: enough for `wanted`
+/// commits, or everything. Returns whether anything was fetched.
+fn confirm_deepen(state: &RepoState, wanted: Option<usize>) -> Result<bool, CommitauraError> {
+    let available = state.available_commits.unwrap_or(0);
+    let prompt = match wanted {
+        Some(wanted) => format!(
+            "Shallow clone has {} commit(s) of history. Fetch {} more from the remote?",
+            available,
+            wanted - available
+        ),
+        None => format!("Shallow clone has {} commit(s) of history. Fetch the full history and tags?", available),
+    };
+    if !ui::confirm(&prompt, true)? {
+        return Ok(false);
+    }
+    match wanted {
+        Some(wanted) => commitaura::__private::git::deepen(wanted - available)?,
+        None => commitaura::__private::git::unshallow()?,
+    }
+    Ok(true)
 }
 
 fn display_commit_messages(commits: &str) {
     println!("{} {}", "📜".bold().blue(), "Recent Commit Messages:".bold().white());
-    println!("{}", "────────────────────────────────────────────".white());
+    ui::separator();
     for (i, message) in commits.lines().enumerate() {
         println!(
             "{} {}",
@@ -256,7 +2553,32 @@ fn display_commit_messages(commits: &str) {
             message.white().italic()
         );
     }
-    println!("{}\n", "────────────────────────────────────────────".white());
+    ui::separator();
+    println!();
+}
+
+/// Show the suggested subject aligned under the recent subjects, with any
+/// deviation from the history's tense, prefix or length called out.
+fn display_style_comparison(history: &str, commit_message: &str) {
+    let Some(profile) = StyleProfile::from_history(history) else {
+        return;
+    };
+    let subject = commit_message.lines().next().unwrap_or("");
+    let mismatches = profile.mismatches(subject);
+
+    println!("{} {}", "🎨".bold().blue(), "Style Check:".bold().white());
+    for previous in history.lines() {
+        println!("  {:<10} {}", "history".dimmed(), previous.white().italic());
+    }
+    if mismatches.is_empty() {
+        println!("  {:<10} {}", "suggested".green().bold(), subject.green().bold());
+        println!("  {}", "✔ Matches the style of recent commits".green());
+    } else {
+        println!("  {:<10} {}", "suggested".yellow().bold(), subject.yellow().bold());
+        for mismatch in &mismatches {
+            println!("  {} {}", "⚠".yellow(), mismatch.to_string().yellow());
+        }
+    }
 }
 
 #[cfg(test)]
@@ -272,6 +2594,31 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("5s"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("1500ms"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2"), Ok(Duration::from_secs(2)));
+        assert!(parse_duration("5h").is_err());
+        assert!(parse_duration(&"9".repeat(400)).is_err());
+    }
+
+    #[test]
+    fn caps_candidate_counts() {
+        assert_eq!(parse_candidates("3"), Ok(Spec::Count(3)));
+        assert!(parse_candidates("1").is_err());
+        assert!(parse_candidates("500").is_err());
+    }
+
+    #[test]
+    fn parses_hook_arguments() {
+        let cli = Cli::try_parse_from(["commitaura", "hook", ".git/COMMIT_EDITMSG", "commit", "HEAD"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Hook { source: Some(source), sha: Some(_), .. }) if source == "commit"
+        ));
+    }
+
     #[test]
     fn test_generate_commit_message() {
         // Mock the OpenAI client and test the generate_commit_message function
@@ -0,0 +1,286 @@
+//! Messages for octopus and subtree merges, written locally.
+//!
+//! The staged diff of such a merge is everything the merged branches
+//! changed, often a whole imported project, and a summary of it says less
+//! than what git already knows: which heads are merged, and under which
+//! prefix a subtree lives. The message lists those instead.
+//!
+//! An octopus merge is recognised by having more than one `MERGE_HEAD`. A
+//! subtree merge is one whose staged changes all lie under a directory
+//! that holds the merged head's top-level files, as after
+//! `git merge -s subtree` or `git read-tree --prefix`.
+
+use crate::diff::{self, FileStatus};
+use crate::repo_state::{Operation, RepoState};
+use crate::{git, CommitauraError};
+
+/// A commit being merged into HEAD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedHead {
+    /// Abbreviated hash.
+    pub commit: String,
+    /// The branch or tag it is the tip of, if any.
+    pub name: Option<String>,
+    pub subject: String,
+    /// Commits it brings that HEAD does not have.
+    pub commits: usize,
+}
+
+impl MergedHead {
+    /// Read the merged commit `commit`.
+    pub fn load(commit: &str) -> Result<MergedHead, CommitauraError> {
+        Ok(MergedHead {
+            commit: git::short_hash(commit)?,
+            name: git::tip_name(commit),
+            subject: git::commit_message(commit)?
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            commits: git::count_commits(&format!("HEAD..{}", commit))?,
+        })
+    }
+
+    /// `'name'`, or `commit 'hash'` for a commit no branch points at.
+    fn quoted(&self) -> String {
+        match &self.name {
+            Some(name) => format!("'{}'", name),
+            None => format!("commit '{}'", self.commit),
+        }
+    }
+
+    /// One line of the message body describing this head.
+    fn describe(&self) -> String {
+        let commits = match self.commits {
+            1 => "1 commit".to_string(),
+            n => format!("{} commits", n),
+        };
+        match &self.name {
+            Some(name) => format!("{} ({}): {}, {}", name, self.commit, self.subject, commits),
+            None => format!("{}: {}, {}", self.commit, self.subject, commits),
+        }
+    }
+}
+
+/// A merge whose message is written locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Merge {
+    /// More than one head merged at once.
+    Octopus(Vec<MergedHead>),
+    /// A head merged into the directory `prefix`.
+    Subtree {
+        head: MergedHead,
+        prefix: String,
+        /// The directory did not exist before: the subtree is being added.
+        added: bool,
+    },
+}
+
+impl Merge {
+    /// What kind of merge this is, for notes such as "octopus merge of 3
+    /// heads".
+    pub fn describe(&self) -> String {
+        match self {
+            Merge::Octopus(heads) => format!("octopus merge of {} heads", heads.len()),
+            Merge::Subtree { prefix, .. } => format!("subtree merge into {}/", prefix),
+        }
+    }
+
+    /// The commit message, for a merge into the branch `into`.
+    pub fn message(&self, into: Option<&str>) -> String {
+        let into = into
+            .map(|branch| format!(" into {}", branch))
+            .unwrap_or_default();
+        match self {
+            Merge::Octopus(heads) => {
+                let all_named = heads.iter().all(|head| head.name.is_some());
+                let quoted: Vec<String> = heads.iter().map(MergedHead::quoted).collect();
+                let lines: Vec<String> = heads
+                    .iter()
+                    .map(|head| format!("- {}", head.describe()))
+                    .collect();
+                format!(
+                    "Merge {}{}{}\n\nMerged heads:\n{}",
+                    if all_named { "branches " } else { "" },
+                    join_list(&quoted),
+                    into,
+                    lines.join("\n")
+                )
+            }
+            Merge::Subtree {
+                head,
+                prefix,
+                added,
+            } => {
+                let subject = if *added {
+                    format!("Add {} as a subtree in {}/", head.quoted(), prefix)
+                } else {
+                    format!("Merge {} into the {}/ subtree", head.quoted(), prefix)
+                };
+                format!(
+                    "{}\n\nSubtree {}/ now at {}.",
+                    subject,
+                    prefix,
+                    head.describe()
+                )
+            }
+        }
+    }
+}
+
+/// The octopus or subtree merge that `state` and the staged `diff` are
+/// part of, if any.
+pub fn detect(state: &RepoState, diff: &str) -> Result<Option<Merge>, CommitauraError> {
+    if state.operation != Some(Operation::Merge) {
+        return Ok(None);
+    }
+    if state.is_octopus() {
+        let heads = state
+            .merge_heads
+            .iter()
+            .map(|commit| MergedHead::load(commit))
+            .collect::<Result<_, _>>()?;
+        return Ok(Some(Merge::Octopus(heads)));
+    }
+    let [commit] = state.merge_heads.as_slice() else {
+        return Ok(None);
+    };
+    let files = diff::parse(diff);
+    let staged: Vec<String> = files.iter().map(|file| file.path.clone()).collect();
+    let head_names = git::tree_names(commit)?;
+    let Some(prefix) = subtree_prefix(&staged, &head_names, git::index_names)? else {
+        return Ok(None);
+    };
+    Ok(Some(Merge::Subtree {
+        head: MergedHead::load(commit)?,
+        prefix,
+        added: files.iter().all(|file| file.status == FileStatus::Added),
+    }))
+}
+
+/// The deepest directory holding all of the `staged` paths whose entries,
+/// as `index_names` lists them, include every one of `head_names`, the
+/// merged head's top-level names. `None` if there is none below the root.
+pub fn subtree_prefix(
+    staged: &[String],
+    head_names: &[String],
+    index_names: impl Fn(&str) -> Result<Vec<String>, CommitauraError>,
+) -> Result<Option<String>, CommitauraError> {
+    if staged.is_empty() || head_names.is_empty() {
+        return Ok(None);
+    }
+    let mut common: Vec<&str> = staged[0].split('/').collect();
+    common.pop();
+    for path in &staged[1..] {
+        let dirs: Vec<&str> = path.split('/').collect();
+        let shared = common
+            .iter()
+            .zip(&dirs[..dirs.len() - 1])
+            .take_while(|(a, b)| a == b)
+            .count();
+        common.truncate(shared);
+    }
+    while !common.is_empty() {
+        let dir = common.join("/");
+        let names = index_names(&dir)?;
+        if head_names.iter().all(|name| names.contains(name)) {
+            return Ok(Some(dir));
+        }
+        common.pop();
+    }
+    Ok(None)
+}
+
+/// `a`, `a and b`, or `a, b and c`.
+fn join_list(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn head(name: Option<&str>, commit: &str, commits: usize) -> MergedHead {
+        MergedHead {
+            commit: commit.to_string(),
+            name: name.map(str::to_string),
+            subject: format!("Tip of {}", commit),
+            commits,
+        }
+    }
+
+    #[test]
+    fn finds_the_subtree_prefix() {
+        let index = |dir: &str| -> Result<Vec<String>, CommitauraError> {
+            Ok(match dir {
+                "vendor/lib" => vec!["README.md".into(), "src".into()],
+                "vendor/lib/src" => vec!["parse.rs".into()],
+                "vendor" => vec!["lib".into()],
+                _ => Vec::new(),
+            })
+        };
+        let names = vec!["README.md".to_string(), "src".to_string()];
+        let staged = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            subtree_prefix(&staged(&["vendor/lib/src/parse.rs"]), &names, index).unwrap(),
+            Some("vendor/lib".to_string())
+        );
+        assert_eq!(
+            subtree_prefix(
+                &staged(&["vendor/lib/README.md", "vendor/lib/src/parse.rs"]),
+                &names,
+                index
+            )
+            .unwrap(),
+            Some("vendor/lib".to_string())
+        );
+        assert_eq!(
+            subtree_prefix(
+                &staged(&["vendor/lib/src/parse.rs", "src/main.rs"]),
+                &names,
+                index
+            )
+            .unwrap(),
+            None
+        );
+        assert_eq!(
+            subtree_prefix(&staged(&["README.md"]), &names, index).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn lists_merged_heads_and_prefixes() {
+        let octopus = Merge::Octopus(vec![
+            head(Some("feature/a"), "1a2b3c4", 3),
+            head(Some("feature/b"), "5d6e7f8", 1),
+            head(None, "9a8b7c6", 2),
+        ]);
+        assert_eq!(
+            octopus.message(Some("main")),
+            "Merge 'feature/a', 'feature/b' and commit '9a8b7c6' into main\n\nMerged heads:\n- feature/a (1a2b3c4): Tip of 1a2b3c4, 3 commits\n- feature/b (5d6e7f8): Tip of 5d6e7f8, 1 commit\n- 9a8b7c6: Tip of 9a8b7c6, 2 commits"
+        );
+        assert_eq!(octopus.describe(), "octopus merge of 3 heads");
+        let named = Merge::Octopus(vec![head(Some("a"), "1", 1), head(Some("b"), "2", 1)]);
+        assert!(named
+            .message(None)
+            .starts_with("Merge branches 'a' and 'b'\n\n"));
+
+        let subtree = |added| Merge::Subtree {
+            head: head(Some("lib/main"), "1a2b3c4", 12),
+            prefix: "vendor/lib".to_string(),
+            added,
+        };
+        assert_eq!(
+            subtree(true).message(None),
+            "Add 'lib/main' as a subtree in vendor/lib/\n\nSubtree vendor/lib/ now at lib/main (1a2b3c4): Tip of 1a2b3c4, 12 commits."
+        );
+        assert!(subtree(false)
+            .message(None)
+            .starts_with("Merge 'lib/main' into the vendor/lib/ subtree\n\n"));
+    }
+}
@@ -0,0 +1,403 @@
+//! Final assembly of the commit message returned by the model.
+
+use icu_normalizer::DecomposingNormalizerBorrowed;
+
+use crate::generate::PromptStyle;
+use crate::gitmoji;
+use crate::rules::{self, Rules};
+use crate::GenerateOptions;
+
+/// Turn the raw model output into the message that will be committed.
+pub fn assemble(raw: &str, opts: &GenerateOptions) -> String {
+    let message = if opts.prompt_style != PromptStyle::Gitmoji {
+        tidy(raw, opts)
+    } else {
+        tidy(&gitmoji::normalize(raw.trim(), opts.gitmoji_format()), opts)
+    };
+    let message = fit_subject(&message);
+    match &opts.ticket {
+        // `Reference::find` has checked the subject template already.
+        Some(reference) => reference.apply(&message).unwrap_or(message),
+        None => message,
+    }
+}
+
+/// `message` with a subject longer than [`MAX_SUBJECT_LEN`] cut at the best
+/// break (see [`proposed_split`]) and the rest moved to the top of the
+/// body. A message that is only a subject is left for the user to split,
+/// see [`is_run_on`].
+fn fit_subject(message: &str) -> String {
+    let Some((subject, body)) = message.split_once('\n') else {
+        return message.to_string();
+    };
+    let subject = subject.trim_end();
+    if subject.chars().count() <= MAX_SUBJECT_LEN {
+        return message.to_string();
+    }
+    let points = split_points(subject);
+    match proposed_split(subject, &points) {
+        Some(i) => format!("{}\n\n{}", split_at(subject, points[i]), body.trim_start()),
+        None => message.to_string(),
+    }
+}
+
+/// Raw model output trimmed, and made ASCII if `opts` asks for it, for
+/// text other than commit messages.
+pub fn tidy(raw: &str, opts: &GenerateOptions) -> String {
+    let text = raw.trim();
+    if opts.ascii_only {
+        to_ascii(text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Replace typographic punctuation with ASCII equivalents, transliterate
+/// other characters through their compatibility decomposition (NFKD), so
+/// `é` becomes `e` and `ﬁ` becomes `fi`, and drop whatever is left that is
+/// not ASCII, such as combining marks, emoji and symbols. For tooling that
+/// only accepts 7-bit messages.
+pub fn to_ascii(text: &str) -> String {
+    let nfkd = DecomposingNormalizerBorrowed::new_nfkd();
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            c if c.is_ascii() => out.push(c),
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' => out.push('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' | '«' | '»' => out.push('"'),
+            '\u{2010}'..='\u{2015}' | '\u{2212}' => out.push('-'),
+            '\u{2026}' => out.push_str("..."),
+            '\u{2022}' | '\u{00B7}' => out.push('*'),
+            '\u{2192}' => out.push_str("->"),
+            '\u{2190}' => out.push_str("<-"),
+            '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' => out.push(' '),
+            c => out.extend(
+                nfkd.normalize_iter(std::iter::once(c))
+                    .filter(char::is_ascii),
+            ),
+        }
+    }
+
+    // Dropped emoji leave stray spaces behind ("✨ Add" -> " Add").
+    out.lines()
+        .map(|line| {
+            let indent = line.len() - line.trim_start().len();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            format!("{}{}", &line[..indent], words.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Longest subject line [`lint`] accepts.
+pub const MAX_SUBJECT_LEN: usize = 72;
+
+/// Formatting problems with `message` that git tooling commonly flags: it
+/// breaks the default [`crate::rules::Rules`].
+pub fn lint(message: &str) -> Vec<String> {
+    rules::check(message, &Rules::default())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|v| v.message)
+        .collect()
+}
+
+/// The trailer block `message` ends in, if it does: a last paragraph,
+/// after the subject, of `Token: value` lines.
+pub fn trailer_block(message: &str) -> Option<&str> {
+    let (_, last) = message.trim_end().rsplit_once("\n\n")?;
+    last.lines()
+        .all(|line| {
+            line.split_once(": ")
+                .is_some_and(|(token, _)| !token.is_empty() && !token.contains(' '))
+        })
+        .then_some(last)
+}
+
+/// `message` with the trailer `token: value`, added to its trailer block if
+/// it ends in one.
+pub fn add_trailer(message: &str, token: &str, value: &str) -> String {
+    let message = message.trim_end();
+    let separator = if trailer_block(message).is_some() {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    format!("{}{}{}: {}", message, separator, token, value)
+}
+
+/// The parts of a Conventional Commits subject such as `feat(parser)!: text`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conventional<'a> {
+    pub kind: &'a str,
+    pub scope: Option<&'a str>,
+    /// Marked `!`, for a breaking change.
+    pub breaking: bool,
+    /// The text after the prefix.
+    pub description: &'a str,
+}
+
+/// `subject` split at its Conventional Commits prefix; `None` unless it
+/// starts with a lowercase type, an optional `(scope)` and `!`, and `: `.
+pub fn conventional(subject: &str) -> Option<Conventional<'_>> {
+    let (prefix, description) = subject.split_once(": ")?;
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+    let (kind, scope) = match prefix.split_once('(') {
+        Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?)),
+        None => (prefix, None),
+    };
+    if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+    Some(Conventional {
+        kind,
+        scope,
+        breaking,
+        description,
+    })
+}
+
+/// `message` with `paragraph` at the end of its body, before the trailer
+/// block if it ends in one.
+pub fn add_paragraph(message: &str, paragraph: &str) -> String {
+    let message = message.trim_end();
+    match trailer_block(message) {
+        Some(trailers) => {
+            let body = message[..message.len() - trailers.len()].trim_end();
+            format!("{}\n\n{}\n\n{}", body, paragraph, trailers)
+        }
+        None => format!("{}\n\n{}", message, paragraph),
+    }
+}
+
+/// Whether `message` is a single line too long to be a subject, as when
+/// the model writes one paragraph where a subject and body were wanted.
+pub fn is_run_on(message: &str) -> bool {
+    let message = message.trim();
+    !message.contains('\n') && message.chars().count() > MAX_SUBJECT_LEN
+}
+
+/// Byte offsets of the breaks between words in `text`, where a subject
+/// could end.
+pub fn split_points(text: &str) -> Vec<usize> {
+    text.char_indices()
+        .filter(|&(i, c)| c.is_whitespace() && i > 0 && !text[..i].ends_with(char::is_whitespace))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// The index in `points` of the best place to end the subject: the last
+/// sentence end that leaves a subject of at most [`MAX_SUBJECT_LEN`]
+/// characters, else the last clause break that does, else the last word
+/// break that does. `None` if there are no points.
+pub fn proposed_split(text: &str, points: &[usize]) -> Option<usize> {
+    let fits =
+        |&(_, &at): &(usize, &usize)| subject_part(text, at).chars().count() <= MAX_SUBJECT_LEN;
+    let ends_with = |marks: &[char]| {
+        points
+            .iter()
+            .enumerate()
+            .filter(fits)
+            .filter(|(_, &at)| text[..at].ends_with(marks))
+            .map(|(i, _)| i)
+            .next_back()
+    };
+    ends_with(&['.', '!', '?'])
+        .or_else(|| ends_with(&[',', ';', ':']))
+        .or_else(|| {
+            points
+                .iter()
+                .enumerate()
+                .filter(fits)
+                .map(|(i, _)| i)
+                .next_back()
+        })
+        .or((!points.is_empty()).then_some(0))
+}
+
+/// `text` up to `at`, without the punctuation that ended it there.
+fn subject_part(text: &str, at: usize) -> &str {
+    text[..at].trim_end_matches(['.', ',', ';', ':']).trim_end()
+}
+
+/// `text` split at `at` into a subject and a body: the subject loses the
+/// punctuation that ended it there, and the body starts with a capital and
+/// is wrapped like a full message's.
+pub fn split_at(text: &str, at: usize) -> String {
+    let subject = subject_part(text, at);
+    let rest = text[at..].trim();
+    let mut chars = rest.chars();
+    let body = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    };
+    let body = crate::full_message::wrap(&body, crate::full_message::BODY_WIDTH);
+    if body.is_empty() {
+        subject.to_string()
+    } else {
+        format!("{}\n\n{}", subject, body)
+    }
+}
+
+/// The subject of `message` and its body: everything after the subject,
+/// without the blank lines around it. The body may be empty.
+pub fn split(message: &str) -> (&str, &str) {
+    let message = message.trim();
+    match message.split_once('\n') {
+        Some((subject, body)) => (subject.trim_end(), body.trim()),
+        None => (message, ""),
+    }
+}
+
+/// The message with the subject of `subject_from` and the body of
+/// `body_from`, such as two candidates that are each half right.
+pub fn combine(subject_from: &str, body_from: &str) -> String {
+    let (subject, _) = split(subject_from);
+    match split(body_from) {
+        (_, "") => subject.to_string(),
+        (_, body) => format!("{}\n\n{}", subject, body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_emoji_and_smart_punctuation() {
+        assert_eq!(
+            to_ascii("✨ Add “fancy” parser — it’s fast…"),
+            "Add \"fancy\" parser - it's fast..."
+        );
+    }
+
+    #[test]
+    fn transliterates_accents_and_ligatures() {
+        assert_eq!(
+            to_ascii("Résumé ﬁeld for Zoë, naïve café №①"),
+            "Resume field for Zoe, naive cafe No1"
+        );
+        assert_eq!(to_ascii("Übersetze 東京"), "Ubersetze");
+    }
+
+    #[test]
+    fn keeps_body_layout() {
+        assert_eq!(
+            to_ascii("Fix crash 🐛\n\n- handle empty input\n  • nested"),
+            "Fix crash\n\n- handle empty input\n  * nested"
+        );
+    }
+
+    #[test]
+    fn lints_subject_and_layout() {
+        assert!(lint("Add parser\n\nWith tests.").is_empty());
+        assert_eq!(
+            lint("Add parser.\nWith tests."),
+            [
+                "Subject ends with a period",
+                "No blank line between subject and body"
+            ]
+        );
+        assert_eq!(lint(&"x".repeat(80)).len(), 1);
+    }
+
+    #[test]
+    fn combines_the_subject_and_body_of_two_messages() {
+        let terse = "Add retries to the uploader";
+        let detailed = "Retry failed chunk uploads\n\nTransient 503s failed the whole sync.\n\nRefs: SYNC-12\n";
+        assert_eq!(split(terse), (terse, ""));
+        assert_eq!(
+            split(detailed),
+            (
+                "Retry failed chunk uploads",
+                "Transient 503s failed the whole sync.\n\nRefs: SYNC-12"
+            )
+        );
+        assert_eq!(
+            combine(terse, detailed),
+            "Add retries to the uploader\n\nTransient 503s failed the whole sync.\n\nRefs: SYNC-12"
+        );
+        assert_eq!(combine(detailed, terse), "Retry failed chunk uploads");
+    }
+
+    #[test]
+    fn parses_conventional_prefixes() {
+        assert_eq!(
+            conventional("feat(parser)!: Drop v1 syntax"),
+            Some(Conventional {
+                kind: "feat",
+                scope: Some("parser"),
+                breaking: true,
+                description: "Drop v1 syntax",
+            })
+        );
+        assert_eq!(
+            conventional("fix: x").map(|c| (c.kind, c.scope)),
+            Some(("fix", None))
+        );
+        assert_eq!(conventional("Fix: x"), None);
+        assert_eq!(conventional("fix(http: x"), None);
+        assert_eq!(conventional("Fix the parser"), None);
+    }
+
+    #[test]
+    fn adds_paragraphs_before_the_trailers() {
+        assert_eq!(
+            add_paragraph("Fix login\n\nRefs: JIRA-123\n", "all 12 tests pass"),
+            "Fix login\n\nall 12 tests pass\n\nRefs: JIRA-123"
+        );
+        assert_eq!(
+            add_paragraph("Fix login", "all 12 tests pass"),
+            "Fix login\n\nall 12 tests pass"
+        );
+    }
+
+    #[test]
+    fn splits_a_run_on_message_into_subject_and_body() {
+        let text = "Add a retry loop to the uploader. Transient 503s from the storage API used to fail the whole sync, so each chunk is now retried three times.";
+        assert!(is_run_on(text));
+        assert!(!is_run_on("Add a retry loop\n\nbody"));
+        let points = split_points(text);
+        let at = points[proposed_split(text, &points).unwrap()];
+        assert_eq!(
+            split_at(text, at),
+            "Add a retry loop to the uploader\n\n\
+             Transient 503s from the storage API used to fail the whole sync, so each\n\
+             chunk is now retried three times."
+        );
+
+        // Without a sentence end that fits, a clause break, then a word break.
+        let text = "Retry uploads of each chunk on transient errors from the storage API, which used to fail the whole sync";
+        let points = split_points(text);
+        let at = points[proposed_split(text, &points).unwrap()];
+        assert_eq!(
+            &text[..at],
+            "Retry uploads of each chunk on transient errors from the storage API,"
+        );
+        let text = "word ".repeat(30);
+        let points = split_points(text.trim());
+        let at = points[proposed_split(text.trim(), &points).unwrap()];
+        assert_eq!(at, 69);
+    }
+
+    #[test]
+    fn moves_the_end_of_a_long_subject_into_the_body() {
+        let opts = GenerateOptions::new("key");
+        let long = "Add a retry loop to the uploader. Retry each chunk three times";
+        let message = format!("{} on transient errors\n\nThe sync used to fail.", long);
+        assert_eq!(
+            assemble(&message, &opts),
+            "Add a retry loop to the uploader\n\n\
+             Retry each chunk three times on transient errors\n\n\
+             The sync used to fail."
+        );
+        // A one-line message is left for the user to split.
+        let run_on = format!("{} on transient errors", long);
+        assert_eq!(assemble(&run_on, &opts), run_on);
+    }
+}
@@ -0,0 +1,227 @@
+//! Destinations for an accepted commit message.
+//!
+//! The end of the pipeline is a list of sinks, so one run can commit, copy the
+//! message to the clipboard and write it to a file.
+
+#[cfg(any(unix, feature = "clipboard"))]
+use std::io::Write;
+use std::path::PathBuf;
+#[cfg(feature = "clipboard")]
+use std::process::{Command, Stdio};
+
+use crate::forge::{self, RemoteRepo};
+use crate::{editmsg, git, CommitauraError};
+
+pub trait OutputSink {
+    /// Short description shown to the user, e.g. "clipboard".
+    fn describe(&self) -> String;
+    fn write(&self, message: &str) -> Result<(), CommitauraError>;
+}
+
+/// Commit the staged changes with the message, or amend them into HEAD.
+pub struct CommitSink {
+    pub amend: bool,
+    /// When amending, date the commit now instead of keeping HEAD's author date.
+    pub redate: bool,
+}
+
+impl OutputSink for CommitSink {
+    fn describe(&self) -> String {
+        if self.amend {
+            "amend".to_string()
+        } else {
+            "commit".to_string()
+        }
+    }
+
+    fn write(&self, message: &str) -> Result<(), CommitauraError> {
+        if self.amend && self.redate {
+            git::perform_git_amend_redated(message)
+        } else if self.amend {
+            git::perform_git_amend(message)
+        } else {
+            git::perform_git_commit(message)
+        }
+    }
+}
+
+/// Write the message to a file, replacing its contents.
+pub struct FileSink(pub PathBuf);
+
+impl OutputSink for FileSink {
+    fn describe(&self) -> String {
+        self.0.display().to_string()
+    }
+
+    fn write(&self, message: &str) -> Result<(), CommitauraError> {
+        std::fs::write(&self.0, format!("{}\n", message))?;
+        Ok(())
+    }
+}
+
+/// Merge the message into git's commit message file, keeping the
+/// `commit.template` sections and comments git put there.
+pub struct MessageFileSink(pub PathBuf);
+
+impl OutputSink for MessageFileSink {
+    fn describe(&self) -> String {
+        self.0.display().to_string()
+    }
+
+    fn write(&self, message: &str) -> Result<(), CommitauraError> {
+        editmsg::write_message_file(&self.0, message)
+    }
+}
+
+/// Print the bare message to stdout, for piping into other tools.
+pub struct StdoutSink;
+
+/// The real stdout, once [`StdoutSink::reserve`] pointed the process's
+/// stdout at stderr.
+#[cfg(unix)]
+static RESERVED: std::sync::OnceLock<std::fs::File> = std::sync::OnceLock::new();
+
+impl StdoutSink {
+    /// Keep stdout for the message alone: from now on, whatever else is
+    /// printed there, such as progress, prompts and previews, goes to
+    /// stderr instead. Only on Unix; elsewhere stdout is left as it is.
+    pub fn reserve() -> std::io::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::fd::FromRawFd;
+
+            if RESERVED.get().is_some() {
+                return Ok(());
+            }
+            std::io::stdout().flush()?;
+            // SAFETY: `dup` and `dup2` only take and return descriptors, and
+            // the descriptor `dup` returns is owned by nothing else.
+            unsafe {
+                let saved = libc::dup(libc::STDOUT_FILENO);
+                if saved < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let saved = std::fs::File::from_raw_fd(saved);
+                if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let _ = RESERVED.set(saved);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OutputSink for StdoutSink {
+    fn describe(&self) -> String {
+        "stdout".to_string()
+    }
+
+    fn write(&self, message: &str) -> Result<(), CommitauraError> {
+        #[cfg(unix)]
+        if let Some(mut stdout) = RESERVED.get() {
+            writeln!(stdout, "{}", message)?;
+            return Ok(());
+        }
+        println!("{}", message);
+        Ok(())
+    }
+}
+
+/// Copy the message with the platform's clipboard tool. Without the
+/// `clipboard` feature, writing fails.
+pub struct ClipboardSink;
+
+/// Clipboard commands to try, in order, on this platform.
+#[cfg(feature = "clipboard")]
+pub(crate) fn clipboard_commands() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+            // WSL can reach the Windows clipboard.
+            &["clip.exe"],
+        ]
+    }
+}
+
+impl OutputSink for ClipboardSink {
+    fn describe(&self) -> String {
+        "clipboard".to_string()
+    }
+
+    #[cfg(feature = "clipboard")]
+    fn write(&self, message: &str) -> Result<(), CommitauraError> {
+        for command in clipboard_commands() {
+            let Ok(mut child) = Command::new(command[0])
+                .args(&command[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+            else {
+                continue;
+            };
+            child
+                .stdin
+                .take()
+                .expect("stdin is piped")
+                .write_all(message.as_bytes())?;
+            if child.wait()?.success() {
+                return Ok(());
+            }
+        }
+        Err(CommitauraError::IoError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "no clipboard tool found (tried pbcopy, clip, wl-copy, xclip, xsel)",
+        )))
+    }
+
+    #[cfg(not(feature = "clipboard"))]
+    fn write(&self, _message: &str) -> Result<(), CommitauraError> {
+        Err(CommitauraError::ConfigError(
+            "Commitaura was built without the `clipboard` feature".to_string(),
+        ))
+    }
+}
+
+/// Push the current branch and open a pull request whose title and body are
+/// the message's subject and body.
+pub struct PrSink {
+    pub repo: RemoteRepo,
+}
+
+impl OutputSink for PrSink {
+    fn describe(&self) -> String {
+        self.repo.forge.pr_noun().to_string()
+    }
+
+    fn write(&self, message: &str) -> Result<(), CommitauraError> {
+        let (title, body) = message.split_once('\n').unwrap_or((message, ""));
+        let base = git::default_branch("origin");
+        let target = base.split_once('/').map(|(_, b)| b).unwrap_or(&base);
+        git::push_current_branch("origin")?;
+        let source = git::current_branch()?;
+        forge::create_pr(&self.repo, title.trim(), body.trim(), &source, target)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_sink_writes_message_with_trailing_newline() {
+        let path = std::env::temp_dir().join(format!("commitaura-sink-{}.txt", std::process::id()));
+        FileSink(path.clone()).write("Add sinks").unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, "Add sinks\n");
+    }
+}
@@ -0,0 +1,148 @@
+//! Terse and detailed candidates side by side, for `--pair`.
+//!
+//! Each vote is recorded on the journal entry of the chosen reply, see
+//! [`crate::journal`]. Once one style has clearly won in a repository,
+//! generation there uses it by default, unless a `prompt_style` is
+//! configured.
+//!
+//! A configured Conventional or gitmoji style is kept for both candidates,
+//! which differ only in length.
+
+use crate::candidates::{self, Candidate};
+use crate::generate::PromptStyle;
+use crate::journal::{self, JournalEntry};
+use crate::timings::Timings;
+use crate::{CommitauraError, GenerateOptions};
+
+/// Votes needed before the winning style becomes the default.
+pub const MIN_VOTES: u32 = 5;
+
+/// The two styles offered, in display order.
+pub const STYLES: [PromptStyle; 2] = [PromptStyle::Brief, PromptStyle::Detailed];
+
+/// How often each style was chosen in this repository.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub terse: u32,
+    pub detailed: u32,
+}
+
+impl Tally {
+    /// Count a vote for `style`; styles other than the two offered are ignored.
+    pub fn record(&mut self, style: PromptStyle) {
+        match style {
+            PromptStyle::Brief => self.terse += 1,
+            PromptStyle::Detailed => self.detailed += 1,
+            _ => {}
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.terse + self.detailed
+    }
+
+    /// The style to use by default: the one that won at least two votes in
+    /// three, once there are [`MIN_VOTES`].
+    pub fn preferred(&self) -> Option<PromptStyle> {
+        if self.total() < MIN_VOTES {
+            return None;
+        }
+        if self.terse * 3 >= self.total() * 2 {
+            Some(PromptStyle::Brief)
+        } else if self.detailed * 3 >= self.total() * 2 {
+            Some(PromptStyle::Detailed)
+        } else {
+            None
+        }
+    }
+}
+
+/// A label for one of the [`STYLES`].
+pub fn label(style: PromptStyle) -> &'static str {
+    match style {
+        PromptStyle::Brief => "Terse",
+        PromptStyle::Detailed => "Detailed",
+        _ => "Default",
+    }
+}
+
+/// The candidates of one round, with what each generation recorded.
+pub struct Pair {
+    /// One candidate in each of the [`STYLES`], in order.
+    pub candidates: Vec<Candidate>,
+    timings: Vec<Timings>,
+}
+
+/// One candidate in each of the [`STYLES`], requested at the same time.
+pub fn generate(diff: &str, opts: &GenerateOptions) -> Pair {
+    let variants: Vec<GenerateOptions> = STYLES
+        .iter()
+        .map(|style| {
+            let mut opts = opts.clone();
+            match opts.prompt_style {
+                PromptStyle::Conventional | PromptStyle::Gitmoji => {
+                    opts.length_style = Some(*style)
+                }
+                _ => opts.prompt_style = *style,
+            }
+            // Kept apart, so the journal entry of each reply is known.
+            opts.timings = Timings::default();
+            opts
+        })
+        .collect();
+    let timings = variants.iter().map(|v| v.timings.clone()).collect();
+    Pair {
+        candidates: candidates::generate_each(diff, variants),
+        timings,
+    }
+}
+
+/// The votes cast in `entries`.
+pub fn tally(entries: &[JournalEntry]) -> Tally {
+    let mut tally = Tally::default();
+    for style in entries.iter().filter_map(|e| e.vote) {
+        tally.record(style);
+    }
+    tally
+}
+
+/// The votes cast in this repository; none if its journal cannot be read.
+pub fn load() -> Tally {
+    journal::repo_journal()
+        .and_then(|path| journal::load(&path))
+        .map_or_else(|_| Tally::default(), |entries| tally(&entries))
+}
+
+/// Vote for candidate `i` of `pair`: record it on the journal entry of its
+/// reply, take its timings into `opts`, and return the new tally.
+pub fn vote(pair: &Pair, i: usize, opts: &GenerateOptions) -> Result<Tally, CommitauraError> {
+    opts.timings.absorb(&pair.timings[i]);
+    let (Some(path), Some(id)) = (&opts.journal, pair.timings[i].journal_entry()) else {
+        return Err(CommitauraError::ConfigError(
+            "the reply was not recorded in the journal".to_string(),
+        ));
+    };
+    journal::set_vote(path, id, STYLES[i])?;
+    Ok(tally(&journal::load(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_a_style_only_after_a_clear_win() {
+        let mut tally = Tally::default();
+        for _ in 0..4 {
+            tally.record(PromptStyle::Brief);
+        }
+        assert_eq!(tally.preferred(), None);
+        tally.record(PromptStyle::Detailed);
+        assert_eq!(tally.preferred(), Some(PromptStyle::Brief));
+        tally.record(PromptStyle::Detailed);
+        tally.record(PromptStyle::Detailed);
+        assert_eq!(tally.preferred(), None);
+        tally.record(PromptStyle::Conventional);
+        assert_eq!(tally.total(), 7);
+    }
+}
@@ -0,0 +1,216 @@
+//! What this build can do on this system, for `commitaura doctor`.
+//!
+//! Platform integrations are optional twice over: each is a cargo feature,
+//! so static musl and Windows ARM builds can leave out what does not build
+//! or work there, and each relies on tools that are looked up at run time
+//! rather than linked in.
+
+#[cfg(any(feature = "keychain", feature = "clipboard"))]
+use std::ffi::OsStr;
+use std::io::IsTerminal;
+#[cfg(any(feature = "keychain", feature = "clipboard"))]
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Whether a capability can be used here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// Compiled in and usable; says how, e.g. which tool was found.
+    Available(String),
+    /// Compiled in, but missing something on this system.
+    Unavailable(String),
+    /// Left out of this build; names the cargo feature that adds it.
+    NotCompiled(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capability {
+    pub name: &'static str,
+    pub status: Status,
+}
+
+/// The platform this binary was built for, e.g. `x86_64-linux-musl`, and
+/// whether it is statically linked.
+pub fn target() -> String {
+    let env = if cfg!(target_env = "musl") {
+        "-musl"
+    } else if cfg!(target_env = "msvc") {
+        "-msvc"
+    } else if cfg!(target_env = "gnu") {
+        "-gnu"
+    } else {
+        ""
+    };
+    let linking = if is_static() { ", static" } else { "" };
+    format!(
+        "{}-{}{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        env,
+        linking
+    )
+}
+
+/// Whether the C runtime is linked in statically, as in musl release builds.
+pub fn is_static() -> bool {
+    cfg!(target_feature = "crt-static")
+}
+
+/// Whether dynamic libraries can be loaded at run time. A static binary on
+/// Unix has no dynamic loader; Windows can always load DLLs.
+pub fn can_load_libraries() -> bool {
+    !(cfg!(unix) && is_static())
+}
+
+/// Every optional capability and whether it can be used.
+pub fn capabilities() -> Vec<Capability> {
+    vec![
+        Capability {
+            name: "git",
+            status: git(),
+        },
+        Capability {
+            name: "libgit2",
+            status: libgit2(),
+        },
+        Capability {
+            name: "keychain",
+            status: keychain(),
+        },
+        Capability {
+            name: "clipboard",
+            status: clipboard(),
+        },
+        Capability {
+            name: "plugins",
+            status: plugins(),
+        },
+        Capability {
+            name: "terminal",
+            status: terminal(),
+        },
+    ]
+}
+
+/// Where `program` is found on `PATH`, trying `.exe` on Windows.
+#[cfg(any(feature = "keychain", feature = "clipboard"))]
+pub(crate) fn find_program(program: impl AsRef<OsStr>) -> Option<PathBuf> {
+    let program = program.as_ref();
+    let path = PathBuf::from(program);
+    if path.components().count() > 1 {
+        return path.is_file().then_some(path);
+    }
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var).find_map(|dir| {
+        let candidate = dir.join(program);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        let exe = candidate.with_extension("exe");
+        (cfg!(windows) && exe.is_file()).then_some(exe)
+    })
+}
+
+fn git() -> Status {
+    match Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => {
+            Status::Available(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+        _ => Status::Unavailable("`git` was not found on PATH".to_string()),
+    }
+}
+
+#[cfg(feature = "libgit2")]
+fn libgit2() -> Status {
+    let (major, minor, patch) = git2::Version::get().libgit2_version();
+    Status::Available(format!("libgit2 {}.{}.{}", major, minor, patch))
+}
+
+#[cfg(not(feature = "libgit2"))]
+fn libgit2() -> Status {
+    Status::NotCompiled("libgit2")
+}
+
+#[cfg(feature = "keychain")]
+fn keychain() -> Status {
+    let found: Vec<String> = crate::keychain::available()
+        .into_iter()
+        .filter_map(|backend| {
+            let tool = find_program(backend.program())?;
+            Some(format!("{} ({})", backend.name(), tool.display()))
+        })
+        .collect();
+    if found.is_empty() {
+        Status::Unavailable("no credential store tool found".to_string())
+    } else {
+        Status::Available(found.join(", "))
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+fn keychain() -> Status {
+    Status::NotCompiled("keychain")
+}
+
+#[cfg(feature = "clipboard")]
+fn clipboard() -> Status {
+    let commands = crate::output::clipboard_commands();
+    match commands.iter().find_map(|command| find_program(command[0])) {
+        Some(tool) => Status::Available(tool.display().to_string()),
+        None => Status::Unavailable(format!(
+            "no clipboard tool found (tried {})",
+            commands
+                .iter()
+                .map(|command| command[0])
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn clipboard() -> Status {
+    Status::NotCompiled("clipboard")
+}
+
+fn plugins() -> Status {
+    if !cfg!(feature = "plugins") {
+        Status::NotCompiled("plugins")
+    } else if !can_load_libraries() {
+        Status::Unavailable("a static build cannot load dynamic libraries".to_string())
+    } else {
+        Status::Available("dynamic libraries".to_string())
+    }
+}
+
+fn terminal() -> Status {
+    if !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal()) {
+        return Status::Unavailable("not a terminal; interactive choices are skipped".to_string());
+    }
+    let colors = std::env::var_os("NO_COLOR").is_none()
+        && std::env::var("TERM").map_or(true, |term| term != "dumb");
+    Status::Available(if colors {
+        "interactive, colors".to_string()
+    } else {
+        "interactive, no colors".to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_features_left_out_of_the_build() {
+        let names: Vec<&str> = capabilities().iter().map(|c| c.name).collect();
+        assert_eq!(
+            names,
+            ["git", "libgit2", "keychain", "clipboard", "plugins", "terminal"]
+        );
+        let plugins = capabilities().into_iter().find(|c| c.name == "plugins");
+        if !cfg!(feature = "plugins") {
+            assert_eq!(plugins.unwrap().status, Status::NotCompiled("plugins"));
+        }
+        assert!(target().starts_with(std::env::consts::ARCH));
+    }
+}
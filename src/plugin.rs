@@ -0,0 +1,376 @@
+//! In-process pipeline stages loaded from dynamic libraries.
+//!
+//! Subprocess-free extensions, such as custom redactors or semantic
+//! analyzers, are built as `cdylib` crates exporting a C-ABI declaration.
+//! Only `#[repr(C)]` types and `extern "C"` functions cross the boundary, so
+//! a plugin does not need to be built with the same compiler as Commitaura.
+//!
+//! A plugin exports [`DECLARATION_SYMBOL`], a function returning a pointer
+//! to a static [`PluginDeclaration`]. Its first field is the ABI version the
+//! plugin was built against; nothing else is read unless it equals
+//! [`ABI_VERSION`], which changes whenever the layout of these types does.
+//!
+//! ```ignore
+//! use commitaura::plugin::{Output, PluginDeclaration, StageDeclaration, StrRef};
+//!
+//! extern "C" fn shout(input: StrRef, output: &Output) -> i32 {
+//!     output.write_str(&unsafe { input.as_str() }.to_uppercase());
+//!     0
+//! }
+//!
+//! static STAGES: [StageDeclaration; 1] = [StageDeclaration {
+//!     name: c"shout".as_ptr(),
+//!     phase: commitaura::plugin::PHASE_MESSAGE,
+//!     run: shout,
+//! }];
+//! static PLUGIN: PluginDeclaration = PluginDeclaration::new(c"shouting".as_ptr(), &STAGES);
+//!
+//! #[no_mangle]
+//! pub extern "C" fn commitaura_plugin_declaration() -> *const PluginDeclaration {
+//!     &PLUGIN
+//! }
+//! ```
+//!
+//! Loading requires the `plugins` feature.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::path::PathBuf;
+
+use crate::diff;
+use crate::pipeline::{Context, Phase, Registry, Stage};
+use crate::CommitauraError;
+
+/// Version of the types in this module. Plugins declaring another version
+/// are refused.
+pub const ABI_VERSION: u32 = 1;
+
+/// Name of the function a plugin exports to declare itself.
+pub const DECLARATION_SYMBOL: &str = "commitaura_plugin_declaration";
+
+/// The stage receives the staged diff and returns a replacement diff.
+pub const PHASE_DIFF: u32 = 0;
+/// The stage receives the message (or the raw reply) and returns a new one.
+pub const PHASE_MESSAGE: u32 = 2;
+
+/// Borrowed UTF-8 text passed to a stage.
+#[repr(C)]
+pub struct StrRef {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl StrRef {
+    fn new(text: &str) -> StrRef {
+        StrRef {
+            ptr: text.as_ptr(),
+            len: text.len(),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `ptr` and `len` must describe valid UTF-8 that outlives the result,
+    /// as they do for the input a stage is called with.
+    pub unsafe fn as_str<'a>(&self) -> &'a str {
+        std::str::from_utf8_unchecked(std::slice::from_raw_parts(self.ptr, self.len))
+    }
+}
+
+/// Where a stage writes its result, or an error message when it fails.
+/// The buffer belongs to Commitaura, so no memory changes hands.
+#[repr(C)]
+pub struct Output {
+    ctx: *mut c_void,
+    write: extern "C" fn(ctx: *mut c_void, ptr: *const u8, len: usize),
+}
+
+impl Output {
+    /// Append `text` to the output.
+    pub fn write_str(&self, text: &str) {
+        (self.write)(self.ctx, text.as_ptr(), text.len());
+    }
+}
+
+extern "C" fn append_to_string(ctx: *mut c_void, ptr: *const u8, len: usize) {
+    // SAFETY: `ctx` is the `String` set up by `call_stage`, and the plugin
+    // passes text it owns for the duration of the call.
+    let (out, bytes) = unsafe {
+        (
+            &mut *(ctx as *mut String),
+            std::slice::from_raw_parts(ptr, len),
+        )
+    };
+    out.push_str(&String::from_utf8_lossy(bytes));
+}
+
+/// A stage's entry point. Returns 0 on success; anything else is a failure
+/// described by what was written to `output`.
+pub type StageFn = extern "C" fn(input: StrRef, output: &Output) -> i32;
+
+#[repr(C)]
+pub struct StageDeclaration {
+    /// NUL-terminated stage name used in `[pipeline] stages`.
+    pub name: *const c_char,
+    /// [`PHASE_DIFF`] or [`PHASE_MESSAGE`].
+    pub phase: u32,
+    pub run: StageFn,
+}
+
+// SAFETY: declarations are immutable statics of the plugin.
+unsafe impl Sync for StageDeclaration {}
+
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// Must stay the first field; see the module documentation.
+    pub abi_version: u32,
+    /// NUL-terminated plugin name, used in error messages.
+    pub name: *const c_char,
+    pub stages: *const StageDeclaration,
+    pub stage_count: usize,
+}
+
+// SAFETY: declarations are immutable statics of the plugin.
+unsafe impl Sync for PluginDeclaration {}
+
+impl PluginDeclaration {
+    /// A declaration for the current [`ABI_VERSION`].
+    pub const fn new(
+        name: *const c_char,
+        stages: &'static [StageDeclaration],
+    ) -> PluginDeclaration {
+        PluginDeclaration {
+            abi_version: ABI_VERSION,
+            name,
+            stages: stages.as_ptr(),
+            stage_count: stages.len(),
+        }
+    }
+}
+
+fn call_stage(run: StageFn, input: &str) -> (i32, String) {
+    let mut out = String::new();
+    let output = Output {
+        ctx: &mut out as *mut String as *mut c_void,
+        write: append_to_string,
+    };
+    let status = run(StrRef::new(input), &output);
+    (status, out)
+}
+
+/// A pipeline stage implemented by a plugin.
+struct PluginStage {
+    name: &'static str,
+    phase: Phase,
+    run: StageFn,
+}
+
+impl Stage for PluginStage {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    fn run(&self, ctx: &mut Context) -> Result<(), CommitauraError> {
+        let input = match self.phase {
+            Phase::Diff => diff::render(&ctx.files),
+            _ => ctx
+                .message
+                .clone()
+                .or_else(|| ctx.reply.clone())
+                .unwrap_or_default(),
+        };
+        let (status, output) = call_stage(self.run, &input);
+        if status != 0 {
+            return Err(CommitauraError::ConfigError(format!(
+                "Plugin stage '{}' failed: {}",
+                self.name,
+                output.trim()
+            )));
+        }
+        match self.phase {
+            Phase::Diff => ctx.files = diff::parse(&output),
+            _ => ctx.message = Some(output),
+        }
+        Ok(())
+    }
+}
+
+/// The stages `declaration` provides, after checking its ABI version.
+///
+/// # Safety
+///
+/// `declaration` must point to a valid declaration whose strings and stage
+/// array stay valid for the rest of the program.
+pub unsafe fn stages_from_declaration(
+    declaration: *const PluginDeclaration,
+) -> Result<Vec<Box<dyn Stage>>, CommitauraError> {
+    let declaration = declaration.as_ref().ok_or_else(|| {
+        CommitauraError::ConfigError("Plugin returned no declaration".to_string())
+    })?;
+    if declaration.abi_version != ABI_VERSION {
+        return Err(CommitauraError::ConfigError(format!(
+            "Plugin was built for plugin ABI {}, this Commitaura supports {}",
+            declaration.abi_version, ABI_VERSION
+        )));
+    }
+    let plugin = CStr::from_ptr(declaration.name).to_string_lossy();
+    let declared = if declaration.stage_count == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(declaration.stages, declaration.stage_count)
+    };
+    let mut stages: Vec<Box<dyn Stage>> = Vec::new();
+    for stage in declared {
+        let name = CStr::from_ptr(stage.name).to_str().map_err(|_| {
+            CommitauraError::ConfigError(format!("Plugin '{}' has a non-UTF-8 stage name", plugin))
+        })?;
+        let phase = match stage.phase {
+            PHASE_DIFF => Phase::Diff,
+            PHASE_MESSAGE => Phase::Message,
+            other => {
+                return Err(CommitauraError::ConfigError(format!(
+                    "Plugin '{}' stage '{}' has unknown phase {}",
+                    plugin, name, other
+                )))
+            }
+        };
+        stages.push(Box::new(PluginStage {
+            name,
+            phase,
+            run: stage.run,
+        }));
+    }
+    Ok(stages)
+}
+
+/// Load the plugin library at `path` and return its stages. The library
+/// stays loaded for the rest of the process, since its stages are.
+///
+/// # Safety
+///
+/// Loading a library runs its initialisation code, and its stages run
+/// in-process with no sandboxing: only load plugins you trust.
+#[cfg(feature = "plugins")]
+pub unsafe fn load(path: &std::path::Path) -> Result<Vec<Box<dyn Stage>>, CommitauraError> {
+    let context = |e: &dyn std::fmt::Display| {
+        CommitauraError::ConfigError(format!("Plugin {}: {}", path.display(), e))
+    };
+    let library = libloading::Library::new(path).map_err(|e| context(&e))?;
+    let declare: libloading::Symbol<extern "C" fn() -> *const PluginDeclaration> = library
+        .get(DECLARATION_SYMBOL.as_bytes())
+        .map_err(|e| context(&e))?;
+    let stages = stages_from_declaration(declare()).map_err(|e| context(&e))?;
+    std::mem::forget(library);
+    Ok(stages)
+}
+
+/// Load the plugins at `paths` and add their stages to `registry`.
+///
+/// Libraries inside the repository's working tree are refused, so checking
+/// out a repository can never make Commitaura run code from it.
+pub fn register_plugins(registry: &mut Registry, paths: &[PathBuf]) -> Result<(), CommitauraError> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let worktree = crate::git::toplevel()?.canonicalize()?;
+    for path in paths {
+        let path = path.canonicalize().map_err(|e| {
+            CommitauraError::ConfigError(format!("Plugin {}: {}", path.display(), e))
+        })?;
+        if path.starts_with(&worktree) {
+            return Err(CommitauraError::ConfigError(format!(
+                "Plugin {} is inside the repository; install plugins elsewhere",
+                path.display()
+            )));
+        }
+        load_into(registry, &path)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "plugins")]
+fn load_into(registry: &mut Registry, path: &std::path::Path) -> Result<(), CommitauraError> {
+    if !crate::platform::can_load_libraries() {
+        return Err(CommitauraError::ConfigError(
+            "Plugins are configured but this static build cannot load dynamic libraries"
+                .to_string(),
+        ));
+    }
+    // SAFETY: the user listed this library in their configuration, and it is
+    // not part of the checked-out repository.
+    for stage in unsafe { load(path)? } {
+        registry.register(stage);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "plugins"))]
+fn load_into(_registry: &mut Registry, _path: &std::path::Path) -> Result<(), CommitauraError> {
+    Err(CommitauraError::ConfigError(
+        "Plugins are configured but Commitaura was built without the `plugins` feature".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn shout(input: StrRef, output: &Output) -> i32 {
+        output.write_str(&unsafe { input.as_str() }.to_uppercase());
+        0
+    }
+
+    extern "C" fn refuse(_input: StrRef, output: &Output) -> i32 {
+        output.write_str("not today");
+        1
+    }
+
+    static STAGES: [StageDeclaration; 2] = [
+        StageDeclaration {
+            name: c"shout".as_ptr(),
+            phase: PHASE_MESSAGE,
+            run: shout,
+        },
+        StageDeclaration {
+            name: c"refuse".as_ptr(),
+            phase: PHASE_DIFF,
+            run: refuse,
+        },
+    ];
+    static PLUGIN: PluginDeclaration = PluginDeclaration::new(c"test".as_ptr(), &STAGES);
+
+    fn context() -> Context {
+        Context {
+            files: Vec::new(),
+            opts: crate::GenerateOptions::new("key"),
+            reply: Some("add retries".to_string()),
+            message: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn runs_declared_stages() {
+        let stages = unsafe { stages_from_declaration(&PLUGIN) }.unwrap();
+        assert_eq!(stages[0].name(), "shout");
+        assert_eq!(stages[1].phase(), Phase::Diff);
+
+        let mut ctx = context();
+        stages[0].run(&mut ctx).unwrap();
+        assert_eq!(ctx.message.as_deref(), Some("ADD RETRIES"));
+        let error = stages[1].run(&mut ctx).unwrap_err().to_string();
+        assert!(error.ends_with("Plugin stage 'refuse' failed: not today"));
+    }
+
+    #[test]
+    fn refuses_other_abi_versions() {
+        let old = PluginDeclaration {
+            abi_version: 0,
+            ..PluginDeclaration::new(c"old".as_ptr(), &STAGES)
+        };
+        assert!(unsafe { stages_from_declaration(&old) }.is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Pull request title and description generation for a branch.
+
+use crate::forge::Forge;
+use crate::generate::{complete, GenerateOptions, Prompt};
+use crate::{message, release, CommitauraError};
+
+const SYSTEM_MESSAGE: &str =
+    "You are a helpful assistant that writes clear, reviewer-friendly pull request descriptions.";
+
+/// Response tokens for the title and description together.
+const MAX_RESPONSE_TOKENS: u32 = 800;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrDescription {
+    pub title: String,
+    pub body: String,
+}
+
+impl PrDescription {
+    /// Split a reply into its first line (the title) and the rest (the body).
+    pub fn parse(reply: &str) -> Option<PrDescription> {
+        let reply = reply.trim();
+        let (title, body) = reply.split_once('\n').unwrap_or((reply, ""));
+        let title = title.trim().trim_start_matches('#').trim();
+        if title.is_empty() {
+            return None;
+        }
+        Some(PrDescription {
+            title: title.to_string(),
+            body: body.trim().to_string(),
+        })
+    }
+}
+
+/// Build the PR prompt, asking for the Markdown dialect `forge` renders.
+pub fn build_pr_prompt(
+    diff: &str,
+    commits: &str,
+    forge: Forge,
+    opts: &GenerateOptions,
+) -> Result<Prompt, CommitauraError> {
+    let instructions = format!(
+        "Write a {noun} title and description for the following branch. Reply with the title on the first line, a blank line, then a description in {flavor} that explains what changed and why, in at most {limit} characters. Be specific and do not include any other text.",
+        noun = forge.pr_noun(),
+        flavor = forge.markdown_flavor(),
+        limit = forge.description_limit(),
+    );
+    release::range_prompt(
+        SYSTEM_MESSAGE,
+        &instructions,
+        commits,
+        diff,
+        MAX_RESPONSE_TOKENS,
+        opts,
+    )
+}
+
+/// Generate a PR title and body from the branch's commits and diff.
+pub fn generate_pr_description(
+    diff: &str,
+    commits: &str,
+    forge: Forge,
+    opts: &GenerateOptions,
+) -> Result<PrDescription, CommitauraError> {
+    let prompt = build_pr_prompt(diff, commits, forge, opts)?;
+    let reply = complete(&prompt, opts, MAX_RESPONSE_TOKENS)?;
+    PrDescription::parse(&message::tidy(&reply, opts)).ok_or(CommitauraError::ApiRequestFailed(
+        "Received empty PR description from LLM.".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_title_and_body() {
+        let pr = PrDescription::parse("# Add reword command\n\n## Summary\n- adds it\n").unwrap();
+        assert_eq!(pr.title, "Add reword command");
+        assert_eq!(pr.body, "## Summary\n- adds it");
+        assert_eq!(PrDescription::parse("  \n"), None);
+    }
+}
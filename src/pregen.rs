@@ -0,0 +1,210 @@
+//! Messages generated ahead of time by `commitaura watch`, so a later commit
+//! can use them instantly.
+//!
+//! The cache holds one entry, keyed by a hash of the model, endpoint and
+//! full prompt. Any change to the index changes the diff and with it the
+//! prompt, so a stale entry is never used. Pre-generation is rate limited,
+//! since it spends tokens on changes that may never be committed as they
+//! are.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::generate::{build_prompt, generate_message, GenerateOptions, Prompt};
+use crate::{git, CommitauraError};
+
+const CACHE_FILE: &str = "pregenerated.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    message: String,
+}
+
+/// The cache of the current repository, in its `.git/commitaura/` directory.
+pub fn repo_cache() -> Result<PathBuf, CommitauraError> {
+    Ok(git::git_dir()?.join("commitaura").join(CACHE_FILE))
+}
+
+/// FNV-1a, which unlike the standard hasher is stable across builds.
+pub(crate) fn fnv1a(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        for byte in part.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// The cache key for sending `prompt` with `opts`.
+pub fn key(prompt: &Prompt, opts: &GenerateOptions) -> String {
+    let sampling = format!("{} {}", opts.temperature, opts.max_response_tokens);
+    format!(
+        "{:016x}",
+        fnv1a(&[
+            &opts.api_base,
+            &opts.model,
+            &sampling,
+            &prompt.system,
+            &prompt.user
+        ])
+    )
+}
+
+/// The pre-generated message for `key`, if the cache at `path` has it.
+pub fn lookup(path: &Path, key: &str) -> Option<String> {
+    let entry: Entry = serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()?;
+    (entry.key == key).then_some(entry.message)
+}
+
+pub fn store(path: &Path, key: &str, message: &str) -> Result<(), CommitauraError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let entry = Entry {
+        key: key.to_string(),
+        message: message.to_string(),
+    };
+    std::fs::write(
+        path,
+        serde_json::to_string(&entry).map_err(std::io::Error::from)?,
+    )?;
+    Ok(())
+}
+
+/// The message pre-generated for `diff` with `opts`, if there is one.
+pub fn cached(diff: &str, opts: &GenerateOptions) -> Option<String> {
+    let prompt = build_prompt(diff, opts).ok()?;
+    lookup(&repo_cache().ok()?, &key(&prompt, opts))
+}
+
+/// Limits on how often the model is asked in the background.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    min_interval: Duration,
+    max_per_hour: usize,
+    recent: Vec<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(min_interval: Duration, max_per_hour: usize) -> RateLimiter {
+        RateLimiter {
+            min_interval,
+            max_per_hour,
+            recent: Vec::new(),
+        }
+    }
+
+    /// Whether a generation may start at `now`.
+    pub fn allows(&mut self, now: Instant) -> bool {
+        let hour = Duration::from_secs(3600);
+        self.recent.retain(|t| now.duration_since(*t) < hour);
+        let spaced = self
+            .recent
+            .last()
+            .is_none_or(|last| now.duration_since(*last) >= self.min_interval);
+        spaced && self.recent.len() < self.max_per_hour
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        self.recent.push(now);
+    }
+}
+
+/// How long to wait before trying again after a transient failure, given
+/// the wait before the last try, if it failed too: doubling from 30
+/// seconds up to 15 minutes.
+pub fn retry_delay(previous: Option<Duration>) -> Duration {
+    match previous {
+        Some(delay) => (delay * 2).min(Duration::from_secs(15 * 60)),
+        None => Duration::from_secs(30),
+    }
+}
+
+/// What [`pregenerate`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The cache already had a message for these changes.
+    Cached,
+    /// The prompt is over the token limit for background generation.
+    TooLarge(usize),
+    /// A message was generated and stored.
+    Generated(String),
+}
+
+/// Generate and cache a message for `diff` unless one is cached already or
+/// the prompt is larger than `max_prompt_tokens`.
+pub fn pregenerate(
+    diff: &str,
+    opts: &GenerateOptions,
+    max_prompt_tokens: usize,
+) -> Result<Outcome, CommitauraError> {
+    let prompt = build_prompt(diff, opts)?;
+    let key = key(&prompt, opts);
+    let path = repo_cache()?;
+    if lookup(&path, &key).is_some() {
+        return Ok(Outcome::Cached);
+    }
+    let tokenizer = opts.tokenizer();
+    let tokens = tokenizer.count(&prompt.system)? + tokenizer.count(&prompt.user)?;
+    if tokens > max_prompt_tokens {
+        return Ok(Outcome::TooLarge(tokens));
+    }
+    let message = generate_message(diff, opts)?;
+    store(&path, &key, &message)?;
+    Ok(Outcome::Generated(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_change_with_the_prompt_and_model() {
+        let opts = GenerateOptions::new("key");
+        let prompt = build_prompt("diff --git a/x b/x\n+one\n", &opts).unwrap();
+        let changed = build_prompt("diff --git a/x b/x\n+two\n", &opts).unwrap();
+        assert_eq!(key(&prompt, &opts), key(&prompt, &opts));
+        assert_ne!(key(&prompt, &opts), key(&changed, &opts));
+        assert_ne!(
+            key(&prompt, &opts),
+            key(&prompt, &opts.with_model("gpt-4o-mini"))
+        );
+
+        let path = std::env::temp_dir().join(format!("commitaura-pregen-{}", std::process::id()));
+        store(&path, &key(&prompt, &opts), "Add one").unwrap();
+        assert_eq!(
+            lookup(&path, &key(&prompt, &opts)).as_deref(),
+            Some("Add one")
+        );
+        assert_eq!(lookup(&path, &key(&changed, &opts)), None);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn limits_rate() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        assert!(limiter.allows(start));
+        limiter.record(start);
+        assert!(!limiter.allows(start + Duration::from_secs(30)));
+        limiter.record(start + Duration::from_secs(60));
+        assert!(!limiter.allows(start + Duration::from_secs(600)));
+        assert!(limiter.allows(start + Duration::from_secs(3601)));
+    }
+
+    #[test]
+    fn backs_off_up_to_a_limit() {
+        let first = retry_delay(None);
+        assert_eq!(first, Duration::from_secs(30));
+        assert_eq!(retry_delay(Some(first)), Duration::from_secs(60));
+        assert_eq!(
+            retry_delay(Some(Duration::from_secs(600))),
+            Duration::from_secs(900)
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Approximate list prices, context windows and output limits of hosted
+//! models, for cost hints, the model picker and sizing the prompt.
+//!
+//! Prices are USD per million tokens and only as current as this table; they
+//! are meant for comparing options, not for billing.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+    /// Context window in tokens.
+    pub context_window: usize,
+    /// Longest reply the model can write, in tokens.
+    pub max_output_tokens: u32,
+}
+
+const PRICES: &[(&str, ModelPricing)] = &[
+    ("gpt-4o-mini", price(0.15, 0.60, 128_000, 16_384)),
+    ("gpt-4o", price(2.50, 10.00, 128_000, 16_384)),
+    ("gpt-4.1-nano", price(0.10, 0.40, 1_047_576, 32_768)),
+    ("gpt-4.1-mini", price(0.40, 1.60, 1_047_576, 32_768)),
+    ("gpt-4.1", price(2.00, 8.00, 1_047_576, 32_768)),
+    ("gpt-4-turbo", price(10.00, 30.00, 128_000, 4_096)),
+    ("gpt-3.5-turbo", price(0.50, 1.50, 16_385, 4_096)),
+    ("o3-mini", price(1.10, 4.40, 200_000, 100_000)),
+    ("o4-mini", price(1.10, 4.40, 200_000, 100_000)),
+    ("claude-haiku-4-5", price(1.00, 5.00, 200_000, 64_000)),
+    ("claude-3-5-haiku", price(0.80, 4.00, 200_000, 8_192)),
+    ("claude-sonnet-4", price(3.00, 15.00, 200_000, 64_000)),
+    ("claude-opus-4", price(15.00, 75.00, 200_000, 32_000)),
+];
+
+const fn price(
+    input_per_million: f64,
+    output_per_million: f64,
+    context_window: usize,
+    max_output_tokens: u32,
+) -> ModelPricing {
+    ModelPricing {
+        input_per_million,
+        output_per_million,
+        context_window,
+        max_output_tokens,
+    }
+}
+
+/// Every model family in the table.
+pub fn known_models() -> Vec<&'static str> {
+    PRICES.iter().map(|(name, _)| *name).collect()
+}
+
+/// Look up a model by name. Dated snapshots such as `gpt-4o-2024-08-06`
+/// resolve to their family via the longest matching prefix.
+pub fn pricing(model: &str) -> Option<ModelPricing> {
+    PRICES
+        .iter()
+        .filter(|(name, _)| model.starts_with(name))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, pricing)| *pricing)
+}
+
+/// Estimated cost in USD of a request, if the model's price is known.
+pub fn estimate_cost(model: &str, input_tokens: usize, output_tokens: usize) -> Option<f64> {
+    let p = pricing(model)?;
+    Some(
+        (input_tokens as f64 * p.input_per_million + output_tokens as f64 * p.output_per_million)
+            / 1_000_000.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_snapshots_to_the_longest_prefix() {
+        assert_eq!(pricing("gpt-4o-2024-08-06"), pricing("gpt-4o"));
+        assert_eq!(pricing("gpt-4o-mini-2024-07-18"), pricing("gpt-4o-mini"));
+        assert_ne!(pricing("gpt-4o-mini"), pricing("gpt-4o"));
+        assert_eq!(pricing("llama3"), None);
+    }
+
+    #[test]
+    fn estimates_cost() {
+        let cost = estimate_cost("gpt-4o", 1_000_000, 0).unwrap();
+        assert!((cost - 2.50).abs() < 1e-9);
+    }
+}
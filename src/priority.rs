@@ -0,0 +1,287 @@
+//! Which parts of a diff to keep when it has to be truncated.
+//!
+//! Cutting the diff at the token limit always hides its tail, however
+//! important. Instead, files are ranked by what they are, source code
+//! before tests, tests before documentation and documentation before
+//! assets, and smaller files before larger ones within a rank. Hunks are
+//! then kept in that order while they fit; one that does not is cut short
+//! while some room is left. The diff keeps its order, a file with hunks
+//! left out says so in its header, and files left out entirely are listed
+//! at the end, so the model knows they changed.
+
+use crate::diff::{self, FileDiff, Hunk};
+use crate::tokenizer::Tokenizer;
+use crate::CommitauraError;
+
+/// What a changed file is, most important first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    Source,
+    Test,
+    Docs,
+    Asset,
+}
+
+/// Directories whose files are tests.
+const TEST_DIRS: &[&str] = &["test", "tests", "__tests__", "spec", "specs", "testdata"];
+
+/// Directories whose files are documentation.
+const DOC_DIRS: &[&str] = &["doc", "docs", "documentation"];
+
+const DOC_EXTENSIONS: &[&str] = &["md", "markdown", "rst", "adoc", "txt", "org"];
+
+/// File names, without extension, of documentation at any level.
+const DOC_NAMES: &[&str] = &[
+    "readme",
+    "changelog",
+    "changes",
+    "license",
+    "copying",
+    "authors",
+    "contributing",
+    "notice",
+];
+
+const ASSET_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "icns", "svg", "webp", "avif", "tiff", "psd",
+    "woff", "woff2", "ttf", "otf", "eot", "mp3", "mp4", "wav", "ogg", "webm", "mov", "pdf", "zip",
+    "gz", "tar",
+];
+
+/// Least room left for showing the start of a hunk that does not fit.
+const MIN_PARTIAL_TOKENS: usize = 64;
+
+/// Most of the budget spent listing files left out entirely.
+const MAX_NOTE_SHARE: usize = 10;
+
+/// The category of the file at `path`.
+pub fn category(path: &str) -> Category {
+    let lower = path.to_lowercase();
+    let (dirs, name) = match lower.rsplit_once('/') {
+        Some((dirs, name)) => (dirs, name),
+        None => ("", lower.as_str()),
+    };
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    if ASSET_EXTENSIONS.contains(&extension) {
+        return Category::Asset;
+    }
+    let in_dir = |names: &[&str]| dirs.split('/').any(|dir| names.contains(&dir));
+    if in_dir(TEST_DIRS)
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_tests")
+        || stem.ends_with("_spec")
+        || stem.ends_with(".test")
+        || stem.ends_with(".spec")
+        || is_camel_case_test(path)
+    {
+        return Category::Test;
+    }
+    if in_dir(DOC_DIRS) || DOC_EXTENSIONS.contains(&extension) || DOC_NAMES.contains(&stem) {
+        return Category::Docs;
+    }
+    Category::Source
+}
+
+/// Whether the file at `path` is named like `ParserTest.java`.
+fn is_camel_case_test(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let stem = name.split('.').next().unwrap_or(name);
+    stem.len() > 4 && (stem.ends_with("Test") || stem.ends_with("Tests"))
+}
+
+/// `diff` cut down to `available` tokens, as `tokenizer` counts them, by
+/// keeping its most important hunks; `diff` itself if it fits.
+pub fn truncate(
+    diff: &str,
+    available: usize,
+    tokenizer: Tokenizer,
+) -> Result<String, CommitauraError> {
+    if tokenizer.count(diff)? <= available {
+        return Ok(diff.to_string());
+    }
+    let mut files = diff::parse(diff);
+    if files.is_empty() {
+        return tokenizer.truncate(diff, available);
+    }
+    let sizes = files
+        .iter()
+        .map(|file| tokenizer.count(&file.to_patch()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut order: Vec<usize> = (0..files.len()).collect();
+    order.sort_by_key(|&i| (category(&files[i].path), files[i].binary, sizes[i]));
+
+    // Room for listing the files that do not fit at all, at worst all.
+    let note_room = tokenizer
+        .count(&left_out_note(&files.iter().collect::<Vec<_>>()))?
+        .min(available / MAX_NOTE_SHARE);
+    let mut left = available.saturating_sub(note_room);
+    // Lines kept of each hunk; a hunk that does not fit is left out, or
+    // cut short if it is the first not to fit while room is left.
+    let mut kept: Vec<Vec<usize>> = files.iter().map(|file| vec![0; file.hunks.len()]).collect();
+    let mut shown = vec![false; files.len()];
+    for &i in &order {
+        let header = tokenizer.count(&files[i].header.join("\n"))? + 1;
+        if files[i].hunks.is_empty() {
+            if header <= left {
+                left -= header;
+                shown[i] = true;
+            }
+            continue;
+        }
+        for (h, hunk) in files[i].hunks.iter().enumerate() {
+            let header = if shown[i] { 0 } else { header };
+            let head = tokenizer.count(&hunk.header)? + 1;
+            let body = tokenizer.count(&hunk.lines.join("\n"))? + 1;
+            if header + head + body <= left {
+                left -= header + head + body;
+                kept[i][h] = hunk.lines.len();
+                shown[i] = true;
+            } else if left >= MIN_PARTIAL_TOKENS && header + head < left {
+                let mut room = left - header - head;
+                let mut lines = 0;
+                for line in &hunk.lines {
+                    let tokens = tokenizer.count(line)? + 1;
+                    if tokens > room {
+                        break;
+                    }
+                    room -= tokens;
+                    lines += 1;
+                }
+                if lines > 0 {
+                    left = room;
+                    kept[i][h] = lines;
+                    shown[i] = true;
+                }
+            }
+        }
+    }
+
+    let mut omitted = Vec::new();
+    for (i, file) in files.iter_mut().enumerate() {
+        if !shown[i] {
+            omitted.push(file.clone());
+            continue;
+        }
+        let whole = |(hunk, &lines): (&Hunk, &usize)| lines == hunk.lines.len();
+        if file.hunks.iter().zip(&kept[i]).all(whole) {
+            continue;
+        }
+        let (additions, deletions) = (file.additions(), file.deletions());
+        let hunks = std::mem::take(&mut file.hunks);
+        file.hunks = hunks
+            .into_iter()
+            .zip(&kept[i])
+            .filter(|(_, &lines)| lines > 0)
+            .map(|(mut hunk, &lines)| {
+                hunk.lines.truncate(lines);
+                hunk.recount();
+                hunk
+            })
+            .collect();
+        file.header.push(format!(
+            "Hunks left out or cut short to fit the token limit (+{} -{} not shown)",
+            additions - file.additions(),
+            deletions - file.deletions()
+        ));
+    }
+    let shown: Vec<FileDiff> = files
+        .into_iter()
+        .zip(shown)
+        .filter_map(|(file, shown)| shown.then_some(file))
+        .collect();
+    let mut text = diff::render(&shown);
+    if !omitted.is_empty() {
+        text.push_str(&left_out_note(&omitted.iter().collect::<Vec<_>>()));
+    }
+    // The notes are not counted exactly; never exceed the budget for them.
+    tokenizer.truncate(&text, available)
+}
+
+/// A line listing `files`, left out of the diff entirely.
+fn left_out_note(files: &[&FileDiff]) -> String {
+    let list: Vec<String> = files
+        .iter()
+        .map(|file| {
+            if file.binary {
+                format!("{} (binary)", file.path)
+            } else {
+                format!(
+                    "{} (+{} -{})",
+                    file.path,
+                    file.additions(),
+                    file.deletions()
+                )
+            }
+        })
+        .collect();
+    format!(
+        "Also changed, left out to fit the token limit: {}\n",
+        list.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P50K: Tokenizer = Tokenizer::P50kBase;
+
+    #[test]
+    fn ranks_source_over_tests_docs_and_assets() {
+        assert_eq!(category("src/parser.rs"), Category::Source);
+        assert_eq!(category("Cargo.toml"), Category::Source);
+        assert_eq!(category("tests/parser.rs"), Category::Test);
+        assert_eq!(category("src/parser_test.go"), Category::Test);
+        assert_eq!(category("web/app.spec.ts"), Category::Test);
+        assert_eq!(category("app/src/main/ParserTest.java"), Category::Test);
+        assert_eq!(category("src/latest.rs"), Category::Source);
+        assert_eq!(category("README.md"), Category::Docs);
+        assert_eq!(category("docs/guide/setup.html"), Category::Docs);
+        assert_eq!(category("assets/logo.svg"), Category::Asset);
+        assert!(Category::Source < Category::Test && Category::Docs < Category::Asset);
+    }
+
+    #[test]
+    fn keeps_the_most_important_hunks() {
+        let file = |path: &str, lines: usize| {
+            let body: String = (0..lines)
+                .map(|i| format!("+line {} of {}\n", i, path))
+                .collect();
+            format!(
+                "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -0,0 +1,{1} @@\n{2}",
+                path, lines, body
+            )
+        };
+        let diff = file("README.md", 40) + &file("src/big.rs", 60) + &file("src/small.rs", 5);
+        // Both source files fit, with room for the note, but not the README.
+        let sources = P50K
+            .count(&(file("src/big.rs", 60) + &file("src/small.rs", 5)))
+            .unwrap();
+        let files = diff::parse(&diff);
+        let note = P50K
+            .count(&left_out_note(&files.iter().collect::<Vec<_>>()))
+            .unwrap();
+        let budget = sources + note + 30;
+        let cut = truncate(&diff, budget, P50K).unwrap();
+        assert!(P50K.count(&cut).unwrap() <= budget);
+        assert!(cut.starts_with("diff --git a/src/big.rs"), "{}", cut);
+        assert!(cut.contains("+line 4 of src/small.rs\n"), "{}", cut);
+        assert!(
+            cut.ends_with("Also changed, left out to fit the token limit: README.md (+40 -0)\n"),
+            "{}",
+            cut
+        );
+
+        // A hunk larger than the whole budget is cut short, not left out.
+        let cut = truncate(&file("src/big.rs", 60), 200, P50K).unwrap();
+        assert!(P50K.count(&cut).unwrap() <= 200);
+        assert!(
+            cut.contains("Hunks left out or cut short to fit the token limit (+"),
+            "{}",
+            cut
+        );
+        assert!(cut.contains("+line 0 of src/big.rs\n"), "{}", cut);
+        assert_eq!(truncate(&diff, 100_000, P50K).unwrap(), diff);
+    }
+}
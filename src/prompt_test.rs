@@ -0,0 +1,254 @@
+//! Golden tests for prompt changes.
+//!
+//! Each fixture is a diff with a canned model reply. Rendering a fixture
+//! builds the prompt with the current options and template, and runs the
+//! canned reply through the usual post-processing, without contacting a
+//! model. The result is compared with a snapshot committed next to the
+//! fixtures, so a template change shows up as a diff before it reaches a
+//! real commit.
+
+use std::path::{Path, PathBuf};
+
+use crate::{build_prompt, message, CommitauraError, GenerateOptions};
+
+/// Directory of the repository's own fixtures and snapshots, relative to
+/// its root.
+pub const PROMPT_TESTS_DIR: &str = ".commitaura/prompt-tests";
+
+/// History every fixture is rendered with, so snapshots do not depend on
+/// the repository's log.
+pub const FIXTURE_HISTORY: &str =
+    "Add retry limit to the HTTP client\nDocument the config file format\nFix panic on empty input";
+
+/// Branch every fixture is rendered on, for templates that use it.
+pub const FIXTURE_BRANCH: &str = "main";
+
+/// Fixtures shipped with Commitaura: name, diff and canned reply.
+const BUNDLED: &[(&str, &str, &str)] = &[
+    (
+        "bugfix",
+        include_str!("fixtures/bugfix.diff"),
+        "Back off exponentially between transient HTTP retries",
+    ),
+    (
+        "new-module",
+        include_str!("fixtures/new_module.diff"),
+        "Add an in-memory response cache keyed by URL",
+    ),
+    (
+        "docs",
+        include_str!("fixtures/docs.diff"),
+        "Document setting EXAMPLE_TOKEN before the first run",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub name: String,
+    pub diff: String,
+    /// What the fake model replies with.
+    pub reply: String,
+}
+
+/// The bundled fixtures, followed by the `NAME.diff` files in `dir`. A
+/// `NAME.reply` file next to a diff holds its canned reply.
+pub fn fixtures(dir: &Path) -> Result<Vec<Fixture>, CommitauraError> {
+    let mut fixtures: Vec<Fixture> = BUNDLED
+        .iter()
+        .map(|(name, diff, reply)| Fixture {
+            name: name.to_string(),
+            diff: diff.to_string(),
+            reply: reply.to_string(),
+        })
+        .collect();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(fixtures);
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "diff"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let name = path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let reply = std::fs::read_to_string(path.with_extension("reply"))
+            .unwrap_or_else(|_| format!("Update {}", name));
+        fixtures.retain(|f| f.name != name);
+        fixtures.push(Fixture {
+            name,
+            diff: std::fs::read_to_string(&path)?,
+            reply: reply.trim().to_string(),
+        });
+    }
+    Ok(fixtures)
+}
+
+/// The prompt built for `fixture` with `opts`, and the message its canned
+/// reply turns into, as snapshot text.
+pub fn render(fixture: &Fixture, opts: &GenerateOptions) -> Result<String, CommitauraError> {
+    // Only settings a user configures take part; per-run context would make
+    // the snapshot depend on the repository.
+    let mut opts = opts.clone();
+    opts.history = FIXTURE_HISTORY.to_string();
+    opts.history_pool.clear();
+    opts.branch = Some(FIXTURE_BRANCH.to_string());
+    opts.follow_up_of = None;
+    opts.parent_message = None;
+    opts.amending = None;
+    opts.public_api_report = None;
+    opts.related_issues = None;
+    opts.intent = None;
+    let prompt = build_prompt(&fixture.diff, &opts)?;
+    Ok(format!(
+        "## system\n{}\n\n## user\n{}\n\n## message\n{}\n",
+        prompt.system.trim_end(),
+        prompt.user.trim_end(),
+        message::assemble(&fixture.reply, &opts)
+    ))
+}
+
+pub fn snapshot_path(dir: &Path, fixture: &Fixture) -> PathBuf {
+    dir.join("snapshots").join(format!("{}.snap", fixture.name))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// No snapshot yet; it was written.
+    Created,
+    /// The snapshot was replaced by the rendered output.
+    Updated,
+    /// The rendered output differs from the snapshot, shown as a line diff.
+    Changed(String),
+}
+
+/// Compare `rendered` with the snapshot at `path`. A missing snapshot is
+/// written; a differing one only with `update`.
+pub fn check(path: &Path, rendered: &str, update: bool) -> Result<Outcome, CommitauraError> {
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            write_snapshot(path, rendered)?;
+            return Ok(Outcome::Created);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if expected == rendered {
+        Ok(Outcome::Passed)
+    } else if update {
+        write_snapshot(path, rendered)?;
+        Ok(Outcome::Updated)
+    } else {
+        Ok(Outcome::Changed(line_diff(&expected, rendered)))
+    }
+}
+
+fn write_snapshot(path: &Path, rendered: &str) -> Result<(), CommitauraError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, rendered)?;
+    Ok(())
+}
+
+/// Lines of unchanged context kept around each change in [`line_diff`].
+const CONTEXT_LINES: usize = 3;
+
+/// The lines of `old` and `new` prefixed `-`, `+` or ` `, from their
+/// longest common subsequence. Unchanged lines further than
+/// `CONTEXT_LINES` from a change are left out, marked by `...`. Snapshots
+/// are small, so the quadratic table is fine.
+pub fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+
+    let changes: Vec<usize> = (0..lines.len())
+        .filter(|&n| !lines[n].starts_with(' '))
+        .collect();
+    let near_change = |n: usize| changes.iter().any(|&c| c.abs_diff(n) <= CONTEXT_LINES);
+    let mut out = String::new();
+    let mut skipped = false;
+    for (n, line) in lines.iter().enumerate() {
+        if near_change(n) {
+            out.push_str(line);
+            out.push('\n');
+            skipped = false;
+        } else if !skipped {
+            out.push_str("...\n");
+            skipped = true;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_prompt_and_message() {
+        let dir =
+            std::env::temp_dir().join(format!("commitaura-prompt-test-{}", std::process::id()));
+        let mut opts = GenerateOptions::new("key");
+        let fixture = &fixtures(&dir).unwrap()[0];
+        let path = snapshot_path(&dir, fixture);
+
+        let rendered = render(fixture, &opts).unwrap();
+        assert!(rendered.contains("Fix panic on empty input"));
+        assert!(rendered
+            .ends_with("## message\nBack off exponentially between transient HTTP retries\n"));
+        assert_eq!(check(&path, &rendered, false).unwrap(), Outcome::Created);
+        assert_eq!(check(&path, &rendered, false).unwrap(), Outcome::Passed);
+
+        opts.prompt_template = Some("Summarize:\n{{ diff }}".to_string());
+        let changed = render(fixture, &opts).unwrap();
+        let outcome = check(&path, &changed, false).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let Outcome::Changed(diff) = outcome else {
+            panic!("expected a changed snapshot, got {:?}", outcome);
+        };
+        assert!(diff.contains("\n+Summarize:\n"), "{}", diff);
+        assert!(
+            diff.starts_with(&format!("...\n {}", crate::injection::HIERARCHY_RULE)),
+            "{}",
+            diff
+        );
+        // The template leaves out the history, but the changes stay fenced.
+        assert!(diff.contains("\n-Recent commit messages:\n"), "{}", diff);
+        assert!(!diff.contains("\n-```\n"), "{}", diff);
+    }
+
+    #[test]
+    fn diffs_lines() {
+        assert_eq!(line_diff("a\nb\nc\n", "a\nx\nc\n"), " a\n-b\n+x\n c\n");
+    }
+}
@@ -0,0 +1,325 @@
+//! The LLM APIs Commitaura can send prompts to.
+//!
+//! Each provider knows its endpoint, authentication, request body and reply
+//! shape; everything else (prompt assembly, timeouts, journaling) is shared.
+
+use std::net::IpAddr;
+
+use serde_json::{json, Value};
+
+use crate::generate::{GenerateOptions, Prompt};
+
+pub const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1/";
+pub const OLLAMA_API_BASE: &str = "http://localhost:11434/api/";
+
+/// Context window assumed for local models, whose size depends on the model
+/// and the machine. Ollama is told to use it, since its own default is small
+/// enough to silently cut off a diff.
+pub const OLLAMA_CONTEXT: usize = 8192;
+
+/// Version of the Messages API the requests are written against.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Provider {
+    /// OpenAI's Chat Completions API, or any endpoint compatible with it.
+    #[default]
+    OpenAi,
+    /// Anthropic's Messages API.
+    Anthropic,
+    /// A local Ollama server, so no code leaves the machine.
+    Ollama,
+}
+
+/// One HTTP request to a provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Value,
+}
+
+impl Provider {
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::OpenAi => "openai",
+            Provider::Anthropic => "anthropic",
+            Provider::Ollama => "ollama",
+        }
+    }
+
+    /// Parse a `provider` setting.
+    pub fn parse(name: &str) -> Option<Provider> {
+        match name.to_ascii_lowercase().as_str() {
+            "openai" => Some(Provider::OpenAi),
+            "anthropic" | "claude" => Some(Provider::Anthropic),
+            "ollama" => Some(Provider::Ollama),
+            _ => None,
+        }
+    }
+
+    /// Environment variable holding the API key, if the provider needs one.
+    pub fn key_var(self) -> Option<&'static str> {
+        match self {
+            Provider::OpenAi => Some("OPENAI_API_KEY"),
+            Provider::Anthropic => Some("ANTHROPIC_API_KEY"),
+            Provider::Ollama => None,
+        }
+    }
+
+    pub fn default_api_base(self) -> &'static str {
+        match self {
+            Provider::OpenAi => crate::generate::OPENAI_API_BASE,
+            Provider::Anthropic => ANTHROPIC_API_BASE,
+            Provider::Ollama => OLLAMA_API_BASE,
+        }
+    }
+
+    pub fn default_model(self) -> &'static str {
+        match self {
+            Provider::OpenAi => crate::generate::MODEL_NAME,
+            Provider::Anthropic => "claude-sonnet-4-5",
+            Provider::Ollama => "llama3.1",
+        }
+    }
+
+    /// Whether this provider is likely to serve `model`, one of the hosted
+    /// models in [`crate::pricing`]. OpenAI-compatible endpoints are assumed
+    /// to serve anything but Claude; Ollama serves none of them.
+    pub fn serves(self, model: &str) -> bool {
+        match self {
+            Provider::OpenAi => !model.starts_with("claude"),
+            Provider::Anthropic => model.starts_with("claude"),
+            Provider::Ollama => false,
+        }
+    }
+
+    /// The request for `prompt`. The Messages API has no JSON mode, so
+    /// `response_format` is ignored for Anthropic; prompts asking for JSON
+    /// say so in their text anyway.
+    pub fn request(
+        self,
+        opts: &GenerateOptions,
+        model: &str,
+        prompt: &Prompt,
+        max_response_tokens: u32,
+        temperature: f64,
+        response_format: Option<&Value>,
+    ) -> Request {
+        match self {
+            Provider::OpenAi => {
+                let mut body = json!({
+                    "model": model,
+                    "max_tokens": max_response_tokens,
+                    "temperature": temperature,
+                    "top_p": 1.0,
+                    "n": 1,
+                    "stream": false,
+                    "messages": [
+                        { "role": "system", "content": prompt.system },
+                        { "role": "user", "content": prompt.user },
+                    ],
+                });
+                if let Some(format) = response_format {
+                    body["response_format"] = format.clone();
+                }
+                Request {
+                    url: format!("{}chat/completions", opts.api_base),
+                    headers: vec![("Authorization", format!("Bearer {}", opts.api_key))],
+                    body,
+                }
+            }
+            Provider::Anthropic => Request {
+                url: format!("{}messages", opts.api_base),
+                headers: vec![
+                    ("x-api-key", opts.api_key.clone()),
+                    ("anthropic-version", ANTHROPIC_VERSION.to_string()),
+                ],
+                body: json!({
+                    "model": model,
+                    "max_tokens": max_response_tokens,
+                    "temperature": temperature,
+                    "system": prompt.system,
+                    "messages": [
+                        { "role": "user", "content": prompt.user },
+                    ],
+                }),
+            },
+            Provider::Ollama => {
+                let mut body = json!({
+                    "model": model,
+                    "stream": false,
+                    "messages": [
+                        { "role": "system", "content": prompt.system },
+                        { "role": "user", "content": prompt.user },
+                    ],
+                    "options": {
+                        "temperature": temperature,
+                        "num_predict": max_response_tokens,
+                        "num_ctx": opts.max_tokens + opts.headroom_tokens + max_response_tokens as usize,
+                    },
+                });
+                if response_format.is_some() {
+                    body["format"] = json!("json");
+                }
+                Request {
+                    url: format!("{}chat", opts.api_base),
+                    headers: Vec::new(),
+                    body,
+                }
+            }
+        }
+    }
+
+    /// The reply text of a response.
+    pub fn reply_text(self, response: &Value) -> Option<String> {
+        match self {
+            Provider::OpenAi => response["choices"][0]["message"]["content"]
+                .as_str()
+                .map(str::to_string),
+            Provider::Anthropic => {
+                let blocks = response["content"].as_array()?;
+                let text: Vec<&str> = blocks
+                    .iter()
+                    .filter(|block| block["type"] == "text")
+                    .filter_map(|block| block["text"].as_str())
+                    .collect();
+                (!text.is_empty()).then(|| text.concat())
+            }
+            Provider::Ollama => response["message"]["content"].as_str().map(str::to_string),
+        }
+    }
+
+    /// Prompt and completion token counts reported in a response.
+    pub fn usage(self, response: &Value) -> (Option<u64>, Option<u64>) {
+        let usage = &response["usage"];
+        match self {
+            Provider::OpenAi => (
+                usage["prompt_tokens"].as_u64(),
+                usage["completion_tokens"].as_u64(),
+            ),
+            Provider::Anthropic => (
+                usage["input_tokens"].as_u64(),
+                usage["output_tokens"].as_u64(),
+            ),
+            Provider::Ollama => (
+                response["prompt_eval_count"].as_u64(),
+                response["eval_count"].as_u64(),
+            ),
+        }
+    }
+}
+
+/// The API base for an `OLLAMA_HOST` value, which may omit the scheme.
+pub fn ollama_base(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.contains("://") {
+        format!("{}/api/", host)
+    } else {
+        format!("http://{}/api/", host)
+    }
+}
+
+/// Whether `url` names this machine: `localhost` or a loopback address.
+/// The scheme may be omitted, as in `OLLAMA_HOST`.
+pub fn loopback(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    let rest = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_anthropic_requests_and_reads_replies() {
+        let prompt = Prompt {
+            system: "Be brief.".to_string(),
+            user: "diff".to_string(),
+        };
+        let mut opts = GenerateOptions::new("sk-ant");
+        opts.api_base = ANTHROPIC_API_BASE.to_string();
+        let request =
+            Provider::Anthropic.request(&opts, "claude-sonnet-4-5", &prompt, 100, 0.7, None);
+        assert_eq!(request.url, "https://api.anthropic.com/v1/messages");
+        assert!(request
+            .headers
+            .contains(&("x-api-key", "sk-ant".to_string())));
+        assert_eq!(request.body["system"], "Be brief.");
+        assert_eq!(request.body["messages"][0]["content"], "diff");
+
+        let response = json!({
+            "content": [{ "type": "text", "text": "Add parser" }],
+            "usage": { "input_tokens": 12, "output_tokens": 3 },
+        });
+        assert_eq!(
+            Provider::Anthropic.reply_text(&response).as_deref(),
+            Some("Add parser")
+        );
+        assert_eq!(Provider::Anthropic.usage(&response), (Some(12), Some(3)));
+        assert_eq!(Provider::OpenAi.reply_text(&response), None);
+    }
+
+    #[test]
+    fn builds_ollama_requests_and_reads_replies() {
+        let opts = GenerateOptions::for_provider("", Provider::Ollama);
+        let prompt = Prompt {
+            system: "Be brief.".to_string(),
+            user: "diff".to_string(),
+        };
+        let json_format = json!({ "type": "json_object" });
+        let request = Provider::Ollama.request(
+            &opts,
+            "qwen2.5-coder",
+            &prompt,
+            100,
+            0.7,
+            Some(&json_format),
+        );
+        assert_eq!(request.url, "http://localhost:11434/api/chat");
+        assert!(request.headers.is_empty());
+        assert_eq!(request.body["format"], "json");
+        assert_eq!(request.body["options"]["num_ctx"], OLLAMA_CONTEXT);
+
+        let response = json!({
+            "message": { "role": "assistant", "content": "Add parser" },
+            "prompt_eval_count": 40,
+            "eval_count": 4,
+        });
+        assert_eq!(
+            Provider::Ollama.reply_text(&response).as_deref(),
+            Some("Add parser")
+        );
+        assert_eq!(Provider::Ollama.usage(&response), (Some(40), Some(4)));
+    }
+
+    #[test]
+    fn parses_names() {
+        assert_eq!(Provider::parse("Anthropic"), Some(Provider::Anthropic));
+        assert_eq!(Provider::parse("openai"), Some(Provider::OpenAi));
+        assert_eq!(Provider::parse("gemini"), None);
+        assert!(Provider::Anthropic.serves("claude-haiku-4-5"));
+        assert!(!Provider::OpenAi.serves("claude-haiku-4-5"));
+        assert!(!Provider::Ollama.serves("gpt-4o"));
+        assert_eq!(
+            ollama_base("127.0.0.1:11434"),
+            "http://127.0.0.1:11434/api/"
+        );
+        assert_eq!(
+            ollama_base("https://gpu-box:8443/"),
+            "https://gpu-box:8443/api/"
+        );
+        assert!(loopback(OLLAMA_API_BASE));
+        assert!(loopback("127.0.0.1:11434"));
+        assert!(loopback("http://[::1]:11434/api/"));
+        assert!(!loopback("http://gpu-box:11434/api/"));
+        assert!(!loopback("https://api.openai.com/v1/"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Public API changes of a Rust library crate, from `cargo public-api`.
+//!
+//! The API of HEAD and of the index are listed by exporting each tree to a
+//! scratch directory and running `cargo public-api --simplified` on it. Both
+//! listings are cached by tree hash under `.git/commitaura/public-api/`, and
+//! builds share one target directory there, so only the first run is slow.
+//! Building runs the crate's build scripts and macros, so `cargo` is run
+//! in the [`crate::sandbox`] and must be in its allow list.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use log::debug;
+
+use crate::sandbox::Sandbox;
+use crate::{git, CommitauraError};
+
+/// Longest a `cargo public-api` run may take.
+const TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiChange {
+    Added(String),
+    Removed(String),
+    /// The same item with a different signature: `(old, new)`.
+    Changed(String, String),
+}
+
+/// Whether the repository root is a crate with a library target.
+pub fn is_library_crate(root: &Path) -> bool {
+    let Ok(manifest) = std::fs::read_to_string(root.join("Cargo.toml")) else {
+        return false;
+    };
+    manifest.contains("[package]")
+        && (root.join("src/lib.rs").is_file() || manifest.contains("[lib]"))
+}
+
+/// Whether the `cargo public-api` subcommand is installed.
+pub fn tool_available() -> bool {
+    Command::new("cargo")
+        .args(["public-api", "--version"])
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Public API changes between HEAD and the index, or `None` when the
+/// repository is not a library crate or `cargo public-api` is missing.
+/// `cargo` runs in `sandbox`.
+pub fn staged_changes(sandbox: &Sandbox) -> Result<Option<Vec<ApiChange>>, CommitauraError> {
+    let root = git::toplevel()?;
+    if !is_library_crate(&root) || !tool_available() {
+        return Ok(None);
+    }
+    let cache = cache_dir()?;
+    std::fs::create_dir_all(&cache)?;
+
+    let old = listing(&git::rev_tree("HEAD")?, &cache, sandbox)?;
+    let new = listing(&git::index_tree()?, &cache, sandbox)?;
+    Ok(Some(diff_listings(&old, &new)))
+}
+
+/// The simplified public API listing of `tree`, from the cache if possible.
+fn listing(tree: &str, cache: &Path, sandbox: &Sandbox) -> Result<String, CommitauraError> {
+    let cached = cache.join(format!("{}.txt", tree));
+    if let Ok(text) = std::fs::read_to_string(&cached) {
+        debug!("Using cached public API listing for {}", tree);
+        return Ok(text);
+    }
+
+    let scratch =
+        std::env::temp_dir().join(format!("commitaura-api-{}-{}", std::process::id(), tree));
+    git::export_tree(tree, &scratch)?;
+    let words = [
+        "cargo".to_string(),
+        "public-api".to_string(),
+        "--simplified".to_string(),
+        "--manifest-path".to_string(),
+        scratch.join("Cargo.toml").display().to_string(),
+    ];
+    let target = cache.join("target").display().to_string();
+    let outcome = sandbox.run_words(&words, &[("CARGO_TARGET_DIR", target)], TIMEOUT);
+    let _ = std::fs::remove_dir_all(&scratch);
+    let outcome = outcome?;
+    if outcome.timed_out {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "cargo public-api timed out after {}s",
+            TIMEOUT.as_secs()
+        )));
+    }
+    if !outcome.success {
+        return Err(CommitauraError::GitOperationFailed(format!(
+            "cargo public-api failed: {}",
+            outcome.stderr.trim()
+        )));
+    }
+    let text = outcome.stdout;
+    std::fs::write(&cached, &text)?;
+    Ok(text)
+}
+
+/// The item path a listing line describes, e.g. `commitaura::config::Config::load`
+/// for `pub fn commitaura::config::Config::load() -> ...`.
+fn item_path(line: &str) -> Option<String> {
+    let token = line.split_whitespace().find(|t| t.contains("::"))?;
+    let end = token.find(['(', '<']).unwrap_or(token.len());
+    Some(token[..end].trim_end_matches(':').to_string())
+}
+
+/// Compare two `cargo public-api` listings. An item whose path appears in
+/// both with different lines is reported once as changed.
+pub fn diff_listings(old: &str, new: &str) -> Vec<ApiChange> {
+    let index = |text: &str| -> BTreeMap<String, Vec<String>> {
+        let mut items: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(path) = item_path(line) {
+                items.entry(path).or_default().push(line.to_string());
+            }
+        }
+        items
+    };
+    let (old, new) = (index(old), index(new));
+
+    let mut changes = Vec::new();
+    for (path, old_lines) in &old {
+        match new.get(path) {
+            None => changes.extend(old_lines.iter().cloned().map(ApiChange::Removed)),
+            Some(new_lines) if new_lines != old_lines => {
+                if let ([old_line], [new_line]) = (old_lines.as_slice(), new_lines.as_slice()) {
+                    changes.push(ApiChange::Changed(old_line.clone(), new_line.clone()));
+                } else {
+                    changes.extend(
+                        old_lines
+                            .iter()
+                            .filter(|l| !new_lines.contains(l))
+                            .cloned()
+                            .map(ApiChange::Removed),
+                    );
+                    changes.extend(
+                        new_lines
+                            .iter()
+                            .filter(|l| !old_lines.contains(l))
+                            .cloned()
+                            .map(ApiChange::Added),
+                    );
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    for (path, new_lines) in &new {
+        if !old.contains_key(path) {
+            changes.extend(new_lines.iter().cloned().map(ApiChange::Added));
+        }
+    }
+    changes
+}
+
+/// Render changes for the prompt, one per line.
+pub fn report(changes: &[ApiChange]) -> String {
+    changes
+        .iter()
+        .map(|change| match change {
+            ApiChange::Added(item) => format!("added: {}", item),
+            ApiChange::Removed(item) => format!("removed: {}", item),
+            ApiChange::Changed(old, new) => format!("changed: {} -> {}", old, new),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Where the public API cache lives for the current repository.
+pub fn cache_dir() -> Result<PathBuf, CommitauraError> {
+    Ok(git::git_dir()?.join("commitaura").join("public-api"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_added_removed_and_changed_items() {
+        let old = "pub mod demo\npub fn demo::Config::new_from_str(s: &str) -> demo::Config\npub fn demo::Config::load() -> demo::Config\n";
+        let new = "pub mod demo\npub fn demo::Config::load() -> core::result::Result<demo::Config, demo::Error>\npub fn demo::Config::from_path(path: &std::path::Path) -> demo::Config\n";
+        assert_eq!(
+            diff_listings(old, new),
+            vec![
+                ApiChange::Changed(
+                    "pub fn demo::Config::load() -> demo::Config".to_string(),
+                    "pub fn demo::Config::load() -> core::result::Result<demo::Config, demo::Error>".to_string()
+                ),
+                ApiChange::Removed("pub fn demo::Config::new_from_str(s: &str) -> demo::Config".to_string()),
+                ApiChange::Added("pub fn demo::Config::from_path(path: &std::path::Path) -> demo::Config".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Finding open issues a change may address.
+//!
+//! The words of the changed paths and symbols are matched against the
+//! titles and bodies of the repository's open issues. The best matches go
+//! into the prompt, so the message can say which issue it addresses.
+
+use crate::diff;
+use crate::forge::OpenIssue;
+use crate::summary::summarize_file;
+
+/// Issues put in the prompt at most.
+pub const MAX_RELATED: usize = 3;
+
+/// A title match counts this much more than a body match.
+const TITLE_WEIGHT: usize = 3;
+
+/// Issues scoring less than this are not considered related.
+const MIN_SCORE: usize = 4;
+
+/// Words too common in paths and code to say anything about an issue.
+const STOPWORDS: &[&str] = &[
+    "src",
+    "lib",
+    "mod",
+    "main",
+    "test",
+    "tests",
+    "index",
+    "util",
+    "utils",
+    "impl",
+    "struct",
+    "enum",
+    "trait",
+    "const",
+    "static",
+    "type",
+    "class",
+    "interface",
+    "def",
+    "function",
+    "func",
+    "new",
+    "get",
+    "set",
+    "from",
+    "into",
+    "with",
+    "the",
+    "and",
+    "for",
+];
+
+/// Lowercase search terms from the paths and changed symbols of `diff`,
+/// with identifiers split at underscores and case changes.
+pub fn terms(diff: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for summary in diff::parse(diff).iter().map(summarize_file) {
+        let path = summary
+            .path
+            .rsplit_once('.')
+            .map_or(summary.path.as_str(), |(stem, _)| stem);
+        let symbols = summary
+            .added_symbols
+            .iter()
+            .chain(&summary.removed_symbols)
+            .chain(&summary.touched)
+            .filter_map(|symbol| symbol.split_whitespace().last());
+        for name in path.split('/').chain(symbols) {
+            for word in split_identifier(name) {
+                if word.len() > 2 && !STOPWORDS.contains(&word.as_str()) && !words.contains(&word) {
+                    words.push(word);
+                }
+            }
+        }
+    }
+    words
+}
+
+/// `retry_policy`, `RetryPolicy` and `retry-policy` all become `retry`, `policy`.
+fn split_identifier(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            words.push(std::mem::take(&mut current));
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.extend(c.to_lowercase());
+    }
+    words.push(current);
+    words.retain(|w| !w.is_empty());
+    words
+}
+
+/// How well `issue` matches `terms`: each term found in the title scores
+/// `TITLE_WEIGHT`, each term found only in the body scores one.
+fn score(issue: &OpenIssue, terms: &[String]) -> usize {
+    let title = split_identifier(&issue.title);
+    let body = split_identifier(&issue.body);
+    terms
+        .iter()
+        .map(|term| {
+            if title.contains(term) {
+                TITLE_WEIGHT
+            } else if body.contains(term) {
+                1
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+/// The issues most related to `terms`, best first, at most `limit` of them.
+pub fn rank(issues: &[OpenIssue], terms: &[String], limit: usize) -> Vec<OpenIssue> {
+    let mut scored: Vec<(usize, &OpenIssue)> = issues
+        .iter()
+        .map(|issue| (score(issue, terms), issue))
+        .filter(|(score, _)| *score >= MIN_SCORE)
+        .collect();
+    // Stable, so equally scored issues keep the tracker's most-recent-first order.
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, issue)| issue.clone())
+        .collect()
+}
+
+/// The issues as a list for the prompt, one `#number title` per line.
+pub fn report(issues: &[OpenIssue]) -> String {
+    issues
+        .iter()
+        .map(|issue| format!("#{} {}", issue.number, issue.title.trim()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(number: u64, title: &str, body: &str) -> OpenIssue {
+        OpenIssue {
+            number,
+            title: title.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn takes_terms_from_paths_and_symbols() {
+        let diff = "diff --git a/src/http_client.rs b/src/http_client.rs\n--- a/src/http_client.rs\n+++ b/src/http_client.rs\n@@ -1,2 +1,3 @@ fn send_request(\n+fn retryBackoff(attempt: u32) {}\n";
+        assert_eq!(
+            terms(diff),
+            vec!["http", "client", "retry", "backoff", "send", "request"]
+        );
+    }
+
+    #[test]
+    fn ranks_issues_by_matching_terms() {
+        let terms: Vec<String> = ["retry", "backoff", "http"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let issues = vec![
+            issue(12, "Dark mode", "Colors are off"),
+            issue(40, "Docs", "Mention the retry backoff and http timeouts"),
+            issue(88, "Flaky retry behavior", "The backoff never resets"),
+            issue(91, "HTTP retry limit", ""),
+        ];
+        let ranked = rank(&issues, &terms, MAX_RELATED);
+        assert_eq!(
+            ranked.iter().map(|i| i.number).collect::<Vec<_>>(),
+            vec![91, 88]
+        );
+        assert_eq!(
+            report(&ranked),
+            "#91 HTTP retry limit\n#88 Flaky retry behavior"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Summaries of a range of commits: annotated tag messages for releases, and
+//! the shared prompt scaffolding used for anything that describes a range
+//! rather than a single change (pull requests, release notes).
+
+use crate::generate::{complete, GenerateOptions, Prompt};
+use crate::{git, message, CommitauraError};
+
+const SYSTEM_MESSAGE: &str =
+    "You are a helpful assistant that writes concise, accurate release notes for Git tags.";
+
+/// Response tokens for a tag message.
+const MAX_RESPONSE_TOKENS: u32 = 500;
+
+/// The commits and combined diff between a starting point and HEAD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitRange {
+    /// Where the range starts (exclusive); `None` means the whole history.
+    pub since: Option<String>,
+    /// Commit subjects, newest first, one per line.
+    pub commits: String,
+    pub diff: String,
+}
+
+impl CommitRange {
+    /// Collect `since..HEAD`, or everything up to HEAD when `since` is `None`.
+    pub fn collect(since: Option<&str>) -> Result<CommitRange, CommitauraError> {
+        let (commits, diff) = match since {
+            Some(since) => (
+                git::commit_subjects(&format!("{}..HEAD", since))?,
+                git::range_diff(&format!("{}..HEAD", since))?,
+            ),
+            None => (
+                git::commit_subjects("HEAD")?,
+                git::range_diff(&format!("{}..HEAD", git::EMPTY_TREE))?,
+            ),
+        };
+        Ok(CommitRange {
+            since: since.map(str::to_string),
+            commits,
+            diff,
+        })
+    }
+
+    /// The work of the current branch relative to `target`: only commits
+    /// unique to the branch, and the diff from the merge base. Mainline
+    /// commits merged into the branch are not part of either.
+    pub fn branch(target: &str) -> Result<CommitRange, CommitauraError> {
+        let base = git::merge_base(target, "HEAD")?;
+        Ok(CommitRange {
+            since: Some(target.to_string()),
+            commits: git::unique_subjects(target)?,
+            diff: git::range_diff(&format!("{}..HEAD", base))?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commits.trim().is_empty()
+    }
+}
+
+/// Build a prompt that asks for `instructions` about a range of commits. The
+/// commit list is always sent in full; the diff is truncated to what is left
+/// of the token budget.
+pub fn range_prompt(
+    system: &str,
+    instructions: &str,
+    commits: &str,
+    diff: &str,
+    reply: u32,
+    opts: &GenerateOptions,
+) -> Result<Prompt, CommitauraError> {
+    let preamble = format!("{}\n\nCommits:\n{}\n\nDiff:\n", instructions, commits);
+    let tokenizer = opts.tokenizer();
+    let fixed_tokens = tokenizer.count(system)? + tokenizer.count(&preamble)?;
+    let diff = match &opts.redactor {
+        Some(redactor) => redactor.redact(diff).0,
+        None => diff.to_string(),
+    };
+    let diff = tokenizer.truncate(&diff, opts.prompt_budget(reply).saturating_sub(fixed_tokens))?;
+    Ok(Prompt {
+        system: system.to_string(),
+        user: preamble + &diff,
+    })
+}
+
+/// Build the prompt for the annotated message of tag `name`.
+pub fn build_tag_prompt(
+    name: &str,
+    range: &CommitRange,
+    opts: &GenerateOptions,
+) -> Result<Prompt, CommitauraError> {
+    let since = match &range.since {
+        Some(since) => format!("the changes since {}", since),
+        None => "the first release".to_string(),
+    };
+    let instructions = format!(
+        "Write the annotated tag message for release {name}, covering {since}. Reply with a one-line summary of the release, a blank line, then a plain-text bulleted list of the notable changes, most important first. Mention breaking changes explicitly. Do not use Markdown headings or include any other text."
+    );
+    range_prompt(
+        SYSTEM_MESSAGE,
+        &instructions,
+        &range.commits,
+        &range.diff,
+        MAX_RESPONSE_TOKENS,
+        opts,
+    )
+}
+
+/// Generate the annotated message for tag `name` from the commits in `range`.
+pub fn generate_tag_message(
+    name: &str,
+    range: &CommitRange,
+    opts: &GenerateOptions,
+) -> Result<String, CommitauraError> {
+    let prompt = build_tag_prompt(name, range, opts)?;
+    let reply = complete(&prompt, opts, MAX_RESPONSE_TOKENS)?;
+    let tag_message = message::tidy(&reply, opts);
+    if tag_message.is_empty() {
+        return Err(CommitauraError::ApiRequestFailed(
+            "Received empty tag message from LLM.".to_string(),
+        ));
+    }
+    Ok(tag_message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_prompt_names_release_and_previous_tag() {
+        let range = CommitRange {
+            since: Some("v1.3.0".to_string()),
+            commits: "Add reword command\nFix crash on empty diff".to_string(),
+            diff: "diff --git a/src/main.rs b/src/main.rs\n+fn reword() {}\n".to_string(),
+        };
+        let prompt = build_tag_prompt("v1.4.0", &range, &GenerateOptions::new("key")).unwrap();
+        assert!(prompt.user.contains("release v1.4.0"));
+        assert!(prompt.user.contains("since v1.3.0"));
+        assert!(prompt.user.contains("Fix crash on empty diff"));
+        assert!(prompt.user.ends_with("+fn reword() {}\n"));
+    }
+}
@@ -0,0 +1,472 @@
+//! Git access through libgit2 for the calls every commit makes: checking for
+//! staged changes, reading the staged diff and the log, and committing.
+//!
+//! Calling the library saves a `git` process per call and works where no
+//! `git` is installed. The functions in [`crate::git`] try a [`GitRepo`]
+//! first and run `git` instead when it fails, as on a partial clone's
+//! missing blobs, or when it would not do what `git` does, such as running
+//! hooks or signing a commit.
+
+use git2::{
+    Commit, Diff, DiffFindOptions, DiffFormat, DiffOptions, ErrorCode, Oid, Repository, Signature,
+};
+
+use crate::editmsg::{self, Cleanup};
+use crate::history::HistoryCommit;
+
+/// Hooks `git commit` runs and libgit2 does not.
+const COMMIT_HOOKS: &[&str] = &["pre-commit", "prepare-commit-msg", "commit-msg", "post-commit"];
+
+/// Variables `git commit` reads the author and committer from.
+const IDENTITY_VARS: &[&str] = &[
+    "GIT_AUTHOR_NAME",
+    "GIT_AUTHOR_EMAIL",
+    "GIT_AUTHOR_DATE",
+    "GIT_COMMITTER_NAME",
+    "GIT_COMMITTER_EMAIL",
+    "GIT_COMMITTER_DATE",
+];
+
+pub struct GitRepo {
+    repo: Repository,
+}
+
+impl GitRepo {
+    /// The repository around the current directory, honouring `GIT_DIR`,
+    /// `GIT_INDEX_FILE` and the other variables `git` reads.
+    pub fn open() -> Result<GitRepo, git2::Error> {
+        Ok(GitRepo {
+            repo: Repository::open_from_env()?,
+        })
+    }
+
+    /// The repository at or above `path`.
+    pub fn discover(path: &std::path::Path) -> Result<GitRepo, git2::Error> {
+        Ok(GitRepo {
+            repo: Repository::discover(path)?,
+        })
+    }
+
+    /// The `.git` directory.
+    pub fn git_dir(&self) -> &std::path::Path {
+        self.repo.path()
+    }
+
+    /// Root of the work tree; `None` in a bare repository.
+    pub fn toplevel(&self) -> Option<&std::path::Path> {
+        self.repo.workdir()
+    }
+
+    /// The branch HEAD points at, e.g. `refs/heads/main`, even before its
+    /// first commit; `None` when detached.
+    pub fn symbolic_head(&self) -> Result<Option<String>, git2::Error> {
+        Ok(self
+            .repo
+            .find_reference("HEAD")?
+            .symbolic_target()
+            .map(str::to_string))
+    }
+
+    pub fn is_shallow(&self) -> bool {
+        self.repo.is_shallow()
+    }
+
+    /// Git config `key` as written; `None` if unset.
+    pub fn config_value(&self, key: &str) -> Result<Option<String>, git2::Error> {
+        match self.repo.config()?.get_string(key) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// HEAD's commit; `None` on a branch with no commits yet.
+    fn head(&self) -> Result<Option<Commit<'_>>, git2::Error> {
+        match self.repo.head() {
+            Ok(head) => Ok(Some(head.peel_to_commit()?)),
+            Err(e) if matches!(e.code(), ErrorCode::UnbornBranch | ErrorCode::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Pair deletions with additions as renames, as `git diff` does unless
+    /// `diff.renames` is off.
+    fn find_renames(&self, diff: &mut Diff<'_>) -> Result<(), git2::Error> {
+        if self.repo.config()?.get_bool("diff.renames").unwrap_or(true) {
+            diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+        }
+        Ok(())
+    }
+
+    fn staged(&self) -> Result<Diff<'_>, git2::Error> {
+        let tree = match self.head()? {
+            Some(commit) => Some(commit.tree()?),
+            None => None,
+        };
+        let mut diff = self
+            .repo
+            .diff_tree_to_index(tree.as_ref(), None, Some(&mut DiffOptions::new()))?;
+        self.find_renames(&mut diff)?;
+        Ok(diff)
+    }
+
+    pub fn has_staged_changes(&self) -> Result<bool, git2::Error> {
+        Ok(self.staged()?.deltas().len() > 0)
+    }
+
+    /// The staged changes as the patch `git diff --staged` prints.
+    pub fn staged_diff(&self) -> Result<String, git2::Error> {
+        patch_text(&self.staged()?)
+    }
+
+    /// The changes from `base`'s tree, or an empty tree, to `tree`, as
+    /// `git diff-tree -p -M` prints them.
+    pub fn tree_diff(&self, base: Option<&str>, tree: &str) -> Result<String, git2::Error> {
+        let old = match base {
+            Some(rev) => Some(self.repo.revparse_single(rev)?.peel_to_tree()?),
+            None => None,
+        };
+        let new = self.repo.revparse_single(tree)?.peel_to_tree()?;
+        let mut diff = self.repo.diff_tree_to_tree(old.as_ref(), Some(&new), None)?;
+        self.find_renames(&mut diff)?;
+        patch_text(&diff)
+    }
+
+    /// The last `count` commits, newest first, as `git log --name-only`
+    /// lists them: merges without files, renames under their new path.
+    pub fn recent_commits(&self, count: usize) -> Result<Vec<HistoryCommit>, git2::Error> {
+        match self.head()? {
+            Some(head) => self.commits_from(head.id(), count),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Like [`GitRepo::recent_commits`], starting at `rev` instead of HEAD.
+    pub fn recent_commits_from(
+        &self,
+        rev: &str,
+        count: usize,
+    ) -> Result<Vec<HistoryCommit>, git2::Error> {
+        self.commits_from(self.find_commit(rev)?.id(), count)
+    }
+
+    fn commits_from(&self, start: Oid, count: usize) -> Result<Vec<HistoryCommit>, git2::Error> {
+        let mut walk = self.repo.revwalk()?;
+        walk.push(start)?;
+        let mut commits = Vec::new();
+        for oid in walk.take(count) {
+            let commit = self.repo.find_commit(oid?)?;
+            let subject = commit.summary().unwrap_or_default().trim().to_string();
+            if subject.is_empty() {
+                continue;
+            }
+            commits.push(HistoryCommit {
+                subject,
+                body: commit.body().unwrap_or_default().trim().to_string(),
+                files: self.touched(&commit)?,
+            });
+        }
+        Ok(commits)
+    }
+
+    fn touched(&self, commit: &Commit<'_>) -> Result<Vec<String>, git2::Error> {
+        if commit.parent_count() > 1 {
+            return Ok(Vec::new());
+        }
+        let parent = match commit.parent_count() {
+            0 => None,
+            _ => Some(commit.parent(0)?.tree()?),
+        };
+        let mut diff = self
+            .repo
+            .diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?;
+        self.find_renames(&mut diff)?;
+        Ok(delta_paths(&diff))
+    }
+
+    /// The commit `rev`, such as `HEAD^`, names.
+    fn find_commit(&self, rev: &str) -> Result<Commit<'_>, git2::Error> {
+        self.repo.revparse_single(rev)?.peel_to_commit()
+    }
+
+    /// Full message of `rev`, without trailing blank lines.
+    pub fn commit_message(&self, rev: &str) -> Result<String, git2::Error> {
+        Ok(String::from_utf8_lossy(self.find_commit(rev)?.message_bytes())
+            .trim_end()
+            .to_string())
+    }
+
+    /// Committer timestamp of `rev` in seconds since the epoch.
+    pub fn commit_time(&self, rev: &str) -> Result<u64, git2::Error> {
+        Ok(self.find_commit(rev)?.committer().when().seconds().max(0) as u64)
+    }
+
+    /// Whether `rev` is not a root commit.
+    pub fn has_parent(&self, rev: &str) -> Result<bool, git2::Error> {
+        Ok(self.find_commit(rev)?.parent_count() > 0)
+    }
+
+    /// Paths `rev` changed against its first parent, renames as a deletion
+    /// and an addition.
+    pub fn commit_paths(&self, rev: &str) -> Result<Vec<String>, git2::Error> {
+        let commit = self.find_commit(rev)?;
+        let parent = match commit.parent_count() {
+            0 => None,
+            _ => Some(commit.parent(0)?.tree()?),
+        };
+        let diff = self
+            .repo
+            .diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?;
+        Ok(delta_paths(&diff))
+    }
+
+    /// Staged paths, renames as a deletion and an addition.
+    pub fn staged_paths(&self) -> Result<Vec<String>, git2::Error> {
+        let tree = match self.head()? {
+            Some(commit) => Some(commit.tree()?),
+            None => None,
+        };
+        Ok(delta_paths(&self.repo.diff_tree_to_index(tree.as_ref(), None, None)?))
+    }
+
+    /// Size in bytes of the staged version of `path`.
+    pub fn staged_size(&self, path: &str) -> Result<Option<u64>, git2::Error> {
+        match self.repo.index()?.get_path(std::path::Path::new(path), 0) {
+            Some(entry) => Ok(Some(self.repo.find_blob(entry.id)?.size() as u64)),
+            None => Ok(None),
+        }
+    }
+
+    /// Untracked, non-ignored paths; wholly untracked directories are
+    /// listed once, with a trailing `/`.
+    pub fn untracked_paths(&self) -> Result<Vec<String>, git2::Error> {
+        let mut options = git2::StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(false)
+            .include_ignored(false);
+        Ok(self
+            .repo
+            .statuses(Some(&mut options))?
+            .iter()
+            .filter(|entry| entry.status().contains(git2::Status::WT_NEW))
+            .filter_map(|entry| entry.path().map(str::to_string))
+            .collect())
+    }
+
+    /// Why `git commit` would do more than libgit2 can here, if it would.
+    fn needs_git_to_commit(&self) -> Result<Option<&'static str>, git2::Error> {
+        if self.repo.state() != git2::RepositoryState::Clean {
+            return Ok(Some("a merge or other operation is in progress"));
+        }
+        if IDENTITY_VARS.iter().any(|var| std::env::var_os(var).is_some()) {
+            return Ok(Some("the author or committer is set in the environment"));
+        }
+        let config = self.repo.config()?;
+        if config.get_bool("commit.gpgSign").unwrap_or(false) {
+            return Ok(Some("commits are signed"));
+        }
+        if config.get_string("core.hooksPath").is_ok() {
+            return Ok(Some("core.hooksPath is set"));
+        }
+        let hooks = self.repo.commondir().join("hooks");
+        if COMMIT_HOOKS.iter().any(|hook| is_executable(&hooks.join(hook))) {
+            return Ok(Some("the repository has commit hooks"));
+        }
+        Ok(None)
+    }
+
+    /// Commit the index with `message`, cleaned up as `git commit -F`
+    /// would. Fails without committing when only `git` would get it right.
+    pub fn commit(&self, message: &str) -> Result<(), git2::Error> {
+        if let Some(reason) = self.needs_git_to_commit()? {
+            return Err(git2::Error::from_str(reason));
+        }
+        let message = editmsg::cleanup(
+            &editmsg::for_commit(message),
+            Cleanup::configured(),
+            false,
+            editmsg::comment_char(message),
+        );
+        if message.is_empty() {
+            return Err(git2::Error::from_str("the message is empty after cleanup"));
+        }
+        let tree = self.repo.find_tree(self.repo.index()?.write_tree()?)?;
+        let signature = self.repo.signature()?;
+        let parents: Vec<Commit<'_>> = self.head()?.into_iter().collect();
+        let parents: Vec<&Commit<'_>> = parents.iter().collect();
+        self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &format!("{}\n", message),
+            &tree,
+            &parents,
+        )?;
+        Ok(())
+    }
+
+    /// Create a commit of `tree` with `parents` and the already cleaned-up
+    /// `message`, leaving HEAD, the index and any work tree alone, and
+    /// return its id. With `update_ref`, the ref is moved to the commit, but
+    /// only if it does not exist yet or still points at the first parent.
+    pub fn commit_tree(
+        &self,
+        tree: &str,
+        parents: &[String],
+        message: &str,
+        update_ref: Option<&str>,
+    ) -> Result<String, git2::Error> {
+        if IDENTITY_VARS
+            .iter()
+            .filter(|var| var.ends_with("_DATE"))
+            .any(|var| std::env::var_os(var).is_some())
+        {
+            return Err(git2::Error::from_str("a commit date is set in the environment"));
+        }
+        let tree = self.repo.revparse_single(tree)?.peel_to_tree()?;
+        let parents = parents
+            .iter()
+            .map(|rev| self.find_commit(rev))
+            .collect::<Result<Vec<_>, _>>()?;
+        let parents: Vec<&Commit<'_>> = parents.iter().collect();
+        let oid = self.repo.commit(
+            update_ref,
+            &self.signature_for("AUTHOR")?,
+            &self.signature_for("COMMITTER")?,
+            &format!("{}\n", message),
+            &tree,
+            &parents,
+        )?;
+        Ok(oid.to_string())
+    }
+
+    /// The author or committer (`role`) `git` would record: the
+    /// `GIT_<ROLE>_NAME` and `GIT_<ROLE>_EMAIL` variables, else the config.
+    fn signature_for(&self, role: &str) -> Result<Signature<'static>, git2::Error> {
+        let var = |key: &str| std::env::var(format!("GIT_{}_{}", role, key)).ok();
+        if let (Some(name), Some(email)) = (var("NAME"), var("EMAIL")) {
+            return Signature::now(&name, &email);
+        }
+        let configured = self.repo.signature()?;
+        Signature::now(
+            &var("NAME").unwrap_or_else(|| configured.name().unwrap_or_default().to_string()),
+            &var("EMAIL").unwrap_or_else(|| configured.email().unwrap_or_default().to_string()),
+        )
+    }
+}
+
+/// `diff` as the patch `git diff` prints.
+fn patch_text(diff: &Diff<'_>) -> Result<String, git2::Error> {
+    let mut patch = Vec::new();
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin() as u8);
+        }
+        patch.extend_from_slice(line.content());
+        true
+    })?;
+    String::from_utf8(patch).map_err(|e| git2::Error::from_str(&e.to_string()))
+}
+
+fn delta_paths(diff: &Diff<'_>) -> Vec<String> {
+    diff.deltas()
+        .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn is_executable(path: &std::path::Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &std::path::Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=T", "-c", "user.email=t@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?} failed", args);
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn reads_like_git() {
+        let dir = std::env::temp_dir().join(format!("commitaura-repo-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "-q"]);
+        std::fs::write(dir.join("a.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.join("b.txt"), "unchanged\n").unwrap();
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-q", "-m", "Add files", "-m", "With a body."]);
+        std::fs::write(dir.join("a.txt"), "one\n2\nthree").unwrap();
+        git(&dir, &["mv", "b.txt", "c.txt"]);
+        git(&dir, &["add", "a.txt"]);
+
+        let repo = GitRepo::discover(&dir).unwrap();
+        assert!(repo.has_staged_changes().unwrap());
+        let diff = repo.staged_diff().unwrap();
+        let expected = git(&dir, &["diff", "--staged"]);
+        // Abbreviated blob hashes may differ in length.
+        let without_index = |d: &str| d.lines().filter(|l| !l.starts_with("index ")).collect::<Vec<_>>().join("\n");
+        assert_eq!(without_index(&diff), without_index(&expected));
+
+        let commits = repo.recent_commits(5).unwrap();
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].subject, "Add files");
+        assert_eq!(commits[0].body, "With a body.");
+        assert_eq!(commits[0].files, ["a.txt", "b.txt"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn commits_a_tree_in_a_bare_repository() {
+        let dir = std::env::temp_dir().join(format!("commitaura-bare-{}", std::process::id()));
+        let work = dir.join("work");
+        std::fs::create_dir_all(&work).unwrap();
+        git(&dir, &["init", "-q", "--bare", "repo.git"]);
+        git(&work, &["init", "-q"]);
+        std::fs::write(work.join("a.txt"), "one\n").unwrap();
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "Add a"]);
+        git(&work, &["push", "-q", "../repo.git", "HEAD:refs/heads/main"]);
+        std::fs::write(work.join("a.txt"), "one\ntwo\n").unwrap();
+        git(&work, &["add", "."]);
+        let tree = git(&work, &["write-tree"]).trim().to_string();
+        // Automation writes the tree's objects into the bare repository.
+        git(&work, &["push", "-q", "../repo.git", &format!("{}:refs/heads/tmp", git(&work, &["commit-tree", &tree, "-m", "x"]).trim())]);
+
+        git(&dir.join("repo.git"), &["config", "user.name", "Bot"]);
+        git(&dir.join("repo.git"), &["config", "user.email", "bot@example.com"]);
+        let repo = GitRepo::discover(&dir.join("repo.git")).unwrap();
+        assert!(repo.toplevel().is_none());
+        let diff = repo.tree_diff(Some("main"), &tree).unwrap();
+        assert!(diff.contains("+two"), "{}", diff);
+        let parent = git(&dir.join("repo.git"), &["rev-parse", "main"]).trim().to_string();
+        let commit = repo
+            .commit_tree(&tree, std::slice::from_ref(&parent), "Add two", Some("refs/heads/main"))
+            .unwrap();
+        assert_eq!(repo.commit_message("main").unwrap(), "Add two");
+        assert_eq!(git(&dir.join("repo.git"), &["rev-parse", "main^"]).trim(), parent);
+        // The ref has moved on from the parent, so it is left alone.
+        assert!(repo.commit_tree(&tree, &[parent], "Again", Some("refs/heads/main")).is_err());
+        assert_eq!(git(&dir.join("repo.git"), &["rev-parse", "main"]).trim(), commit);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
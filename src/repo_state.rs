@@ -0,0 +1,211 @@
+//! Inspection of the repository's state before committing: detached HEAD,
+//! operations in progress and shallow history.
+//!
+//! Each of these changes what Commitaura can sensibly do (amending during a
+//! merge fails, pushing a detached HEAD goes nowhere), so they are checked
+//! once up front instead of surfacing as git errors halfway through.
+
+use std::path::Path;
+
+use crate::{git, CommitauraError};
+
+/// A multi-step git operation that has stopped for user input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Rebase,
+    /// `git am` applying a patch series.
+    Am,
+    Merge,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+impl Operation {
+    /// Marker files and directories git leaves in the git dir, by operation.
+    const MARKERS: &'static [(&'static str, Operation)] = &[
+        ("rebase-merge", Operation::Rebase),
+        ("rebase-apply/applying", Operation::Am),
+        ("rebase-apply", Operation::Rebase),
+        ("MERGE_HEAD", Operation::Merge),
+        ("CHERRY_PICK_HEAD", Operation::CherryPick),
+        ("REVERT_HEAD", Operation::Revert),
+        ("BISECT_LOG", Operation::Bisect),
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Rebase => "rebase",
+            Operation::Am => "am",
+            Operation::Merge => "merge",
+            Operation::CherryPick => "cherry-pick",
+            Operation::Revert => "revert",
+            Operation::Bisect => "bisect",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RepoState {
+    /// HEAD points at a commit rather than a branch.
+    pub detached: bool,
+    pub operation: Option<Operation>,
+    /// The commits being merged into HEAD while a merge is in progress:
+    /// one for an ordinary merge, more for an octopus merge.
+    pub merge_heads: Vec<String>,
+    /// The clone has truncated history.
+    pub shallow: bool,
+    /// Commits reachable from HEAD, counted only for shallow clones.
+    pub available_commits: Option<usize>,
+}
+
+impl RepoState {
+    /// Inspect the current repository.
+    pub fn inspect() -> Result<RepoState, CommitauraError> {
+        let git_dir = git::git_dir()?;
+        let shallow = git::is_shallow();
+        Ok(RepoState {
+            detached: git::symbolic_head().is_none(),
+            operation: operation_in(&git_dir),
+            merge_heads: merge_heads_in(&git_dir),
+            shallow,
+            available_commits: if shallow {
+                git::count_commits("HEAD").ok()
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Whether an octopus merge, of more than one commit into HEAD, is in
+    /// progress.
+    pub fn is_octopus(&self) -> bool {
+        self.merge_heads.len() > 1
+    }
+
+    /// Whether HEAD can be amended: git refuses while a merge, cherry-pick or
+    /// revert is half done, and amending mid-rebase or mid-bisect rewrites a
+    /// commit the operation is still working on.
+    pub fn can_amend(&self) -> bool {
+        self.operation.is_none()
+    }
+
+    /// Whether follow-ups that act on the current branch, such as pushing or
+    /// opening a pull request, make sense.
+    pub fn on_branch(&self) -> bool {
+        !self.detached && self.operation.is_none()
+    }
+
+    /// How many recent subjects to use as history context: `wanted`, or
+    /// fewer when a shallow clone does not have that many.
+    pub fn history_depth(&self, wanted: usize) -> usize {
+        self.available_commits.map_or(wanted, |n| n.min(wanted))
+    }
+
+    /// Whether a shallow clone has fewer than `wanted` commits of history.
+    pub fn needs_deepening(&self, wanted: usize) -> bool {
+        self.available_commits.is_some_and(|n| n < wanted)
+    }
+
+    /// Plain-text notes on how this state changes the run, one per line.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        match self.operation {
+            Some(Operation::Rebase) => warnings.push(
+                "A rebase is in progress; the commit becomes part of it (continue with `git rebase --continue`).".to_string(),
+            ),
+            Some(Operation::Am) => warnings.push(
+                "`git am` is in progress; the commit is added between applied patches.".to_string(),
+            ),
+            Some(Operation::Bisect) => warnings.push(
+                "A bisect is in progress; HEAD is a bisect checkout, not your branch.".to_string(),
+            ),
+            Some(Operation::Merge) if self.is_octopus() => warnings.push(format!(
+                "An octopus merge of {} branches is in progress; the commit concludes it.",
+                self.merge_heads.len()
+            )),
+            Some(op) => warnings.push(format!(
+                "A {} is in progress; the commit concludes it.",
+                op.name()
+            )),
+            None if self.detached => warnings.push(
+                "HEAD is detached; the commit will not be on any branch.".to_string(),
+            ),
+            None => {}
+        }
+        if !self.can_amend() {
+            warnings.push("Amending HEAD is disabled until it finishes.".to_string());
+        }
+        if let Some(n) = self.available_commits {
+            warnings.push(format!(
+                "Shallow clone: only {} commit(s) of history available for context.",
+                n
+            ));
+        }
+        warnings
+    }
+}
+
+/// The operation in progress in `git_dir`, judged by the marker files git
+/// leaves behind.
+pub fn operation_in(git_dir: &Path) -> Option<Operation> {
+    Operation::MARKERS
+        .iter()
+        .find(|(marker, _)| git_dir.join(marker).exists())
+        .map(|(_, op)| *op)
+}
+
+/// The commits listed in `git_dir`'s `MERGE_HEAD`, one per merged branch.
+pub fn merge_heads_in(git_dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(git_dir.join("MERGE_HEAD"))
+        .map(|text| text.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_operations_from_marker_files() {
+        let dir = std::env::temp_dir().join(format!("commitaura-state-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("rebase-apply")).unwrap();
+        assert_eq!(operation_in(&dir), Some(Operation::Rebase));
+        std::fs::write(dir.join("rebase-apply/applying"), "").unwrap();
+        assert_eq!(operation_in(&dir), Some(Operation::Am));
+        std::fs::remove_dir_all(dir.join("rebase-apply")).unwrap();
+        std::fs::write(dir.join("MERGE_HEAD"), "").unwrap();
+        assert_eq!(operation_in(&dir), Some(Operation::Merge));
+        assert!(merge_heads_in(&dir).is_empty());
+        std::fs::write(dir.join("MERGE_HEAD"), "1a2b3c\n4d5e6f\n").unwrap();
+        assert_eq!(merge_heads_in(&dir), vec!["1a2b3c", "4d5e6f"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn limits_what_is_offered() {
+        let merging = RepoState {
+            operation: Some(Operation::Merge),
+            ..RepoState::default()
+        };
+        assert!(!merging.can_amend() && !merging.on_branch());
+        let octopus = RepoState {
+            operation: Some(Operation::Merge),
+            merge_heads: vec!["1a2b3c".to_string(), "4d5e6f".to_string()],
+            ..RepoState::default()
+        };
+        assert!(octopus.is_octopus());
+        assert!(octopus.warnings()[0].starts_with("An octopus merge of 2 branches"));
+
+        let shallow = RepoState {
+            shallow: true,
+            available_commits: Some(2),
+            ..RepoState::default()
+        };
+        assert!(shallow.can_amend() && shallow.on_branch());
+        assert_eq!(shallow.history_depth(5), 2);
+        assert!(shallow.needs_deepening(5) && !shallow.needs_deepening(2));
+        assert_eq!(RepoState::default().history_depth(5), 5);
+        assert!(RepoState::default().warnings().is_empty());
+    }
+}
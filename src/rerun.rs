@@ -0,0 +1,54 @@
+//! Folding a hook's fix-ups back into the commit it ran on.
+//!
+//! Formatters run from hooks often rewrite files right after a commit. Those
+//! changes are restaged and amended into HEAD; the message is only revisited
+//! when they change more than whitespace.
+
+use crate::{git, CommitauraError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmendPlan {
+    /// The hook changed nothing HEAD touched.
+    Nothing,
+    /// Only whitespace or blank lines changed: amend and keep the message.
+    NoEdit,
+    /// The content changed: amend and offer a regenerated message.
+    Regenerate,
+}
+
+/// Decide from the staged diff against HEAD and the same diff with
+/// whitespace changes ignored.
+pub fn plan(staged_diff: &str, semantic_diff: &str) -> AmendPlan {
+    if staged_diff.trim().is_empty() {
+        AmendPlan::Nothing
+    } else if semantic_diff.trim().is_empty() {
+        AmendPlan::NoEdit
+    } else {
+        AmendPlan::Regenerate
+    }
+}
+
+/// Restage the work-tree changes to HEAD's files and plan the amend.
+pub fn restage_and_plan() -> Result<AmendPlan, CommitauraError> {
+    let head_paths = git::commit_paths("HEAD")?;
+    git::restage(&head_paths)?;
+    Ok(plan(
+        &git::get_staged_diff()?,
+        &git::staged_diff_ignoring_whitespace()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_semantic_changes_revisit_the_message() {
+        assert_eq!(plan("", ""), AmendPlan::Nothing);
+        assert_eq!(plan("diff --git a/x b/x\n-a  b\n+a b\n", ""), AmendPlan::NoEdit);
+        assert_eq!(
+            plan("diff --git a/x b/x\n-a\n+b\n", "diff --git a/x b/x\n-a\n+b\n"),
+            AmendPlan::Regenerate
+        );
+    }
+}
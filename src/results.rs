@@ -0,0 +1,202 @@
+//! Test and benchmark results for the commit body, from a command the user
+//! configures (e.g. `cargo test --quiet`).
+//!
+//! The command runs in the [`crate::sandbox`]. Its output is boiled down to one line such as
+//! `all 214 tests pass (+3); bench parse_diff -8%`. Test counts are compared
+//! with the previous run in this repository; benchmark changes are the ones
+//! criterion reports against its own baseline.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sandbox::RunOutcome;
+use crate::{git, CommitauraError};
+
+const LAST_COUNTS_FILE: &str = "results.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestCounts {
+    pub passed: u64,
+    pub failed: u64,
+}
+
+/// `N passed` / `N failed` counts on one line, in the styles of cargo,
+/// pytest and jest.
+fn counts_in(line: &str) -> Option<TestCounts> {
+    let words: Vec<&str> = line
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let mut counts = None;
+    for pair in words.windows(2) {
+        let Ok(n) = pair[0].parse::<u64>() else {
+            continue;
+        };
+        let counts = counts.get_or_insert(TestCounts::default());
+        if pair[1].starts_with("pass") {
+            counts.passed += n;
+        } else if pair[1].starts_with("fail") {
+            counts.failed += n;
+        }
+    }
+    counts.filter(|c| c.passed + c.failed > 0)
+}
+
+/// Total test counts in `output`: the sum of cargo's per-binary
+/// `test result:` lines, or else the last summary line of other runners.
+pub fn test_counts(output: &str) -> Option<TestCounts> {
+    let cargo: Vec<TestCounts> = output
+        .lines()
+        .filter(|l| l.trim_start().starts_with("test result:"))
+        .filter_map(counts_in)
+        .collect();
+    if !cargo.is_empty() {
+        return Some(cargo.iter().fold(TestCounts::default(), |a, c| TestCounts {
+            passed: a.passed + c.passed,
+            failed: a.failed + c.failed,
+        }));
+    }
+    output.lines().rev().find_map(counts_in)
+}
+
+/// Benchmark changes reported by criterion, as `(name, percent)`; the
+/// middle estimate of each `change:` line, rounded.
+pub fn bench_changes(output: &str) -> Vec<(String, i64)> {
+    let mut changes = Vec::new();
+    let mut current = None;
+    for line in output.lines() {
+        if let Some((name, _)) = line.split_once("time:") {
+            if !name.trim().is_empty() {
+                current = Some(name.trim().to_string());
+            }
+        } else if let Some((_, rest)) = line.split_once("change:") {
+            let percents: Vec<f64> = rest
+                .split_whitespace()
+                .filter_map(|w| w.trim_matches(['[', ']']).strip_suffix('%'))
+                .filter_map(|w| w.parse().ok())
+                .collect();
+            if let (Some(name), Some(middle)) = (current.take(), percents.get(1)) {
+                let rounded = middle.round() as i64;
+                if rounded != 0 {
+                    changes.push((name, rounded));
+                }
+            }
+        }
+    }
+    changes
+}
+
+/// One line for the commit body describing `outcome`, with the change in
+/// test count since `previous` when known.
+pub fn summarize(command: &str, outcome: &RunOutcome, previous: Option<TestCounts>) -> String {
+    if outcome.timed_out {
+        return format!(
+            "`{}` timed out after {}s",
+            command,
+            outcome.elapsed.as_secs()
+        );
+    }
+    let mut parts = Vec::new();
+    if let Some(counts) = test_counts(&outcome.output()) {
+        let total = counts.passed + counts.failed;
+        let mut part = if counts.failed == 0 {
+            format!("all {} tests pass", total)
+        } else {
+            format!("{} of {} tests fail", counts.failed, total)
+        };
+        if let Some(previous) = previous {
+            let delta = total as i64 - (previous.passed + previous.failed) as i64;
+            if delta != 0 {
+                part.push_str(&format!(" ({:+})", delta));
+            }
+        }
+        parts.push(part);
+    }
+    for (name, percent) in bench_changes(&outcome.output()) {
+        parts.push(format!("bench {} {:+}%", name, percent));
+    }
+    if parts.is_empty() {
+        let verb = if outcome.success { "passes" } else { "fails" };
+        parts.push(format!("`{}` {}", command, verb));
+    }
+    parts.join("; ")
+}
+
+/// Where the last run's test counts are kept, in `.git/commitaura/`.
+pub fn last_counts_path() -> Result<PathBuf, CommitauraError> {
+    Ok(git::git_dir()?.join("commitaura").join(LAST_COUNTS_FILE))
+}
+
+pub fn load_last_counts() -> Option<TestCounts> {
+    let text = std::fs::read_to_string(last_counts_path().ok()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+pub fn save_last_counts(counts: TestCounts) -> Result<(), CommitauraError> {
+    let path = last_counts_path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(
+        path,
+        serde_json::to_string(&counts).map_err(std::io::Error::from)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn outcome(output: &str) -> RunOutcome {
+        RunOutcome {
+            success: true,
+            timed_out: false,
+            stdout: output.to_string(),
+            stderr: String::new(),
+            elapsed: Duration::from_secs(3),
+        }
+    }
+
+    #[test]
+    fn counts_tests_from_several_runners() {
+        let cargo = "running 53 tests\ntest result: ok. 53 passed; 0 failed; 0 ignored\n\nrunning 3 tests\ntest result: FAILED. 2 passed; 1 failed; 0 ignored\n";
+        assert_eq!(
+            test_counts(cargo),
+            Some(TestCounts {
+                passed: 55,
+                failed: 1
+            })
+        );
+        assert_eq!(
+            test_counts("==== 2 failed, 210 passed in 1.20s ===="),
+            Some(TestCounts {
+                passed: 210,
+                failed: 2
+            })
+        );
+        assert_eq!(test_counts("Compiling 12 crates"), None);
+    }
+
+    #[test]
+    fn summarizes_tests_and_benchmarks() {
+        let output = "test result: ok. 214 passed; 0 failed\nparse_diff              time:   [1.1 ms 1.2 ms 1.3 ms]\n                        change: [-9.1% -8.2% -7.0%] (p = 0.00 < 0.05)\n";
+        assert_eq!(
+            summarize(
+                "cargo test",
+                &outcome(output),
+                Some(TestCounts {
+                    passed: 211,
+                    failed: 0
+                })
+            ),
+            "all 214 tests pass (+3); bench parse_diff -8%"
+        );
+        assert_eq!(
+            summarize("make check", &outcome(""), None),
+            "`make check` passes"
+        );
+    }
+}
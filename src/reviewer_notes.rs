@@ -0,0 +1,106 @@
+//! Reviewer notes: a longer explanation of a change for code review,
+//! produced in the same request as the commit message.
+//!
+//! The model replies with a JSON object holding both, so the diff is sent
+//! once. The notes cover what a reviewer needs and the commit message
+//! should not carry: trade-offs, alternatives considered and where to look
+//! first.
+
+use serde::Deserialize;
+
+use crate::generate::{build_prompt, complete_json, GenerateOptions, Prompt};
+use crate::{message, CommitauraError};
+
+const NOTES_INSTRUCTIONS: &str = "\n\nAlso write notes for the reviewers of these changes: a few short paragraphs or bullets on what to look at first, the trade-offs made and any alternatives considered (from the author's intent, if given). Keep the commit message as concise as it would otherwise be; the detail belongs in the notes.\n\nReply with a JSON object with exactly two string fields: \"commit_message\" and \"reviewer_notes\".";
+
+/// Response tokens for the message and notes together.
+const MAX_RESPONSE_TOKENS: u32 = 700;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageWithNotes {
+    pub message: String,
+    /// Markdown for reviewers.
+    pub notes: String,
+}
+
+#[derive(Deserialize)]
+struct Reply {
+    commit_message: String,
+    #[serde(default)]
+    reviewer_notes: String,
+}
+
+/// The usual prompt for `diff`, extended to ask for reviewer notes in a
+/// JSON reply. The diff is shortened to leave room for the instructions.
+pub fn build_notes_prompt(diff: &str, opts: &GenerateOptions) -> Result<Prompt, CommitauraError> {
+    let mut budget = opts.clone();
+    budget.max_tokens = opts
+        .prompt_budget(MAX_RESPONSE_TOKENS)
+        .saturating_sub(opts.tokenizer().count(NOTES_INSTRUCTIONS)?);
+    let base = build_prompt(diff, &budget)?;
+    Ok(Prompt {
+        system: base.system,
+        user: base.user + NOTES_INSTRUCTIONS,
+    })
+}
+
+/// Read the message and notes from the model's reply, which may be wrapped
+/// in a code fence.
+pub fn parse_reply(
+    reply: &str,
+    opts: &GenerateOptions,
+) -> Result<MessageWithNotes, CommitauraError> {
+    let json = reply
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```");
+    let reply: Reply = serde_json::from_str(json).map_err(|e| {
+        CommitauraError::ApiRequestFailed(format!("Malformed message and notes reply: {}", e))
+    })?;
+    let message = message::assemble(&reply.commit_message, opts);
+    if message.is_empty() {
+        return Err(CommitauraError::ApiRequestFailed(
+            "Received empty commit message from LLM.".to_string(),
+        ));
+    }
+    Ok(MessageWithNotes {
+        message,
+        notes: reply.reviewer_notes.trim().to_string(),
+    })
+}
+
+/// Generate a commit message and reviewer notes for `diff` in one request.
+pub fn generate(diff: &str, opts: &GenerateOptions) -> Result<MessageWithNotes, CommitauraError> {
+    let prompt = build_notes_prompt(diff, opts)?;
+    parse_reply(&complete_json(&prompt, opts, MAX_RESPONSE_TOKENS)?, opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asks_for_both_fields_with_the_intent() {
+        let mut opts = GenerateOptions::new("key");
+        opts.intent = Some("Chose polling over inotify for portability".to_string());
+        let prompt = build_notes_prompt("diff --git a/x b/x\n+y\n", &opts).unwrap();
+        assert!(prompt.user.contains("Chose polling over inotify"));
+        assert!(prompt
+            .user
+            .ends_with("\"commit_message\" and \"reviewer_notes\"."));
+    }
+
+    #[test]
+    fn parses_fenced_replies() {
+        let opts = GenerateOptions::new("key");
+        let reply = "```json\n{\"commit_message\": \"Poll for config changes\\n\\nInotify is unavailable on some mounts.\", \"reviewer_notes\": \"- Start with watcher.rs\"}\n```";
+        let parsed = parse_reply(reply, &opts).unwrap();
+        assert_eq!(
+            parsed.message,
+            "Poll for config changes\n\nInotify is unavailable on some mounts."
+        );
+        assert_eq!(parsed.notes, "- Start with watcher.rs");
+        assert!(parse_reply("{\"commit_message\": \"\"}", &opts).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+//! Planning for `commitaura reword`: which commits in a range get a freshly
+//! generated message and which must keep theirs.
+//!
+//! Autosquash commits (`fixup!`, `squash!`, `amend!`) locate their target by
+//! its subject, so neither they nor the commits they point at may be reworded
+//! unless they are folded first.
+//!
+//! For long ranges the proposals can go through a review file instead of the
+//! terminal: one editable file listing every commit, like a rebase todo list.
+
+use crate::git::CommitInfo;
+use crate::CommitauraError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutosquashKind {
+    Fixup,
+    Squash,
+    Amend,
+}
+
+impl AutosquashKind {
+    pub fn marker(self) -> &'static str {
+        match self {
+            AutosquashKind::Fixup => "fixup!",
+            AutosquashKind::Squash => "squash!",
+            AutosquashKind::Amend => "amend!",
+        }
+    }
+}
+
+/// Split an autosquash subject into its kind and the subject it targets.
+/// Nested markers (`fixup! fixup! foo`) resolve to the innermost target.
+pub fn parse_autosquash(subject: &str) -> Option<(AutosquashKind, &str)> {
+    let kinds = [
+        AutosquashKind::Fixup,
+        AutosquashKind::Squash,
+        AutosquashKind::Amend,
+    ];
+    let kind = kinds
+        .into_iter()
+        .find(|k| subject.starts_with(&format!("{} ", k.marker())))?;
+    let mut target = subject[kind.marker().len()..].trim_start();
+    while let Some((_, inner)) = parse_autosquash(target) {
+        target = inner;
+    }
+    Some((kind, target))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewordAction {
+    /// Generate a new message for this commit.
+    Regenerate,
+    /// An autosquash commit; its marker and target subject must stay intact.
+    KeepAutosquash(AutosquashKind),
+    /// Targeted by a later autosquash commit; rewording it would orphan that commit.
+    KeepTarget,
+}
+
+/// Decide what to do with each commit of a range, in the same order.
+pub fn plan(commits: &[CommitInfo]) -> Vec<RewordAction> {
+    let targets: Vec<&str> = commits
+        .iter()
+        .filter_map(|c| parse_autosquash(c.subject()))
+        .map(|(_, target)| target)
+        .filter(|target| !target.is_empty())
+        .collect();
+
+    // A target names a commit by hash only when it looks like one and no
+    // other commit in the range shares the prefix.
+    let by_hash = |commit: &CommitInfo, target: &str| {
+        target.len() >= 4
+            && target.chars().all(|c| c.is_ascii_hexdigit())
+            && commit.hash.starts_with(target)
+            && commits
+                .iter()
+                .filter(|c| c.hash.starts_with(target))
+                .count()
+                == 1
+    };
+
+    commits
+        .iter()
+        .map(|commit| {
+            if let Some((kind, _)) = parse_autosquash(commit.subject()) {
+                RewordAction::KeepAutosquash(kind)
+            } else if targets
+                .iter()
+                .any(|t| commit.subject() == *t || by_hash(commit, t))
+            {
+                RewordAction::KeepTarget
+            } else {
+                RewordAction::Regenerate
+            }
+        })
+        .collect()
+}
+
+const REVIEW_HELP: &str = "\
+# Each commit starts with a line \"<action> <hash> <original subject>\".
+#   reword = use the message below the line (edit it freely)
+#   keep   = keep the commit's original message
+# Lines starting with '#' are ignored; a message line that starts with '#'
+# is indented by one space, which is removed again. Remove everything to
+# cancel.
+";
+
+/// Render the review file for `commits` with what was planned for each and
+/// the `proposed` messages, all in range order. Kept commits say why they
+/// are kept.
+pub fn write_review(
+    base: &str,
+    commits: &[CommitInfo],
+    actions: &[RewordAction],
+    proposed: &[String],
+) -> String {
+    let mut out = format!("# Reword {}..HEAD ({} commits)\n", base, commits.len());
+    out.push_str(REVIEW_HELP);
+    for ((commit, action), message) in commits.iter().zip(actions).zip(proposed) {
+        let short = &commit.hash[..commit.hash.len().min(7)];
+        out.push('\n');
+        if *message == commit.message {
+            out.push_str(&format!("keep {} {}\n", short, commit.subject()));
+            match action {
+                RewordAction::KeepAutosquash(kind) => {
+                    out.push_str(&format!("# kept: {} commit\n", kind.marker()))
+                }
+                RewordAction::KeepTarget => {
+                    out.push_str("# kept: target of a fixup!/squash! commit\n")
+                }
+                RewordAction::Regenerate => {}
+            }
+        } else {
+            out.push_str(&format!("reword {} {}\n", short, commit.subject()));
+            for line in message.trim_end().lines() {
+                out.push_str(&protect_line(line));
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Whether `line` would be read as a comment once its indentation is gone:
+/// it is only spaces up to a `#`.
+fn commented(line: &str) -> bool {
+    line.trim_start_matches(' ').starts_with('#')
+}
+
+/// `line` with one more space in front when it could pass for a comment,
+/// undone by [`unprotect_line`].
+fn protect_line(line: &str) -> String {
+    if commented(line) {
+        format!(" {}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+fn unprotect_line(line: &str) -> &str {
+    match line.strip_prefix(' ') {
+        Some(rest) if commented(rest) => rest,
+        _ => line,
+    }
+}
+
+/// Read an edited review file back into one message per commit, in range
+/// order. Commits the file leaves out keep their message. Returns `None`
+/// when the file no longer lists any commit.
+pub fn parse_review(
+    text: &str,
+    commits: &[CommitInfo],
+) -> Result<Option<Vec<String>>, CommitauraError> {
+    let mut messages: Vec<String> = commits.iter().map(|c| c.message.clone()).collect();
+    let mut current: Option<(usize, bool, Vec<&str>)> = None;
+    let mut seen = false;
+
+    let mut finish = |entry: Option<(usize, bool, Vec<&str>)>| -> Result<(), CommitauraError> {
+        if let Some((index, true, lines)) = entry {
+            let message = lines.join("\n").trim().to_string();
+            if message.is_empty() {
+                return Err(CommitauraError::ReviewFileError(format!(
+                    "empty message for {}",
+                    &commits[index].hash[..7]
+                )));
+            }
+            messages[index] = message;
+        }
+        Ok(())
+    };
+
+    for line in text.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((index, reword)) = review_header(line, commits)? {
+            finish(current.take())?;
+            current = Some((index, reword, Vec::new()));
+            seen = true;
+        } else if let Some((_, _, lines)) = current.as_mut() {
+            lines.push(unprotect_line(line));
+        } else if !line.trim().is_empty() {
+            return Err(CommitauraError::ReviewFileError(format!(
+                "text before the first commit: {}",
+                line
+            )));
+        }
+    }
+    finish(current.take())?;
+    Ok(seen.then_some(messages))
+}
+
+/// Parse `<action> <hash> ...` if `line` is an entry header for one of
+/// `commits`; the hash must match so message lines are never mistaken for
+/// headers.
+fn review_header(
+    line: &str,
+    commits: &[CommitInfo],
+) -> Result<Option<(usize, bool)>, CommitauraError> {
+    let mut words = line.split_whitespace();
+    let (Some(action), Some(hash)) = (words.next(), words.next()) else {
+        return Ok(None);
+    };
+    let reword = match action {
+        "reword" | "r" => true,
+        "keep" | "k" => false,
+        _ => return Ok(None),
+    };
+    if hash.len() < 4 {
+        return Ok(None);
+    }
+    let mut matching = commits
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.hash.starts_with(hash));
+    match (matching.next(), matching.next()) {
+        (Some((index, _)), None) => Ok(Some((index, reword))),
+        (Some(_), Some(_)) => Err(CommitauraError::ReviewFileError(format!(
+            "{} names more than one commit; give more of the hash",
+            hash
+        ))),
+        (None, _) if hash.chars().all(|c| c.is_ascii_hexdigit()) => Err(
+            CommitauraError::ReviewFileError(format!("{} is not in the range", hash)),
+        ),
+        (None, _) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(hash: &str, message: &str) -> CommitInfo {
+        CommitInfo {
+            hash: hash.to_string(),
+            tree: String::new(),
+            author_name: String::new(),
+            author_email: String::new(),
+            author_date: String::new(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_autosquash_subjects() {
+        assert_eq!(
+            parse_autosquash("fixup! Add parser"),
+            Some((AutosquashKind::Fixup, "Add parser"))
+        );
+        assert_eq!(
+            parse_autosquash("squash! fixup! Add parser"),
+            Some((AutosquashKind::Squash, "Add parser"))
+        );
+        assert_eq!(parse_autosquash("Fix fixup! handling"), None);
+    }
+
+    #[test]
+    fn keeps_autosquash_commits_and_their_targets() {
+        let commits = [
+            commit("aaa111", "Add parser"),
+            commit("bbb222", "Add lexer"),
+            commit("ccc333", "fixup! Add parser"),
+            commit("ddd444", "squash! bbb2"),
+            commit("eee555", "Update docs"),
+            commit("fff666", "fixup! f"),
+            commit("abcd777", "Add cache"),
+            commit("abcd888", "Add store"),
+            commit("ddd999", "fixup! abcd"),
+        ];
+        assert_eq!(
+            plan(&commits),
+            vec![
+                RewordAction::KeepTarget,
+                RewordAction::KeepTarget,
+                RewordAction::KeepAutosquash(AutosquashKind::Fixup),
+                RewordAction::KeepAutosquash(AutosquashKind::Squash),
+                RewordAction::Regenerate,
+                RewordAction::KeepAutosquash(AutosquashKind::Fixup),
+                RewordAction::Regenerate,
+                RewordAction::Regenerate,
+                RewordAction::KeepAutosquash(AutosquashKind::Fixup),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_an_edited_review_file() {
+        let commits = [
+            commit("aaa1111", "Add parser"),
+            commit("bbb2222", "fixup! Add parser"),
+            commit("ccc3333", "wip"),
+        ];
+        let proposed = [
+            "Add recursive descent parser".to_string(),
+            "fixup! Add parser".to_string(),
+            "Document parser errors".to_string(),
+        ];
+        let review = write_review("main", &commits, &plan(&commits), &proposed);
+        assert!(review.contains("\nreword aaa1111 Add parser\nAdd recursive descent parser\n"));
+        assert!(review.contains("\nkeep bbb2222 fixup! Add parser\n# kept: fixup! commit\n"));
+
+        let edited = review.replace("reword aaa1111", "keep aaa1111").replace(
+            "Document parser errors",
+            "Document parser errors\n\nList every error variant.",
+        );
+        assert_eq!(
+            parse_review(&edited, &commits).unwrap(),
+            Some(vec![
+                "Add parser".to_string(),
+                "fixup! Add parser".to_string(),
+                "Document parser errors\n\nList every error variant.".to_string(),
+            ])
+        );
+
+        let proposed = [
+            "Add parser".to_string(),
+            "fixup! Add parser".to_string(),
+            "Fix overflow\n\n#88 was caused by this.\n #1 too.".to_string(),
+        ];
+        let review = write_review("main", &commits, &plan(&commits), &proposed);
+        assert!(review.contains("\n #88 was caused by this.\n  #1 too.\n"));
+        assert_eq!(
+            parse_review(&review, &commits).unwrap().unwrap()[2],
+            proposed[2]
+        );
+
+        assert_eq!(parse_review("# cancelled\n", &commits).unwrap(), None);
+        assert!(parse_review("reword ccc3333 wip\n\n", &commits).is_err());
+        assert!(parse_review("keep ddd4444 other\n", &commits).is_err());
+        let twins = [commit("abcd111", "One"), commit("abcd222", "Two")];
+        assert!(parse_review("keep abcd One\n", &twins).is_err());
+    }
+}
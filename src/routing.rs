@@ -0,0 +1,293 @@
+//! Choosing the model by the size of the change.
+//!
+//! Most commits are small and a cheap, fast model describes them as well
+//! as any; a change spanning many files or several languages is where a
+//! stronger model earns its price. With `[routing]` configured, a diff is
+//! small while it stays within every threshold, files, tokens and
+//! programming languages, and large once it exceeds one. Each size can
+//! name its own model; a size without one keeps the configured model.
+//!
+//! The size is that of the diff as the prompt shows it, so ignored files,
+//! collapsed vendored packages and summarized generated files do not make
+//! a change large. Routing only switches models of the configured
+//! provider.
+
+use std::collections::BTreeSet;
+
+use log::warn;
+
+use crate::config::RoutingConfig;
+use crate::priority::{self, Category};
+use crate::tokenizer::Tokenizer;
+use crate::{diff, generate, CommitauraError, GenerateOptions};
+
+/// Most files a small change touches, unless configured.
+pub const DEFAULT_MAX_FILES: usize = 8;
+
+/// Most diff tokens of a small change, unless configured.
+pub const DEFAULT_MAX_TOKENS: usize = 3000;
+
+/// Most programming languages a small change touches, unless configured.
+pub const DEFAULT_MAX_LANGUAGES: usize = 1;
+
+/// Programming languages by file extension. Configuration, data and
+/// documentation files do not count as a language of their own.
+const LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "Rust"),
+    ("go", "Go"),
+    ("py", "Python"),
+    ("rb", "Ruby"),
+    ("js", "JavaScript"),
+    ("jsx", "JavaScript"),
+    ("mjs", "JavaScript"),
+    ("cjs", "JavaScript"),
+    ("ts", "TypeScript"),
+    ("tsx", "TypeScript"),
+    ("java", "Java"),
+    ("kt", "Kotlin"),
+    ("kts", "Kotlin"),
+    ("scala", "Scala"),
+    ("swift", "Swift"),
+    ("m", "Objective-C"),
+    ("mm", "Objective-C"),
+    ("c", "C"),
+    ("h", "C"),
+    ("cc", "C++"),
+    ("cpp", "C++"),
+    ("cxx", "C++"),
+    ("hpp", "C++"),
+    ("cs", "C#"),
+    ("fs", "F#"),
+    ("php", "PHP"),
+    ("pl", "Perl"),
+    ("lua", "Lua"),
+    ("dart", "Dart"),
+    ("ex", "Elixir"),
+    ("exs", "Elixir"),
+    ("erl", "Erlang"),
+    ("hs", "Haskell"),
+    ("ml", "OCaml"),
+    ("clj", "Clojure"),
+    ("zig", "Zig"),
+    ("nim", "Nim"),
+    ("jl", "Julia"),
+    ("r", "R"),
+    ("sh", "Shell"),
+    ("bash", "Shell"),
+    ("zsh", "Shell"),
+    ("ps1", "PowerShell"),
+    ("sql", "SQL"),
+    ("vue", "Vue"),
+    ("svelte", "Svelte"),
+    ("css", "CSS"),
+    ("scss", "CSS"),
+    ("html", "HTML"),
+];
+
+/// The size of a diff, as routing sees it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub files: usize,
+    pub tokens: usize,
+    /// Programming languages of the changed source and test files, sorted.
+    pub languages: Vec<&'static str>,
+}
+
+impl Metrics {
+    /// The size of `diff`, its tokens counted by `tokenizer`.
+    pub fn measure(diff: &str, tokenizer: Tokenizer) -> Result<Metrics, CommitauraError> {
+        let files = diff::parse(diff);
+        let languages: BTreeSet<&'static str> = files
+            .iter()
+            .filter(|file| priority::category(&file.path) <= Category::Test)
+            .filter_map(|file| language(&file.path))
+            .collect();
+        Ok(Metrics {
+            files: files.len(),
+            tokens: tokenizer.count(diff)?,
+            languages: languages.into_iter().collect(),
+        })
+    }
+}
+
+/// The programming language of the file at `path`, if it is one.
+pub fn language(path: &str) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let (_, extension) = name.rsplit_once('.')?;
+    let extension = extension.to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
+
+/// The model chosen for a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub model: String,
+    pub large: bool,
+    /// The thresholds a large change exceeds, or the size of a small one.
+    pub reason: String,
+}
+
+impl Route {
+    /// A note such as "Routed to gpt-4o for a large change: 12 files (over 8)".
+    pub fn describe(&self) -> String {
+        format!(
+            "Routed to {} for a {} change: {}",
+            self.model,
+            if self.large { "large" } else { "small" },
+            self.reason
+        )
+    }
+}
+
+/// The model `config` routes a diff of this size to; `None` when it names
+/// no model for that size.
+pub fn route(metrics: &Metrics, config: &RoutingConfig) -> Option<Route> {
+    let max_files = config.max_files.unwrap_or(DEFAULT_MAX_FILES);
+    let max_tokens = config.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+    let max_languages = config.max_languages.unwrap_or(DEFAULT_MAX_LANGUAGES);
+    let mut exceeded = Vec::new();
+    if metrics.files > max_files {
+        exceeded.push(format!(
+            "{} (over {})",
+            plural(metrics.files, "file"),
+            max_files
+        ));
+    }
+    if metrics.tokens > max_tokens {
+        exceeded.push(format!("{} tokens (over {})", metrics.tokens, max_tokens));
+    }
+    if metrics.languages.len() > max_languages {
+        exceeded.push(format!(
+            "{} ({}; over {})",
+            plural(metrics.languages.len(), "language"),
+            metrics.languages.join(", "),
+            max_languages
+        ));
+    }
+    let large = !exceeded.is_empty();
+    let model = if large {
+        config.large_model.clone()?
+    } else {
+        config.small_model.clone()?
+    };
+    let reason = if large {
+        exceeded.join(", ")
+    } else {
+        let mut size = vec![
+            plural(metrics.files, "file"),
+            format!("{} tokens", metrics.tokens),
+        ];
+        size.extend(metrics.languages.iter().map(|l| l.to_string()));
+        size.join(", ")
+    };
+    Some(Route {
+        model,
+        large,
+        reason,
+    })
+}
+
+/// The route `config` picks for `diff` as the prompt for `opts` would show
+/// it; `None` when it keeps `opts`' model. A known hosted model that the
+/// configured provider does not serve is not routed to, since the request
+/// would go to the wrong API.
+pub fn route_for(
+    diff: &str,
+    opts: &GenerateOptions,
+    config: &RoutingConfig,
+) -> Result<Option<Route>, CommitauraError> {
+    let metrics = Metrics::measure(&generate::prepare(diff, opts), opts.tokenizer())?;
+    let Some(route) = route(&metrics, config).filter(|route| route.model != opts.model) else {
+        return Ok(None);
+    };
+    if crate::pricing::pricing(&route.model).is_some() && !opts.provider.serves(&route.model) {
+        warn!(
+            "Not routing to {}: {} does not serve it",
+            route.model,
+            opts.provider.name()
+        );
+        return Ok(None);
+    }
+    Ok(Some(route))
+}
+
+/// `1 file` or `3 files`.
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {}", noun),
+        n => format!("{} {}s", n, noun),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_languages_of_source_and_test_files() {
+        assert_eq!(language("src/main.rs"), Some("Rust"));
+        assert_eq!(language("web/App.TSX"), Some("TypeScript"));
+        assert_eq!(language("Cargo.toml"), None);
+        assert_eq!(language("Makefile"), None);
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\ndiff --git a/tests/api.py b/tests/api.py\n--- a/tests/api.py\n+++ b/tests/api.py\n@@ -1 +1 @@\n-a\n+b\ndiff --git a/docs/setup.sh b/docs/setup.sh\n--- a/docs/setup.sh\n+++ b/docs/setup.sh\n@@ -1 +1 @@\n-a\n+b\n";
+        let metrics = Metrics::measure(diff, Tokenizer::Heuristic).unwrap();
+        assert_eq!(metrics.files, 3);
+        assert_eq!(metrics.languages, vec!["Python", "Rust"]);
+    }
+
+    #[test]
+    fn routes_by_the_thresholds_exceeded() {
+        let config: RoutingConfig = toml::from_str(
+            "small_model = \"gpt-4o-mini\"\nlarge_model = \"gpt-4o\"\nmax_files = 4",
+        )
+        .unwrap();
+        let metrics = |files, tokens, languages: &[&'static str]| Metrics {
+            files,
+            tokens,
+            languages: languages.to_vec(),
+        };
+        let small = route(&metrics(1, 200, &["Rust"]), &config).unwrap();
+        assert_eq!(
+            small.describe(),
+            "Routed to gpt-4o-mini for a small change: 1 file, 200 tokens, Rust"
+        );
+        let large = route(&metrics(6, 200, &["Go", "Rust"]), &config).unwrap();
+        assert_eq!(
+            large.describe(),
+            "Routed to gpt-4o for a large change: 6 files (over 4), 2 languages (Go, Rust; over 1)"
+        );
+        let large = route(&metrics(1, 5000, &[]), &config).unwrap();
+        assert!(large.large && large.reason == "5000 tokens (over 3000)");
+
+        let only_large: RoutingConfig = toml::from_str("large_model = \"gpt-4o\"").unwrap();
+        assert_eq!(route(&metrics(1, 200, &["Rust"]), &only_large), None);
+    }
+
+    #[test]
+    fn measures_the_prepared_diff_and_keeps_the_provider() {
+        let config: RoutingConfig = toml::from_str(
+            "small_model = \"gpt-4o-mini\"\nlarge_model = \"gpt-4o\"\nmax_tokens = 100",
+        )
+        .unwrap();
+        let file = |path: &str, lines: usize| {
+            format!(
+                "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -0,0 +1,{1} @@\n{2}",
+                path,
+                lines,
+                "+let value = compute(input);\n".repeat(lines)
+            )
+        };
+        let diff = file("src/lib.rs", 1) + &file("vendor/dep/lib.rs", 200);
+        let mut opts = GenerateOptions::new("");
+        opts.model = "o3".to_string();
+        opts.ignore = crate::ignore::IgnoreFile::parse("vendor/").unwrap();
+        let small = route_for(&diff, &opts, &config).unwrap().unwrap();
+        assert_eq!(small.model, "gpt-4o-mini");
+
+        let claude = GenerateOptions::for_provider("", crate::provider::Provider::Anthropic);
+        assert_eq!(route_for(&diff, &claude, &config).unwrap(), None);
+    }
+}
@@ -0,0 +1,556 @@
+//! Rules commit messages are checked against: by [`crate::message::lint`]
+//! for generated messages, and by `commitaura assert` for any message,
+//! e.g. those of a pull request in CI.
+//!
+//! The default rules are the ones generated messages have always been
+//! linted with. A `rules.toml` file turns on more and makes them stricter.
+
+use std::fmt;
+use std::path::Path;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::message::{self, Conventional, MAX_SUBJECT_LEN};
+use crate::style::{self, Tense};
+use crate::CommitauraError;
+
+/// Where `commitaura assert` looks for rules, relative to the repository.
+pub const RULES_FILE: &str = ".commitaura/rules.toml";
+
+/// Conventional Commits types allowed when `types` is empty.
+const CONVENTIONAL_TYPES: &[&str] = &[
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+/// Each rule's id and what it checks, as listed in SARIF reports.
+pub const RULE_IDS: &[(&str, &str)] = &[
+    (
+        "subject-length",
+        "The subject is at most `max_subject_length` characters long",
+    ),
+    ("subject-period", "The subject does not end with a period"),
+    (
+        "blank-line",
+        "A blank line separates the subject from the body",
+    ),
+    (
+        "body-line-length",
+        "Body lines are at most `max_body_line_length` characters long",
+    ),
+    ("imperative", "The subject is in the imperative mood"),
+    (
+        "conventional",
+        "The subject has a Conventional Commits `type(scope): ` prefix",
+    ),
+    ("type", "The Conventional Commits type is one of `types`"),
+    ("scope", "The Conventional Commits scope is one of `scopes`"),
+    ("subject-pattern", "The subject matches `subject_pattern`"),
+    (
+        "forbidden",
+        "The message contains none of the `forbidden` patterns",
+    ),
+    (
+        "trailer",
+        "The message's final trailer block has each of the `trailers`",
+    ),
+];
+
+/// The rules file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Rules {
+    /// Longest subject, in characters.
+    pub max_subject_length: usize,
+    /// Allow a subject that ends with a period.
+    pub allow_subject_period: bool,
+    /// Require a blank line between the subject and the body.
+    pub blank_line: bool,
+    /// Longest body line, in characters. `None` allows any length.
+    pub max_body_line_length: Option<usize>,
+    /// Require the subject to be in the imperative mood, e.g. "Add", not
+    /// "Added" or "Adds".
+    pub imperative: bool,
+    /// Require a Conventional Commits prefix, `type(scope): subject`.
+    pub conventional: bool,
+    /// Types allowed with `conventional`. Empty allows the standard ones.
+    pub types: Vec<String>,
+    /// Scopes allowed with `conventional`. Empty allows any.
+    pub scopes: Vec<String>,
+    /// Require a scope with `conventional`.
+    pub require_scope: bool,
+    /// Regular expression the subject must match.
+    pub subject_pattern: Option<String>,
+    /// Regular expressions no line of the message may match.
+    pub forbidden: Vec<String>,
+    /// Trailer tokens the message must have, e.g. `Signed-off-by`.
+    pub trailers: Vec<String>,
+    /// Regular expressions for subjects that are not checked at all, such
+    /// as the messages git writes for merges and reverts.
+    pub ignore: Vec<String>,
+    /// Ids of rules whose failures are only warnings.
+    pub warn: Vec<String>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            max_subject_length: MAX_SUBJECT_LEN,
+            allow_subject_period: false,
+            blank_line: true,
+            max_body_line_length: None,
+            imperative: false,
+            conventional: false,
+            types: Vec::new(),
+            scopes: Vec::new(),
+            require_scope: false,
+            subject_pattern: None,
+            forbidden: Vec::new(),
+            trailers: Vec::new(),
+            ignore: vec!["^Merge ".to_string(), "^Revert \"".to_string()],
+            warn: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A rule a message breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The rule's id, from [`RULE_IDS`].
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// Line of the message, from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Rules {
+    /// The rules in `path`.
+    pub fn load(path: &Path) -> Result<Rules, CommitauraError> {
+        let text = std::fs::read_to_string(path)?;
+        let rules: Rules = toml::from_str(&text).map_err(|e| {
+            CommitauraError::ConfigError(format!("{}: {}", path.display(), e.message()))
+        })?;
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    /// Check that the patterns compile and `warn` names known rules.
+    pub fn validate(&self) -> Result<(), CommitauraError> {
+        for pattern in self.patterns() {
+            regex(pattern)?;
+        }
+        if let Some(unknown) = self
+            .warn
+            .iter()
+            .find(|id| !RULE_IDS.iter().any(|(known, _)| known == id))
+        {
+            return Err(CommitauraError::ConfigError(format!(
+                "warn: unknown rule `{}`",
+                unknown
+            )));
+        }
+        Ok(())
+    }
+
+    fn patterns(&self) -> impl Iterator<Item = &String> {
+        self.subject_pattern
+            .iter()
+            .chain(&self.forbidden)
+            .chain(&self.ignore)
+    }
+
+    /// Whether `message` is exempt through `ignore`.
+    pub fn ignores(&self, message: &str) -> Result<bool, CommitauraError> {
+        let subject = message.trim().lines().next().unwrap_or_default();
+        for pattern in &self.ignore {
+            if regex(pattern)?.is_match(subject) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+fn regex(pattern: &str) -> Result<Regex, CommitauraError> {
+    Regex::new(pattern)
+        .map_err(|e| CommitauraError::ConfigError(format!("invalid pattern `{}`: {}", pattern, e)))
+}
+
+/// The rules `message` breaks, in the order they are checked.
+pub fn check(message: &str, rules: &Rules) -> Result<Vec<Violation>, CommitauraError> {
+    let message = message.trim();
+    let lines: Vec<&str> = message.lines().collect();
+    let subject = lines.first().copied().unwrap_or_default();
+    let mut found: Vec<(&'static str, usize, String)> = Vec::new();
+
+    let len = subject.chars().count();
+    if len > rules.max_subject_length {
+        found.push((
+            "subject-length",
+            1,
+            format!(
+                "Subject is {} characters long (limit {})",
+                len, rules.max_subject_length
+            ),
+        ));
+    }
+    if !rules.allow_subject_period && subject.ends_with('.') {
+        found.push((
+            "subject-period",
+            1,
+            "Subject ends with a period".to_string(),
+        ));
+    }
+    if rules.blank_line && lines.get(1).is_some_and(|l| !l.trim().is_empty()) {
+        found.push((
+            "blank-line",
+            2,
+            "No blank line between subject and body".to_string(),
+        ));
+    }
+    if let Some(max) = rules.max_body_line_length {
+        for (i, line) in lines.iter().enumerate().skip(1) {
+            let len = line.chars().count();
+            // A long URL cannot be wrapped.
+            if len > max && !line.contains("://") {
+                found.push((
+                    "body-line-length",
+                    i + 1,
+                    format!("Body line is {} characters long (limit {})", len, max),
+                ));
+            }
+        }
+    }
+    if rules.imperative {
+        let tense = style::analyze_subject(subject).tense;
+        if tense != Tense::Imperative {
+            found.push((
+                "imperative",
+                1,
+                format!(
+                    "Subject is in the {}; use the imperative mood, e.g. \"Add\"",
+                    tense.label()
+                ),
+            ));
+        }
+    }
+    if rules.conventional {
+        check_conventional(subject, rules, &mut found);
+    }
+    if let Some(pattern) = &rules.subject_pattern {
+        if !regex(pattern)?.is_match(subject) {
+            found.push((
+                "subject-pattern",
+                1,
+                format!("Subject does not match `{}`", pattern),
+            ));
+        }
+    }
+    for pattern in &rules.forbidden {
+        let re = regex(pattern)?;
+        if let Some(i) = lines.iter().position(|line| re.is_match(line)) {
+            found.push((
+                "forbidden",
+                i + 1,
+                format!("Message matches forbidden pattern `{}`", pattern),
+            ));
+        }
+    }
+    // Like git, only the block the message ends in holds trailers.
+    let trailers = message::trailer_block(message).unwrap_or_default();
+    for token in &rules.trailers {
+        let prefix = format!("{}:", token.to_lowercase());
+        if !trailers
+            .lines()
+            .any(|line| line.to_lowercase().starts_with(&prefix))
+        {
+            found.push((
+                "trailer",
+                lines.len().max(1),
+                format!("Missing `{}:` trailer", token),
+            ));
+        }
+    }
+
+    Ok(found
+        .into_iter()
+        .map(|(rule, line, message)| Violation {
+            rule,
+            severity: if rules.warn.iter().any(|id| id == rule) {
+                Severity::Warning
+            } else {
+                Severity::Error
+            },
+            line,
+            message,
+        })
+        .collect())
+}
+
+fn check_conventional(
+    subject: &str,
+    rules: &Rules,
+    found: &mut Vec<(&'static str, usize, String)>,
+) {
+    let Some(Conventional { kind, scope, .. }) = message::conventional(subject) else {
+        found.push((
+            "conventional",
+            1,
+            "Subject has no `type(scope): ` prefix".to_string(),
+        ));
+        return;
+    };
+    let allowed = |list: &[String], value: &str| list.iter().any(|t| t == value);
+    let kind_ok = if rules.types.is_empty() {
+        CONVENTIONAL_TYPES.contains(&kind)
+    } else {
+        allowed(&rules.types, kind)
+    };
+    if !kind_ok {
+        found.push(("type", 1, format!("`{}` is not an allowed type", kind)));
+    }
+    match scope {
+        None if rules.require_scope => {
+            found.push(("scope", 1, "Subject has no scope".to_string()));
+        }
+        Some(scope) if !rules.scopes.is_empty() && !allowed(&rules.scopes, scope) => {
+            found.push(("scope", 1, format!("`{}` is not an allowed scope", scope)));
+        }
+        _ => {}
+    }
+}
+
+/// Where a checked message came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// A message file, such as the one a `commit-msg` hook is given.
+    File(String),
+    /// A commit, by its hash.
+    Commit(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File(name) | Source::Commit(name) => f.write_str(name),
+        }
+    }
+}
+
+/// The messages checked, each with where it came from and the rules it
+/// breaks.
+pub type Report = [(Source, Vec<Violation>)];
+
+/// `report` as SARIF 2.1.0, for code scanning annotations.
+pub fn to_sarif(report: &Report) -> String {
+    let rules: Vec<serde_json::Value> = RULE_IDS
+        .iter()
+        .map(|(id, description)| {
+            serde_json::json!({ "id": id, "shortDescription": { "text": description } })
+        })
+        .collect();
+    let results: Vec<serde_json::Value> = report
+        .iter()
+        .flat_map(|(source, violations)| {
+            violations.iter().map(move |v| {
+                serde_json::json!({
+                    "ruleId": v.rule,
+                    "level": match v.severity {
+                        Severity::Error => "error",
+                        Severity::Warning => "warning",
+                    },
+                    "message": { "text": v.message },
+                    "locations": [location(source, v.line)]
+                })
+            })
+        })
+        .collect();
+    let sarif = serde_json::json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "commitaura",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/cybrdelic/commitaura",
+                    "rules": rules
+                }
+            },
+            "results": results
+        }]
+    });
+    serde_json::to_string_pretty(&sarif).unwrap_or_default()
+}
+
+/// Where in `source` line `line` is. A commit is not a file in the tree,
+/// so it is named as a logical location.
+fn location(source: &Source, line: usize) -> serde_json::Value {
+    match source {
+        Source::File(path) => serde_json::json!({
+            "physicalLocation": {
+                "artifactLocation": { "uri": path },
+                "region": { "startLine": line }
+            }
+        }),
+        Source::Commit(hash) => serde_json::json!({
+            "logicalLocations": [{ "name": hash, "kind": "resource" }],
+            "message": { "text": format!("Line {} of the commit message", line) }
+        }),
+    }
+}
+
+/// `report` as JUnit XML: one test case per message, failing on errors.
+/// Warnings are listed in the test case's output.
+pub fn to_junit(report: &Report) -> String {
+    let failures = report
+        .iter()
+        .filter(|(_, violations)| violations.iter().any(|v| v.severity == Severity::Error))
+        .count();
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n  <testsuite name=\"commitaura assert\" tests=\"{}\" failures=\"{}\">\n",
+        report.len(),
+        failures
+    );
+    for (source, violations) in report {
+        xml.push_str(&format!(
+            "    <testcase classname=\"commit-messages\" name=\"{}\"",
+            escape(&source.to_string())
+        ));
+        if violations.is_empty() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        let describe = |v: &Violation| format!("line {}: {} [{}]", v.line, v.message, v.rule);
+        let (errors, warnings): (Vec<&Violation>, Vec<&Violation>) = violations
+            .iter()
+            .partition(|v| v.severity == Severity::Error);
+        if let Some(first) = errors.first() {
+            let details: Vec<String> = errors.iter().map(|v| describe(v)).collect();
+            xml.push_str(&format!(
+                "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                first.rule,
+                escape(&first.message),
+                escape(&details.join("\n"))
+            ));
+        }
+        if !warnings.is_empty() {
+            let details: Vec<String> = warnings
+                .iter()
+                .map(|v| format!("warning: {}", describe(v)))
+                .collect();
+            xml.push_str(&format!(
+                "      <system-out>{}</system-out>\n",
+                escape(&details.join("\n"))
+            ));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(message: &str, rules: &Rules) -> Vec<&'static str> {
+        check(message, rules)
+            .unwrap()
+            .iter()
+            .map(|v| v.rule)
+            .collect()
+    }
+
+    #[test]
+    fn checks_configured_rules() {
+        let rules: Rules = toml::from_str(
+            "conventional = true\nscopes = [\"parser\"]\nimperative = true\nmax_body_line_length = 30\ntrailers = [\"Signed-off-by\"]\nforbidden = [\"(?i)\\\\bwip\\\\b\"]\nwarn = [\"body-line-length\"]\n",
+        )
+        .unwrap();
+        rules.validate().unwrap();
+        assert_eq!(
+            ids(
+                "feat(parser): Add lexer\n\nShort body.\n\nSigned-off-by: A <a@b.c>",
+                &rules
+            ),
+            Vec::<&str>::new()
+        );
+        let violations = check(
+            "feature(cli): Added WIP flag.\n\nA body line that is far too long.",
+            &rules,
+        )
+        .unwrap();
+        let found: Vec<(&str, Severity)> =
+            violations.iter().map(|v| (v.rule, v.severity)).collect();
+        assert_eq!(
+            found,
+            [
+                ("subject-period", Severity::Error),
+                ("body-line-length", Severity::Warning),
+                ("imperative", Severity::Error),
+                ("type", Severity::Error),
+                ("scope", Severity::Error),
+                ("forbidden", Severity::Error),
+                ("trailer", Severity::Error),
+            ]
+        );
+        // Only the trailer block the message ends in counts.
+        assert_eq!(
+            ids(
+                "feat(parser): Add lexer\n\nSigned-off-by: A <a@b.c>\n\nShort body.",
+                &rules
+            ),
+            ["trailer"]
+        );
+        assert!(rules.ignores("Merge branch 'main' into topic").unwrap());
+        assert!(toml::from_str::<Rules>("warn = [\"lenght\"]")
+            .unwrap()
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn reports_as_sarif_and_junit() {
+        let report = vec![
+            (
+                Source::File("msg.txt".to_string()),
+                check("Fix bug.", &Rules::default()).unwrap(),
+            ),
+            (Source::Commit("abc1234".to_string()), Vec::new()),
+            (
+                Source::Commit("def5678".to_string()),
+                check("Fix bug.", &Rules::default()).unwrap(),
+            ),
+        ];
+        let sarif: serde_json::Value = serde_json::from_str(&to_sarif(&report)).unwrap();
+        let results = &sarif["runs"][0]["results"];
+        assert_eq!(results[0]["ruleId"], "subject-period");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "msg.txt"
+        );
+        let commit = &results[1]["locations"][0];
+        assert!(commit.get("physicalLocation").is_none());
+        assert_eq!(commit["logicalLocations"][0]["name"], "def5678");
+        let junit = to_junit(&report);
+        assert!(junit.contains("tests=\"3\" failures=\"2\""), "{}", junit);
+        assert!(junit.contains("<testcase classname=\"commit-messages\" name=\"abc1234\"/>"));
+    }
+}
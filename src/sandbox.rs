@@ -0,0 +1,375 @@
+//! The safety layer for commands taken from configuration, such as the
+//! `[results]` command.
+//!
+//! A configured command only runs if its program is listed in the
+//! `[sandbox]` allowlist, which only the global config may set. It is
+//! executed directly, without a shell, so the allowlist cannot be sidestepped
+//! with `;` or `&&`. The child sees a scrubbed environment with no API keys
+//! or tokens, and is killed along with everything it started when it runs
+//! past its timeout, or when a command run in the background is abandoned.
+
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::config::SandboxConfig;
+use crate::{git, CommitauraError};
+
+/// Variables a typical build or test command needs, passed through when set.
+const BASE_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "SHELL",
+    "LANG",
+    "LC_ALL",
+    "LC_CTYPE",
+    "TERM",
+    "TMPDIR",
+    "TMP",
+    "TEMP",
+    "SYSTEMROOT",
+    "USERPROFILE",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "CARGO_TARGET_DIR",
+    "GOPATH",
+    "VIRTUAL_ENV",
+    "JAVA_HOME",
+];
+
+/// Name fragments of variables that are never passed on, even when listed
+/// in `pass_env`.
+const SECRET_MARKERS: &[&str] = &[
+    "KEY",
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "SESSION",
+    "COOKIE",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutcome {
+    pub success: bool,
+    /// The command was killed after running past its timeout.
+    pub timed_out: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub elapsed: Duration,
+}
+
+impl RunOutcome {
+    /// Standard output followed by standard error.
+    pub fn output(&self) -> String {
+        format!("{}{}", self.stdout, self.stderr)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    allow: Vec<String>,
+    pass_env: Vec<String>,
+}
+
+impl Sandbox {
+    pub fn new(config: &SandboxConfig) -> Sandbox {
+        Sandbox {
+            allow: config.allow.clone(),
+            pass_env: config.pass_env.clone(),
+        }
+    }
+
+    /// Split `command` into program and arguments, failing unless the
+    /// program is allowlisted.
+    pub fn check(&self, command: &str) -> Result<Vec<String>, CommitauraError> {
+        let words = split(command)?;
+        self.check_program(&words[0])?;
+        Ok(words)
+    }
+
+    fn check_program(&self, program: &str) -> Result<(), CommitauraError> {
+        if !self.allow.iter().any(|allowed| allowed == program) {
+            return Err(CommitauraError::CommandNotAllowed(format!(
+                "`{}` is not in the [sandbox] allow list",
+                program
+            )));
+        }
+        Ok(())
+    }
+
+    /// The environment a sandboxed command gets out of `vars`.
+    pub fn environment(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Vec<(String, String)> {
+        vars.into_iter()
+            .filter(|(name, _)| {
+                (BASE_ENV.contains(&name.as_str()) || self.pass_env.contains(name))
+                    && !is_secret(name)
+            })
+            .collect()
+    }
+
+    /// Run `command` at the repository root, killing it after `timeout`.
+    pub fn run(&self, command: &str, timeout: Duration) -> Result<RunOutcome, CommitauraError> {
+        let words = self.check(command)?;
+        self.run_words(&words, &[], timeout)
+    }
+
+    /// Start [`Sandbox::run`] in the background, for work to go on while
+    /// it runs. Fails at once if the command is not allowed.
+    pub fn spawn(self, command: &str, timeout: Duration) -> Result<Background, CommitauraError> {
+        let words = self.check(command)?;
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let flag = abandoned.clone();
+        let handle = std::thread::spawn(move || self.execute(&words, &[], timeout, &flag));
+        Ok(Background {
+            handle: Some(handle),
+            abandoned,
+        })
+    }
+
+    /// Run the program and arguments in `words` at the repository root,
+    /// with `env` set over the scrubbed environment, killing it after
+    /// `timeout`. For commands Commitaura builds itself, which need no
+    /// splitting but the same allowlist.
+    pub fn run_words(
+        &self,
+        words: &[String],
+        env: &[(&str, String)],
+        timeout: Duration,
+    ) -> Result<RunOutcome, CommitauraError> {
+        self.execute(words, env, timeout, &AtomicBool::new(false))
+    }
+
+    /// [`Sandbox::run_words`], also killing the command once `abandoned`
+    /// is set.
+    fn execute(
+        &self,
+        words: &[String],
+        env: &[(&str, String)],
+        timeout: Duration,
+        abandoned: &AtomicBool,
+    ) -> Result<RunOutcome, CommitauraError> {
+        let Some(program) = words.first() else {
+            return Err(CommitauraError::ConfigError("empty command".to_string()));
+        };
+        self.check_program(program)?;
+        let started = Instant::now();
+        let mut command = Command::new(program);
+        command
+            .args(&words[1..])
+            .current_dir(git::toplevel()?)
+            .env_clear()
+            .envs(self.environment(std::env::vars()))
+            .envs(env.iter().map(|(name, value)| (name, value)))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Its own process group, so a timeout also stops what it started.
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command.spawn()?;
+        // Drain both pipes while waiting so a chatty command cannot block on them.
+        let drain = |pipe: Option<Box<dyn Read + Send>>| {
+            std::thread::spawn(move || {
+                let mut bytes = Vec::new();
+                if let Some(mut pipe) = pipe {
+                    let _ = pipe.read_to_end(&mut bytes);
+                }
+                String::from_utf8_lossy(&bytes).into_owned()
+            })
+        };
+        let stdout = drain(
+            child
+                .stdout
+                .take()
+                .map(|p| Box::new(p) as Box<dyn Read + Send>),
+        );
+        let stderr = drain(
+            child
+                .stderr
+                .take()
+                .map(|p| Box::new(p) as Box<dyn Read + Send>),
+        );
+
+        let mut timed_out = false;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if abandoned.load(Ordering::Relaxed) || started.elapsed() >= timeout {
+                timed_out = true;
+                kill_group(&mut child);
+                break child.wait()?;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        // Processes that left the group may outlive it and keep the pipes
+        // open, so after a timeout the output is abandoned rather than
+        // waited for.
+        let (stdout, stderr) = if timed_out {
+            (String::new(), String::new())
+        } else {
+            (
+                stdout.join().unwrap_or_default(),
+                stderr.join().unwrap_or_default(),
+            )
+        };
+        Ok(RunOutcome {
+            success: status.success() && !timed_out,
+            timed_out,
+            stdout,
+            stderr,
+            elapsed: started.elapsed(),
+        })
+    }
+}
+
+/// A command started by [`Sandbox::spawn`]. Dropping it before
+/// [`Background::wait`], as when the user cancels, kills the command and
+/// everything it started.
+pub struct Background {
+    handle: Option<JoinHandle<Result<RunOutcome, CommitauraError>>>,
+    abandoned: Arc<AtomicBool>,
+}
+
+impl Background {
+    /// Wait for the command to finish or time out.
+    pub fn wait(mut self) -> Result<RunOutcome, CommitauraError> {
+        let handle = self.handle.take().expect("waited for only once");
+        handle.join().unwrap_or_else(|_| {
+            Err(CommitauraError::ConfigError(
+                "the command's thread panicked".to_string(),
+            ))
+        })
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            self.abandoned.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Kill `child` and, on Unix, the rest of the process group it leads.
+pub fn kill_group(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pid) = i32::try_from(child.id()) {
+        // SAFETY: `kill` has no memory-safety preconditions; a negative pid
+        // names the group `child` was started as the leader of.
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+}
+
+/// Whether the variable `name` looks like it holds a credential.
+pub fn is_secret(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| upper.contains(marker))
+}
+
+/// Split `command` into words the way a shell would for plain commands:
+/// on whitespace, with single and double quotes grouping. Shell operators
+/// are not interpreted.
+pub fn split(command: &str) -> Result<Vec<String>, CommitauraError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(CommitauraError::ConfigError(format!(
+            "unterminated quote in `{}`",
+            command
+        )));
+    }
+    words.extend(word);
+    if words.is_empty() {
+        return Err(CommitauraError::ConfigError("empty command".to_string()));
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox() -> Sandbox {
+        Sandbox {
+            allow: vec!["cargo".to_string()],
+            pass_env: vec!["RUST_LOG".to_string(), "GITHUB_TOKEN".to_string()],
+        }
+    }
+
+    #[test]
+    fn only_allowlisted_programs_run() {
+        assert_eq!(
+            sandbox().check("cargo test -- 'parse diff'").unwrap(),
+            ["cargo", "test", "--", "parse diff"]
+        );
+        assert!(sandbox().check("sh -c 'cargo test'").is_err());
+        assert!(sandbox().check("cargo test 'oops").is_err());
+        assert!(sandbox()
+            .run_words(&["sh".to_string()], &[], Duration::from_secs(1))
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_timeout_kills_the_whole_group() {
+        let sandbox = Sandbox {
+            allow: vec!["sh".to_string()],
+            pass_env: Vec::new(),
+        };
+        let marker =
+            std::env::temp_dir().join(format!("commitaura-sandbox-{}", std::process::id()));
+        let script = format!("(sleep 2; touch {}) & sleep 30", marker.display());
+        let words = ["sh", "-c", &script].map(String::from);
+        let outcome = sandbox
+            .run_words(&words, &[], Duration::from_millis(300))
+            .unwrap();
+        assert!(outcome.timed_out);
+        std::thread::sleep(Duration::from_secs(3));
+        assert!(!marker.exists(), "the background job outlived the timeout");
+    }
+
+    #[test]
+    fn scrubs_secrets_from_the_environment() {
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("RUST_LOG", "debug"),
+            ("OPENAI_API_KEY", "sk-test"),
+            ("GITHUB_TOKEN", "ghp-test"),
+            ("EDITOR", "vim"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        let names: Vec<String> = sandbox()
+            .environment(vars)
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(names, ["PATH", "RUST_LOG"]);
+    }
+}
@@ -0,0 +1,146 @@
+//! Conventional Commits scopes inferred from the changed paths.
+//!
+//! Each changed file is given a scope: the one configured for the most
+//! specific glob it matches, or else the module it is in, which is its
+//! first directory once roots such as `src/` and `crates/` are skipped.
+//! When every file with a scope agrees, that is the commit's scope, so a
+//! change to `src/parser/` alone becomes `feat(parser): ...`; when they
+//! disagree, the choice is left to the model.
+
+use std::collections::BTreeMap;
+
+use crate::diff::FileDiff;
+use crate::glob;
+
+/// Directories that hold a project's modules or packages rather than
+/// being one.
+const ROOTS: &[&str] = &[
+    "src", "lib", "crates", "packages", "pkg", "internal", "cmd", "apps", "modules",
+];
+
+/// File stems that stand for the directory they are in, not a module of
+/// their own.
+const ENTRY_FILES: &[&str] = &["mod", "lib", "main", "index", "__init__"];
+
+/// The scope of the file at `path`, if it has one.
+pub fn scope_of(path: &str, scopes: &BTreeMap<String, String>) -> Option<String> {
+    let configured = scopes
+        .iter()
+        .filter(|(glob, _)| glob::matches(glob, path))
+        .max_by_key(|(glob, _)| glob.len());
+    if let Some((_, scope)) = configured {
+        return Some(scope.clone());
+    }
+    let parts: Vec<&str> = path.split('/').collect();
+    let (dirs, file) = parts.split_at(parts.len() - 1);
+    let rooted = dirs.first().is_some_and(|dir| ROOTS.contains(dir));
+    let module = match dirs.iter().find(|dir| !ROOTS.contains(dir)) {
+        Some(dir) => dir.trim_start_matches('.'),
+        // A file directly in a root is a module of its own.
+        None if rooted => {
+            let stem = file[0].split('.').next().unwrap_or_default();
+            if ENTRY_FILES.contains(&stem) {
+                return None;
+            }
+            stem
+        }
+        None => return None,
+    };
+    (!module.is_empty()).then(|| module.to_string())
+}
+
+/// The scope shared by every file in `files` that has one, or `None` if
+/// they disagree or none has a scope.
+pub fn infer(files: &[FileDiff], scopes: &BTreeMap<String, String>) -> Option<String> {
+    let mut found: Option<String> = None;
+    for scope in files.iter().filter_map(|file| scope_of(&file.path, scopes)) {
+        match &found {
+            Some(existing) if *existing != scope => return None,
+            _ => found = Some(scope),
+        }
+    }
+    found
+}
+
+/// `message` with `scope` as the scope of its subject, if the subject is a
+/// Conventional Commits one such as `fix: text` or `fix(other)!: text`.
+/// Other messages are returned as they are.
+pub fn apply(message: &str, scope: &str) -> String {
+    let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+    let Some(prefix) = crate::message::conventional(subject) else {
+        return message.to_string();
+    };
+    let breaking = if prefix.breaking { "!" } else { "" };
+    let subject = format!(
+        "{}({}){}: {}",
+        prefix.kind, scope, breaking, prefix.description
+    );
+    if message.contains('\n') {
+        format!("{}\n{}", subject, rest)
+    } else {
+        subject
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff;
+
+    #[test]
+    fn infers_the_scope_shared_by_the_changed_files() {
+        let none = BTreeMap::new();
+        let files = |paths: &[&str]| {
+            let text: String = paths
+                .iter()
+                .map(|p| format!("diff --git a/{0} b/{0}\n+x\n", p))
+                .collect();
+            diff::parse(&text)
+        };
+        assert_eq!(
+            infer(&files(&["src/parser/lexer.rs", "src/parser/mod.rs"]), &none),
+            Some("parser".to_string())
+        );
+        assert_eq!(
+            infer(&files(&["src/parser.rs", "README.md"]), &none),
+            Some("parser".to_string())
+        );
+        assert_eq!(
+            infer(&files(&["crates/http/src/lib.rs"]), &none),
+            Some("http".to_string())
+        );
+        assert_eq!(
+            infer(&files(&["src/parser.rs", "src/ui/mod.rs"]), &none),
+            None
+        );
+        assert_eq!(infer(&files(&["src/main.rs", "Cargo.toml"]), &none), None);
+
+        let scopes = BTreeMap::from([
+            ("src/ui/*".to_string(), "tui".to_string()),
+            ("*.proto".to_string(), "api".to_string()),
+        ]);
+        assert_eq!(
+            infer(&files(&["src/ui/select.rs"]), &scopes),
+            Some("tui".to_string())
+        );
+        assert_eq!(
+            scope_of("proto/v1/user.proto", &scopes),
+            Some("api".to_string())
+        );
+    }
+
+    #[test]
+    fn sets_the_scope_of_conventional_subjects() {
+        assert_eq!(
+            apply("feat: add lexer", "parser"),
+            "feat(parser): add lexer"
+        );
+        assert_eq!(
+            apply("fix(lexer)!: drop tabs\n\nBody", "parser"),
+            "fix(parser)!: drop tabs\n\nBody"
+        );
+        assert_eq!(apply("Add lexer: tokens", "parser"), "Add lexer: tokens");
+        assert_eq!(apply("Add lexer", "parser"), "Add lexer");
+        assert_eq!(apply("Update: lexer", "parser"), "Update: lexer");
+    }
+}
@@ -0,0 +1,107 @@
+//! Sparse-checkout and partial-clone awareness.
+//!
+//! In a partial clone, blobs outside the sparse cone are not on disk and git
+//! downloads them on demand. With `sparse_cone_only` set, every diff
+//! Commitaura takes is limited to the cone and on-demand fetching is turned
+//! off, so analysis never pulls in objects the user chose not to have.
+
+use crate::{git, CommitauraError};
+
+/// The directories of a cone-mode sparse checkout. Files at the top level are
+/// always part of a cone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseCone {
+    pub dirs: Vec<String>,
+}
+
+impl SparseCone {
+    /// Parse the output of `git sparse-checkout list`. Returns `None` for
+    /// non-cone pattern sets, which cannot be turned into pathspecs reliably.
+    pub fn from_list(list: &str) -> Option<SparseCone> {
+        let dirs: Vec<String> = list
+            .lines()
+            .map(|l| l.trim().trim_matches('/').to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        if dirs.iter().any(|d| d.contains(['*', '?', '[', '!'])) {
+            return None;
+        }
+        Some(SparseCone { dirs })
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        !path.contains('/')
+            || self.dirs.iter().any(|dir| {
+                path.strip_prefix(dir.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+            })
+    }
+
+    /// Pathspecs selecting exactly the cone, relative to the repository root.
+    pub fn pathspecs(&self) -> Vec<String> {
+        let mut specs = vec![":(top,glob)*".to_string()];
+        specs.extend(self.dirs.iter().map(|dir| format!(":(top){}/", dir)));
+        specs
+    }
+
+    /// The paths outside the cone.
+    pub fn outside<'a>(&self, paths: &'a [String]) -> Vec<&'a str> {
+        paths
+            .iter()
+            .filter(|p| !self.contains(p))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// How the repository was cloned and checked out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layout {
+    /// Objects are fetched lazily from a promisor remote.
+    pub partial_clone: bool,
+    /// The cone, if a cone-mode sparse checkout is active.
+    pub cone: Option<SparseCone>,
+    /// A sparse checkout is active but uses non-cone patterns.
+    pub non_cone_sparse: bool,
+}
+
+/// Inspect the current repository.
+pub fn detect() -> Result<Layout, CommitauraError> {
+    let partial_clone = git::config_value("extensions.partialClone").is_some();
+    let sparse = git::config_value("core.sparseCheckout").as_deref() == Some("true");
+    if !sparse {
+        return Ok(Layout {
+            partial_clone,
+            ..Layout::default()
+        });
+    }
+    let cone = if git::config_value("core.sparseCheckoutCone").as_deref() == Some("false") {
+        None
+    } else {
+        SparseCone::from_list(&git::sparse_checkout_list()?)
+    };
+    Ok(Layout {
+        partial_clone,
+        non_cone_sparse: cone.is_none(),
+        cone,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cone_contains_top_level_files_and_listed_directories() {
+        let cone = SparseCone::from_list("services/api\nlibs/core/\n").unwrap();
+        assert!(cone.contains("Cargo.toml"));
+        assert!(cone.contains("services/api/src/main.rs"));
+        assert!(!cone.contains("services/api-gateway/main.go"));
+        assert!(!cone.contains("services/web/index.ts"));
+        assert_eq!(
+            cone.pathspecs(),
+            [":(top,glob)*", ":(top)services/api/", ":(top)libs/core/"]
+        );
+        assert!(SparseCone::from_list("/*\n!/*/\n").is_none());
+    }
+}
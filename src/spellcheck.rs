@@ -0,0 +1,262 @@
+//! Spelling, terminology and identifier checks for a generated message.
+//!
+//! Three sources of issues, all local:
+//! - identifiers the message mentions that do not occur in the diff, with the
+//!   closest identifier that does as the fix (catches model typos);
+//! - configured terminology rules such as `Github` -> `GitHub`;
+//! - optionally, words flagged by `aspell` or `hunspell` in pipe mode.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    Spelling,
+    Terminology,
+    Identifier,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub word: String,
+    /// Byte offset of `word` in the message.
+    pub start: usize,
+    pub suggestion: Option<String>,
+}
+
+/// Words of `text` with their byte offsets. Identifier characters (`_`, `:`
+/// between word characters, digits) stay inside a word.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut out = Vec::new();
+    let mut start = None;
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    for (i, &(pos, c)) in chars.iter().enumerate() {
+        let joins_path = c == ':'
+            && start.is_some()
+            && chars
+                .get(i + 1)
+                .is_some_and(|&(_, n)| n == ':' || is_word(n))
+            && (chars[i - 1].1 == ':' || is_word(chars[i - 1].1));
+        if is_word(c) || joins_path {
+            start.get_or_insert(pos);
+        } else if let Some(s) = start.take() {
+            out.push((s, &text[s..pos]));
+        }
+    }
+    if let Some(s) = start {
+        out.push((s, &text[s..]));
+    }
+    out
+}
+
+/// Whether `word` looks like code rather than prose: `snake_case`,
+/// `camelCase`/`PascalCase` with an inner capital, or a `path::to::item`.
+fn is_identifier(word: &str) -> bool {
+    word.contains('_')
+        || word.contains("::")
+        || (word.chars().skip(1).any(|c| c.is_uppercase())
+            && word.chars().any(|c| c.is_lowercase()))
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(row[j + 1])
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// Identifiers in the message that the diff never mentions. The fix is the
+/// diff identifier within two edits, if there is exactly one best match.
+pub fn check_identifiers(message: &str, diff: &str) -> Vec<Issue> {
+    let known: BTreeSet<&str> = words(diff).into_iter().map(|(_, w)| w).collect();
+    words(message)
+        .into_iter()
+        .filter(|(_, w)| is_identifier(w) && !known.contains(w))
+        .filter_map(|(start, word)| {
+            let mut best: Vec<(usize, &str)> = known
+                .iter()
+                .map(|k| (edit_distance(word, k), *k))
+                .filter(|(d, _)| *d <= 2)
+                .collect();
+            best.sort();
+            let suggestion = match best.as_slice() {
+                [(d, k), (d2, _), ..] if d < d2 => Some(k.to_string()),
+                [(_, k)] => Some(k.to_string()),
+                _ => None,
+            };
+            // Without a near miss the identifier may simply be prose.
+            suggestion.map(|s| Issue {
+                kind: IssueKind::Identifier,
+                word: word.to_string(),
+                start,
+                suggestion: Some(s),
+            })
+        })
+        .collect()
+}
+
+/// Words matching a terminology rule's wrong spelling (`rules` maps wrong to
+/// right).
+pub fn check_terminology(message: &str, rules: &BTreeMap<String, String>) -> Vec<Issue> {
+    words(message)
+        .into_iter()
+        .filter_map(|(start, word)| {
+            rules.get(word).map(|right| Issue {
+                kind: IssueKind::Terminology,
+                word: word.to_string(),
+                start,
+                suggestion: Some(right.clone()),
+            })
+        })
+        .collect()
+}
+
+/// Prose words flagged by `aspell -a` or `hunspell -a`, skipping anything
+/// that occurs in the diff. Returns nothing if neither tool is installed.
+pub fn check_spelling(message: &str, diff: &str) -> Vec<Issue> {
+    let known: BTreeSet<&str> = words(diff).into_iter().map(|(_, w)| w).collect();
+    let Some(output) = ["aspell", "hunspell"]
+        .iter()
+        .find_map(|tool| run_pipe_mode(tool, message))
+    else {
+        return Vec::new();
+    };
+
+    // One result line per misspelling: `& word count offset: a, b` or `# word offset`.
+    let flagged: BTreeMap<String, Option<String>> = output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            let head: Vec<&str> = parts.next()?.split_whitespace().collect();
+            match head.as_slice() {
+                ["&", word, ..] => Some((
+                    word.to_string(),
+                    parts
+                        .next()
+                        .and_then(|s| s.split(',').next())
+                        .map(|s| s.trim().to_string()),
+                )),
+                ["#", word, ..] => Some((word.to_string(), None)),
+                _ => None,
+            }
+        })
+        .collect();
+
+    words(message)
+        .into_iter()
+        .filter(|(_, w)| !is_identifier(w) && !known.contains(w))
+        .filter_map(|(start, word)| {
+            flagged.get(word).map(|suggestion| Issue {
+                kind: IssueKind::Spelling,
+                word: word.to_string(),
+                start,
+                suggestion: suggestion.clone(),
+            })
+        })
+        .collect()
+}
+
+fn run_pipe_mode(tool: &str, text: &str) -> Option<String> {
+    let mut child = Command::new(tool)
+        .arg("-a")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take()?;
+    // `^` keeps lines from being read as pipe-mode commands.
+    for line in text.lines() {
+        writeln!(stdin, "^{}", line).ok()?;
+    }
+    drop(stdin);
+    let output = child.wait_with_output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// All issues in `message`, ordered by position, at most one per word.
+pub fn check(
+    message: &str,
+    diff: &str,
+    terminology: &BTreeMap<String, String>,
+    spelling: bool,
+) -> Vec<Issue> {
+    let mut issues = check_terminology(message, terminology);
+    issues.extend(check_identifiers(message, diff));
+    if spelling {
+        issues.extend(check_spelling(message, diff));
+    }
+    issues.sort_by_key(|i| i.start);
+    issues.dedup_by_key(|i| i.start);
+    issues
+}
+
+/// Apply `issue`'s suggestion, shifting the offsets of the remaining issues.
+pub fn apply(message: &str, issue: &Issue, remaining: &mut [Issue]) -> String {
+    let Some(fix) = &issue.suggestion else {
+        return message.to_string();
+    };
+    let end = issue.start + issue.word.len();
+    let fixed = format!("{}{}{}", &message[..issue.start], fix, &message[end..]);
+    for other in remaining.iter_mut().filter(|o| o.start > issue.start) {
+        other.start = other.start + fix.len() - issue.word.len();
+    }
+    fixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "+pub fn parse_config(path: &Path) -> Config {}\n+impl GitHubClient {}\n";
+
+    #[test]
+    fn suggests_the_identifier_the_diff_actually_contains() {
+        let issues = check_identifiers("Add parse_confg and GitHubClinet", DIFF);
+        let fixes: Vec<_> = issues
+            .iter()
+            .map(|i| (i.word.as_str(), i.suggestion.as_deref()))
+            .collect();
+        assert_eq!(
+            fixes,
+            [
+                ("parse_confg", Some("parse_config")),
+                ("GitHubClinet", Some("GitHubClient"))
+            ]
+        );
+        assert!(check_identifiers("Add parse_config", DIFF).is_empty());
+    }
+
+    #[test]
+    fn applies_terminology_fixes_and_shifts_offsets() {
+        let rules = BTreeMap::from([
+            ("Github".to_string(), "GitHub".to_string()),
+            ("postgres".to_string(), "PostgreSQL".to_string()),
+        ]);
+        let message = "Sync Github issues to postgres";
+        let mut issues = check(message, "", &rules, false);
+        assert_eq!(issues.len(), 2);
+        let first = issues.remove(0);
+        let message = apply(message, &first, &mut issues);
+        let message = apply(&message, &issues[0].clone(), &mut []);
+        assert_eq!(message, "Sync GitHub issues to PostgreSQL");
+    }
+}
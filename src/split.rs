@@ -0,0 +1,125 @@
+//! Splitting the staged changes into several commits.
+//!
+//! Files are grouped by the module they belong to: documentation goes
+//! together, and other files by their first two directories, so
+//! `src/ui/select.rs` and `src/ui/stage.rs` share a commit while
+//! `src/git.rs` gets its own. Each group then gets its own message. A file
+//! is never split across commits, so binary files, renames and deletions
+//! are committed whole.
+
+use crate::diff::{self, FileDiff};
+
+/// Extensions of files that are documentation wherever they are.
+const DOC_EXTENSIONS: &[&str] = &["md", "markdown", "rst", "adoc", "txt"];
+
+/// The name of the group for documentation.
+pub const DOCS: &str = "docs";
+
+/// Staged files that are committed together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    /// The module, such as `src/ui`; empty for files at the top level.
+    pub name: String,
+    pub files: Vec<FileDiff>,
+}
+
+impl Group {
+    /// The group's name for display.
+    pub fn label(&self) -> &str {
+        if self.name.is_empty() {
+            "top level"
+        } else {
+            &self.name
+        }
+    }
+
+    /// Every path the group's commit touches, both sides of renames included.
+    pub fn paths(&self) -> Vec<String> {
+        self.files
+            .iter()
+            .flat_map(|file| std::iter::once(&file.path).chain(&file.old_path))
+            .cloned()
+            .collect()
+    }
+
+    pub fn diff(&self) -> String {
+        diff::render(&self.files)
+    }
+
+    /// Add `other`'s files to this group.
+    pub fn merge(&mut self, other: Group) {
+        self.files.extend(other.files);
+        if !other.name.is_empty() && self.name != other.name {
+            self.name = if self.name.is_empty() {
+                other.name
+            } else {
+                format!("{} + {}", self.name, other.name)
+            };
+        }
+    }
+}
+
+/// The module `path` belongs to.
+pub fn module_of(path: &str) -> String {
+    let parts: Vec<&str> = path.split('/').collect();
+    let (dirs, file) = parts.split_at(parts.len() - 1);
+    let extension = file[0].rsplit_once('.').map(|(_, ext)| ext.to_lowercase());
+    let is_doc = extension.is_some_and(|ext| DOC_EXTENSIONS.contains(&ext.as_str()))
+        || matches!(dirs.first(), Some(&("docs" | "doc")));
+    if is_doc {
+        return DOCS.to_string();
+    }
+    dirs.iter().take(2).copied().collect::<Vec<_>>().join("/")
+}
+
+/// `files` grouped by module, modules in path order with documentation
+/// last, files in diff order within each.
+pub fn group(files: Vec<FileDiff>) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    for file in files {
+        let name = module_of(&file.path);
+        match groups.iter_mut().find(|group| group.name == name) {
+            Some(group) => group.files.push(file),
+            None => groups.push(Group {
+                name,
+                files: vec![file],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| (a.name == DOCS, &a.name).cmp(&(b.name == DOCS, &b.name)));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_files_by_module() {
+        let diff = "\
+diff --git a/src/ui/stage.rs b/src/ui/stage.rs
++a
+diff --git a/README.md b/README.md
++b
+diff --git a/src/git.rs b/src/git.rs
++c
+diff --git a/src/ui/select.rs b/src/ui/select.rs
++d
+diff --git a/Cargo.toml b/Cargo.toml
++e
+diff --git a/docs/guide/usage.html b/docs/guide/usage.html
++f
+";
+        let groups = group(diff::parse(diff));
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["", "src", "src/ui", "docs"]);
+        assert_eq!(groups[2].paths(), ["src/ui/stage.rs", "src/ui/select.rs"]);
+        assert_eq!(groups[3].paths(), ["README.md", "docs/guide/usage.html"]);
+        assert_eq!(groups[0].label(), "top level");
+
+        let mut merged = groups[1].clone();
+        merged.merge(groups[2].clone());
+        assert_eq!(merged.name, "src + src/ui");
+        assert_eq!(merged.files.len(), 3);
+    }
+}
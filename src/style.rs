@@ -0,0 +1,251 @@
+//! Style profile of a repository's commit subjects.
+//!
+//! Used to check whether a generated subject fits the conventions already in
+//! the history: verb tense, prefix style and length.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tense {
+    /// "Add", "Fix"
+    Imperative,
+    /// "Added", "Fixed"
+    Past,
+    /// "Adding", "Fixing"
+    Progressive,
+    /// "Adds", "Fixes"
+    ThirdPerson,
+}
+
+impl Tense {
+    pub fn label(self) -> &'static str {
+        match self {
+            Tense::Imperative => "imperative",
+            Tense::Past => "past tense",
+            Tense::Progressive => "-ing form",
+            Tense::ThirdPerson => "third person",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prefix {
+    /// `feat(scope): ...`
+    Conventional,
+    /// `[tag] ...`
+    Bracketed,
+    /// `✨ ...` or `:sparkles: ...`
+    Gitmoji,
+    None,
+}
+
+impl Prefix {
+    pub fn label(self) -> &'static str {
+        match self {
+            Prefix::Conventional => "conventional `type:` prefix",
+            Prefix::Bracketed => "`[tag]` prefix",
+            Prefix::Gitmoji => "gitmoji prefix",
+            Prefix::None => "no prefix",
+        }
+    }
+}
+
+/// Style features of a single subject line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubjectStyle {
+    pub tense: Tense,
+    pub prefix: Prefix,
+    pub length: usize,
+}
+
+pub fn analyze_subject(subject: &str) -> SubjectStyle {
+    let subject = subject.trim();
+    let (prefix, rest) = split_prefix(subject);
+    let first_word = rest
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .trim_matches(|c: char| !c.is_alphabetic())
+        .to_lowercase();
+
+    SubjectStyle {
+        tense: classify_tense(&first_word),
+        prefix,
+        length: subject.chars().count(),
+    }
+}
+
+/// Dominant style of the recent history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleProfile {
+    pub tense: Tense,
+    pub prefix: Prefix,
+    pub max_length: usize,
+    pub average_length: usize,
+}
+
+impl StyleProfile {
+    /// Build a profile from subjects, one per line. Returns `None` for an empty history.
+    pub fn from_history(history: &str) -> Option<StyleProfile> {
+        let styles: Vec<SubjectStyle> = history
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(analyze_subject)
+            .collect();
+        if styles.is_empty() {
+            return None;
+        }
+
+        let tense = most_common(styles.iter().map(|s| s.tense));
+        let prefix = most_common(styles.iter().map(|s| s.prefix));
+        let max_length = styles.iter().map(|s| s.length).max().unwrap_or(0);
+        let average_length = styles.iter().map(|s| s.length).sum::<usize>() / styles.len();
+
+        Some(StyleProfile {
+            tense,
+            prefix,
+            max_length,
+            average_length,
+        })
+    }
+
+    /// Describe how `subject` deviates from this profile.
+    pub fn mismatches(&self, subject: &str) -> Vec<Mismatch> {
+        let style = analyze_subject(subject);
+        let mut out = Vec::new();
+        if style.tense != self.tense {
+            out.push(Mismatch::Tense {
+                expected: self.tense,
+                found: style.tense,
+            });
+        }
+        if style.prefix != self.prefix {
+            out.push(Mismatch::Prefix {
+                expected: self.prefix,
+                found: style.prefix,
+            });
+        }
+        if style.length > self.max_length {
+            out.push(Mismatch::Length {
+                max: self.max_length,
+                found: style.length,
+            });
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    Tense { expected: Tense, found: Tense },
+    Prefix { expected: Prefix, found: Prefix },
+    Length { max: usize, found: usize },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Tense { expected, found } => write!(
+                f,
+                "tense: history uses {}, suggestion uses {}",
+                expected.label(),
+                found.label()
+            ),
+            Mismatch::Prefix { expected, found } => write!(
+                f,
+                "prefix: history uses {}, suggestion has {}",
+                expected.label(),
+                found.label()
+            ),
+            Mismatch::Length { max, found } => write!(
+                f,
+                "length: {} chars, longest recent subject is {}",
+                found, max
+            ),
+        }
+    }
+}
+
+fn split_prefix(subject: &str) -> (Prefix, &str) {
+    if let Some((_, rest)) = crate::gitmoji::split(subject) {
+        return (Prefix::Gitmoji, rest);
+    }
+    if subject.starts_with('[') {
+        if let Some(end) = subject.find(']') {
+            return (Prefix::Bracketed, subject[end + 1..].trim_start());
+        }
+    }
+    if let Some((head, rest)) = subject.split_once(": ") {
+        let kind = head.split('(').next().unwrap_or("").trim_end_matches('!');
+        if !kind.is_empty() && kind.chars().all(|c| c.is_ascii_lowercase()) {
+            return (Prefix::Conventional, rest);
+        }
+    }
+    (Prefix::None, subject)
+}
+
+fn classify_tense(word: &str) -> Tense {
+    if word.len() > 4 && word.ends_with("ing") {
+        Tense::Progressive
+    } else if word.len() > 3 && word.ends_with("ed") {
+        Tense::Past
+    } else if word.len() > 3
+        && word.ends_with('s')
+        && !word.ends_with("ss")
+        && !word.ends_with("us")
+    {
+        Tense::ThirdPerson
+    } else {
+        Tense::Imperative
+    }
+}
+
+fn most_common<T: PartialEq + Copy>(items: impl Iterator<Item = T>) -> T {
+    let mut counts: Vec<(T, usize)> = Vec::new();
+    for item in items {
+        match counts.iter_mut().find(|(t, _)| *t == item) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((item, 1)),
+        }
+    }
+    // `max_by_key` keeps the last maximum; reverse so ties go to the most recent subject.
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, n)| *n)
+        .map(|(t, _)| t)
+        .expect("most_common called with no items")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyzes_subject_features() {
+        let s = analyze_subject("feat(auth): add token refresh");
+        assert_eq!(s.prefix, Prefix::Conventional);
+        assert_eq!(s.tense, Tense::Imperative);
+
+        assert_eq!(analyze_subject("Fixed typo").tense, Tense::Past);
+        assert_eq!(
+            analyze_subject("[ui] Adding spinner").prefix,
+            Prefix::Bracketed
+        );
+        assert_eq!(analyze_subject("Adding spinner").tense, Tense::Progressive);
+        let s = analyze_subject(":bug: Fixed login");
+        assert_eq!(s.prefix, Prefix::Gitmoji);
+        assert_eq!(s.tense, Tense::Past);
+    }
+
+    #[test]
+    fn reports_mismatches_against_history() {
+        let profile = StyleProfile::from_history(
+            "fix: handle empty diff\nfeat: add spinner\nchore: bump deps",
+        )
+        .unwrap();
+        assert_eq!(profile.prefix, Prefix::Conventional);
+
+        let mismatches = profile.mismatches("Added a much longer description of the change");
+        assert_eq!(mismatches.len(), 3);
+        assert!(profile.mismatches("fix: clamp budget").is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! Local, heuristic analysis of a staged diff.
+//!
+//! Used by low-bandwidth mode: instead of the raw diff, only the structured
+//! description built here is sent to the LLM, so no source lines leave the
+//! machine.
+
+use crate::diff::{FileDiff, FileStatus};
+
+/// Keywords that introduce a named definition in the languages we commonly see.
+const DEFINITION_KEYWORDS: &[&str] = &[
+    "fn",
+    "struct",
+    "enum",
+    "trait",
+    "impl",
+    "mod",
+    "type",
+    "const",
+    "static",
+    "class",
+    "interface",
+    "def",
+    "function",
+    "func",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSummary {
+    pub path: String,
+    pub old_path: Option<String>,
+    pub status: FileStatus,
+    pub binary: bool,
+    pub additions: usize,
+    pub deletions: usize,
+    pub added_symbols: Vec<String>,
+    pub removed_symbols: Vec<String>,
+    /// Definitions enclosing the changed lines, taken from the hunk headers.
+    /// Only the recognised `keyword name` is kept, never the raw header text.
+    pub touched: Vec<String>,
+}
+
+pub fn summarize_file(file: &FileDiff) -> FileSummary {
+    let added: Vec<String> = file
+        .hunks
+        .iter()
+        .flat_map(|h| h.added())
+        .filter_map(definition_name)
+        .collect();
+    let removed: Vec<String> = file
+        .hunks
+        .iter()
+        .flat_map(|h| h.removed())
+        .filter_map(definition_name)
+        .collect();
+
+    // A symbol present on both sides was modified in place, not added or removed.
+    let added_symbols = dedup(added.iter().filter(|s| !removed.contains(s)).cloned());
+    let removed_symbols = dedup(removed.iter().filter(|s| !added.contains(s)).cloned());
+    let touched = dedup(
+        file.hunks
+            .iter()
+            .filter_map(|h| h.context())
+            .filter_map(definition_name),
+    );
+
+    FileSummary {
+        path: file.path.clone(),
+        old_path: file.old_path.clone(),
+        status: file.status,
+        binary: file.binary,
+        additions: file.additions(),
+        deletions: file.deletions(),
+        added_symbols,
+        removed_symbols,
+        touched,
+    }
+}
+
+/// Render a plain-text change description suitable for the prompt.
+pub fn describe(files: &[FileDiff]) -> String {
+    let mut out = String::new();
+    for summary in files.iter().map(summarize_file) {
+        let status = match summary.status {
+            FileStatus::Added => "added",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Modified => "modified",
+            FileStatus::Renamed => "renamed",
+        };
+        out.push_str(&format!("- {} ({}", summary.path, status));
+        if let Some(old) = &summary.old_path {
+            out.push_str(&format!(" from {}", old));
+        }
+        if summary.binary {
+            out.push_str(", binary");
+        } else {
+            out.push_str(&format!(", +{} -{}", summary.additions, summary.deletions));
+        }
+        out.push_str(")\n");
+        if !summary.added_symbols.is_empty() {
+            out.push_str(&format!("  added: {}\n", summary.added_symbols.join(", ")));
+        }
+        if !summary.removed_symbols.is_empty() {
+            out.push_str(&format!(
+                "  removed: {}\n",
+                summary.removed_symbols.join(", ")
+            ));
+        }
+        if !summary.touched.is_empty() {
+            out.push_str(&format!("  touched: {}\n", summary.touched.join("; ")));
+        }
+    }
+    out
+}
+
+/// Extract `keyword name` from a definition line, e.g. `pub fn foo(` -> `fn foo`.
+fn definition_name(line: &str) -> Option<String> {
+    const MODIFIERS: &[&str] = &[
+        "pub",
+        "pub(crate)",
+        "pub(super)",
+        "async",
+        "export",
+        "default",
+        "unsafe",
+        "extern",
+        "abstract",
+    ];
+
+    let mut words = line.split_whitespace();
+    while let Some(word) = words.next() {
+        if DEFINITION_KEYWORDS.contains(&word) {
+            let name: String = words
+                .next()?
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            if name.is_empty() {
+                return None;
+            }
+            return Some(format!("{} {}", word, name));
+        }
+        if !MODIFIERS.contains(&word) {
+            return None;
+        }
+    }
+    None
+}
+
+fn dedup(items: impl Iterator<Item = String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for item in items {
+        if !out.contains(&item) {
+            out.push(item);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff;
+
+    #[test]
+    fn extracts_definition_names() {
+        assert_eq!(definition_name("pub fn foo(x: u8)"), Some("fn foo".into()));
+        assert_eq!(
+            definition_name("pub(crate) struct Bar<T>"),
+            Some("struct Bar".into())
+        );
+        assert_eq!(
+            definition_name("class Widget:"),
+            Some("class Widget".into())
+        );
+        assert_eq!(definition_name("let x = fn_call();"), None);
+    }
+
+    #[test]
+    fn describes_without_source_lines() {
+        let diff = "\
+diff --git a/src/a.rs b/src/a.rs
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,2 +1,2 @@ impl Foo {
+-fn secret_old() { let token = \"abc\"; }
++fn secret_new() { let token = \"xyz\"; }
+";
+        let text = describe(&diff::parse(diff));
+        assert!(text.contains("src/a.rs (modified, +1 -1)"));
+        assert!(text.contains("added: fn secret_new"));
+        assert!(text.contains("removed: fn secret_old"));
+        assert!(text.contains("touched: impl Foo"));
+        assert!(!text.contains("xyz"));
+    }
+}
@@ -0,0 +1,527 @@
+//! A cache of messages shared by a team, served by `commitaura cache-server`.
+//!
+//! Dependency bumps and regenerated files produce the same diff on every
+//! machine that makes them, so one generated message can serve everyone.
+//! Only diffs made entirely of such changes are shared: lock files,
+//! generated files and vendored packages (see [`shareable`]). They are keyed
+//! by a hash of their content and the settings that shape the message, so
+//! the diff itself never leaves the machine.
+//!
+//! The server speaks a small JSON API:
+//!
+//! - `GET /v1/summaries/<key>` answers `{"summary": "..."}`, or 404;
+//! - `PUT /v1/summaries/<key>` with `{"summary": "..."}` stores it.
+//!
+//! When the server has a token, every request must send it as
+//! `Authorization: Bearer <token>`. Clients only send it over https, or over
+//! plain http to a server on the same machine.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::generate::GenerateOptions;
+use crate::generated::Detector;
+use crate::{diff, provider, vendor, CommitauraError};
+
+/// Environment variable holding the token, for the server and its clients.
+pub const TOKEN_ENV: &str = "COMMITAURA_CACHE_TOKEN";
+
+/// Environment variable naming the server, besides the global config.
+pub const URL_ENV: &str = "COMMITAURA_CACHE_URL";
+
+/// Address the server listens on unless told otherwise.
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:7878";
+
+const ENTRY_PATH: &str = "/v1/summaries/";
+
+/// Largest request body the server reads.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Largest request line and headers the server reads, together.
+const MAX_HEADER_BYTES: u64 = 8 * 1024;
+
+/// Longest message the server stores.
+const MAX_SUMMARY_BYTES: usize = 4 * 1024;
+
+/// Most messages the server keeps.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Most connections the server answers at once.
+const MAX_CONNECTIONS: usize = 32;
+
+/// How long a client waits for the server; it is only a shortcut.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long the server waits for a slow client.
+const SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    summary: String,
+}
+
+/// Whether every file `diff` changes is a lock file, a generated file or
+/// part of a vendored package, so its message is worth sharing.
+pub fn shareable(diff: &str, detector: &Detector) -> bool {
+    let files = diff::parse(diff);
+    !files.is_empty()
+        && files
+            .iter()
+            .all(|file| vendor::is_vendored(&file.path) || detector.classify(file).is_some())
+}
+
+/// The key of the message for `diff` written with `opts`: a SHA-256 hash,
+/// since other clients trust what is stored under it.
+pub fn key(diff: &str, opts: &GenerateOptions) -> String {
+    let mut style = format!(
+        "{:?} {:?} {}",
+        opts.prompt_style,
+        opts.gitmoji_format(),
+        opts.ascii_only
+    );
+    if let Some(length) = opts.length_style {
+        style.push_str(&format!(" {:?}", length));
+    }
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    for part in ["v1", &opts.model, &style, diff] {
+        context.update(part.as_bytes());
+        context.update(&[0]);
+    }
+    context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Whether the token may be sent to `url`: over https, or over plain http
+/// to this machine.
+fn secure(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    url.starts_with("https://") || (url.starts_with("http://") && provider::loopback(&url))
+}
+
+/// Talks to a cache server.
+#[derive(Debug, Clone)]
+pub struct Client {
+    url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    /// A client for the server at `url`, e.g. `https://cache.internal:7878`.
+    /// Plain http is refused except for a server on this machine.
+    pub fn new(url: &str, token: Option<String>) -> Result<Client, CommitauraError> {
+        if !secure(url) {
+            return Err(CommitauraError::ConfigError(format!(
+                "team cache {}: use https, or http only to localhost",
+                url
+            )));
+        }
+        Ok(Client {
+            url: url.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    fn request(&self, method: &str, key: &str) -> ureq::Request {
+        let agent = ureq::AgentBuilder::new().timeout(CLIENT_TIMEOUT).build();
+        let request = agent.request(method, &format!("{}{}{}", self.url, ENTRY_PATH, key));
+        match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    /// The message stored under `key`, if there is one.
+    pub fn get(&self, key: &str) -> Result<Option<String>, CommitauraError> {
+        debug!("GET team cache entry {}", key);
+        let response = match self.request("GET", key).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(cache_error(e)),
+        };
+        let entry: Entry = serde_json::from_str(&response.into_string()?).map_err(|e| {
+            CommitauraError::ApiRequestFailed(format!("Invalid team cache entry: {}", e))
+        })?;
+        Ok(Some(entry.summary))
+    }
+
+    /// Store `summary` under `key`.
+    pub fn put(&self, key: &str, summary: &str) -> Result<(), CommitauraError> {
+        debug!("PUT team cache entry {}", key);
+        let body = serde_json::json!({ "summary": summary });
+        self.request("PUT", key)
+            .send_json(body)
+            .map_err(cache_error)?;
+        Ok(())
+    }
+}
+
+fn cache_error(error: ureq::Error) -> CommitauraError {
+    match error {
+        ureq::Error::Status(status, response) => CommitauraError::ApiRequestFailed(format!(
+            "team cache: HTTP {}: {}",
+            status,
+            response.into_string().unwrap_or_default().trim()
+        )),
+        ureq::Error::Transport(e) => {
+            CommitauraError::ApiRequestFailed(format!("team cache: {}", e))
+        }
+    }
+}
+
+/// A request as the server sees it.
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// A response with a JSON body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: serde_json::Value) -> Response {
+        Response {
+            status,
+            body: body.to_string(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Response {
+        Response::json(status, serde_json::json!({ "error": message }))
+    }
+}
+
+/// The cache server: entries in memory, written through to `store`.
+#[derive(Debug)]
+pub struct Server {
+    entries: Mutex<BTreeMap<String, String>>,
+    store: Option<PathBuf>,
+    token: Option<String>,
+    /// Connections being answered.
+    active: AtomicUsize,
+}
+
+impl Server {
+    /// A server with the entries already in `store`, if it exists, that
+    /// requires `token` when there is one.
+    pub fn open(store: Option<PathBuf>, token: Option<String>) -> Result<Server, CommitauraError> {
+        let entries = match &store {
+            Some(path) if path.is_file() => {
+                let text = std::fs::read_to_string(path)?;
+                serde_json::from_str(&text).map_err(|e| {
+                    CommitauraError::ConfigError(format!("{}: {}", path.display(), e))
+                })?
+            }
+            _ => BTreeMap::new(),
+        };
+        Ok(Server {
+            entries: Mutex::new(entries),
+            store,
+            token,
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Answer `request`.
+    pub fn handle(&self, request: &Request) -> Response {
+        if let Some(token) = &self.token {
+            let sent = request
+                .authorization
+                .as_deref()
+                .and_then(|value| value.strip_prefix("Bearer "));
+            if !sent.is_some_and(|sent| same(sent.trim(), token)) {
+                return Response::error(401, "missing or wrong token");
+            }
+        }
+        let Some(key) = request.path.strip_prefix(ENTRY_PATH) else {
+            return Response::error(404, "not found");
+        };
+        if key.is_empty() || key.len() > 64 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Response::error(400, "keys are hexadecimal hashes");
+        }
+        match request.method.as_str() {
+            "GET" => match self.lock().get(key) {
+                Some(summary) => Response::json(200, serde_json::json!({ "summary": summary })),
+                None => Response::error(404, "not cached"),
+            },
+            "PUT" => {
+                let entry: Entry = match serde_json::from_slice(&request.body) {
+                    Ok(entry) => entry,
+                    Err(e) => return Response::error(400, &e.to_string()),
+                };
+                if entry.summary.len() > MAX_SUMMARY_BYTES {
+                    return Response::error(413, "message too long");
+                }
+                let mut entries = self.lock();
+                if entries.len() >= MAX_ENTRIES && !entries.contains_key(key) {
+                    return Response::error(507, "the cache is full");
+                }
+                entries.insert(key.to_string(), entry.summary);
+                if let Err(e) = self.save(&entries) {
+                    warn!("Could not save the cache: {}", e);
+                    return Response::error(500, "could not save the cache");
+                }
+                Response::json(200, serde_json::json!({ "stored": key }))
+            }
+            _ => Response::error(405, "only GET and PUT are supported"),
+        }
+    }
+
+    /// Write `entries` to the store, replacing it in one step.
+    fn save(&self, entries: &BTreeMap<String, String>) -> Result<(), CommitauraError> {
+        let Some(path) = &self.store else {
+            return Ok(());
+        };
+        let temporary = path.with_extension("tmp");
+        std::fs::write(
+            &temporary,
+            serde_json::to_string(entries).map_err(std::io::Error::from)?,
+        )?;
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Answer requests on `listener` until the process ends, each
+    /// connection on its own thread, up to [`MAX_CONNECTIONS`] at once.
+    pub fn serve(self, listener: TcpListener) -> Result<(), CommitauraError> {
+        let server = Arc::new(self);
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Could not accept a connection: {}", e);
+                    continue;
+                }
+            };
+            if server.active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                server.active.fetch_sub(1, Ordering::SeqCst);
+                let busy = Response::error(503, "too many connections");
+                if let Err(e) = respond(&mut stream, &busy) {
+                    debug!("Dropped a connection: {}", e);
+                }
+                continue;
+            }
+            let server = Arc::clone(&server);
+            std::thread::spawn(move || {
+                if let Err(e) = server.answer(stream) {
+                    debug!("Dropped a connection: {}", e);
+                }
+                server.active.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        Ok(())
+    }
+
+    fn answer(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(SERVER_TIMEOUT))?;
+        let response = match read_request(&mut stream)? {
+            Some(request) => self.handle(&request),
+            None => Response::error(413, "request too large"),
+        };
+        respond(&mut stream, &response)
+    }
+}
+
+fn respond(stream: &mut TcpStream, response: &Response) -> std::io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        507 => "Insufficient Storage",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// The request on `stream`; `None` if its headers are over
+/// [`MAX_HEADER_BYTES`] or its body over [`MAX_BODY_BYTES`].
+fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Request>> {
+    let mut reader = BufReader::new(Read::take(stream, MAX_HEADER_BYTES));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut words = line.split_whitespace();
+    let mut request = Request {
+        method: words.next().unwrap_or_default().to_string(),
+        path: words.next().unwrap_or_default().to_string(),
+        ..Request::default()
+    };
+    let mut length = 0;
+    loop {
+        line.clear();
+        // Running out of the allowance before the blank line ends the
+        // headers means they are too large.
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = value.trim().parse().unwrap_or(0),
+            "authorization" => request.authorization = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    if length > MAX_BODY_BYTES {
+        return Ok(None);
+    }
+    reader.get_mut().set_limit(length as u64);
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(Some(request))
+}
+
+/// Compare secrets in time that does not depend on where they differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, token: Option<&str>, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            authorization: token.map(|t| format!("Bearer {}", t)),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn shares_only_lock_file_and_vendored_changes() {
+        let lock = "diff --git a/Cargo.lock b/Cargo.lock\n@@ -1 +1 @@\n-a\n+b\n";
+        let vendored = "diff --git a/vendor/left-pad/index.js b/vendor/left-pad/index.js\n@@ -1 +1 @@\n-a\n+b\n";
+        let source = "diff --git a/src/lib.rs b/src/lib.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        let detector = Detector::default();
+        assert!(shareable(lock, &detector));
+        assert!(shareable(&format!("{}{}", lock, vendored), &detector));
+        assert!(!shareable(&format!("{}{}", lock, source), &detector));
+        assert!(!shareable("", &detector));
+    }
+
+    #[test]
+    fn stores_entries_for_holders_of_the_token() {
+        let server = Server::open(None, Some("s3cret".to_string())).unwrap();
+        let path = "/v1/summaries/00ff";
+        let put = request("PUT", path, Some("s3cret"), r#"{"summary":"Bump serde"}"#);
+        assert_eq!(server.handle(&put).status, 200);
+        assert_eq!(
+            server.handle(&request("GET", path, Some("s3cret"), "")),
+            Response {
+                status: 200,
+                body: r#"{"summary":"Bump serde"}"#.to_string()
+            }
+        );
+        assert_eq!(
+            server
+                .handle(&request("GET", path, Some("guess"), ""))
+                .status,
+            401
+        );
+        assert_eq!(server.handle(&request("GET", path, None, "")).status, 401);
+        let missing = request("GET", "/v1/summaries/abcd", Some("s3cret"), "");
+        assert_eq!(server.handle(&missing).status, 404);
+        let bad_key = request("GET", "/v1/summaries/../x", Some("s3cret"), "");
+        assert_eq!(server.handle(&bad_key).status, 400);
+    }
+
+    #[test]
+    fn client_round_trips_through_the_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = Server::open(None, Some("t0ken".to_string())).unwrap();
+        std::thread::spawn(move || server.serve(listener));
+
+        let client = Client::new(&url, Some("t0ken".to_string())).unwrap();
+        assert_eq!(client.get("0123").unwrap(), None);
+        client.put("0123", "Update Cargo.lock").unwrap();
+        assert_eq!(
+            client.get("0123").unwrap().as_deref(),
+            Some("Update Cargo.lock")
+        );
+        assert!(Client::new(&url, None).unwrap().get("0123").is_err());
+        assert!(client
+            .put("0123", &"x".repeat(MAX_SUMMARY_BYTES + 1))
+            .is_err());
+
+        // Headers that never end are cut off instead of read forever.
+        let mut stream = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+        let flood = format!(
+            "GET /v1/summaries/0123 HTTP/1.1\r\nX-Flood: {}\r\n",
+            "a".repeat(16 * 1024)
+        );
+        let _ = stream.write_all(flood.as_bytes());
+        let mut reply = String::new();
+        let _ = stream.read_to_string(&mut reply);
+        assert!(reply.starts_with("HTTP/1.1 413"), "{}", reply);
+    }
+
+    #[test]
+    fn sends_the_token_only_over_https_or_to_this_machine() {
+        assert!(secure("https://cache.example.com"));
+        assert!(secure("http://localhost:7878"));
+        assert!(secure("http://127.0.0.1:7878/"));
+        assert!(secure("http://[::1]:7878"));
+        assert!(!secure("http://cache.internal:7878"));
+        assert!(!secure("http://localhost.attacker.example"));
+        assert!(!secure("http://127.0.0.1@attacker.example"));
+        assert!(!secure("ftp://localhost"));
+        assert!(Client::new("http://cache.internal:7878", None).is_err());
+
+        let opts = GenerateOptions::new("key");
+        let key = key("diff --git a/Cargo.lock b/Cargo.lock\n", &opts);
+        assert_eq!(key.len(), 64);
+        assert!(key.bytes().all(|b| b.is_ascii_hexdigit()));
+    }
+}
@@ -0,0 +1,124 @@
+//! `{{ variable }}` substitution for user-supplied prompts.
+//!
+//! Each placeholder is replaced by its variable's value and nothing else is
+//! interpreted: there are no conditionals, loops, filters or escapes, and a
+//! placeholder that is not a bare variable name is an error.
+
+use crate::CommitauraError;
+
+/// Variables available to user prompt templates. `last_commits` is another
+/// name for `history`.
+pub const PROMPT_VARIABLES: &[&str] = &[
+    "diff",
+    "history",
+    "last_commits",
+    "examples",
+    "branch",
+    "files",
+    "guidelines",
+];
+
+/// Variables available to system prompt templates, which are rendered
+/// before the diff is budgeted.
+pub const SYSTEM_VARIABLES: &[&str] = &["branch", "files"];
+
+/// Names referenced by `{{ ... }}` placeholders, in order of appearance.
+pub fn variables(template: &str) -> Result<Vec<String>, CommitauraError> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| {
+            CommitauraError::PromptTemplateError("unclosed `{{` placeholder".to_string())
+        })?;
+        let name = after[..end].trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(CommitauraError::PromptTemplateError(format!(
+                "invalid placeholder `{{{{{}}}}}`",
+                &after[..end]
+            )));
+        }
+        names.push(name.to_string());
+        rest = &after[end + 2..];
+    }
+    Ok(names)
+}
+
+/// Check that a prompt template only uses known variables and includes the diff.
+pub fn validate_prompt(template: &str) -> Result<(), CommitauraError> {
+    let names = known_variables(template, PROMPT_VARIABLES)?;
+    if !names.iter().any(|n| n == "diff") {
+        return Err(CommitauraError::PromptTemplateError(
+            "template never uses `{{ diff }}`, so the model would not see the changes".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a system prompt template only uses the variables known
+/// before the diff is read.
+pub fn validate_system(template: &str) -> Result<(), CommitauraError> {
+    known_variables(template, SYSTEM_VARIABLES).map(|_| ())
+}
+
+fn known_variables(template: &str, known: &[&str]) -> Result<Vec<String>, CommitauraError> {
+    let names = variables(template)?;
+    if let Some(unknown) = names.iter().find(|n| !known.contains(&n.as_str())) {
+        return Err(CommitauraError::PromptTemplateError(format!(
+            "unknown variable `{}` (available: {})",
+            unknown,
+            known.join(", ")
+        )));
+    }
+    Ok(names)
+}
+
+/// Substitute `vars` into `template`. Unknown placeholders are an error.
+pub fn render(template: &str, vars: &[(&str, &str)]) -> Result<String, CommitauraError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or_else(|| {
+            CommitauraError::PromptTemplateError("unclosed `{{` placeholder".to_string())
+        })?;
+        let name = after[..end].trim();
+        let value = vars
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| *v)
+            .ok_or_else(|| {
+                CommitauraError::PromptTemplateError(format!("unknown variable `{}`", name))
+            })?;
+        out.push_str(value);
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders() {
+        let out = render(
+            "Recent:\n{{history}}\nDiff:\n{{ diff }}",
+            &[("history", "a"), ("diff", "b")],
+        );
+        assert_eq!(out.unwrap(), "Recent:\na\nDiff:\nb");
+    }
+
+    #[test]
+    fn validates_variables() {
+        assert!(validate_prompt("Summarize {{ diff }} given {{ history }}").is_ok());
+        assert!(validate_prompt("Summarize {{ changes }}").is_err());
+        assert!(validate_prompt("Summarize the history {{ history }}").is_err());
+        assert!(validate_prompt("Summarize {{ diff").is_err());
+        assert!(validate_prompt("{{ branch }}: {{ files }} {{ last_commits }} {{ diff }}").is_ok());
+        assert!(validate_system("You write commits for {{ branch }}.").is_ok());
+        assert!(validate_system("You summarize {{ diff }}.").is_err());
+    }
+}
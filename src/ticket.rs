@@ -0,0 +1,152 @@
+//! Ticket IDs, such as `JIRA-123`, taken from the branch name.
+//!
+//! Teams that name branches after tickets (`feature/JIRA-123-login`) and
+//! link every commit to its ticket set a `[ticket]` pattern. The ticket it
+//! finds in the branch name is added to each generated message, as a
+//! footer or in the subject, unless the message already mentions it.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::TicketConfig;
+use crate::{message, template, CommitauraError};
+
+/// Where the ticket goes in the message.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Placement {
+    /// A trailer such as `Refs: JIRA-123`.
+    #[default]
+    Footer,
+    /// The subject, rewritten through the `subject` template.
+    Subject,
+}
+
+/// The ticket `pattern` finds in `branch`: its first capture group, or
+/// else the whole match.
+pub fn extract(pattern: &str, branch: &str) -> Result<Option<String>, CommitauraError> {
+    let regex = Regex::new(pattern).map_err(|e| {
+        CommitauraError::ConfigError(format!("Invalid ticket pattern '{}': {}", pattern, e))
+    })?;
+    Ok(regex.captures(branch).and_then(|captures| {
+        captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map(|m| m.as_str().to_string())
+    }))
+}
+
+/// A ticket found in the branch name, and how messages reference it.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub ticket: String,
+    pub config: TicketConfig,
+}
+
+impl Reference {
+    /// The ticket `config`'s pattern finds in `branch`; `None` without a
+    /// pattern or a match. Fails on a bad pattern or subject template.
+    pub fn find(config: &TicketConfig, branch: &str) -> Result<Option<Reference>, CommitauraError> {
+        let Some(pattern) = &config.pattern else {
+            return Ok(None);
+        };
+        let Some(ticket) = extract(pattern, branch)? else {
+            return Ok(None);
+        };
+        template::render(&config.subject, &[("ticket", &ticket), ("subject", "")])?;
+        Ok(Some(Reference {
+            ticket,
+            config: config.clone(),
+        }))
+    }
+
+    /// `message` referencing the ticket; see [`apply`].
+    pub fn apply(&self, message: &str) -> Result<String, CommitauraError> {
+        apply(message, &self.ticket, &self.config)
+    }
+}
+
+/// `message` referencing `ticket` as `config` places it; unchanged if it
+/// already mentions the ticket.
+pub fn apply(
+    message: &str,
+    ticket: &str,
+    config: &TicketConfig,
+) -> Result<String, CommitauraError> {
+    if message.contains(ticket) {
+        return Ok(message.to_string());
+    }
+    match config.placement {
+        Placement::Footer => Ok(message::add_trailer(message, &config.footer, ticket)),
+        Placement::Subject => {
+            let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+            let subject =
+                template::render(&config.subject, &[("ticket", ticket), ("subject", subject)])?;
+            Ok(if rest.is_empty() {
+                subject
+            } else {
+                format!("{}\n{}", subject, rest)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_ticket_in_the_branch_name() {
+        let pattern = r"[A-Z]+-\d+";
+        assert_eq!(
+            extract(pattern, "feature/JIRA-123-login").unwrap(),
+            Some("JIRA-123".to_string())
+        );
+        assert_eq!(extract(pattern, "main").unwrap(), None);
+        assert_eq!(
+            extract(r"^(\d+)-", "42-fix-login").unwrap(),
+            Some("42".to_string())
+        );
+        assert!(extract("[", "main").is_err());
+    }
+
+    #[test]
+    fn finds_a_reference_only_with_a_pattern() {
+        let mut config = TicketConfig::default();
+        assert!(Reference::find(&config, "feature/JIRA-123")
+            .unwrap()
+            .is_none());
+        config.pattern = Some(r"[A-Z]+-\d+".to_string());
+        let reference = Reference::find(&config, "feature/JIRA-123")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            reference.apply("Fix login").unwrap(),
+            "Fix login\n\nRefs: JIRA-123"
+        );
+        config.subject = "{{ tikcet }} {{ subject }}".to_string();
+        assert!(Reference::find(&config, "feature/JIRA-123").is_err());
+    }
+
+    #[test]
+    fn adds_the_ticket_once() {
+        let mut config = TicketConfig::default();
+        assert_eq!(
+            apply("Fix login\n\nBody.", "JIRA-123", &config).unwrap(),
+            "Fix login\n\nBody.\n\nRefs: JIRA-123"
+        );
+        assert_eq!(
+            apply("Fix login\n\nSigned-off-by: A <a@b>", "JIRA-123", &config).unwrap(),
+            "Fix login\n\nSigned-off-by: A <a@b>\nRefs: JIRA-123"
+        );
+        config.placement = Placement::Subject;
+        assert_eq!(
+            apply("Fix login\n\nBody.", "JIRA-123", &config).unwrap(),
+            "JIRA-123 Fix login\n\nBody."
+        );
+        assert_eq!(
+            apply("JIRA-123 Fix login", "JIRA-123", &config).unwrap(),
+            "JIRA-123 Fix login"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Per-stage timings of a generation, for `--timings` and the journal.
+//!
+//! A [`Timings`] handle is shared by every clone of the
+//! [`crate::GenerateOptions`] it belongs to, so stages recorded deep inside
+//! generation end up in the same list as those recorded by the caller.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub stage: String,
+    pub micros: u64,
+}
+
+#[derive(Debug, Default)]
+struct Recorded {
+    stages: Vec<StageTiming>,
+    journal_entry: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Timings(Arc<Mutex<Recorded>>);
+
+impl Timings {
+    pub fn record(&self, stage: &str, elapsed: Duration) {
+        self.lock().stages.push(StageTiming {
+            stage: stage.to_string(),
+            micros: elapsed.as_micros() as u64,
+        });
+    }
+
+    /// Run `f`, recording how long it took as `stage`.
+    pub fn time<T>(&self, stage: &str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.record(stage, started.elapsed());
+        result
+    }
+
+    /// Everything recorded so far, in order.
+    pub fn stages(&self) -> Vec<StageTiming> {
+        self.lock().stages.clone()
+    }
+
+    /// Forget what was recorded, e.g. before the next generation.
+    pub fn clear(&self) {
+        *self.lock() = Recorded::default();
+    }
+
+    /// The journal entry written for the last request, so the stages that
+    /// follow it can be added there too.
+    pub fn journal_entry(&self) -> Option<u64> {
+        self.lock().journal_entry
+    }
+
+    pub fn set_journal_entry(&self, id: u64) {
+        self.lock().journal_entry = Some(id);
+    }
+
+    /// Add the stages and journal entry `other` recorded, as when one of
+    /// several generations given their own timings is kept.
+    pub fn absorb(&self, other: &Timings) {
+        let other = other.lock();
+        let mut recorded = self.lock();
+        recorded.stages.extend(other.stages.iter().cloned());
+        if other.journal_entry.is_some() {
+            recorded.journal_entry = other.journal_entry;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recorded> {
+        // A panic while holding the lock cannot leave a Vec half-written.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A table of `stages` and their total, one per line, in milliseconds.
+/// Stages are timed without the interactive prompts between them, so the
+/// total is the time spent working rather than the wall-clock time.
+pub fn report(stages: &[StageTiming]) -> String {
+    let width = stages
+        .iter()
+        .map(|s| s.stage.len())
+        .chain([5])
+        .max()
+        .unwrap_or_default();
+    let line = |name: &str, micros: u64| {
+        format!(
+            "{:<width$}  {:>9.1} ms\n",
+            name,
+            micros as f64 / 1000.0,
+            width = width
+        )
+    };
+    let mut out: String = stages.iter().map(|s| line(&s.stage, s.micros)).collect();
+    out.push_str(&line("total", stages.iter().map(|s| s.micros).sum()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shares_stages_between_clones_and_reports_them() {
+        let timings = Timings::default();
+        timings
+            .clone()
+            .record("git diff", Duration::from_micros(4_100));
+        assert_eq!(timings.time("tokenize", || 7), 7);
+        let stages = timings.stages();
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].micros, 4_100);
+
+        let report = report(&[stages[0].clone(), stages[0].clone()]);
+        assert_eq!(
+            report,
+            "git diff        4.1 ms\ngit diff        4.1 ms\ntotal           8.2 ms\n"
+        );
+        timings.clear();
+        assert!(timings.stages().is_empty());
+    }
+}
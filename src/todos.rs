@@ -0,0 +1,206 @@
+//! Tracking issues for TODO and FIXME comments a change adds.
+//!
+//! New markers are found in the added lines of the staged diff, each with a
+//! few lines of surrounding code. The model turns a marker into an issue
+//! title; the issue itself is opened through [`crate::forge::create_issue`]
+//! and referenced from the commit message.
+
+use crate::diff::FileDiff;
+use crate::forge::Issue;
+use crate::generate::{complete, GenerateOptions, Prompt};
+use crate::CommitauraError;
+
+const MARKERS: &[&str] = &["TODO", "FIXME"];
+
+/// Lines of code shown before and after a marker.
+const CONTEXT_LINES: usize = 3;
+
+const SYSTEM_MESSAGE: &str =
+    "You are a helpful assistant that writes short, specific issue titles for software projects.";
+
+/// A TODO or FIXME comment introduced by the change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewTodo {
+    pub path: String,
+    /// Line number in the new version of the file, when the hunk header has one.
+    pub line: Option<usize>,
+    pub marker: String,
+    /// The comment after the marker, e.g. `handle retries` for `// TODO: handle retries`.
+    pub text: String,
+    /// The surrounding code from the new version of the file.
+    pub context: String,
+}
+
+impl NewTodo {
+    pub fn location(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{}", self.path, line),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// The marker in `line` and the comment after it. Markers already pointing
+/// at an issue, like `TODO(#12)`, are skipped.
+fn marker_in(line: &str) -> Option<(&'static str, String)> {
+    for marker in MARKERS {
+        let Some(start) = line.find(marker) else {
+            continue;
+        };
+        let before = line[..start].chars().next_back();
+        let rest = &line[start + marker.len()..];
+        // Whole words only: not `TODOS` or `autoFIXME`.
+        if before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+            || rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+        {
+            continue;
+        }
+        let mut text = rest.trim_start();
+        if let Some(tag) = text.strip_prefix('(') {
+            let (owner, after) = tag.split_once(')').unwrap_or((tag, ""));
+            if owner.contains('#') || owner.contains("://") {
+                return None;
+            }
+            text = after;
+        }
+        let text = text
+            .trim_start_matches([':', '-', ' '])
+            .trim_end_matches("*/")
+            .trim_end_matches("-->")
+            .trim();
+        if text.contains("://") || text.split_whitespace().any(is_issue_ref) {
+            return None;
+        }
+        return Some((marker, text.to_string()));
+    }
+    None
+}
+
+fn is_issue_ref(word: &str) -> bool {
+    word.trim_matches(|c: char| !c.is_alphanumeric() && c != '#')
+        .strip_prefix('#')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Find the TODO and FIXME comments `files` add.
+pub fn find(files: &[FileDiff]) -> Vec<NewTodo> {
+    let mut todos = Vec::new();
+    for file in files {
+        for hunk in &file.hunks {
+            // The new version of the hunk: added and context lines.
+            let new_lines: Vec<&str> = hunk
+                .lines
+                .iter()
+                .filter(|l| !l.starts_with('-'))
+                .map(|l| &l[l.len().min(1)..])
+                .collect();
+            let added = hunk.lines.iter().filter(|l| !l.starts_with('-'));
+            for (i, raw) in added.enumerate() {
+                if !raw.starts_with('+') {
+                    continue;
+                }
+                let Some((marker, text)) = marker_in(&raw[1..]) else {
+                    continue;
+                };
+                let from = i.saturating_sub(CONTEXT_LINES);
+                let to = (i + CONTEXT_LINES + 1).min(new_lines.len());
+                todos.push(NewTodo {
+                    path: file.path.clone(),
+                    line: hunk.new_start().map(|start| start + i),
+                    marker: marker.to_string(),
+                    text,
+                    context: new_lines[from..to].join("\n"),
+                });
+            }
+        }
+    }
+    todos
+}
+
+pub fn build_title_prompt(todo: &NewTodo) -> Prompt {
+    Prompt {
+        system: SYSTEM_MESSAGE.to_string(),
+        user: format!(
+            "Write a title for an issue tracking this {} comment, under 70 characters, naming the concrete work to be done. Reply with the title only.\n\nFile: {}\nComment: {}\n\nCode around it:\n{}\n",
+            todo.marker,
+            todo.location(),
+            todo.text,
+            todo.context
+        ),
+    }
+}
+
+/// An issue title for `todo`, falling back to the comment itself if the
+/// model's reply is empty.
+pub fn issue_title(todo: &NewTodo, opts: &GenerateOptions) -> Result<String, CommitauraError> {
+    let reply = complete(&build_title_prompt(todo), opts, 40)?;
+    let title = reply
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches(['"', '`']);
+    Ok(if title.is_empty() {
+        format!("{}: {}", todo.marker, todo.text)
+    } else {
+        title.to_string()
+    })
+}
+
+/// Issue body: where the marker is, the comment and the code around it.
+pub fn issue_body(todo: &NewTodo) -> String {
+    format!(
+        "`{}` added in `{}`:\n\n> {}\n\n```\n{}\n```\n",
+        todo.marker,
+        todo.location(),
+        todo.text,
+        todo.context
+    )
+}
+
+/// `message` with a `Refs` line for `issues` appended to its body.
+pub fn append_refs(message: &str, issues: &[Issue]) -> String {
+    if issues.is_empty() {
+        return message.to_string();
+    }
+    let refs: Vec<String> = issues.iter().map(|i| format!("#{}", i.number)).collect();
+    format!("{}\n\nRefs {}", message.trim_end(), refs.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff;
+
+    #[test]
+    fn finds_new_untracked_markers_with_context() {
+        let files = diff::parse(
+            "diff --git a/src/net.rs b/src/net.rs\n--- a/src/net.rs\n+++ b/src/net.rs\n@@ -10,3 +10,5 @@\n fn send() {\n-    // TODO: old\n+    // TODO(ana): retry on connection reset\n+    // FIXME(#12): already tracked\n+    let todos = 1; // TODOS are not markers\n     write();\n }\n",
+        );
+        let todos = find(&files);
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].location(), "src/net.rs:11");
+        assert_eq!(todos[0].marker, "TODO");
+        assert_eq!(todos[0].text, "retry on connection reset");
+        assert!(todos[0].context.starts_with("fn send() {\n"));
+        assert!(todos[0].context.ends_with("    write();"));
+    }
+
+    #[test]
+    fn appends_refs() {
+        let issues = [
+            Issue {
+                number: 41,
+                url: String::new(),
+            },
+            Issue {
+                number: 42,
+                url: String::new(),
+            },
+        ];
+        assert_eq!(
+            append_refs("Add retries\n", &issues),
+            "Add retries\n\nRefs #41, #42"
+        );
+    }
+}
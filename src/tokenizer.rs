@@ -0,0 +1,182 @@
+//! Token counts for the model a prompt is sent to.
+//!
+//! OpenAI models split text with one of three byte-pair encodings:
+//! `o200k_base` for gpt-4o, gpt-4.1 and the o-series reasoning models,
+//! `cl100k_base` for gpt-4 and gpt-3.5-turbo, and `p50k_base` for the older
+//! completion models. Other models, such as Claude or those served by
+//! Ollama, use tokenizers that are not available offline, so their counts
+//! are estimated from the characters of the text, erring high so that a
+//! prompt sized by them still fits.
+
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton, p50k_base_singleton, CoreBPE};
+
+use crate::CommitauraError;
+
+/// How a model splits text into tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tokenizer {
+    O200kBase,
+    Cl100kBase,
+    P50kBase,
+    /// An estimate for models whose tokenizer is unknown.
+    Heuristic,
+}
+
+/// Model name prefixes by tokenizer, checked in order, so `gpt-4o` is
+/// matched before `gpt-4`.
+const MODELS: &[(&str, Tokenizer)] = &[
+    ("gpt-4o", Tokenizer::O200kBase),
+    ("chatgpt-4o", Tokenizer::O200kBase),
+    ("gpt-4.1", Tokenizer::O200kBase),
+    ("gpt-4.5", Tokenizer::O200kBase),
+    ("gpt-5", Tokenizer::O200kBase),
+    ("o1", Tokenizer::O200kBase),
+    ("o3", Tokenizer::O200kBase),
+    ("o4", Tokenizer::O200kBase),
+    ("gpt-4", Tokenizer::Cl100kBase),
+    ("gpt-3.5", Tokenizer::Cl100kBase),
+    ("text-embedding-3", Tokenizer::Cl100kBase),
+    ("text-embedding-ada-002", Tokenizer::Cl100kBase),
+    ("text-davinci", Tokenizer::P50kBase),
+    ("code-davinci", Tokenizer::P50kBase),
+    ("code-cushman", Tokenizer::P50kBase),
+];
+
+/// Heuristic cost of an ASCII character, in thirds of a token: code and
+/// English average three to four characters per token.
+const ASCII_THIRDS: usize = 1;
+
+/// Heuristic cost of any other character, in thirds of a token: most take
+/// a token or more of their own.
+const OTHER_THIRDS: usize = 3;
+
+impl Tokenizer {
+    /// The tokenizer of `model`. Names routed through a gateway, such as
+    /// `openai/gpt-4o`, are matched without the vendor.
+    pub fn for_model(model: &str) -> Tokenizer {
+        let name = model.rsplit('/').next().unwrap_or(model);
+        MODELS
+            .iter()
+            .find(|(prefix, _)| name.starts_with(prefix))
+            .map_or(Tokenizer::Heuristic, |(_, tokenizer)| *tokenizer)
+    }
+
+    /// The encoding's name, or `heuristic`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tokenizer::O200kBase => "o200k_base",
+            Tokenizer::Cl100kBase => "cl100k_base",
+            Tokenizer::P50kBase => "p50k_base",
+            Tokenizer::Heuristic => "heuristic",
+        }
+    }
+
+    /// How many tokens `text` takes.
+    pub fn count(self, text: &str) -> Result<usize, CommitauraError> {
+        let counted = self.with_bpe(|bpe| bpe.encode_with_special_tokens(text).len());
+        Ok(counted.unwrap_or_else(|| thirds(text).div_ceil(3)))
+    }
+
+    /// `text` cut down to at most `available` tokens.
+    pub fn truncate(self, text: &str, available: usize) -> Result<String, CommitauraError> {
+        let cut = self.with_bpe(|bpe| {
+            let tokens = bpe.encode_with_special_tokens(text);
+            if tokens.len() <= available {
+                return Ok(text.to_string());
+            }
+            bpe.decode(tokens[..available].to_vec())
+                .map_err(|e| CommitauraError::TokenizerError(e.to_string()))
+        });
+        if let Some(cut) = cut {
+            return cut;
+        }
+        let mut left = available.saturating_mul(3);
+        let end = text
+            .char_indices()
+            .find_map(|(i, c)| match left.checked_sub(char_thirds(c)) {
+                Some(rest) => {
+                    left = rest;
+                    None
+                }
+                None => Some(i),
+            })
+            .unwrap_or(text.len());
+        Ok(text[..end].to_string())
+    }
+
+    /// `f` applied to the encoding, or `None` for the heuristic.
+    fn with_bpe<T>(self, f: impl FnOnce(&CoreBPE) -> T) -> Option<T> {
+        let singleton = match self {
+            Tokenizer::O200kBase => o200k_base_singleton,
+            Tokenizer::Cl100kBase => cl100k_base_singleton,
+            Tokenizer::P50kBase => p50k_base_singleton,
+            Tokenizer::Heuristic => return None,
+        };
+        let bpe = singleton();
+        let bpe = bpe.lock();
+        Some(f(&bpe))
+    }
+}
+
+/// The heuristic cost of `text`, in thirds of a token.
+fn thirds(text: &str) -> usize {
+    text.chars().map(char_thirds).sum()
+}
+
+fn char_thirds(c: char) -> usize {
+    if c.is_ascii() {
+        ASCII_THIRDS
+    } else {
+        OTHER_THIRDS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_tokenizer_of_the_model() {
+        assert_eq!(Tokenizer::for_model("gpt-4o-mini"), Tokenizer::O200kBase);
+        assert_eq!(
+            Tokenizer::for_model("gpt-4o-2024-08-06"),
+            Tokenizer::O200kBase
+        );
+        assert_eq!(Tokenizer::for_model("o3-mini"), Tokenizer::O200kBase);
+        assert_eq!(Tokenizer::for_model("openai/gpt-4.1"), Tokenizer::O200kBase);
+        assert_eq!(Tokenizer::for_model("gpt-4-turbo"), Tokenizer::Cl100kBase);
+        assert_eq!(Tokenizer::for_model("gpt-3.5-turbo"), Tokenizer::Cl100kBase);
+        assert_eq!(
+            Tokenizer::for_model("text-davinci-003"),
+            Tokenizer::P50kBase
+        );
+        assert_eq!(
+            Tokenizer::for_model("claude-sonnet-4-5"),
+            Tokenizer::Heuristic
+        );
+        assert_eq!(Tokenizer::for_model("llama3.2"), Tokenizer::Heuristic);
+    }
+
+    #[test]
+    fn counts_and_truncates_consistently() {
+        let text = "fn main() {\n    println!(\"héllo, wörld\");\n}\n".repeat(20);
+        for tokenizer in [
+            Tokenizer::O200kBase,
+            Tokenizer::Cl100kBase,
+            Tokenizer::P50kBase,
+            Tokenizer::Heuristic,
+        ] {
+            let total = tokenizer.count(&text).unwrap();
+            assert!(total > 0);
+            assert_eq!(tokenizer.truncate(&text, total).unwrap(), text);
+            let cut = tokenizer.truncate(&text, total / 2).unwrap();
+            assert!(text.starts_with(&cut) && cut.len() < text.len());
+            assert!(tokenizer.count(&cut).unwrap() <= total / 2);
+        }
+        // The estimate errs high: it never counts fewer tokens than the
+        // encodings do for ordinary code.
+        let heuristic = Tokenizer::Heuristic.count(&text).unwrap();
+        assert!(heuristic >= Tokenizer::Cl100kBase.count(&text).unwrap());
+        assert_eq!(Tokenizer::Heuristic.count("abcd").unwrap(), 2);
+    }
+}
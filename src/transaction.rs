@@ -0,0 +1,240 @@
+//! Rolling back multi-step operations that fail partway.
+//!
+//! Rewording with `--fold-fixups` rebases before it rewrites any message,
+//! `--amend-no-edit` restages files before it amends, and `split` makes
+//! several commits in a row. An API error or a hook rejecting a commit in
+//! between would leave the repository half changed. A [`Transaction`]
+//! records the branch HEAD is on, the commit it points at and the index
+//! before the first step, and puts them back if a later one fails or the
+//! user cancels. The record is kept in a log under `.git/commitaura/` until
+//! the operation ends, so if Commitaura itself is killed, `commitaura
+//! rollback` can still restore the repository.
+//!
+//! Once an operation finishes, its record moves to a second log together
+//! with the commit it left the branch at, so `commitaura rollback --last`
+//! can undo the whole session while nothing has moved the branch since.
+//! Either record can also restore the staged contents of single files.
+
+use std::path::PathBuf;
+
+use crate::repo_state::{self, Operation};
+use crate::{git, CommitauraError};
+
+const LOG_FILE: &str = "transaction";
+
+/// Where the record of the last operation that finished is kept.
+const LAST_FILE: &str = "last-transaction";
+
+/// Where the repository stood before an operation began.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    /// What is being done, such as `reword`.
+    pub operation: String,
+    /// The ref HEAD was on, such as `refs/heads/main`, or `HEAD` when it
+    /// was detached.
+    pub reference: String,
+    /// The commit `reference` pointed at.
+    pub head: String,
+    /// Tree of the index.
+    pub index: String,
+    /// The commit the operation left `reference` at, once it finished.
+    pub result: Option<String>,
+}
+
+impl Transaction {
+    /// The log's contents: one `key value` line per field.
+    pub fn to_log(&self) -> String {
+        let mut log = format!(
+            "operation {}\nref {}\nhead {}\nindex {}\n",
+            self.operation, self.reference, self.head, self.index
+        );
+        if let Some(result) = &self.result {
+            log.push_str(&format!("result {}\n", result));
+        }
+        log
+    }
+
+    /// Read back [`Transaction::to_log`]; `None` if a field is missing. Logs
+    /// from before the ref was recorded moved HEAD.
+    pub fn parse_log(text: &str) -> Option<Transaction> {
+        let field = |key: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '))
+                .map(str::to_string)
+        };
+        Some(Transaction {
+            operation: field("operation")?,
+            reference: field("ref").unwrap_or_else(|| "HEAD".to_string()),
+            head: field("head")?,
+            index: field("index")?,
+            result: field("result"),
+        })
+    }
+
+    /// Record the repository's state before `operation`. Refused while the
+    /// log of an interrupted operation is still there, since starting
+    /// another would lose the state it recorded.
+    pub fn begin(operation: &str) -> Result<Transaction, CommitauraError> {
+        let path = log_path(LOG_FILE)?;
+        if let Some(interrupted) = pending()? {
+            return Err(CommitauraError::GitOperationFailed(format!(
+                "an interrupted {} left {}; run `commitaura rollback` to restore the repository first, or delete the file to keep it as it is",
+                interrupted.operation,
+                path.display()
+            )));
+        }
+        let transaction = Transaction {
+            operation: operation.to_string(),
+            reference: git::symbolic_head().unwrap_or_else(|| "HEAD".to_string()),
+            head: git::head_commit()?,
+            index: git::index_tree()?,
+            result: None,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, transaction.to_log())?;
+        Ok(transaction)
+    }
+
+    /// The operation succeeded: keep the recorded state, with the commit
+    /// the branch is now at, as the last operation.
+    pub fn commit(mut self) -> Result<(), CommitauraError> {
+        self.result = Some(git::ref_commit(&self.reference)?);
+        let last = log_path(LAST_FILE)?;
+        let temp = last.with_extension("tmp");
+        std::fs::write(&temp, self.to_log())?;
+        std::fs::rename(&temp, &last)?;
+        remove_log(LOG_FILE)
+    }
+
+    /// The commit `reference` is at now, which a rollback moves it away
+    /// from.
+    pub fn current(&self) -> Result<String, CommitauraError> {
+        git::ref_commit(&self.reference)
+    }
+
+    /// Put the branch and the index back as they were, abandoning a rebase
+    /// left in progress first. The branch is only moved while it still
+    /// points at `expected`, so commits made since are never lost. The
+    /// index is only restored while HEAD is still on the branch. The work
+    /// tree is not touched; the operations rolled back only change it
+    /// through a rebase, which restores it when aborted. Steps that are
+    /// already undone are skipped.
+    pub fn rollback(self, expected: &str) -> Result<(), CommitauraError> {
+        if repo_state::operation_in(&git::git_dir()?) == Some(Operation::Rebase) {
+            git::abort_rebase()?;
+        }
+        if expected != self.head {
+            git::move_ref(
+                &self.reference,
+                &self.head,
+                expected,
+                &format!("commitaura: roll back {}", self.operation),
+            )?;
+        }
+        let on_branch = match git::symbolic_head() {
+            Some(head) => head == self.reference,
+            None => self.reference == "HEAD",
+        };
+        if on_branch && git::index_tree()? != self.index {
+            git::read_tree(&self.index)?;
+        }
+        remove_log(if self.result.is_some() {
+            LAST_FILE
+        } else {
+            LOG_FILE
+        })
+    }
+
+    /// Put the staged contents of `paths` back as they were, leaving the
+    /// branch and the other files alone. The record is kept.
+    pub fn rollback_paths(&self, paths: &[String]) -> Result<(), CommitauraError> {
+        git::reset_paths_to(&self.index, paths)
+    }
+}
+
+fn log_path(file: &str) -> Result<PathBuf, CommitauraError> {
+    Ok(git::git_dir()?.join("commitaura").join(file))
+}
+
+fn remove_log(file: &str) -> Result<(), CommitauraError> {
+    match std::fs::remove_file(log_path(file)?) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn read_log(file: &str) -> Result<Option<Transaction>, CommitauraError> {
+    match std::fs::read_to_string(log_path(file)?) {
+        Ok(text) => Transaction::parse_log(&text).map(Some).ok_or_else(|| {
+            CommitauraError::GitOperationFailed("unreadable transaction log".to_string())
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The transaction an interrupted operation left behind, if any.
+pub fn pending() -> Result<Option<Transaction>, CommitauraError> {
+    read_log(LOG_FILE)
+}
+
+/// The transaction of the last operation that finished, if any.
+pub fn last() -> Result<Option<Transaction>, CommitauraError> {
+    read_log(LAST_FILE)
+}
+
+/// Run `steps` as `operation`, which returns whether to keep what it did.
+/// If it returns `false`, as when the user cancels, or fails, the
+/// repository is rolled back. A failure is returned as it was; should the
+/// rollback fail too, the log stays for `commitaura rollback`.
+pub fn run(
+    operation: &str,
+    steps: impl FnOnce() -> Result<bool, CommitauraError>,
+) -> Result<(), CommitauraError> {
+    let transaction = Transaction::begin(operation)?;
+    let roll_back = |transaction: Transaction| {
+        let expected = transaction.current()?;
+        transaction.rollback(&expected)
+    };
+    match steps() {
+        Ok(true) => transaction.commit(),
+        Ok(false) => roll_back(transaction),
+        Err(e) => {
+            if let Err(rollback) = roll_back(transaction) {
+                log::error!("Could not roll back the {}: {}", operation, rollback);
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_round_trips() {
+        let mut transaction = Transaction {
+            operation: "reword".to_string(),
+            reference: "refs/heads/main".to_string(),
+            head: "1a2b3c".to_string(),
+            index: "4d5e6f".to_string(),
+            result: None,
+        };
+        assert_eq!(
+            Transaction::parse_log(&transaction.to_log()),
+            Some(transaction.clone())
+        );
+        transaction.result = Some("7a8b9c".to_string());
+        assert_eq!(
+            Transaction::parse_log(&transaction.to_log()),
+            Some(transaction)
+        );
+        assert_eq!(
+            Transaction::parse_log("operation reword\nref HEAD\nhead 1a2b3c\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! Messages for trivial changes, written locally without the model.
+//!
+//! A version bump, a formatting-only change or a typo fix needs no model to
+//! describe, and a large share of commits are one of these. They are
+//! recognised from the diff alone, and the message comes from a configured
+//! template such as `Bump version to {{ version }}`.
+
+use crate::config::TrivialConfig;
+use crate::diff::{self, FileDiff};
+use crate::generated::LOCK_FILES;
+use crate::spellcheck::edit_distance;
+use crate::{routing, template, CommitauraError};
+
+/// Files that carry a package's version.
+const MANIFESTS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.cfg",
+    "setup.py",
+    "pom.xml",
+    "build.gradle",
+    "VERSION",
+    "version.txt",
+    "__init__.py",
+    "_version.py",
+    "version.py",
+];
+
+/// Prose files, where any small fix counts as a typo fix.
+const PROSE_EXTENSIONS: &[&str] = &["md", "txt", "rst", "adoc"];
+
+/// Most changed characters per line for a typo fix.
+const MAX_TYPO_EDITS: usize = 3;
+
+/// Most changed lines for a typo fix.
+const MAX_TYPO_LINES: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trivial {
+    /// Only version numbers changed, all to this one.
+    VersionBump(String),
+    /// Only trailing whitespace and line breaks changed in these files.
+    Formatting(Vec<String>),
+    /// A few characters changed in comments or prose of this file.
+    Typo(String),
+}
+
+impl Trivial {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Trivial::VersionBump(_) => "version bump",
+            Trivial::Formatting(_) => "formatting",
+            Trivial::Typo(_) => "typo fix",
+        }
+    }
+}
+
+/// What trivial change `diff` is, if it is one.
+pub fn classify(diff: &str) -> Option<Trivial> {
+    let files = diff::parse(diff);
+    if files.is_empty() || files.iter().any(|f| f.binary) {
+        return None;
+    }
+    version_bump(&files)
+        .or_else(|| formatting(&files))
+        .or_else(|| typo(&files))
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn version_bump(files: &[FileDiff]) -> Option<Trivial> {
+    let mut versions = Vec::new();
+    let mut manifests = 0;
+    for file in files {
+        let name = file_name(&file.path);
+        // A lockfile records the package's own version too, and a
+        // dependency update in it shows up as a different version.
+        if MANIFESTS.contains(&name) {
+            manifests += 1;
+        } else if !LOCK_FILES.contains(&name) {
+            return None;
+        }
+        for hunk in &file.hunks {
+            for line in hunk.removed() {
+                version_value(line, name)?;
+            }
+            for line in hunk.added() {
+                versions.push(version_value(line, name)?);
+            }
+        }
+    }
+    let version = versions.first()?;
+    (manifests > 0 && versions.iter().all(|v| v == version))
+        .then(|| Trivial::VersionBump(version.clone()))
+}
+
+/// The version a line assigns, such as `version = "1.2.3"`,
+/// `"version": "1.2.3",` or `__version__ = '1.2.3'`. A `VERSION` file holds
+/// just the number.
+fn version_value(line: &str, file_name: &str) -> Option<String> {
+    let line = line.trim().trim_end_matches(',');
+    let value = if file_name == "VERSION" || file_name == "version.txt" {
+        line
+    } else if let Some(rest) = line.strip_prefix("<version>") {
+        rest.strip_suffix("</version>")?
+    } else {
+        let (key, value) = line.split_once(['=', ':'])?;
+        let key = key.trim().trim_matches(['"', '\'']);
+        if !matches!(key, "version" | "__version__" | "VERSION") {
+            return None;
+        }
+        value.trim().trim_matches(['"', '\''])
+    };
+    let looks_like_version = value.starts_with(|c: char| c.is_ascii_digit())
+        && value.contains('.')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+    looks_like_version.then(|| value.to_string())
+}
+
+fn formatting(files: &[FileDiff]) -> Option<Trivial> {
+    let mut paths = Vec::new();
+    for file in files {
+        if file.hunks.is_empty() {
+            return None;
+        }
+        for hunk in &file.hunks {
+            if !rewrapped(&joined(hunk.removed()), &joined(hunk.added())) {
+                return None;
+            }
+        }
+        paths.push(file.path.clone());
+    }
+    Some(Trivial::Formatting(paths))
+}
+
+/// `lines` without trailing whitespace, one per line.
+fn joined<'a>(lines: impl Iterator<Item = &'a str>) -> String {
+    lines.map(str::trim_end).collect::<Vec<_>>().join("\n")
+}
+
+/// Whether `old` and `new` differ only in where lines break: wherever
+/// either breaks a line, the other may have any whitespace or none, along
+/// with the next line's indentation. Whitespace within a line must match.
+fn rewrapped(old: &str, new: &str) -> bool {
+    let (mut old, mut new) = (old, new);
+    loop {
+        let old_space = old.len() - old.trim_start().len();
+        let new_space = new.len() - new.trim_start().len();
+        let (old_run, new_run) = (&old[..old_space], &new[..new_space]);
+        if old_run != new_run && !old_run.contains('\n') && !new_run.contains('\n') {
+            return false;
+        }
+        old = &old[old_space..];
+        new = &new[new_space..];
+        let old_word = old.find(char::is_whitespace).unwrap_or(old.len());
+        let new_word = new.find(char::is_whitespace).unwrap_or(new.len());
+        // A break may fall inside what is one word on the other side, as
+        // between `f(` and `a`.
+        let common = old[..old_word]
+            .char_indices()
+            .zip(new[..new_word].chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, c), _)| i + c.len_utf8());
+        if common == 0 {
+            return old.is_empty() && new.is_empty();
+        }
+        old = &old[common..];
+        new = &new[common..];
+        let at_break = |rest: &str| rest.is_empty() || rest.starts_with('\n');
+        let old_mid = common < old_word;
+        let new_mid = common < new_word;
+        // Inside a word, the other side must break the line there.
+        if (old_mid && !at_break(new)) || (new_mid && !at_break(old)) {
+            return false;
+        }
+    }
+}
+
+fn typo(files: &[FileDiff]) -> Option<Trivial> {
+    let [file] = files else {
+        return None;
+    };
+    let prose = file
+        .path
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| PROSE_EXTENSIONS.contains(&ext));
+    let markers = comment_markers(&file.path);
+    let mut changed = 0;
+    for hunk in &file.hunks {
+        let removed: Vec<&str> = hunk.removed().collect();
+        let added: Vec<&str> = hunk.added().collect();
+        if removed.len() != added.len() {
+            return None;
+        }
+        for (old, new) in removed.iter().zip(&added) {
+            let edits = edit_distance(old, new);
+            if edits == 0
+                || edits > MAX_TYPO_EDITS
+                || !(prose || is_comment(old, markers) && is_comment(new, markers))
+            {
+                return None;
+            }
+            changed += 1;
+        }
+    }
+    (1..=MAX_TYPO_LINES)
+        .contains(&changed)
+        .then(|| Trivial::Typo(file.path.clone()))
+}
+
+/// What starts a comment line in the file at `path`, by its language.
+/// Config files whose comments start with `#` or `;` count too; any other
+/// file has none.
+fn comment_markers(path: &str) -> &'static [&'static str] {
+    const C_LIKE: &[&str] = &["//", "/*", "*"];
+    const HASH: &[&str] = &["#"];
+    match routing::language(path) {
+        Some(
+            "Python" | "Ruby" | "Perl" | "Elixir" | "Nim" | "Julia" | "R" | "Shell" | "PowerShell",
+        ) => HASH,
+        Some("Lua" | "Haskell" | "SQL") => &["--"],
+        Some("Erlang") => &["%"],
+        Some("Clojure") => &[";"],
+        Some("OCaml") => &["(*", "*"],
+        Some("F#") => &["//", "(*", "*"],
+        Some("PHP") => &["//", "/*", "*", "#"],
+        Some("CSS") => &["/*", "*"],
+        Some("HTML") => &["<!--"],
+        Some("Vue" | "Svelte") => &["<!--", "//", "/*", "*"],
+        Some(_) => C_LIKE,
+        None => match file_name(path).rsplit_once('.') {
+            Some((_, "toml" | "yaml" | "yml" | "cfg" | "conf")) => HASH,
+            Some((_, "ini")) => &[";", "#"],
+            None if matches!(file_name(path), "Dockerfile" | "Makefile") => HASH,
+            _ => &[],
+        },
+    }
+}
+
+fn is_comment(line: &str, markers: &[&str]) -> bool {
+    let line = line.trim_start();
+    markers.iter().any(|marker| line.starts_with(marker))
+}
+
+/// The configured message for `trivial`, or `None` if its template is
+/// empty, which leaves the change to the model.
+pub fn message(
+    trivial: &Trivial,
+    config: &TrivialConfig,
+) -> Result<Option<String>, CommitauraError> {
+    let (template, vars): (&str, Vec<(&str, String)>) = match trivial {
+        Trivial::VersionBump(version) => (&config.version_bump, vec![("version", version.clone())]),
+        Trivial::Formatting(paths) => {
+            let files = match paths.as_slice() {
+                [path] => path.clone(),
+                paths => format!("{} files", paths.len()),
+            };
+            (&config.formatting, vec![("files", files)])
+        }
+        Trivial::Typo(path) => (&config.typo, vec![("file", path.clone())]),
+    };
+    if template.trim().is_empty() {
+        return Ok(None);
+    }
+    let vars: Vec<(&str, &str)> = vars.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    Ok(Some(template::render(template, &vars)?.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(path: &str, lines: &str) -> String {
+        format!(
+            "diff --git a/{0} b/{0}\n--- a/{0}\n+++ b/{0}\n@@ -1,3 +1,3 @@\n{1}",
+            path, lines
+        )
+    }
+
+    #[test]
+    fn recognises_version_bumps() {
+        let diff = patch(
+            "Cargo.toml",
+            " [package]\n-version = \"1.2.2\"\n+version = \"1.2.3\"\n",
+        ) + &patch("Cargo.lock", "-version = \"1.2.2\"\n+version = \"1.2.3\"\n")
+            + &patch(
+                "web/package.json",
+                "-  \"version\": \"1.2.2\",\n+  \"version\": \"1.2.3\",\n",
+            );
+        assert_eq!(
+            classify(&diff),
+            Some(Trivial::VersionBump("1.2.3".to_string()))
+        );
+
+        let with_dependency = patch(
+            "Cargo.toml",
+            "-version = \"1.2.2\"\n+version = \"1.2.3\"\n+serde = \"1\"\n",
+        );
+        assert_eq!(classify(&with_dependency), None);
+        let with_update = patch("Cargo.toml", "-version = \"1.2.2\"\n+version = \"1.2.3\"\n")
+            + &patch("Cargo.lock", "-version = \"1.0.1\"\n+version = \"1.0.2\"\n");
+        assert_eq!(classify(&with_update), None);
+        assert_eq!(
+            classify(&patch("Cargo.lock", "-version = \"1\"\n+version = \"2\"\n")),
+            None
+        );
+    }
+
+    #[test]
+    fn recognises_formatting_and_typos() {
+        let reflowed = patch(
+            "src/a.rs",
+            "-fn f(a: u8, b: u8) {}\n+fn f(\n+    a: u8,\n+    b: u8\n+) {}\n",
+        );
+        assert_eq!(
+            classify(&reflowed),
+            Some(Trivial::Formatting(vec!["src/a.rs".to_string()]))
+        );
+
+        let comment = patch("src/a.rs", "-// Retrun the cache\n+// Return the cache\n");
+        assert_eq!(
+            classify(&comment),
+            Some(Trivial::Typo("src/a.rs".to_string()))
+        );
+        let code = patch("src/a.rs", "-let x = 1;\n+let x = 2;\n");
+        assert_eq!(classify(&code), None);
+        let joined = patch("src/a.rs", "-let name = \"a b\";\n+let name = \"ab\";  \n");
+        assert_eq!(classify(&joined), None);
+        let trailing = patch("src/a.rs", "-let x = 1;  \n+let x = 1;\n");
+        assert_eq!(
+            classify(&trailing),
+            Some(Trivial::Formatting(vec!["src/a.rs".to_string()]))
+        );
+        let hash_in_rust = patch("src/a.rs", "-#[derive(Debg)]\n+#[derive(Debug)]\n");
+        assert_eq!(classify(&hash_in_rust), None);
+        let python = patch("app.py", "-# Retrun the cache\n+# Return the cache\n");
+        assert_eq!(classify(&python), Some(Trivial::Typo("app.py".to_string())));
+        let prose = patch("README.md", "-Instal with cargo.\n+Install with cargo.\n");
+        assert_eq!(
+            classify(&prose),
+            Some(Trivial::Typo("README.md".to_string()))
+        );
+    }
+
+    #[test]
+    fn renders_configured_templates() {
+        let mut config = TrivialConfig::default();
+        let bump = Trivial::VersionBump("1.2.3".to_string());
+        assert_eq!(
+            message(&bump, &config).unwrap().as_deref(),
+            Some("Bump version to 1.2.3")
+        );
+        let formatting = Trivial::Formatting(vec!["a.rs".to_string(), "b.rs".to_string()]);
+        assert_eq!(
+            message(&formatting, &config).unwrap().as_deref(),
+            Some("Format 2 files")
+        );
+        config.typo = String::new();
+        assert_eq!(
+            message(&Trivial::Typo("a.rs".to_string()), &config).unwrap(),
+            None
+        );
+    }
+}
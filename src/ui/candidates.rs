@@ -0,0 +1,203 @@
+//! Side-by-side candidates from several models, labeled by source.
+
+use colored::*;
+use commitaura::__private::candidates::Candidate;
+use commitaura::__private::message;
+use commitaura::CommitauraError;
+use console::{truncate_str, Key, Term};
+
+/// Narrowest pane worth showing side by side.
+const MIN_PANE: usize = 30;
+
+/// What was chosen from the candidates.
+pub enum Picked {
+    Message(String),
+    /// Ask for a fresh set of candidates.
+    RegenerateAll,
+}
+
+/// Print every candidate under its model and let the user choose one, mix
+/// the subject of one with the body of another, or regenerate them all.
+/// Returns `None` if the choice was cancelled.
+pub fn pick(term: &Term, candidates: &[Candidate]) -> Result<Option<Picked>, CommitauraError> {
+    println!("{} {}", "🧪".bold().blue(), "Candidates:".bold().white());
+    let mut choices: Vec<(String, &str)> = Vec::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        super::separator();
+        // Candidates from the same model are told apart by number.
+        let source = if candidates.iter().filter(|c| c.model == candidate.model).count() > 1 {
+            format!("{} #{}", candidate.model, i + 1)
+        } else {
+            candidate.model.clone()
+        };
+        let label = format!("[{}] {:.1}s", source, candidate.elapsed.as_secs_f64());
+        match &candidate.message {
+            Ok(message) => {
+                println!("{}", label.cyan().bold());
+                println!("{}", message.bold().white());
+                choices.push((source, message));
+            }
+            Err(e) => {
+                println!("{}", label.red().bold());
+                println!("{}", e.to_string().red());
+            }
+        }
+    }
+    super::separator();
+
+    let mut items: Vec<String> = choices
+        .iter()
+        .map(|(source, message)| format!("[{}] {}", source, message.lines().next().unwrap_or("")))
+        .collect();
+    let mixable = choices.len() > 1;
+    if mixable {
+        items.push("🔀 Subject from one, body from another".to_string());
+    }
+    items.push("🔄 Regenerate all".to_string());
+    loop {
+        let Some(choice) = super::select("Use which candidate?", &items, 0)? else {
+            return Ok(None);
+        };
+        if let Some((_, message)) = choices.get(choice) {
+            return Ok(Some(Picked::Message(message.to_string())));
+        }
+        if !mixable || choice > choices.len() {
+            return Ok(Some(Picked::RegenerateAll));
+        }
+        // Backing out of the mix returns to the list.
+        if let Some(message) = mix(term, &choices)? {
+            return Ok(Some(Picked::Message(message)));
+        }
+    }
+}
+
+/// Choose a subject and a body from different `choices`, in two panes
+/// with the assembled message shown below them. Accessible mode and narrow
+/// terminals ask for each in turn. `None` if cancelled.
+fn mix(term: &Term, choices: &[(String, &str)]) -> Result<Option<String>, CommitauraError> {
+    let parts: Vec<(&str, &str)> = choices
+        .iter()
+        .map(|(_, text)| message::split(text))
+        .collect();
+    let subjects: Vec<String> = choices
+        .iter()
+        .zip(&parts)
+        .map(|((source, _), (subject, _))| format!("[{}] {}", source, subject))
+        .collect();
+    let bodies: Vec<String> = choices
+        .iter()
+        .zip(&parts)
+        .map(|((source, _), (_, body))| match body.lines().next() {
+            Some(first) => format!("[{}] {}", source, first),
+            None => format!("[{}] (no body)", source),
+        })
+        .collect();
+
+    let (_, width) = term.size();
+    let pane = (width as usize).saturating_sub(super::GUTTER.chars().count()) / 2;
+    if super::accessible() || !term.is_term() || pane < MIN_PANE {
+        let Some(subject) = super::select("Subject from which candidate?", &subjects, 0)? else {
+            return Ok(None);
+        };
+        let Some(body) = super::select("Body from which candidate?", &bodies, 0)? else {
+            return Ok(None);
+        };
+        return Ok(Some(message::combine(choices[subject].1, choices[body].1)));
+    }
+
+    // The subject pane has the focus first; each pane keeps its own choice.
+    let (mut subject, mut body, mut focus_body) = (0, 0, false);
+    let mut drawn = 0;
+    term.hide_cursor()?;
+    let result = loop {
+        term.clear_last_lines(drawn)?;
+        let combined = message::combine(choices[subject].1, choices[body].1);
+        let lines = render(
+            &subjects, &bodies, subject, body, focus_body, &combined, pane,
+        );
+        for line in &lines {
+            term.write_line(line)?;
+        }
+        drawn = lines.len();
+
+        let cursor = if focus_body { &mut body } else { &mut subject };
+        match term.read_key()? {
+            Key::ArrowUp | Key::Char('k') => *cursor = cursor.saturating_sub(1),
+            Key::ArrowDown | Key::Char('j') => *cursor = (*cursor + 1).min(choices.len() - 1),
+            Key::ArrowLeft | Key::ArrowRight | Key::Tab | Key::BackTab => focus_body = !focus_body,
+            Key::Enter => break Some(combined),
+            Key::Escape | Key::Char('q') => break None,
+            _ => {}
+        }
+    };
+    term.clear_last_lines(drawn)?;
+    term.show_cursor()?;
+    Ok(result)
+}
+
+/// The subject and body panes, each pointing at its choice, and the
+/// message they make.
+fn render(
+    subjects: &[String],
+    bodies: &[String],
+    subject: usize,
+    body: usize,
+    focus_body: bool,
+    combined: &str,
+    pane: usize,
+) -> Vec<String> {
+    let title = |text: &str, focused: bool| {
+        let text = super::pad(text, pane);
+        if focused {
+            text.cyan().bold().to_string()
+        } else {
+            text.dimmed().to_string()
+        }
+    };
+    let cell = |items: &[String], row: usize, chosen: usize, focused: bool| {
+        let text = super::pad(&truncate_str(&items[row], pane - 2, "…"), pane - 2);
+        match (row == chosen, focused) {
+            (true, true) => format!("{} {}", ">".cyan().bold(), text.bold().white()),
+            (true, false) => format!("{} {}", ">".dimmed(), text.white()),
+            (false, _) => format!("  {}", text.dimmed()),
+        }
+    };
+    let mut lines = vec![
+        format!(
+            "{} {}",
+            "🔀".bold().blue(),
+            "Subject from one candidate, body from another:"
+                .bold()
+                .white()
+        ),
+        "↑↓ choose · ←→ / tab switch pane · enter use · esc back"
+            .dimmed()
+            .to_string(),
+        format!(
+            "{}{}{}",
+            title("Subject", !focus_body),
+            super::GUTTER.dimmed(),
+            title("Body", focus_body)
+        ),
+    ];
+    for row in 0..subjects.len() {
+        lines.push(format!(
+            "{}{}{}",
+            cell(subjects, row, subject, !focus_body),
+            super::GUTTER.dimmed(),
+            cell(bodies, row, body, focus_body)
+        ));
+    }
+    lines.push(
+        "─"
+            .repeat(pane * 2 + super::GUTTER.chars().count())
+            .dimmed()
+            .to_string(),
+    );
+    lines.extend(
+        super::wrap(combined, pane * 2)
+            .into_iter()
+            .map(|line| line.bold().white().to_string()),
+    );
+    lines
+}
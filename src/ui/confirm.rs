@@ -0,0 +1,27 @@
+//! The final prompt before a suggested message is used.
+
+use commitaura::CommitauraError;
+
+/// What to do with the suggested message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Accept,
+    Edit,
+    Regenerate,
+    Cancel,
+}
+
+const ACTIONS: &[(Action, &str)] = &[
+    (Action::Accept, "✅ Accept"),
+    (Action::Edit, "✏️  Edit in $EDITOR"),
+    (Action::Regenerate, "🔄 Regenerate"),
+    (Action::Cancel, "❌ Cancel"),
+];
+
+/// Ask what to do with the message before sending it to `destinations`.
+/// Escape cancels.
+pub fn ask(destinations: &[String]) -> Result<Action, CommitauraError> {
+    let items: Vec<&str> = ACTIONS.iter().map(|(_, label)| *label).collect();
+    let choice = super::select(&format!("Use this commit message? ({})", destinations.join(", ")), &items, 0)?;
+    Ok(choice.map_or(Action::Cancel, |i| ACTIONS[i].0))
+}
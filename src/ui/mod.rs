@@ -0,0 +1,219 @@
+//! Interactive terminal widgets used by the CLI.
+//!
+//! Every spinner, separator and prompt goes through the helpers here, so
+//! accessible mode (`--a11y`) can replace them with plain lines: status
+//! messages printed once, no box art, and prompts answered by typing a line.
+//! Screen readers read those in order, where redrawn widgets are read as
+//! noise or not at all.
+
+pub mod candidates;
+pub mod confirm;
+pub mod models;
+pub mod pair;
+pub mod review;
+pub mod select;
+pub mod stage;
+pub mod subject;
+pub mod todos;
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use colored::*;
+use commitaura::CommitauraError;
+use console::{measure_text_width, style};
+use dialoguer::{theme::ColorfulTheme, Confirm, MultiSelect, Select};
+use indicatif::{ProgressBar, ProgressStyle};
+
+static ACCESSIBLE: AtomicBool = AtomicBool::new(false);
+
+/// Between columns shown side by side.
+const GUTTER: &str = " │ ";
+
+pub fn enable_accessible() {
+    ACCESSIBLE.store(true, Ordering::Relaxed);
+}
+
+pub fn accessible() -> bool {
+    ACCESSIBLE.load(Ordering::Relaxed)
+}
+
+/// A ticking spinner in `color` showing `message`. In accessible mode the
+/// message is printed once instead, and the bar returned draws nothing.
+pub fn spinner(color: &str, message: impl Into<String>) -> Result<ProgressBar, CommitauraError> {
+    let message = message.into();
+    if accessible() {
+        println!("{}", plain(&message));
+        return Ok(ProgressBar::hidden());
+    }
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(ProgressStyle::default_spinner().template(&format!("{{spinner:.{}}} {{msg}}", color))?);
+    pb.set_message(message);
+    pb.enable_steady_tick(Duration::from_millis(80));
+    Ok(pb)
+}
+
+/// Change what `pb` says; printed as a new line in accessible mode.
+pub fn status(pb: &ProgressBar, message: impl Into<String>) {
+    let message = message.into();
+    if accessible() {
+        println!("{}", plain(&message));
+    } else {
+        pb.set_message(message);
+    }
+}
+
+/// Stop `pb`, leaving `message` in its place; printed as a line in
+/// accessible mode.
+pub fn finish(pb: &ProgressBar, message: impl Into<String>) {
+    let message = message.into();
+    if accessible() {
+        println!("{}", plain(&console::strip_ansi_codes(&message)));
+    } else {
+        pb.finish_with_message(message);
+    }
+}
+
+/// The rule between sections; nothing in accessible mode.
+pub fn separator() {
+    if !accessible() {
+        println!("{}", "────────────────────────────────────────────".white());
+    }
+}
+
+/// `text` without emoji and other pictographs, which screen readers spell
+/// out by name. Letters in any script and typographic punctuation stay.
+pub fn plain(text: &str) -> String {
+    let kept: String = text
+        .chars()
+        .filter(|&c| c.is_ascii() || c.is_alphanumeric() || ('\u{2010}'..='\u{2027}').contains(&c) || c == '→')
+        .collect();
+    kept.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A line typed by the user, trimmed; `None` at end of input.
+pub(crate) fn read_answer(prompt: &str) -> Result<Option<String>, CommitauraError> {
+    print!("{} ", prompt);
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line)? == 0 {
+        println!();
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+/// Ask a yes/no question. End of input in accessible mode answers no.
+pub fn confirm(prompt: &str, default: bool) -> Result<bool, CommitauraError> {
+    if !accessible() {
+        return Ok(Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(style(prompt).cyan().to_string())
+            .default(default)
+            .interact()?);
+    }
+    let hint = if default { "yes or no, Enter for yes" } else { "yes or no, Enter for no" };
+    loop {
+        let Some(answer) = read_answer(&format!("{} ({}):", plain(prompt), hint))? else {
+            return Ok(false);
+        };
+        match answer.to_ascii_lowercase().as_str() {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer yes or no."),
+        }
+    }
+}
+
+/// Choose one of `items`; `None` if cancelled with Escape, or with `q` or
+/// end of input in accessible mode, where the items are a numbered list.
+pub fn select<T: AsRef<str> + ToString>(prompt: &str, items: &[T], default: usize) -> Result<Option<usize>, CommitauraError> {
+    if !accessible() {
+        return Ok(Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(style(prompt).cyan().to_string())
+            .items(items)
+            .default(default)
+            .interact_opt()?);
+    }
+    println!("{}", plain(prompt));
+    for (i, item) in items.iter().enumerate() {
+        println!("{}. {}", i + 1, plain(item.as_ref()));
+    }
+    loop {
+        let question = format!("Choose 1 to {}, Enter for {}, q to cancel:", items.len(), default + 1);
+        let Some(answer) = read_answer(&question)? else {
+            return Ok(None);
+        };
+        match answer.as_str() {
+            "" => return Ok(Some(default)),
+            "q" | "Q" => return Ok(None),
+            number => match number.parse::<usize>() {
+                Ok(n) if (1..=items.len()).contains(&n) => return Ok(Some(n - 1)),
+                _ => println!("Please type a number from 1 to {}.", items.len()),
+            },
+        }
+    }
+}
+
+/// Choose any number of `items`; `None` if cancelled. In accessible mode the
+/// chosen numbers are typed as a list such as `1 3`, or `all`.
+pub fn multi_select<T: AsRef<str> + ToString>(prompt: &str, items: &[T]) -> Result<Option<Vec<usize>>, CommitauraError> {
+    if !accessible() {
+        return Ok(MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(style(format!("{} (space toggles, enter confirms)", prompt)).cyan().to_string())
+            .items(items)
+            .interact_opt()?);
+    }
+    println!("{}", plain(prompt));
+    for (i, item) in items.iter().enumerate() {
+        println!("{}. {}", i + 1, plain(item.as_ref()));
+    }
+    loop {
+        let Some(answer) = read_answer("Type the numbers to choose, separated by spaces, all for every one, or Enter for none:")? else {
+            return Ok(None);
+        };
+        if answer.eq_ignore_ascii_case("all") {
+            return Ok(Some((0..items.len()).collect()));
+        }
+        let chosen: Option<Vec<usize>> = answer
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<usize>().ok().filter(|n| (1..=items.len()).contains(n)).map(|n| n - 1))
+            .collect();
+        match chosen {
+            Some(chosen) => return Ok(Some(chosen)),
+            None => println!("Please type numbers from 1 to {}.", items.len()),
+        }
+    }
+}
+
+/// `text` wrapped to `width`, keeping its blank lines.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    text.lines()
+        .flat_map(|line| {
+            if line.trim().is_empty() {
+                vec![String::new()]
+            } else {
+                textwrap::wrap(line, width).into_iter().map(|l| l.into_owned()).collect()
+            }
+        })
+        .collect()
+}
+
+/// `text` padded with spaces to `width` columns.
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(measure_text_width(text))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_drops_pictographs() {
+        assert_eq!(plain("✅ Accept"), "Accept");
+        assert_eq!(plain("✏️  Edit in $EDITOR"), "Edit in $EDITOR");
+        assert_eq!(plain("🚀 Commit — déjà vu → done"), "Commit — déjà vu → done");
+    }
+}
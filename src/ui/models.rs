@@ -0,0 +1,78 @@
+//! Model table and picker with context window, price and latency hints.
+
+use colored::*;
+use commitaura::__private::journal::{self, JournalEntry};
+use commitaura::__private::provider::Provider;
+use commitaura::__private::pricing;
+use commitaura::CommitauraError;
+
+/// How many recent replies per model the latency hint averages.
+const LATENCY_SAMPLES: usize = 20;
+
+/// One line describing `model`: context window, price per 1K input/output
+/// tokens and average latency from the journal.
+fn describe(model: &str, entries: &[JournalEntry]) -> String {
+    let (context, price) = match pricing::pricing(model) {
+        Some(p) => (
+            format!("{}k ctx", p.context_window / 1000),
+            format!(
+                "${:.5} / ${:.5} per 1K",
+                p.input_per_million / 1000.0,
+                p.output_per_million / 1000.0
+            ),
+        ),
+        None => ("? ctx".to_string(), "price unknown".to_string()),
+    };
+    let latency = journal::average_latency(entries, model, LATENCY_SAMPLES)
+        .map(|ms| format!("~{} ms", ms))
+        .unwrap_or_else(|| "no runs yet".to_string());
+    format!(
+        "{:<16} {:>10}  {:<26} {}",
+        model,
+        context,
+        price,
+        latency.dimmed()
+    )
+}
+
+/// The models to offer: the configured list, or every model with known
+/// pricing that `provider` serves.
+pub fn offered(configured: Option<&[String]>, provider: Provider) -> Vec<String> {
+    match configured {
+        Some(models) => models.to_vec(),
+        None => pricing::known_models()
+            .into_iter()
+            .filter(|model| provider.serves(model))
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// Print the model table, marking `current`.
+pub fn print_models(models: &[String], current: &str, entries: &[JournalEntry]) {
+    println!(
+        "{} {}",
+        "🧠".bold().blue(),
+        "Models (input / output price, recent latency):".bold().white()
+    );
+    super::separator();
+    for model in models {
+        let marker = if model == current { "*".green().bold() } else { " ".normal() };
+        println!("{} {}", marker, describe(model, entries));
+    }
+}
+
+/// Let the user pick a model for this session. Returns `None` if cancelled.
+pub fn pick_model(
+    models: &[String],
+    current: &str,
+    entries: &[JournalEntry],
+) -> Result<Option<String>, CommitauraError> {
+    let items: Vec<String> = models.iter().map(|m| describe(m, entries)).collect();
+    let choice = super::select(
+        "Model for this session",
+        &items,
+        models.iter().position(|m| m == current).unwrap_or(0),
+    )?;
+    Ok(choice.map(|i| models[i].clone()))
+}
@@ -0,0 +1,98 @@
+//! Terse and detailed candidates side by side, chosen with a key press.
+
+use colored::*;
+use commitaura::__private::candidates::Candidate;
+use commitaura::CommitauraError;
+use console::{Key, Term};
+
+/// Narrowest column worth showing side by side.
+const MIN_COLUMN: usize = 30;
+
+/// What the vote decided.
+pub enum Vote {
+    /// The candidate at this index won.
+    Pick(usize),
+    /// Ask for a fresh pair.
+    Regenerate,
+    Cancel,
+}
+
+/// Show `candidates`, labeled by `labels`, next to each other, and wait for
+/// a vote: `a` or `b` (or the arrow keys), `r` to regenerate, Escape to
+/// cancel. Narrow terminals and accessible mode list them one after the
+/// other and read the vote as a typed line.
+pub fn vote(term: &Term, candidates: &[Candidate], labels: &[&str]) -> Result<Vote, CommitauraError> {
+    let texts: Vec<String> = candidates
+        .iter()
+        .map(|c| match &c.message {
+            Ok(message) => message.clone(),
+            Err(e) => format!("(failed: {})", e),
+        })
+        .collect();
+    let headers: Vec<String> = labels
+        .iter()
+        .zip(candidates)
+        .enumerate()
+        .map(|(i, (label, c))| format!("[{}] {} {:.1}s", key(i), label, c.elapsed.as_secs_f64()))
+        .collect();
+    let usable = |i: usize| candidates.get(i).is_some_and(|c| c.message.is_ok());
+
+    let (_, width) = term.size();
+    let column = (width as usize).saturating_sub(super::GUTTER.len()) / 2;
+    if super::accessible() || !term.is_term() || column < MIN_COLUMN {
+        return vote_typed(&headers, &texts, usable);
+    }
+
+    println!("{} {}", "👥".bold().blue(), "Which message do you prefer?".bold().white());
+    super::separator();
+    let left = super::wrap(&texts[0], column);
+    let right = super::wrap(&texts[1], column);
+    println!("{}{}{}", super::pad(&headers[0], column).cyan().bold(), super::GUTTER.dimmed(), headers[1].cyan().bold());
+    for row in 0..left.len().max(right.len()) {
+        let cell = |lines: &[String]| lines.get(row).cloned().unwrap_or_default();
+        let (l, r) = (super::pad(&cell(&left), column), cell(&right));
+        if row == 0 {
+            println!("{}{}{}", l.bold().white(), super::GUTTER.dimmed(), r.bold().white());
+        } else {
+            println!("{}{}{}", l.white(), super::GUTTER.dimmed(), r.white());
+        }
+    }
+    super::separator();
+    println!("{}", "a / ← prefer left · b / → prefer right · r regenerate both · esc cancel".dimmed());
+    loop {
+        match term.read_key()? {
+            Key::Char('a') | Key::Char('A') | Key::ArrowLeft if usable(0) => return Ok(Vote::Pick(0)),
+            Key::Char('b') | Key::Char('B') | Key::ArrowRight if usable(1) => return Ok(Vote::Pick(1)),
+            Key::Char('r') | Key::Char('R') => return Ok(Vote::Regenerate),
+            Key::Escape | Key::Char('q') => return Ok(Vote::Cancel),
+            _ => {}
+        }
+    }
+}
+
+fn vote_typed(headers: &[String], texts: &[String], usable: impl Fn(usize) -> bool) -> Result<Vote, CommitauraError> {
+    println!("Which message do you prefer?");
+    for (header, text) in headers.iter().zip(texts) {
+        super::separator();
+        println!("{}", super::plain(header));
+        println!("{}", text);
+    }
+    super::separator();
+    loop {
+        let Some(answer) = super::read_answer("Type a or b for the one you prefer, r to regenerate both, or q to cancel:")? else {
+            return Ok(Vote::Cancel);
+        };
+        match answer.to_ascii_lowercase().as_str() {
+            "a" if usable(0) => return Ok(Vote::Pick(0)),
+            "b" if usable(1) => return Ok(Vote::Pick(1)),
+            "r" => return Ok(Vote::Regenerate),
+            "q" => return Ok(Vote::Cancel),
+            _ => println!("Please type a, b, r or q."),
+        }
+    }
+}
+
+/// The voting key of the candidate at `index`.
+fn key(index: usize) -> char {
+    (b'a' + index as u8) as char
+}
@@ -0,0 +1,86 @@
+//! Underlined spelling, terminology and identifier issues with one-key fixes.
+
+use colored::*;
+use commitaura::__private::spellcheck::{self, Issue, IssueKind};
+use commitaura::CommitauraError;
+use console::{Key, Term};
+
+/// `message` with every issue's word underlined.
+fn underline(message: &str, issues: &[Issue]) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for issue in issues {
+        out.push_str(&message[pos..issue.start]);
+        let end = issue.start + issue.word.len();
+        out.push_str(&message[issue.start..end].yellow().underline().to_string());
+        pos = end;
+    }
+    out.push_str(&message[pos..]);
+    out
+}
+
+fn kind_label(kind: IssueKind) -> &'static str {
+    match kind {
+        IssueKind::Spelling => "spelling",
+        IssueKind::Terminology => "terminology",
+        IssueKind::Identifier => "not in diff",
+    }
+}
+
+/// Show the issues and let the user fix them one key at a time: a digit
+/// applies that fix, `a` applies all of them, enter keeps the message as is.
+/// Returns the possibly corrected message.
+pub fn review_issues(
+    term: &Term,
+    message: &str,
+    mut issues: Vec<Issue>,
+) -> Result<String, CommitauraError> {
+    let mut message = message.to_string();
+    while !issues.is_empty() {
+        println!("{} {}", "🔤".bold().blue(), "Possible typos:".bold().white());
+        println!("{}", underline(&message, &issues));
+        for (i, issue) in issues.iter().enumerate().take(9) {
+            let fix = issue.suggestion.as_deref().unwrap_or("(no suggestion)");
+            println!(
+                "  {} {} → {} {}",
+                format!("{})", i + 1).yellow().bold(),
+                issue.word.white(),
+                fix.green(),
+                format!("[{}]", kind_label(issue.kind)).dimmed()
+            );
+        }
+        println!(
+            "{}",
+            "1-9 apply fix · a apply all · enter keep as is".dimmed()
+        );
+
+        let key = if super::accessible() {
+            // A typed line, since single keys are not announced.
+            match super::read_answer("Type a number, a, or Enter:")?.as_deref() {
+                Some("a") => Key::Char('a'),
+                Some(answer) => answer.chars().next().filter(|_| answer.len() == 1).map_or(Key::Enter, Key::Char),
+                None => Key::Enter,
+            }
+        } else {
+            term.read_key()?
+        };
+        match key {
+            Key::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                if index < issues.len() {
+                    let issue = issues.remove(index);
+                    message = spellcheck::apply(&message, &issue, &mut issues);
+                }
+            }
+            Key::Char('a') => {
+                while !issues.is_empty() {
+                    let issue = issues.remove(0);
+                    message = spellcheck::apply(&message, &issue, &mut issues);
+                }
+            }
+            _ => break,
+        }
+        println!();
+    }
+    Ok(message)
+}
@@ -0,0 +1,200 @@
+//! Interactive include/exclude picker for files and hunks, with a running
+//! token total and cost estimate.
+
+use colored::*;
+use commitaura::diff::{self, FileDiff};
+use commitaura::__private::tokenizer::Tokenizer;
+use commitaura::__private::pricing;
+use commitaura::CommitauraError;
+use console::{Key, Term};
+
+/// Replies are capped at 100 tokens, so that is what the cost hint assumes.
+const RESPONSE_TOKENS: usize = 100;
+
+struct Row {
+    file: usize,
+    /// `None` for the file row itself, which toggles all of its hunks.
+    hunk: Option<usize>,
+    tokens: usize,
+}
+
+/// Let the user pick files and hunks under `title`, such as which are sent
+/// to the model. Returns `None` if the picker was cancelled.
+pub fn select_changes(
+    term: &Term,
+    files: &[FileDiff],
+    model: &str,
+    title: &str,
+) -> Result<Option<Vec<FileDiff>>, CommitauraError> {
+    let tokenizer = Tokenizer::for_model(model);
+    let mut rows = Vec::new();
+    for (f, file) in files.iter().enumerate() {
+        rows.push(Row {
+            file: f,
+            hunk: None,
+            tokens: tokenizer.count(&file.header.join("\n"))?,
+        });
+        for (h, hunk) in file.hunks.iter().enumerate() {
+            rows.push(Row {
+                file: f,
+                hunk: Some(h),
+                tokens: tokenizer.count(&format!("{}\n{}", hunk.header, hunk.lines.join("\n")))?,
+            });
+        }
+    }
+    if rows.is_empty() {
+        return Ok(Some(files.to_vec()));
+    }
+
+    // Every hunk starts selected; binary files have no hunks, so track files too.
+    let mut selected: Vec<Vec<bool>> = files.iter().map(|f| vec![true; f.hunks.len()]).collect();
+    let mut file_selected = vec![true; files.len()];
+    let mut cursor = 0;
+    let mut drawn = 0;
+
+    term.hide_cursor()?;
+    let result = loop {
+        term.clear_last_lines(drawn)?;
+        let mut lines = heading(&rows, &selected, &file_selected, model, title);
+        lines.extend(render(&rows, files, &selected, &file_selected, cursor, term));
+        for line in &lines {
+            term.write_line(line)?;
+        }
+        drawn = lines.len();
+
+        match term.read_key()? {
+            Key::ArrowUp | Key::Char('k') => cursor = cursor.saturating_sub(1),
+            Key::ArrowDown | Key::Char('j') => cursor = (cursor + 1).min(rows.len() - 1),
+            Key::Char(' ') => {
+                let row = &rows[cursor];
+                match row.hunk {
+                    Some(h) => {
+                        selected[row.file][h] = !selected[row.file][h];
+                        file_selected[row.file] = selected[row.file].iter().any(|s| *s);
+                    }
+                    None => {
+                        let on = !file_selected[row.file];
+                        file_selected[row.file] = on;
+                        selected[row.file].iter_mut().for_each(|s| *s = on);
+                    }
+                }
+            }
+            Key::Char('a') => {
+                let on = !file_selected.iter().all(|s| *s);
+                file_selected.iter_mut().for_each(|s| *s = on);
+                selected.iter_mut().flatten().for_each(|s| *s = on);
+            }
+            Key::Enter => break Some(()),
+            Key::Escape | Key::Char('q') => break None,
+            _ => {}
+        }
+    };
+    term.clear_last_lines(drawn)?;
+    term.show_cursor()?;
+
+    Ok(result.map(|_| {
+        files
+            .iter()
+            .enumerate()
+            .filter(|(f, _)| file_selected[*f])
+            .map(|(f, file)| FileDiff {
+                hunks: file
+                    .hunks
+                    .iter()
+                    .zip(&selected[f])
+                    .filter(|(_, on)| **on)
+                    .map(|(hunk, _)| hunk.clone())
+                    .collect(),
+                ..file.clone()
+            })
+            .collect()
+    }))
+}
+
+fn is_on(row: &Row, selected: &[Vec<bool>], file_selected: &[bool]) -> bool {
+    match row.hunk {
+        Some(h) => selected[row.file][h],
+        None => file_selected[row.file],
+    }
+}
+
+/// The title with the selection's token total and cost, and the key help.
+fn heading(
+    rows: &[Row],
+    selected: &[Vec<bool>],
+    file_selected: &[bool],
+    model: &str,
+    title: &str,
+) -> Vec<String> {
+    let total: usize = rows
+        .iter()
+        .filter(|r| is_on(r, selected, file_selected))
+        .map(|r| r.tokens)
+        .sum();
+    let cost = pricing::estimate_cost(model, total, RESPONSE_TOKENS)
+        .map(|c| format!("~${:.4}", c))
+        .unwrap_or_else(|| "cost unknown".to_string());
+
+    vec![
+        format!(
+            "{} {} {}",
+            title.bold().white(),
+            format!("{} tokens", total).cyan().bold(),
+            format!("({} with {})", cost, model).dimmed()
+        ),
+        "space toggle · a all · enter confirm · esc cancel"
+            .dimmed()
+            .to_string(),
+    ]
+}
+
+fn render(
+    rows: &[Row],
+    files: &[FileDiff],
+    selected: &[Vec<bool>],
+    file_selected: &[bool],
+    cursor: usize,
+    term: &Term,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    // Keep the cursor visible when there are more rows than fit on screen.
+    let height = (term.size().0 as usize).saturating_sub(4).max(5);
+    let start = cursor
+        .saturating_sub(height - 1)
+        .min(rows.len().saturating_sub(height));
+    for (i, row) in rows.iter().enumerate().skip(start).take(height) {
+        let mark = if is_on(row, selected, file_selected) {
+            "[x]".green()
+        } else {
+            "[ ]".dimmed()
+        };
+        let pointer = if i == cursor {
+            ">".cyan().bold()
+        } else {
+            " ".normal()
+        };
+        let file = &files[row.file];
+        let label = match row.hunk {
+            None => file.path.bold().to_string(),
+            Some(h) => format!("  {}", file.hunks[h].header).normal().to_string(),
+        };
+        lines.push(format!(
+            "{} {} {} {}",
+            pointer,
+            mark,
+            label,
+            format!("{} tok", row.tokens).dimmed()
+        ));
+    }
+    lines
+}
+
+/// Run the picker over a raw diff and render the chosen parts back to diff text.
+pub fn select_diff(
+    term: &Term,
+    diff: &str,
+    model: &str,
+) -> Result<Option<String>, CommitauraError> {
+    let files = diff::parse(diff);
+    Ok(select_changes(term, &files, model, "Select changes to include:")?.map(|files| diff::render(&files)))
+}
@@ -0,0 +1,68 @@
+//! Staging from inside Commitaura when nothing is staged yet, instead of a
+//! round trip through `git add -p`.
+
+use colored::*;
+use commitaura::diff::{self, FileDiff, FileStatus};
+use commitaura::__private::git;
+use commitaura::CommitauraError;
+use console::Term;
+
+/// Offer the unstaged changes to tracked files, and untracked files, for
+/// staging: files and hunks in the picker, or whole files by number in
+/// accessible mode. Returns whether anything was staged.
+pub fn offer(term: &Term, model: &str) -> Result<bool, CommitauraError> {
+    if (!super::accessible() && !term.is_term()) || git::ensure_writable("add").is_err() {
+        return Ok(false);
+    }
+    let mut files = diff::parse(&git::get_unstaged_diff()?);
+    let tracked = files.len();
+    files.extend(git::untracked_paths()?.into_iter().map(|path| FileDiff {
+        path,
+        old_path: None,
+        status: FileStatus::Added,
+        binary: false,
+        header: Vec::new(),
+        hunks: Vec::new(),
+    }));
+    if files.is_empty() {
+        return Ok(false);
+    }
+
+    println!("{}", "Nothing is staged yet; choose what to stage.".bold().yellow());
+    let chosen = if super::accessible() {
+        let labels: Vec<String> = files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| {
+                if i >= tracked {
+                    format!("{} (untracked)", file.path)
+                } else {
+                    format!("{} (+{} -{})", file.path, file.additions(), file.deletions())
+                }
+            })
+            .collect();
+        super::multi_select("Files to stage", &labels)?.map(|chosen| chosen.into_iter().map(|i| files[i].clone()).collect())
+    } else {
+        super::select::select_changes(term, &files, model, "Select changes to stage:")?
+    };
+    let Some(chosen) = chosen.filter(|chosen: &Vec<FileDiff>| !chosen.is_empty()) else {
+        return Ok(false);
+    };
+
+    // Files staged whole go through `git add`, which also covers untracked,
+    // binary and deleted files; the rest are staged hunk by hunk.
+    let (mut whole, mut partial) = (Vec::new(), Vec::new());
+    for file in chosen {
+        match files.iter().position(|f| f.path == file.path) {
+            Some(i) if i < tracked && file.hunks.len() < files[i].hunks.len() => partial.push(file),
+            _ => whole.push(file.path),
+        }
+    }
+    if !whole.is_empty() {
+        git::stage(&whole)?;
+    }
+    if !partial.is_empty() {
+        git::apply_to_index(&diff::render(&partial))?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,106 @@
+//! Splitting a one-line message into a subject and a body.
+
+use colored::*;
+use commitaura::__private::message::{self, MAX_SUBJECT_LEN};
+use commitaura::CommitauraError;
+use console::{Key, Term};
+
+/// When `message` is one line too long for a subject, propose a subject and
+/// body split and let the user move it a word at a time with the arrow
+/// keys. Enter splits there; Escape keeps the message as it is.
+pub fn offer_split(term: &Term, message: &str) -> Result<String, CommitauraError> {
+    if !message::is_run_on(message) {
+        return Ok(message.to_string());
+    }
+    let text = message.trim();
+    let points = message::split_points(text);
+    let Some(cursor) = message::proposed_split(text, &points) else {
+        return Ok(message.to_string());
+    };
+    let chosen = if super::accessible() {
+        choose_typed(text, &points, cursor)?
+    } else if term.is_term() {
+        choose_with_keys(term, text, &points, cursor)?
+    } else {
+        None
+    };
+    Ok(chosen.map_or_else(|| message.to_string(), |at| message::split_at(text, at)))
+}
+
+fn choose_with_keys(
+    term: &Term,
+    text: &str,
+    points: &[usize],
+    mut cursor: usize,
+) -> Result<Option<usize>, CommitauraError> {
+    let mut drawn = 0;
+    term.hide_cursor()?;
+    let result = loop {
+        term.clear_last_lines(drawn)?;
+        let mut lines = vec![
+            format!(
+                "{} {}",
+                "✂️".bold(),
+                format!("The message is one line of {} characters. Split it into a subject and body?", text.chars().count())
+                    .bold()
+                    .white()
+            ),
+            "← → move the split · enter split here · esc keep one line".dimmed().to_string(),
+        ];
+        lines.extend(preview(text, points[cursor]));
+        for line in &lines {
+            term.write_line(line)?;
+        }
+        drawn = lines.len();
+
+        match term.read_key()? {
+            Key::ArrowLeft | Key::Char('h') => cursor = cursor.saturating_sub(1),
+            Key::ArrowRight | Key::Char('l') => cursor = (cursor + 1).min(points.len() - 1),
+            Key::Enter => break Some(points[cursor]),
+            Key::Escape | Key::Char('q') => break None,
+            _ => {}
+        }
+    };
+    term.clear_last_lines(drawn)?;
+    term.show_cursor()?;
+    Ok(result)
+}
+
+/// Accessible mode: the split is moved by typing how many words the
+/// subject should have.
+fn choose_typed(text: &str, points: &[usize], mut cursor: usize) -> Result<Option<usize>, CommitauraError> {
+    println!("The message is one line of {} characters. Proposed split:", text.chars().count());
+    loop {
+        for line in preview(text, points[cursor]) {
+            println!("{}", super::plain(&console::strip_ansi_codes(&line)));
+        }
+        let question = format!("Enter splits here; type a number of words from 1 to {} for the subject, or k to keep one line:", points.len());
+        let Some(answer) = super::read_answer(&question)? else {
+            return Ok(None);
+        };
+        match answer.as_str() {
+            "" => return Ok(Some(points[cursor])),
+            "k" | "K" => return Ok(None),
+            number => match number.parse::<usize>() {
+                Ok(n) if (1..=points.len()).contains(&n) => cursor = n - 1,
+                _ => println!("Please type a number from 1 to {}.", points.len()),
+            },
+        }
+    }
+}
+
+/// The message as it would be split at `at`, with the subject's length.
+fn preview(text: &str, at: usize) -> Vec<String> {
+    let split = message::split_at(text, at);
+    let mut lines = split.lines();
+    let subject = lines.next().unwrap_or_default();
+    let length = subject.chars().count();
+    let length = if length > MAX_SUBJECT_LEN {
+        format!("({} characters)", length).red()
+    } else {
+        format!("({} characters)", length).dimmed()
+    };
+    let mut preview = vec![format!("{} {}", subject.bold().green(), length)];
+    preview.extend(lines.map(|line| line.dimmed().to_string()));
+    preview
+}
@@ -0,0 +1,48 @@
+//! Confirmation UI for opening tracking issues for new TODO comments.
+
+use colored::*;
+use commitaura::__private::forge::{Issue, RemoteRepo};
+use commitaura::__private::todos::{self, NewTodo};
+use commitaura::{CommitauraError, GenerateOptions};
+use console::style;
+
+/// List `found`, let the user pick which deserve an issue, show the drafted
+/// titles and open the issues once confirmed. Returns the issues opened.
+pub fn offer_issues(found: &[NewTodo], repo: &RemoteRepo, opts: &GenerateOptions) -> Result<Vec<Issue>, CommitauraError> {
+    println!("{} {}", "📌".bold().yellow(), format!("{} new TODO/FIXME comment(s):", found.len()).bold().white());
+    let items: Vec<String> = found.iter().map(|t| format!("{} {}: {}", t.location(), t.marker, t.text)).collect();
+    let Some(chosen) = super::multi_select("Open tracking issues for which?", &items)? else {
+        return Ok(Vec::new());
+    };
+    if chosen.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let pb = super::spinner("magenta", "Drafting issue titles...")?;
+    let mut drafts = Vec::new();
+    for &i in &chosen {
+        drafts.push((&found[i], todos::issue_title(&found[i], opts)?));
+    }
+    pb.finish_and_clear();
+
+    super::separator();
+    for (todo, title) in &drafts {
+        println!("{} {}", title.bold().white(), format!("({})", todo.location()).dimmed());
+    }
+    super::separator();
+    if !super::confirm(&format!("Open {} issue(s) on {}/{}?", drafts.len(), repo.host, repo.path), true)? {
+        return Ok(Vec::new());
+    }
+
+    let mut opened = Vec::new();
+    for (todo, title) in drafts {
+        match commitaura::__private::forge::create_issue(repo, &title, &todos::issue_body(todo)) {
+            Ok(issue) => {
+                println!("{} {}", style(format!("✅ #{}", issue.number)).bold().green(), issue.url.dimmed());
+                opened.push(issue);
+            }
+            Err(e) => println!("{} {}", "⚠".yellow(), format!("Could not open an issue for {}: {}", todo.location(), e).yellow()),
+        }
+    }
+    Ok(opened)
+}
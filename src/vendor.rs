@@ -0,0 +1,352 @@
+//! Checked-in copies of dependencies, under `vendor/`, `third_party/`,
+//! `node_modules/` and the like.
+//!
+//! Updating one rewrites hundreds of files the project did not write, so
+//! its raw diff either crowds everything else out of the prompt or does not
+//! fit at all, and says nothing a message needs. Each vendored package is
+//! collapsed into one line instead, naming the package and, where its
+//! version metadata changed, the versions it moved between.
+
+use crate::diff::{self, FileDiff, FileStatus};
+
+/// Directory names whose subdirectories are vendored packages.
+pub const VENDOR_DIRS: &[&str] = &[
+    "vendor",
+    "vendored",
+    "third_party",
+    "third-party",
+    "node_modules",
+    "bower_components",
+];
+
+/// Files at a package's root that name its version.
+const METADATA_FILES: &[&str] = &[
+    "package.json",
+    "bower.json",
+    "composer.json",
+    "Cargo.toml",
+    "pyproject.toml",
+    "setup.cfg",
+    "VERSION",
+    "version.txt",
+];
+
+/// Go's list of vendored modules, directly in `vendor/`.
+const GO_MODULES_FILE: &str = "modules.txt";
+
+/// The changes to one vendored package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Package {
+    /// Directory of the package, e.g. `node_modules/@babel/core`.
+    root: String,
+    /// The package's name, e.g. `@babel/core`.
+    name: String,
+    old_version: Option<String>,
+    new_version: Option<String>,
+    files: usize,
+    added_files: usize,
+    deleted_files: usize,
+    additions: usize,
+    deletions: usize,
+}
+
+impl Package {
+    fn describe(&self) -> String {
+        let change = if self.files > 0 && self.added_files == self.files {
+            match &self.new_version {
+                Some(new) => format!("added at {}", new),
+                None => "added".to_string(),
+            }
+        } else if self.files > 0 && self.deleted_files == self.files {
+            "removed".to_string()
+        } else {
+            match (&self.old_version, &self.new_version) {
+                (Some(old), Some(new)) if old != new => format!("updated from {} to {}", old, new),
+                _ => "updated".to_string(),
+            }
+        };
+        format!(
+            "Vendored dependency {} {} ({} file{}, +{} -{}); its diff is left out",
+            self.name,
+            change,
+            self.files,
+            if self.files == 1 { "" } else { "s" },
+            self.additions,
+            self.deletions
+        )
+    }
+}
+
+/// Whether `path` is inside a vendored tree.
+pub fn is_vendored(path: &str) -> bool {
+    path.split('/')
+        .rev()
+        .skip(1)
+        .any(|part| VENDOR_DIRS.contains(&part))
+}
+
+/// `diff` with the files of each vendored package replaced by one section
+/// describing the package's update, e.g. `Vendored dependency left-pad
+/// updated from 1.2.0 to 1.3.0 (4 files, +20 -12)`.
+pub fn collapse(diff: &str) -> String {
+    let files = diff::parse(diff);
+    if !files.iter().any(|file| is_vendored(&file.path)) {
+        return diff.to_string();
+    }
+    let go_modules = go_modules(&files);
+    let mut packages: Vec<Package> = Vec::new();
+    // Each package takes the place of its first file.
+    let mut out: Vec<Result<FileDiff, usize>> = Vec::new();
+    for file in files {
+        // Its changes are told by the versions of the modules it lists.
+        if is_go_modules_file(&file) && !go_modules.is_empty() {
+            continue;
+        }
+        let Some((root, name)) = package_of(&file.path, &go_modules) else {
+            out.push(Ok(file));
+            continue;
+        };
+        let index = match packages.iter().position(|p| p.root == root) {
+            Some(index) => index,
+            None => {
+                packages.push(Package {
+                    root,
+                    name,
+                    ..Package::default()
+                });
+                out.push(Err(packages.len() - 1));
+                packages.len() - 1
+            }
+        };
+        let package = &mut packages[index];
+        package.files += 1;
+        package.added_files += usize::from(file.status == FileStatus::Added);
+        package.deleted_files += usize::from(file.status == FileStatus::Deleted);
+        package.additions += file.additions();
+        package.deletions += file.deletions();
+        let is_metadata = file
+            .path
+            .strip_prefix(&format!("{}/", package.root))
+            .is_some_and(|rest| METADATA_FILES.contains(&rest));
+        if is_metadata {
+            package.old_version = package.old_version.take().or_else(|| version(&file, '-'));
+            package.new_version = package.new_version.take().or_else(|| version(&file, '+'));
+        }
+        if let Some((old, new)) = go_modules
+            .iter()
+            .find(|m| m.0 == package.name)
+            .map(|m| &m.1)
+        {
+            package.old_version = package.old_version.take().or_else(|| old.clone());
+            package.new_version = package.new_version.take().or_else(|| new.clone());
+        }
+    }
+
+    let files: Vec<FileDiff> = out
+        .into_iter()
+        .map(|item| match item {
+            Ok(file) => file,
+            Err(index) => {
+                let package = &packages[index];
+                FileDiff {
+                    path: package.root.clone(),
+                    old_path: None,
+                    status: FileStatus::Modified,
+                    binary: false,
+                    header: vec![
+                        format!("diff --git a/{0} b/{0}", package.root),
+                        package.describe(),
+                    ],
+                    hunks: Vec::new(),
+                }
+            }
+        })
+        .collect();
+    diff::render(&files)
+}
+
+/// The directory and name of the vendored package `path` belongs to. Files
+/// directly in a vendor directory belong to the directory itself. Go
+/// packages are matched against `go_modules`, since their names span
+/// several directories.
+fn package_of(path: &str, go_modules: &[GoModule]) -> Option<(String, String)> {
+    let parts: Vec<&str> = path.split('/').collect();
+    let at = parts[..parts.len() - 1]
+        .iter()
+        .position(|part| VENDOR_DIRS.contains(part))?;
+    let rest = &parts[at + 1..];
+    let vendor_dir = parts[..=at].join("/");
+    if rest.len() == 1 {
+        return Some((vendor_dir.clone(), vendor_dir));
+    }
+    let inside = rest.join("/");
+    let go_module = go_modules
+        .iter()
+        .map(|(module, _)| module)
+        .filter(|module| inside.starts_with(&format!("{}/", module)))
+        .max_by_key(|module| module.len());
+    let name = match go_module {
+        Some(module) => module.clone(),
+        // npm scopes: node_modules/@scope/name
+        None if rest[0].starts_with('@') && rest.len() > 2 => format!("{}/{}", rest[0], rest[1]),
+        None => rest[0].to_string(),
+    };
+    Some((format!("{}/{}", vendor_dir, name), name))
+}
+
+/// A Go module and its old and new versions, as far as the diff shows.
+type GoModule = (String, (Option<String>, Option<String>));
+
+/// The modules listed in a diff of Go's `vendor/modules.txt`, whose lines
+/// read `# github.com/pkg/errors v0.9.1`.
+fn go_modules(files: &[FileDiff]) -> Vec<GoModule> {
+    let mut modules: Vec<GoModule> = Vec::new();
+    let lists = files.iter().filter(|file| is_go_modules_file(file));
+    for line in lists.flat_map(|file| file.hunks.iter().flat_map(|h| &h.lines)) {
+        let (side, text) = line.split_at(line.len().min(1));
+        let Some(module) = text.strip_prefix("# ") else {
+            continue;
+        };
+        let mut words = module.split_whitespace();
+        let (Some(name), version) = (words.next(), words.next().map(str::to_string)) else {
+            continue;
+        };
+        let index = match modules.iter().position(|m| m.0 == name) {
+            Some(index) => index,
+            None => {
+                modules.push((name.to_string(), (None, None)));
+                modules.len() - 1
+            }
+        };
+        let versions = &mut modules[index].1;
+        match side {
+            "-" => versions.0 = version,
+            "+" => versions.1 = version,
+            _ => {}
+        }
+    }
+    modules
+}
+
+fn is_go_modules_file(file: &FileDiff) -> bool {
+    file.path.rsplit_once('/').is_some_and(|(dir, name)| {
+        name == GO_MODULES_FILE && dir.rsplit('/').next() == Some("vendor")
+    })
+}
+
+/// The version on the `side` (`-` or `+`) of a metadata file's diff.
+fn version(file: &FileDiff, side: char) -> Option<String> {
+    let whole_file = matches!(
+        file.path.rsplit('/').next(),
+        Some("VERSION" | "version.txt")
+    );
+    file.hunks
+        .iter()
+        .flat_map(|hunk| &hunk.lines)
+        .filter_map(|line| line.strip_prefix(side))
+        .find_map(|line| version_in(line.trim(), whole_file))
+}
+
+/// The version a metadata line sets: `"version": "1.2.3"`, `version =
+/// "1.2.3"`, or for a version file, the line itself.
+fn version_in(line: &str, whole_file: bool) -> Option<String> {
+    let value = if whole_file {
+        line
+    } else {
+        let rest = line
+            .strip_prefix("\"version\"")
+            .or_else(|| line.strip_prefix("version"))?;
+        rest.trim_start().strip_prefix([':', '='])?
+    };
+    let value = value.trim().trim_end_matches(',').trim_matches(['"', '\'']);
+    let looks_like_version = value
+        .trim_start_matches('v')
+        .starts_with(|c: char| c.is_ascii_digit());
+    looks_like_version.then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_vendored_packages() {
+        let diff = "\
+diff --git a/node_modules/left-pad/package.json b/node_modules/left-pad/package.json
+--- a/node_modules/left-pad/package.json
++++ b/node_modules/left-pad/package.json
+@@ -1,4 +1,4 @@
+ {
+   \"name\": \"left-pad\",
+-  \"version\": \"1.2.0\",
++  \"version\": \"1.3.0\",
+diff --git a/node_modules/left-pad/index.js b/node_modules/left-pad/index.js
+--- a/node_modules/left-pad/index.js
++++ b/node_modules/left-pad/index.js
+@@ -1 +1,2 @@
+-old
++new
++lines
+diff --git a/src/main.js b/src/main.js
+--- a/src/main.js
++++ b/src/main.js
+@@ -1 +1 @@
+-a
++b
+diff --git a/node_modules/@babel/core/lib/index.js b/node_modules/@babel/core/lib/index.js
+new file mode 100644
+--- /dev/null
++++ b/node_modules/@babel/core/lib/index.js
+@@ -0,0 +1 @@
++x
+";
+        let collapsed = collapse(diff);
+        assert_eq!(
+            collapsed,
+            "\
+diff --git a/node_modules/left-pad b/node_modules/left-pad
+Vendored dependency left-pad updated from 1.2.0 to 1.3.0 (2 files, +3 -2); its diff is left out
+diff --git a/src/main.js b/src/main.js
+--- a/src/main.js
++++ b/src/main.js
+@@ -1 +1 @@
+-a
++b
+diff --git a/node_modules/@babel/core b/node_modules/@babel/core
+Vendored dependency @babel/core added (1 file, +1 -0); its diff is left out
+"
+        );
+        let plain = "diff --git a/src/vendor.rs b/src/vendor.rs\n";
+        assert_eq!(collapse(plain), plain);
+    }
+
+    #[test]
+    fn reads_go_module_versions() {
+        let diff = "\
+diff --git a/vendor/modules.txt b/vendor/modules.txt
+--- a/vendor/modules.txt
++++ b/vendor/modules.txt
+@@ -1,2 +1,2 @@
+-# github.com/pkg/errors v0.8.1
++# github.com/pkg/errors v0.9.1
+ ## explicit
+diff --git a/vendor/github.com/pkg/errors/errors.go b/vendor/github.com/pkg/errors/errors.go
+--- a/vendor/github.com/pkg/errors/errors.go
++++ b/vendor/github.com/pkg/errors/errors.go
+@@ -1 +1 @@
+-a
++b
+";
+        let collapsed = collapse(diff);
+        assert!(collapsed.contains(
+            "Vendored dependency github.com/pkg/errors updated from v0.8.1 to v0.9.1 (1 file, +1 -1)"
+        ));
+        assert!(!collapsed.contains("modules.txt"), "{}", collapsed);
+        assert_eq!(
+            version_in("version = \"2.0.1\"", false),
+            Some("2.0.1".to_string())
+        );
+        assert_eq!(version_in("version.workspace = true", false), None);
+        assert_eq!(version_in("1.4", true), Some("1.4".to_string()));
+    }
+}
@@ -0,0 +1,84 @@
+//! `--stdin-diff` run outside any repository, against a stand-in Ollama server.
+#![cfg(feature = "cli")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::process::{Command, Stdio};
+
+const DIFF: &str = "diff --git a/src/greet.rs b/src/greet.rs\n@@ -0,0 +1 @@\n+pub fn greet() {}\n";
+
+/// Answer one chat request with `reply`, returning the request body.
+fn serve_once(listener: TcpListener, reply: &str) -> String {
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+    let response = serde_json::json!({
+        "message": { "role": "assistant", "content": reply },
+        "prompt_eval_count": 10,
+        "eval_count": 3,
+    })
+    .to_string();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.len(),
+        response
+    )
+    .unwrap();
+    String::from_utf8(body).unwrap()
+}
+
+#[test]
+fn writes_a_message_for_a_piped_diff_outside_a_repository() {
+    let dir = std::env::temp_dir().join(format!("commitaura-stdin-diff-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let host = listener.local_addr().unwrap().to_string();
+    let server = std::thread::spawn(move || serve_once(listener, "Add a greeting"));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_commitaura"))
+        .args(["--provider", "ollama", "--stdin-diff"])
+        .current_dir(&dir)
+        // Keep git from finding a repository above the scratch directory,
+        // and the run from reading the user's config.
+        .env("GIT_CEILING_DIRECTORIES", dir.parent().unwrap())
+        .env("HOME", &dir)
+        .env("XDG_CONFIG_HOME", dir.join(".config"))
+        .env("OLLAMA_HOST", &host)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(DIFF.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    // Checked before joining, since a run that fails early never connects.
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let request = server.join().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Add a greeting\n");
+    assert!(request.contains("+pub fn greet() {}"));
+}